@@ -8,20 +8,280 @@
 //!
 //! # Key Functions
 //!
-//! - [`stream_commit_message`]: Generate a commit message with streaming output
+//! - [`build_client`]: Create the HTTP client shared by every call in a run
+//! - [`api_post`]: Start an authorized chat completions request
+//! - [`stream_commit_message`]: Generate a commit message with streaming output,
+//!   retrying once if the model refuses
 //! - [`stream_pr_content`]: Generate PR title and body with streaming output
-//! - [`build_prompt`]: Construct the commit message prompt
+//! - [`build_prompt`]: Construct the per-diff commit message prompt
 //! - [`build_pr_prompt`]: Construct the PR generation prompt
+//! - [`build_pr_prompt_from_commits`]: Construct the PR prompt from commit
+//!   messages alone (`pr --fill-from-commits`)
+//!
+//! # Structured Output
+//!
+//! With [`GenerationOptions::structured_output`] set, the commit message is
+//! requested as JSON matching a schema (type, scope, subject, body, breaking)
+//! and rendered locally by [`StructuredCommit::render`]. If the provider
+//! rejects `response_format` or answers in free text, generation falls back
+//! to the plain streaming path.
+//!
+//! # Refusals
+//!
+//! Strict models occasionally decline a diff ("I can't help with that").
+//! [`stream_commit_message`] detects this with [`looks_like_refusal`] and
+//! retries once, with [`GenerationOptions::fallback_model`] if set. A second
+//! refusal is an error, so refusal text never reaches a commit.
+//!
+//! # Endpoint
+//!
+//! Requests go to OpenRouter unless `base_url` names another
+//! OpenAI-compatible chat completions endpoint (e.g. Ollama's
+//! `http://localhost:11434/v1/chat/completions`), set with [`set_api_url`].
+//!
+//! # Retries
+//!
+//! Requests go through [`send_with_retry`], which retries rate limits (429),
+//! server errors (500, 502, 503), and connection failures with exponential
+//! backoff, honoring `Retry-After`. Other errors, like a bad API key, fail on
+//! the first attempt.
+//!
+//! # Prompt caching
+//!
+//! Commit generation sends [`COMMIT_INSTRUCTIONS`] as a separate system
+//! message that never changes between runs, followed by the per-diff prompt.
+//! Providers that cache prompt prefixes (OpenAI, DeepSeek, and others do so
+//! automatically) can then reuse it across commits; Anthropic and Gemini
+//! models also get an explicit `cache_control` breakpoint on it.
+//!
+//! # Streaming
+//!
+//! Responses are read through [`SseBuffer`], which reassembles event lines
+//! split across network chunks in a single reused buffer (initially sized by
+//! the `stream_buffer_size` config option, and capped so one endless line
+//! can't exhaust memory). Streamed tokens are written to stdout as they
+//! arrive and flushed once per network chunk rather than per token.
 
 use futures::StreamExt;
 use indicatif::ProgressBar;
-use reqwest::Client;
+use reqwest::header::{HeaderMap, HeaderName, HeaderValue};
+use reqwest::{Client, RequestBuilder, Response, StatusCode};
 use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
 use std::io::{self, Write};
+use std::sync::OnceLock;
+use std::time::Duration;
+
+use crate::config::DEFAULT_STREAM_BUFFER_SIZE;
+use crate::format::looks_like_refusal;
+use crate::ui::{phase_message, phase_spinner};
 
 /// OpenRouter API endpoint for chat completions.
 pub const OPENROUTER_API_URL: &str = "https://openrouter.ai/api/v1/chat/completions";
 
+/// Chat completions endpoint from `base_url`, set once at startup.
+static API_URL: OnceLock<String> = OnceLock::new();
+
+/// Makes every request in the process go to `url` instead of OpenRouter.
+///
+/// Called once, before any request is made; later calls are ignored.
+pub fn set_api_url(url: String) {
+    let _ = API_URL.set(url);
+}
+
+/// Returns the chat completions endpoint requests are sent to.
+pub fn api_url() -> &'static str {
+    API_URL.get().map_or(OPENROUTER_API_URL, String::as_str)
+}
+
+/// Whether `url` is an OpenRouter endpoint.
+pub fn is_openrouter_url(url: &str) -> bool {
+    reqwest::Url::parse(url).is_ok_and(|url| url.host_str() == Some("openrouter.ai"))
+}
+
+/// Whether requests go to OpenRouter rather than another compatible server.
+///
+/// OpenRouter-only features (app headers, the models list) are skipped
+/// otherwise, and an API key becomes optional.
+pub fn uses_openrouter() -> bool {
+    is_openrouter_url(api_url())
+}
+
+/// Header name fragments that mark a header value as a secret.
+const SECRET_HEADER_MARKERS: &[&str] = &["auth", "key", "token", "secret", "cookie", "password"];
+
+/// True if a header's value should never be shown (e.g. `Helicone-Auth`).
+pub fn is_secret_header(name: &str) -> bool {
+    let name = name.to_ascii_lowercase();
+    SECRET_HEADER_MARKERS
+        .iter()
+        .any(|marker| name.contains(marker))
+}
+
+/// Renders headers as `Name: value` for display, with secret values redacted.
+pub fn describe_headers(headers: &BTreeMap<String, String>) -> String {
+    headers
+        .iter()
+        .map(|(name, value)| {
+            if is_secret_header(name) {
+                format!("{}: [redacted]", name)
+            } else {
+                format!("{}: {}", name, value)
+            }
+        })
+        .collect::<Vec<_>>()
+        .join(", ")
+}
+
+/// A request that didn't complete within the configured time limit.
+#[derive(Debug)]
+pub struct RequestTimeout {
+    /// The limit that was exceeded, in seconds.
+    pub secs: u64,
+}
+
+impl std::fmt::Display for RequestTimeout {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "Request timed out after {}s", self.secs)
+    }
+}
+
+impl std::error::Error for RequestTimeout {}
+
+/// Replaces a reqwest timeout error with [`RequestTimeout`].
+fn name_timeout(error: Box<dyn std::error::Error>, secs: u64) -> Box<dyn std::error::Error> {
+    match error.downcast_ref::<reqwest::Error>() {
+        Some(e) if e.is_timeout() => Box::new(RequestTimeout { secs }),
+        _ => error,
+    }
+}
+
+/// Builds the HTTP client used for every API call in a run.
+///
+/// A run can make several sequential requests (generation, branch analysis,
+/// branch naming), so idle connections are kept alive for reuse and HTTP/2 is
+/// negotiated via ALPN where the server supports it, avoiding a new TLS
+/// handshake per call.
+///
+/// Every request carries the app identification headers (OpenRouter only)
+/// plus `extra_headers` from config (e.g. for an observability gateway).
+/// Fails if an extra header name or value is invalid.
+///
+/// Each request, including reading a streamed response, is limited to
+/// `timeout_secs` (0 for no limit), so a hung connection can't spin forever.
+pub fn build_client(
+    extra_headers: &BTreeMap<String, String>,
+    timeout_secs: u64,
+) -> Result<Client, Box<dyn std::error::Error>> {
+    let mut headers = HeaderMap::new();
+    if uses_openrouter() {
+        headers.insert("X-Title", HeaderValue::from_static("Committer"));
+        headers.insert(
+            "HTTP-Referer",
+            HeaderValue::from_static("https://github.com/Nolanneff/commiter"),
+        );
+    }
+    for (name, value) in extra_headers {
+        let header_name = HeaderName::from_bytes(name.as_bytes())
+            .map_err(|_| format!("Invalid header name in extra_headers: '{}'", name))?;
+        let mut header_value = HeaderValue::from_str(value)
+            .map_err(|_| format!("Invalid value for extra_headers.{}", name))?;
+        header_value.set_sensitive(is_secret_header(name));
+        headers.insert(header_name, header_value);
+    }
+
+    let mut builder = Client::builder();
+    if timeout_secs > 0 {
+        builder = builder.timeout(Duration::from_secs(timeout_secs));
+    }
+
+    Ok(builder
+        .default_headers(headers)
+        .pool_idle_timeout(Duration::from_secs(90))
+        .pool_max_idle_per_host(2)
+        .tcp_keepalive(Duration::from_secs(60))
+        .http2_keep_alive_interval(Duration::from_secs(30))
+        .http2_keep_alive_while_idle(true)
+        .build()?)
+}
+
+/// Starts a chat completions request to [`api_url`] authorized with `api_key`.
+///
+/// An empty key (allowed for local servers) sends no `Authorization` header.
+/// Identification and `extra_headers` come from the client (see
+/// [`build_client`]); the JSON body sets the content type.
+pub fn api_post(client: &Client, api_key: &str) -> RequestBuilder {
+    let request = client.post(api_url());
+    if api_key.is_empty() {
+        request
+    } else {
+        request.header("Authorization", format!("Bearer {}", api_key))
+    }
+}
+
+/// Delay before the first retry; each further retry doubles it.
+const RETRY_BASE_DELAY: Duration = Duration::from_secs(1);
+
+/// Longest wait between retries, including one asked for by `Retry-After`.
+const MAX_RETRY_DELAY: Duration = Duration::from_secs(60);
+
+/// Whether a response status is worth retrying.
+fn is_retryable_status(status: StatusCode) -> bool {
+    matches!(status.as_u16(), 429 | 500 | 502 | 503)
+}
+
+/// Backoff before retry number `attempt + 1`, capped at [`MAX_RETRY_DELAY`].
+fn backoff_delay(attempt: u32) -> Duration {
+    2u32.checked_pow(attempt)
+        .and_then(|factor| RETRY_BASE_DELAY.checked_mul(factor))
+        .map_or(MAX_RETRY_DELAY, |delay| delay.min(MAX_RETRY_DELAY))
+}
+
+/// Reads a `Retry-After` header given in seconds.
+fn retry_after(response: &Response) -> Option<Duration> {
+    let secs = response
+        .headers()
+        .get(reqwest::header::RETRY_AFTER)?
+        .to_str()
+        .ok()?
+        .trim()
+        .parse()
+        .ok()?;
+    Some(Duration::from_secs(secs))
+}
+
+/// Sends a request, retrying transient failures up to `max_retries` times.
+///
+/// Rate-limited and unavailable responses (429, 500, 502, 503) and connection
+/// errors are retried after `Retry-After` if the server sent one, otherwise
+/// after 1s, 2s, 4s, and so on. `spinner` shows the attempt while waiting.
+/// Every other response is returned as-is for the caller to handle.
+pub async fn send_with_retry(
+    request: RequestBuilder,
+    max_retries: u32,
+    spinner: &ProgressBar,
+) -> Result<Response, reqwest::Error> {
+    let mut attempt = 0;
+    loop {
+        // Only streaming bodies can't be cloned; JSON requests always can
+        let Some(current) = request.try_clone() else {
+            return request.send().await;
+        };
+
+        let delay = match current.send().await {
+            Ok(response) if attempt < max_retries && is_retryable_status(response.status()) => {
+                retry_after(&response).unwrap_or_else(|| backoff_delay(attempt))
+            }
+            Err(e) if attempt < max_retries && e.is_connect() => backoff_delay(attempt),
+            result => return result,
+        };
+
+        attempt += 1;
+        spinner.set_message(format!("Retrying ({}/{})…", attempt, max_retries));
+        tokio::time::sleep(delay.min(MAX_RETRY_DELAY)).await;
+    }
+}
+
 /// Request body for OpenRouter chat completions API.
 #[derive(Serialize)]
 pub struct ChatRequest {
@@ -30,25 +290,162 @@ pub struct ChatRequest {
     pub stream: bool,
     #[serde(skip_serializing_if = "Option::is_none")]
     pub provider: Option<ProviderPreference>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub response_format: Option<serde_json::Value>,
+    /// Sampling temperature; the provider's default when unset.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub temperature: Option<f32>,
+    /// Cap on generated tokens; the provider's default when unset.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub max_tokens: Option<u32>,
+    /// Streaming extras, e.g. `{"include_usage": true}` for a final usage chunk.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub stream_options: Option<serde_json::Value>,
 }
 
 /// Provider ordering preferences for OpenRouter.
-#[derive(Serialize)]
+#[derive(Serialize, Clone)]
 pub struct ProviderPreference {
     pub order: Vec<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub allow_fallbacks: Option<bool>,
+}
+
+impl ProviderPreference {
+    /// Routes requests only to `provider`, with no fallback to others.
+    pub fn only(provider: &str) -> Self {
+        Self {
+            order: vec![provider.to_string()],
+            allow_fallbacks: Some(false),
+        }
+    }
+}
+
+/// Formats an unsuccessful API response, naming a pinned provider if any.
+///
+/// With fallbacks disabled, a routing failure means the pinned provider
+/// can't serve the model, so the error says so instead of just the status.
+pub fn api_error(
+    status: reqwest::StatusCode,
+    body: &str,
+    provider: Option<&ProviderPreference>,
+) -> String {
+    match provider {
+        Some(provider) if provider.allow_fallbacks == Some(false) => format!(
+            "API error ({}) with provider pinned to '{}' (no fallbacks): {}",
+            status,
+            provider.order.join(", "),
+            body
+        ),
+        _ => format!("API error ({}): {}", status, body),
+    }
 }
 
 /// A single message in the chat conversation.
-#[derive(Serialize)]
+#[derive(Serialize, Clone)]
 pub struct Message {
     pub role: String,
-    pub content: String,
+    pub content: MessageContent,
+}
+
+/// Message content: plain text, or text parts carrying cache hints.
+#[derive(Serialize, Clone)]
+#[serde(untagged)]
+pub enum MessageContent {
+    Text(String),
+    Parts(Vec<ContentPart>),
+}
+
+/// A text part of a message's content.
+#[derive(Serialize, Clone)]
+pub struct ContentPart {
+    #[serde(rename = "type")]
+    pub kind: String,
+    pub text: String,
+    /// Marks the prefix up to and including this part as cacheable.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub cache_control: Option<serde_json::Value>,
+}
+
+impl Message {
+    /// A message from the user.
+    pub fn user(content: impl Into<String>) -> Self {
+        Self {
+            role: "user".to_string(),
+            content: MessageContent::Text(content.into()),
+        }
+    }
+
+    /// A previous response from the model.
+    pub fn assistant(content: impl Into<String>) -> Self {
+        Self {
+            role: "assistant".to_string(),
+            content: MessageContent::Text(content.into()),
+        }
+    }
+
+    /// A system message of fixed instructions, marked cacheable for `model`.
+    ///
+    /// Models that take explicit breakpoints (see [`supports_cache_control`])
+    /// get an ephemeral `cache_control` hint; others get plain text, which
+    /// providers with automatic prefix caching reuse as is.
+    pub fn instructions(model: &str, text: &str) -> Self {
+        let content = if supports_cache_control(model) {
+            MessageContent::Parts(vec![ContentPart {
+                kind: "text".to_string(),
+                text: text.to_string(),
+                cache_control: Some(serde_json::json!({ "type": "ephemeral" })),
+            }])
+        } else {
+            MessageContent::Text(text.to_string())
+        };
+        Self {
+            role: "system".to_string(),
+            content,
+        }
+    }
 }
 
 /// A chunk from the streaming response.
 #[derive(Deserialize)]
 pub struct StreamChunk {
+    /// Empty in the final usage-only chunk.
+    #[serde(default)]
     pub choices: Vec<Choice>,
+    #[serde(default)]
+    pub usage: Option<Usage>,
+}
+
+/// Token counts the server reports for a request.
+#[derive(Deserialize, Clone, Copy, Debug, Default)]
+pub struct Usage {
+    #[serde(default)]
+    pub prompt_tokens: u64,
+    #[serde(default)]
+    pub completion_tokens: u64,
+}
+
+impl Usage {
+    /// Adds up the usage of two requests, either of which may be unknown.
+    pub fn combine(first: Option<Usage>, second: Option<Usage>) -> Option<Usage> {
+        match (first, second) {
+            (Some(a), Some(b)) => Some(Usage {
+                prompt_tokens: a.prompt_tokens + b.prompt_tokens,
+                completion_tokens: a.completion_tokens + b.completion_tokens,
+            }),
+            (a, b) => a.or(b),
+        }
+    }
+}
+
+/// A generated commit message and the tokens it took.
+#[derive(Debug)]
+pub struct GeneratedMessage {
+    pub message: String,
+    /// Reported token usage, if the server sent it.
+    pub usage: Option<Usage>,
+    /// The fallback model, if it wrote the message after the first declined.
+    pub fallback_model: Option<String>,
 }
 
 /// A single choice in a streaming chunk.
@@ -79,14 +476,175 @@ pub struct NonStreamMessage {
 #[derive(Deserialize)]
 pub struct NonStreamResponse {
     pub choices: Vec<NonStreamChoice>,
+    #[serde(default)]
+    pub usage: Option<Usage>,
 }
 
-/// Builds the prompt for commit message generation.
+/// Per-call settings for [`stream_commit_message`].
+#[derive(Default, Clone)]
+pub struct GenerationOptions {
+    /// Print the message to stdout as it's generated.
+    pub show_stream: bool,
+    /// With `show_stream`, print tokens as they arrive; otherwise the spinner
+    /// stays up and the finished message is printed once.
+    pub show_tokens: bool,
+    /// Request schema-constrained JSON output instead of free text.
+    pub structured_output: bool,
+    /// Log stream diagnostics to stderr.
+    pub verbose: bool,
+    /// Extra guidance appended to the prompt (e.g. repo commit rules).
+    pub prompt_notes: Vec<String>,
+    /// Initial capacity of the stream line buffer (0 uses the default).
+    pub stream_buffer_size: usize,
+    /// OpenRouter provider routing for the request.
+    pub provider: Option<ProviderPreference>,
+    /// Model to retry with if the first response is a refusal.
+    pub fallback_model: Option<String>,
+    /// Earlier turns (responses and user feedback) sent after the prompt.
+    pub conversation: Vec<Message>,
+    /// The client's request time limit, for reporting a [`RequestTimeout`].
+    pub timeout_secs: u64,
+    /// Retries for transient API failures (see [`send_with_retry`]).
+    pub max_retries: u32,
+    /// Sampling temperature for the request, if set.
+    pub temperature: Option<f32>,
+    /// Cap on generated tokens for the request, if set.
+    pub max_tokens: Option<u32>,
+    /// Ask the server for token usage even without `verbose`.
+    pub request_usage: bool,
+}
+
+/// Reassembles server-sent event lines across network chunks.
 ///
-/// Includes instructions for conventional commit format and the diff/files context.
-pub fn build_prompt(diff: &str, files: &str) -> String {
-    format!(
-        r#"Generate a git commit message for the following changes.
+/// Chunks rarely end on a line boundary, so the unfinished tail of each chunk
+/// stays in the buffer until its newline arrives (an unterminated final line
+/// is discarded, as in the SSE spec). Lines are decoded only once
+/// complete, so multi-byte characters split between chunks survive intact.
+/// The buffer is drained in place and its allocation reused for the whole
+/// response. An unfinished line may grow to [`MAX_SSE_LINE_BYTES`] (or the
+/// initial capacity, if larger); past that the response is rejected rather
+/// than buffered without limit.
+pub struct SseBuffer {
+    pending: Vec<u8>,
+    limit: usize,
+}
+
+/// Longest unfinished line an [`SseBuffer`] holds by default.
+pub const MAX_SSE_LINE_BYTES: usize = 1024 * 1024;
+
+impl SseBuffer {
+    /// Creates a buffer with the given initial capacity (0 uses the default).
+    pub fn with_capacity(capacity: usize) -> Self {
+        let capacity = if capacity == 0 {
+            DEFAULT_STREAM_BUFFER_SIZE
+        } else {
+            capacity
+        };
+        Self {
+            pending: Vec::with_capacity(capacity),
+            limit: capacity.max(MAX_SSE_LINE_BYTES),
+        }
+    }
+
+    /// Appends a chunk and returns the `data:` payloads of the lines it completed.
+    ///
+    /// Fails if the line still unfinished afterwards is longer than the limit.
+    pub fn push(&mut self, chunk: &[u8]) -> Result<Vec<String>, String> {
+        self.pending.extend_from_slice(chunk);
+
+        let mut payloads = Vec::new();
+        let mut start = 0;
+        while let Some(len) = self.pending[start..].iter().position(|&b| b == b'\n') {
+            if let Some(data) = sse_data(&self.pending[start..start + len]) {
+                payloads.push(data);
+            }
+            start += len + 1;
+        }
+        self.pending.drain(..start);
+
+        if self.pending.len() > self.limit {
+            return Err(format!(
+                "Streamed response line exceeded {} bytes without a newline",
+                self.limit
+            ));
+        }
+        Ok(payloads)
+    }
+}
+
+/// Extracts the payload of an SSE `data:` line.
+fn sse_data(line: &[u8]) -> Option<String> {
+    let line = String::from_utf8_lossy(line);
+    line.trim_end_matches('\r')
+        .strip_prefix("data: ")
+        .map(str::to_string)
+}
+
+/// A commit message as returned by structured output.
+#[derive(Deserialize)]
+pub struct StructuredCommit {
+    #[serde(rename = "type")]
+    pub commit_type: String,
+    #[serde(default)]
+    pub scope: String,
+    pub subject: String,
+    #[serde(default)]
+    pub body: String,
+    #[serde(default)]
+    pub breaking: bool,
+}
+
+impl StructuredCommit {
+    /// Renders the fields as a conventional commit message.
+    pub fn render(&self) -> String {
+        let mut message = self.commit_type.trim().to_lowercase();
+        let scope = self.scope.trim();
+        if !scope.is_empty() {
+            message.push_str(&format!("({})", scope));
+        }
+        if self.breaking {
+            message.push('!');
+        }
+        message.push_str(": ");
+        message.push_str(self.subject.trim());
+
+        let body = self.body.trim();
+        if !body.is_empty() {
+            message.push_str("\n\n");
+            message.push_str(body);
+        }
+        message
+    }
+}
+
+/// JSON schema sent as `response_format` for structured commit messages.
+fn commit_message_schema() -> serde_json::Value {
+    serde_json::json!({
+        "type": "json_schema",
+        "json_schema": {
+            "name": "commit_message",
+            "strict": true,
+            "schema": {
+                "type": "object",
+                "properties": {
+                    "type": { "type": "string", "description": "Conventional commit type, lowercase" },
+                    "scope": { "type": "string", "description": "Affected area, or empty string if global" },
+                    "subject": { "type": "string", "description": "Brief description without the type/scope prefix" },
+                    "body": { "type": "string", "description": "Bullet points joined by newlines, or empty string" },
+                    "breaking": { "type": "boolean", "description": "True if this is a breaking change" }
+                },
+                "required": ["type", "scope", "subject", "body", "breaking"],
+                "additionalProperties": false
+            }
+        }
+    })
+}
+
+/// Commit message instructions, identical on every run.
+///
+/// Sent as the system message ahead of the per-diff prompt from
+/// [`build_prompt`], so providers can cache it as a prompt prefix.
+pub const COMMIT_INSTRUCTIONS: &str = r#"Generate a git commit message for the following changes.
 
 FORMAT: type(scope): description
 
@@ -131,24 +689,65 @@ feat(auth): add OAuth2 login support
 
 - Implement Google OAuth provider
 - Add token refresh logic
-- Store credentials in secure keychain
+- Store credentials in secure keychain"#;
 
-Files changed:
+/// True if OpenRouter honors explicit `cache_control` breakpoints for `model`.
+///
+/// Other providers cache a repeated prefix automatically, if at all.
+pub fn supports_cache_control(model: &str) -> bool {
+    model.starts_with("anthropic/") || model.starts_with("google/")
+}
+
+/// Builds the per-diff part of the commit message prompt.
+///
+/// Holds the diff/files context, sent after [`COMMIT_INSTRUCTIONS`]. Each
+/// entry in `notes` is added as repository-specific guidance that takes
+/// precedence over the default rules.
+pub fn build_prompt(diff: &str, files: &str, notes: &[String]) -> String {
+    let notes_text = if notes.is_empty() {
+        String::new()
+    } else {
+        format!(
+            "REPOSITORY RULES (these override the default rules):\n{}\n\n",
+            notes.join("\n")
+        )
+    };
+    format!(
+        r#"{notes}Files changed:
 {files}
 
 Diff:
 {diff}
 
 Commit message:"#,
+        notes = notes_text,
         files = files,
         diff = diff
     )
 }
 
+/// Assembles the commit message conversation for `model`.
+///
+/// The stable [`COMMIT_INSTRUCTIONS`] come first as their own message, then
+/// the per-diff `prompt`, then any earlier exchange from `conversation`.
+pub fn commit_messages(model: &str, prompt: String, conversation: &[Message]) -> Vec<Message> {
+    let mut messages = vec![
+        Message::instructions(model, COMMIT_INSTRUCTIONS),
+        Message::user(prompt),
+    ];
+    messages.extend(conversation.iter().cloned());
+    messages
+}
+
 /// Builds the prompt for PR title and description generation.
 ///
 /// Includes commit list, diff, and files for context.
-pub fn build_pr_prompt(diff: &str, files: &str, commits: &[String]) -> String {
+pub fn build_pr_prompt(
+    diff: &str,
+    files: &str,
+    commits: &[String],
+    template: Option<&str>,
+) -> String {
     let commits_text = commits.join("\n");
     format!(
         r#"Generate a pull request title and description for the following changes.
@@ -158,7 +757,36 @@ Line 1: PR title in format "type(scope): description" (under 72 chars)
 Line 2: (blank)
 Line 3+: Description with sections
 
-DESCRIPTION FORMAT (omit empty sections):
+{description_format}
+
+RULES:
+- Title follows conventional commit format: type(scope): description
+- Summary should explain the "what" and "why" concisely
+- Each bullet should be concise (5-15 words)
+- Focus on behavior changes, not file names
+- Use past tense ("Added", "Fixed", "Updated")
+- Omit empty subsections (e.g., skip Fixed section if no fixes){template_rule}
+
+COMMITS ON THIS BRANCH:
+{commits}
+
+FILES CHANGED:
+{files}
+
+DIFF:
+{diff}
+
+PR title and description:"#,
+        description_format = description_format(PR_DESCRIPTION_FORMAT, template),
+        template_rule = template_rule(template),
+        commits = commits_text,
+        files = files,
+        diff = diff
+    )
+}
+
+/// Default description sections for [`build_pr_prompt`].
+const PR_DESCRIPTION_FORMAT: &str = r#"DESCRIPTION FORMAT (omit empty sections):
 
 ## Summary
 One or two sentences describing what this PR does and why.
@@ -181,111 +809,186 @@ One or two sentences describing what this PR does and why.
 ## Testing
 - what was tested and how
 - specific scenarios verified
-- commands run or manual steps taken
+- commands run or manual steps taken"#;
+
+/// The description format for a PR prompt: `default`, or instructions to
+/// fill in the repository's `template`.
+fn description_format(default: &str, template: Option<&str>) -> String {
+    match template {
+        Some(template) => format!(
+            "DESCRIPTION FORMAT: fill in this repository's PR template. Keep its headings in \
+             order, replace placeholder text and HTML comments with content from the changes, \
+             leave checklist items unchecked unless the changes show they're done, and write \
+             \"N/A\" under a section that doesn't apply.\n\n\
+             TEMPLATE:\n{}",
+            template.trim()
+        ),
+        None => default.to_string(),
+    }
+}
+
+/// The extra rule a PR template adds: its structure wins over the defaults.
+fn template_rule(template: Option<&str>) -> &'static str {
+    match template {
+        Some(_) => {
+            "\n- The template's sections replace the default ones; follow it where rules conflict"
+        }
+        None => "",
+    }
+}
+
+/// Builds the prompt for a PR description organized from commit messages.
+///
+/// Used by `pr --fill-from-commits`: the branch's commit messages stand in
+/// for the diff, grouped by conventional commit type.
+pub fn build_pr_prompt_from_commits(
+    files: &str,
+    messages: &[String],
+    template: Option<&str>,
+) -> String {
+    let messages_text = messages
+        .iter()
+        .map(|message| message.trim())
+        .collect::<Vec<_>>()
+        .join("\n---\n");
+    format!(
+        r#"Generate a pull request title and description from the commit messages on this branch.
+
+The commits already describe the changes: organize and condense them, don't invent detail they don't contain.
+
+OUTPUT FORMAT:
+Line 1: PR title in format "type(scope): description" (under 72 chars)
+Line 2: (blank)
+Line 3+: Description with sections
+
+{description_format}
 
 RULES:
-- Title follows conventional commit format: type(scope): description
-- Summary should explain the "what" and "why" concisely
+- Title summarizes the whole branch, in conventional commit format
+- Base every bullet on the commit messages; merge commits that describe the same change
+- Carry over BREAKING CHANGE notes and important details from commit bodies
 - Each bullet should be concise (5-15 words)
-- Focus on behavior changes, not file names
-- Use past tense ("Added", "Fixed", "Updated")
-- Omit empty subsections (e.g., skip Fixed section if no fixes)
-
-COMMITS ON THIS BRANCH:
-{commits}
+- Do not mention commit SHAs or file names{template_rule}
 
 FILES CHANGED:
 {files}
 
-DIFF:
-{diff}
+COMMIT MESSAGES (oldest first, separated by ---):
+{messages}
 
 PR title and description:"#,
-        commits = commits_text,
+        description_format = description_format(PR_COMMITS_DESCRIPTION_FORMAT, template),
+        template_rule = template_rule(template),
         files = files,
-        diff = diff
+        messages = messages_text
     )
 }
 
+/// Default description sections for [`build_pr_prompt_from_commits`].
+const PR_COMMITS_DESCRIPTION_FORMAT: &str = r#"DESCRIPTION FORMAT (omit empty sections):
+
+## Summary
+One or two sentences describing what this PR does and why.
+
+## Changes
+One subsection per commit type present, in this order:
+### Features
+- feat commits
+### Fixes
+- fix commits
+### Other
+- everything else (refactor, perf, docs, test, chore, ...)
+
+## Notes
+- breaking changes or migration steps from the commit messages"#;
+
 /// Streams PR title and body generation from the LLM.
 ///
-/// Returns (title, body) tuple. Output is printed token-by-token as it streams.
+/// `prompt` comes from [`build_pr_prompt`] or [`build_pr_prompt_from_commits`].
+/// Returns (title, body) tuple. Output is printed token-by-token as it streams,
+/// or all at once when the spinner should stay up instead (`show_tokens` off).
 #[allow(clippy::too_many_arguments)]
 pub async fn stream_pr_content(
     client: &Client,
     api_key: &str,
     model: &str,
-    diff: &str,
-    files: &str,
-    commits: &[String],
+    prompt: String,
     spinner: &ProgressBar,
-    _verbose: bool,
+    buffer_size: usize,
+    provider: Option<&ProviderPreference>,
+    max_retries: u32,
+    show_tokens: bool,
 ) -> Result<(String, String), Box<dyn std::error::Error>> {
-    let prompt = build_pr_prompt(diff, files, commits);
-
     let request = ChatRequest {
         model: model.to_string(),
-        messages: vec![Message {
-            role: "user".to_string(),
-            content: prompt,
-        }],
+        messages: vec![Message::user(prompt)],
         stream: true,
-        provider: None,
+        provider: provider.cloned(),
+        response_format: None,
+        temperature: None,
+        max_tokens: None,
+        stream_options: None,
     };
 
-    let response = client
-        .post(OPENROUTER_API_URL)
-        .header("Authorization", format!("Bearer {}", api_key))
-        .header("Content-Type", "application/json")
-        .header("X-Title", "Committer")
-        .header("HTTP-Referer", "https://github.com/Nolanneff/commiter")
-        .json(&request)
-        .send()
-        .await?;
+    let response = send_with_retry(
+        api_post(client, api_key).json(&request),
+        max_retries,
+        spinner,
+    )
+    .await?;
 
     if !response.status().is_success() {
         let status = response.status();
         let body = response.text().await.unwrap_or_default();
         spinner.disable_steady_tick();
         spinner.finish_and_clear();
-        return Err(format!("API error ({}): {}", status, body).into());
+        return Err(api_error(status, &body, provider).into());
     }
 
     let mut stream = response.bytes_stream();
+    let mut sse = SseBuffer::with_capacity(buffer_size);
     let mut full_message = String::new();
     let mut stdout = io::stdout();
     let mut first_chunk = true;
-    let mut raw_response = String::new();
+    // Only kept for the non-streaming fallback, so dropped once SSE data shows up
+    let mut raw_response = Vec::new();
+    let mut is_sse = false;
 
     'outer: while let Some(chunk_result) = stream.next().await {
         let chunk = chunk_result?;
-        let text = String::from_utf8_lossy(&chunk);
-        raw_response.push_str(&text);
+        if !is_sse {
+            raw_response.extend_from_slice(&chunk);
+        }
 
-        for line in text.lines() {
-            if let Some(data) = line.strip_prefix("data: ") {
-                if data == "[DONE]" {
-                    break 'outer;
-                }
+        for data in sse.push(&chunk)? {
+            if !is_sse {
+                is_sse = true;
+                raw_response = Vec::new();
+            }
+            if data == "[DONE]" {
+                break 'outer;
+            }
 
-                if let Ok(parsed) = serde_json::from_str::<StreamChunk>(data) {
-                    for choice in parsed.choices {
-                        if let Some(content) = choice.delta.content {
-                            if first_chunk {
-                                spinner.disable_steady_tick();
-                                spinner.finish_and_clear();
-                                println!();
-                                first_chunk = false;
-                            }
-                            print!("{}", content);
-                            stdout.flush()?;
-                            full_message.push_str(&content);
+            if let Ok(parsed) = serde_json::from_str::<StreamChunk>(&data) {
+                for choice in parsed.choices {
+                    if let Some(content) = choice.delta.content {
+                        if first_chunk && show_tokens {
+                            spinner.disable_steady_tick();
+                            spinner.finish_and_clear();
+                            println!();
+                        }
+                        first_chunk = false;
+                        if show_tokens {
+                            write!(stdout, "{}", content)?;
                         }
+                        full_message.push_str(&content);
                     }
                 }
             }
         }
+        stdout.flush()?;
     }
+    let raw_response = String::from_utf8_lossy(&raw_response);
 
     // Fallback to non-streaming if needed
     if full_message.is_empty() && !raw_response.is_empty() {
@@ -298,11 +1001,15 @@ pub async fn stream_pr_content(
                 println!("{}", full_message);
             }
         }
-    } else if !first_chunk {
+    } else if show_tokens && !first_chunk {
         println!();
     } else {
         spinner.disable_steady_tick();
         spinner.finish_and_clear();
+        if !first_chunk {
+            println!();
+            println!("{}", full_message.trim());
+        }
     }
 
     // Parse title and body from response
@@ -322,10 +1029,109 @@ pub async fn stream_pr_content(
     Ok((title, body))
 }
 
+/// Requests a structured (JSON schema) commit message and renders it.
+///
+/// Returns `Ok(None)` when the provider rejects `response_format`, so the
+/// caller can fall back to free-text generation. If the model ignores the
+/// schema and answers in plain text, that text is used as the message.
+pub async fn generate_structured_commit_message(
+    client: &Client,
+    api_key: &str,
+    model: &str,
+    diff: &str,
+    files: &str,
+    spinner: &ProgressBar,
+    options: &GenerationOptions,
+) -> Result<Option<GeneratedMessage>, Box<dyn std::error::Error>> {
+    let verbose = options.verbose;
+    let base_prompt = build_prompt(diff, files, &options.prompt_notes);
+    let prompt = format!(
+        "{}\n\nReturn the commit message as JSON. Put only the description in \"subject\" \
+         (no type/scope prefix) and the bullet points, if any, in \"body\".",
+        base_prompt
+            .strip_suffix("Commit message:")
+            .unwrap_or(&base_prompt)
+            .trim_end()
+    );
+
+    let request = ChatRequest {
+        model: model.to_string(),
+        messages: commit_messages(model, prompt, &options.conversation),
+        stream: false,
+        provider: options.provider.clone(),
+        response_format: Some(commit_message_schema()),
+        temperature: options.temperature,
+        max_tokens: options.max_tokens,
+        stream_options: None,
+    };
+
+    let response = send_with_retry(
+        api_post(client, api_key).json(&request),
+        options.max_retries,
+        spinner,
+    )
+    .await?;
+
+    let status = response.status();
+    if !status.is_success() {
+        let body = response.text().await.unwrap_or_default();
+        // 400/404/422 mean the provider can't honor the schema; anything else is a real error
+        if matches!(status.as_u16(), 400 | 404 | 422) {
+            if verbose {
+                eprintln!(
+                    "[Structured] Provider rejected response_format ({}): {}",
+                    status,
+                    body.chars().take(200).collect::<String>()
+                );
+            }
+            return Ok(None);
+        }
+        return Err(api_error(status, &body, options.provider.as_ref()).into());
+    }
+
+    let response_body: NonStreamResponse = response.json().await?;
+    let usage = response_body.usage;
+    let content = response_body
+        .choices
+        .first()
+        .map(|c| c.message.content.clone())
+        .unwrap_or_default();
+
+    let content = content.trim();
+    let json = content.strip_prefix("```json").unwrap_or(content);
+    let json = json.strip_prefix("```").unwrap_or(json);
+    let json = json.strip_suffix("```").unwrap_or(json).trim();
+
+    let message = match serde_json::from_str::<StructuredCommit>(json) {
+        Ok(parsed) => parsed.render(),
+        Err(e) => {
+            if verbose {
+                eprintln!(
+                    "[Structured] Response was not valid JSON ({}), using as text",
+                    e
+                );
+            }
+            content.to_string()
+        }
+    };
+    Ok(Some(GeneratedMessage {
+        message,
+        usage,
+        fallback_model: None,
+    }))
+}
+
 /// Streams commit message generation from the LLM.
 ///
-/// Output is printed token-by-token as it streams. Falls back to non-streaming
-/// parsing if the response doesn't use SSE format.
+/// When `options.show_stream` is set, output is printed token-by-token as it
+/// streams (or all at once without `options.show_tokens`); otherwise the
+/// message is only returned. If the response looks
+/// like a refusal, generation is retried once (with
+/// `options.fallback_model` when set), and a second refusal is an error.
+///
+/// The returned usage covers both attempts when there was a retry. It's only
+/// requested from the server when `options.verbose` or
+/// `options.request_usage` is set.
 pub async fn stream_commit_message(
     client: &Client,
     api_key: &str,
@@ -333,36 +1139,198 @@ pub async fn stream_commit_message(
     diff: &str,
     files: &str,
     spinner: &ProgressBar,
-    verbose: bool,
-) -> Result<String, Box<dyn std::error::Error>> {
-    let prompt = build_prompt(diff, files);
+    options: &GenerationOptions,
+) -> Result<GeneratedMessage, Box<dyn std::error::Error>> {
+    let first = generate_commit_message(client, api_key, model, diff, files, spinner, options)
+        .await
+        .map_err(|e| name_timeout(e, options.timeout_secs))?;
+    if !looks_like_refusal(&first.message) {
+        return Ok(first);
+    }
+
+    let retry_model = options.fallback_model.as_deref().unwrap_or(model);
+    // stderr, so `--print-branch` output stays clean
+    eprintln!(
+        "{} {} declined to write a commit message; retrying with {}",
+        console::style("!").yellow(),
+        model,
+        retry_model
+    );
+
+    let retry_spinner = phase_spinner(phase_message("Generating commit message", retry_model));
+    retry_spinner.enable_steady_tick(Duration::from_millis(80));
+    let retry = generate_commit_message(
+        client,
+        api_key,
+        retry_model,
+        diff,
+        files,
+        &retry_spinner,
+        options,
+    )
+    .await
+    .map_err(|e| name_timeout(e, options.timeout_secs))?;
+    let message = retry.message;
+
+    if looks_like_refusal(&message) {
+        return Err(format!(
+            "{} declined to generate a commit message: \"{}\"\n  Try another model with --model, or set fallback_model",
+            retry_model,
+            message.lines().next().unwrap_or_default()
+        )
+        .into());
+    }
+
+    Ok(GeneratedMessage {
+        message,
+        usage: Usage::combine(first.usage, retry.usage),
+        fallback_model: (retry_model != model).then(|| retry_model.to_string()),
+    })
+}
+
+/// Generates `count` candidate messages with separate requests, all at once.
+///
+/// Candidates are compared whole, so nothing is streamed whatever
+/// `options.show_stream` says; `spinner` counts the finished requests.
+/// Duplicates are merged and failed requests skipped, so there may be fewer
+/// than `count`; it's an error only if every request fails. The usage
+/// covers all requests.
+#[allow(clippy::too_many_arguments)]
+pub async fn generate_candidates(
+    client: &Client,
+    api_key: &str,
+    model: &str,
+    diff: &str,
+    files: &str,
+    spinner: &ProgressBar,
+    options: &GenerationOptions,
+    count: usize,
+) -> Result<(Vec<String>, Option<Usage>), Box<dyn std::error::Error>> {
+    let options = GenerationOptions {
+        show_stream: false,
+        ..options.clone()
+    };
+    let hidden = ProgressBar::hidden();
+    let mut finished = 0;
+    let results: Vec<_> =
+        futures::stream::iter((0..count).map(|_| {
+            stream_commit_message(client, api_key, model, diff, files, &hidden, &options)
+        }))
+        .buffer_unordered(count)
+        .inspect(|_| {
+            finished += 1;
+            spinner.set_message(phase_message(
+                &format!("Generating candidates ({}/{})", finished, count),
+                model,
+            ));
+        })
+        .collect()
+        .await;
+
+    let mut candidates: Vec<String> = Vec::new();
+    let mut usage = None;
+    let mut first_error = None;
+    for result in results {
+        match result {
+            Ok(generated) => {
+                usage = Usage::combine(usage, generated.usage);
+                let message = generated.message.trim().to_string();
+                if !message.is_empty() && !candidates.contains(&message) {
+                    candidates.push(message);
+                }
+            }
+            Err(e) => {
+                if options.verbose {
+                    eprintln!("— A candidate request failed: {}", e);
+                }
+                first_error.get_or_insert(e);
+            }
+        }
+    }
+
+    match (candidates.is_empty(), first_error) {
+        (true, Some(e)) => Err(e),
+        _ => Ok((candidates, usage)),
+    }
+}
+
+/// Runs a single commit message generation request.
+///
+/// Falls back to non-streaming parsing if the response doesn't use SSE format.
+async fn generate_commit_message(
+    client: &Client,
+    api_key: &str,
+    model: &str,
+    diff: &str,
+    files: &str,
+    spinner: &ProgressBar,
+    options: &GenerationOptions,
+) -> Result<GeneratedMessage, Box<dyn std::error::Error>> {
+    let verbose = options.verbose;
+    let show_stream = options.show_stream;
+    let show_tokens = show_stream && options.show_tokens;
+
+    if options.structured_output {
+        let structured = generate_structured_commit_message(
+            client, api_key, model, diff, files, spinner, options,
+        )
+        .await;
+        let structured = match structured {
+            Ok(result) => result,
+            Err(e) => {
+                spinner.disable_steady_tick();
+                spinner.finish_and_clear();
+                return Err(e);
+            }
+        };
+
+        if let Some(generated) = structured {
+            spinner.disable_steady_tick();
+            spinner.finish_and_clear();
+            if show_stream {
+                println!();
+                println!("{}", generated.message);
+            }
+            return Ok(GeneratedMessage {
+                message: generated.message.trim().to_string(),
+                usage: generated.usage,
+                fallback_model: None,
+            });
+        }
+
+        if verbose {
+            eprintln!("[Structured] Falling back to text generation");
+        }
+    }
+
+    let prompt = build_prompt(diff, files, &options.prompt_notes);
 
     let request = ChatRequest {
         model: model.to_string(),
-        messages: vec![Message {
-            role: "user".to_string(),
-            content: prompt,
-        }],
+        messages: commit_messages(model, prompt, &options.conversation),
         stream: true,
-        provider: None,
+        provider: options.provider.clone(),
+        response_format: None,
+        temperature: options.temperature,
+        max_tokens: options.max_tokens,
+        // Usage is only shown with --verbose (or by bench), so don't ask for it otherwise
+        stream_options: (verbose || options.request_usage)
+            .then(|| serde_json::json!({ "include_usage": true })),
     };
 
-    let response = client
-        .post(OPENROUTER_API_URL)
-        .header("Authorization", format!("Bearer {}", api_key))
-        .header("Content-Type", "application/json")
-        .header("X-Title", "Committer")
-        .header("HTTP-Referer", "https://github.com/Nolanneff/commiter")
-        .json(&request)
-        .send()
-        .await?;
+    let response = send_with_retry(
+        api_post(client, api_key).json(&request),
+        options.max_retries,
+        spinner,
+    )
+    .await?;
 
     if !response.status().is_success() {
         let status = response.status();
         let body = response.text().await.unwrap_or_default();
         spinner.disable_steady_tick();
         spinner.finish_and_clear();
-        return Err(format!("API error ({}): {}", status, body).into());
+        return Err(api_error(status, &body, options.provider.as_ref()).into());
     }
 
     if verbose {
@@ -370,17 +1338,21 @@ pub async fn stream_commit_message(
     }
 
     let mut stream = response.bytes_stream();
+    let mut sse = SseBuffer::with_capacity(options.stream_buffer_size);
     let mut full_message = String::new();
     let mut stdout = io::stdout();
     let mut first_chunk = true;
-    let mut raw_response = String::new();
+    // Only kept for the non-streaming fallback, so dropped once SSE data shows up
+    let mut raw_response = Vec::new();
     let mut chunk_count = 0;
     let mut sse_lines_found = 0;
+    let mut usage = None;
 
     'outer: while let Some(chunk_result) = stream.next().await {
         let chunk = chunk_result?;
-        let text = String::from_utf8_lossy(&chunk);
-        raw_response.push_str(&text);
+        if sse_lines_found == 0 {
+            raw_response.extend_from_slice(&chunk);
+        }
         chunk_count += 1;
 
         if verbose {
@@ -388,53 +1360,65 @@ pub async fn stream_commit_message(
                 "[Stream] Chunk {}: {} bytes, preview: {:?}",
                 chunk_count,
                 chunk.len(),
-                text.chars().take(100).collect::<String>()
+                String::from_utf8_lossy(&chunk)
+                    .chars()
+                    .take(100)
+                    .collect::<String>()
             );
         }
 
         // SSE format: each line starts with "data: "
-        for line in text.lines() {
-            if let Some(data) = line.strip_prefix("data: ") {
-                sse_lines_found += 1;
-                if data == "[DONE]" {
-                    if verbose {
-                        eprintln!("[Stream] Received [DONE] signal");
-                    }
-                    break 'outer;
+        for data in sse.push(&chunk)? {
+            if sse_lines_found == 0 {
+                raw_response = Vec::new();
+            }
+            sse_lines_found += 1;
+            if data == "[DONE]" {
+                if verbose {
+                    eprintln!("[Stream] Received [DONE] signal");
                 }
+                break 'outer;
+            }
 
-                match serde_json::from_str::<StreamChunk>(data) {
-                    Ok(parsed) => {
-                        for choice in parsed.choices {
-                            if let Some(content) = choice.delta.content {
-                                if first_chunk {
+            match serde_json::from_str::<StreamChunk>(&data) {
+                Ok(parsed) => {
+                    usage = parsed.usage.or(usage);
+                    for choice in parsed.choices {
+                        if let Some(content) = choice.delta.content {
+                            if first_chunk {
+                                // Without token display the spinner runs until the end
+                                if show_tokens {
                                     if verbose {
                                         eprintln!("[Stream] First content chunk, clearing spinner");
                                     }
                                     spinner.disable_steady_tick();
                                     spinner.finish_and_clear();
                                     println!(); // Ensure clean line after spinner
-                                    first_chunk = false;
                                 }
-                                print!("{}", content);
-                                stdout.flush()?;
-                                full_message.push_str(&content);
+                                first_chunk = false;
+                            }
+                            if show_tokens {
+                                write!(stdout, "{}", content)?;
                             }
+                            full_message.push_str(&content);
                         }
                     }
-                    Err(e) => {
-                        if verbose {
-                            eprintln!(
-                                "[Stream] Parse error: {} for data: {:?}",
-                                e,
-                                data.chars().take(100).collect::<String>()
-                            );
-                        }
+                }
+                Err(e) => {
+                    if verbose {
+                        eprintln!(
+                            "[Stream] Parse error: {} for data: {:?}",
+                            e,
+                            data.chars().take(100).collect::<String>()
+                        );
                     }
                 }
             }
         }
+        // One flush per network chunk keeps long outputs smooth without a syscall per token
+        stdout.flush()?;
     }
+    let raw_response = String::from_utf8_lossy(&raw_response);
 
     if verbose {
         eprintln!(
@@ -460,9 +1444,12 @@ pub async fn stream_commit_message(
 
         // Try parsing as a complete non-streaming response
         if let Ok(parsed) = serde_json::from_str::<NonStreamResponse>(&raw_response) {
+            usage = parsed.usage;
             if let Some(choice) = parsed.choices.first() {
                 full_message = choice.message.content.clone();
-                println!("{}", full_message);
+                if show_stream {
+                    println!("{}", full_message);
+                }
                 if verbose {
                     eprintln!("[Stream] Fallback succeeded");
                 }
@@ -470,14 +1457,83 @@ pub async fn stream_commit_message(
         } else if verbose {
             eprintln!("[Stream] Fallback parse failed");
         }
-    } else if !first_chunk {
+    } else if show_tokens && !first_chunk {
         // Only print newline if we actually printed content
         println!();
     } else {
-        // Spinner still running but no content - clear it
+        // Spinner still running (no content, or none shown yet) - clear it
         spinner.disable_steady_tick();
         spinner.finish_and_clear();
+        if show_stream && !first_chunk {
+            println!();
+            println!("{}", full_message.trim());
+        }
+    }
+
+    Ok(GeneratedMessage {
+        message: full_message.trim().to_string(),
+        usage,
+        fallback_model: None,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn request(temperature: Option<f32>, max_tokens: Option<u32>) -> ChatRequest {
+        ChatRequest {
+            model: "test/model".to_string(),
+            messages: vec![Message::user("Describe the diff")],
+            stream: true,
+            provider: None,
+            response_format: None,
+            temperature,
+            max_tokens,
+            stream_options: None,
+        }
     }
 
-    Ok(full_message.trim().to_string())
+    #[test]
+    fn chat_request_omits_unset_sampling_options() {
+        let body = serde_json::to_value(request(None, None)).unwrap();
+        let body = body.as_object().unwrap();
+        assert!(!body.contains_key("temperature"));
+        assert!(!body.contains_key("max_tokens"));
+        assert_eq!(body["model"], "test/model");
+    }
+
+    #[test]
+    fn chat_request_sends_set_sampling_options() {
+        let body = serde_json::to_value(request(Some(0.5), Some(200))).unwrap();
+        assert_eq!(body["temperature"], 0.5);
+        assert_eq!(body["max_tokens"], 200);
+    }
+
+    #[test]
+    fn sse_buffer_reassembles_lines_split_across_chunks() {
+        let mut sse = SseBuffer::with_capacity(0);
+        assert!(sse.push(b"data: {\"a\":").unwrap().is_empty());
+        assert_eq!(
+            sse.push(b"1}\r\n\ndata: [DONE]\n").unwrap(),
+            vec!["{\"a\":1}".to_string(), "[DONE]".to_string()]
+        );
+    }
+
+    #[test]
+    fn sse_buffer_rejects_an_endless_line() {
+        let mut sse = SseBuffer::with_capacity(0);
+        let chunk = vec![b'x'; MAX_SSE_LINE_BYTES / 2 + 1];
+        assert!(sse.push(&chunk).is_ok());
+        assert!(sse.push(&chunk).is_err());
+    }
+
+    #[test]
+    fn backoff_doubles_then_stays_capped() {
+        assert_eq!(backoff_delay(0), RETRY_BASE_DELAY);
+        assert_eq!(backoff_delay(3), RETRY_BASE_DELAY * 8);
+        assert_eq!(backoff_delay(10), MAX_RETRY_DELAY);
+        assert_eq!(backoff_delay(32), MAX_RETRY_DELAY);
+        assert_eq!(backoff_delay(u32::MAX), MAX_RETRY_DELAY);
+    }
 }
@@ -0,0 +1,247 @@
+//! Model comparison for `committer bench`.
+//!
+//! Generates a message for the staged changes with each model, a few at a
+//! time, and prints every result with its latency, token usage, and cost.
+//! The prompt is the one a commit would use (repo conventions and body
+//! rules included), so models are compared on real work. Nothing is
+//! committed or recorded.
+
+use console::style;
+use futures::StreamExt;
+use indicatif::ProgressBar;
+use reqwest::Client;
+use std::time::{Duration, Instant};
+
+use crate::api::{
+    build_client, describe_headers, stream_commit_message, uses_openrouter, GenerationOptions,
+    Usage,
+};
+use crate::cli::BenchArgs;
+use crate::commit::collect_prompt_notes;
+use crate::config::{get_api_key, Config};
+use crate::convention::load_commit_rules;
+use crate::exit_code;
+use crate::git::{get_git_diff, get_repo_root, get_staged_files, run_diff_preprocessor};
+use crate::models::{estimate_cost, fit_diff_to_context, get_model_info};
+use crate::ui::{format_count, phase_spinner};
+
+/// One model's run.
+struct BenchResult {
+    model: String,
+    elapsed: Duration,
+    /// The generated message and its usage, or why generation failed.
+    outcome: Result<(String, Option<Usage>), String>,
+}
+
+/// Main handler for the `committer bench` subcommand.
+pub async fn handle_bench_command(
+    args: BenchArgs,
+    config: &Config,
+) -> Result<(), Box<dyn std::error::Error>> {
+    let verbose = args.verbose || config.verbose;
+
+    let mut models: Vec<String> = Vec::new();
+    let names = if args.models.is_empty() {
+        &config.bench_models
+    } else {
+        &args.models
+    };
+    for name in names {
+        let model = config.resolve_model(name);
+        if !models.contains(&model) {
+            models.push(model);
+        }
+    }
+    if models.is_empty() {
+        println!("{} No models to compare", style("✗").red());
+        println!(
+            "  {} Pass them as arguments, or set bench_models in the config file",
+            style("→").dim()
+        );
+        std::process::exit(exit_code::FAILURE);
+    }
+
+    let api_key = match get_api_key(config, args.api_key_file.as_deref())? {
+        Some(key) => key,
+        // Local servers usually don't check one
+        None if !uses_openrouter() => String::new(),
+        None => {
+            println!("{} No API key found", style("✗").red());
+            println!(
+                "  {} Set OPENROUTER_API_KEY, api_key in the config file, or pass --api-key-file",
+                style("→").dim()
+            );
+            std::process::exit(exit_code::FAILURE);
+        }
+    };
+
+    let (diff, files) = tokio::join!(get_git_diff(true, verbose), get_staged_files(verbose));
+    let mut diff = diff?;
+    let files = files?;
+    if diff.trim().is_empty() {
+        println!("{} No staged changes to compare on", style("⚠").yellow());
+        println!("  {} Use 'git add' first", style("→").dim());
+        std::process::exit(exit_code::NOTHING_STAGED);
+    }
+    if let Some(command) = &config.diff_preprocessor {
+        diff = run_diff_preprocessor(command, &diff, verbose).await?;
+    }
+
+    let client = build_client(&config.extra_headers, config.timeout_secs)?;
+    if verbose && !config.extra_headers.is_empty() {
+        eprintln!(
+            "— Extra API headers: {}",
+            describe_headers(&config.extra_headers)
+        );
+    }
+    let convention = load_commit_rules(get_repo_root().await.as_deref(), &config.valid_scopes);
+    let prompt_notes = collect_prompt_notes(&diff, convention.as_ref(), config).await;
+    let options = GenerationOptions {
+        show_stream: false,
+        show_tokens: false,
+        structured_output: config.structured_output,
+        verbose,
+        prompt_notes,
+        stream_buffer_size: config.stream_buffer_size,
+        provider: None,
+        // Each model is judged on its own answer
+        fallback_model: None,
+        conversation: Vec::new(),
+        timeout_secs: config.timeout_secs,
+        max_retries: config.max_retries,
+        temperature: config.temperature,
+        max_tokens: config.max_tokens,
+        request_usage: true,
+    };
+
+    let jobs = args.jobs.max(1);
+    let spinner = phase_spinner(format!(
+        "Generating with {} models ({} at a time)",
+        models.len(),
+        jobs.min(models.len())
+    ));
+    spinner.enable_steady_tick(Duration::from_millis(120));
+
+    let total = models.len();
+    let mut done = 0;
+    let mut results: Vec<BenchResult> = futures::stream::iter(
+        models
+            .iter()
+            .map(|model| bench_model(&client, &api_key, model, diff.clone(), &files, &options)),
+    )
+    .buffer_unordered(jobs)
+    .inspect(|result| {
+        done += 1;
+        spinner.set_message(format!("{}/{} done, last: {}", done, total, result.model));
+    })
+    .collect()
+    .await;
+    spinner.finish_and_clear();
+    // Report in the order the models were given, not the order they finished
+    results.sort_by_key(|result| models.iter().position(|model| *model == result.model));
+
+    let mut rows = Vec::new();
+    for result in &results {
+        let (usage_label, cost_label) = match &result.outcome {
+            Ok((_, Some(usage))) => {
+                let cost = get_model_info(&client, &result.model, verbose)
+                    .await
+                    .and_then(|info| {
+                        estimate_cost(
+                            &info,
+                            usage.prompt_tokens as usize,
+                            usage.completion_tokens as usize,
+                        )
+                    });
+                (
+                    format!(
+                        "{} + {} tokens",
+                        format_count(usage.prompt_tokens),
+                        format_count(usage.completion_tokens)
+                    ),
+                    cost.map(|cost| format!("~${:.4}", cost.input + cost.max_output)),
+                )
+            }
+            _ => ("tokens unknown".to_string(), None),
+        };
+        rows.push((usage_label, cost_label));
+    }
+
+    for (result, (usage_label, cost_label)) in results.iter().zip(&rows) {
+        println!();
+        let seconds = format!("{:.1}s", result.elapsed.as_secs_f64());
+        match &result.outcome {
+            Ok((message, _)) => {
+                let mut details = vec![seconds, usage_label.clone()];
+                details.extend(cost_label.clone());
+                println!(
+                    "{} {}",
+                    style(&result.model).cyan().bold(),
+                    style(format!("({})", details.join(", "))).dim()
+                );
+                for line in message.lines() {
+                    println!("  {}", line);
+                }
+            }
+            Err(e) => {
+                println!(
+                    "{} {}",
+                    style(&result.model).cyan().bold(),
+                    style(format!("({})", seconds)).dim()
+                );
+                println!("  {} {}", style("✗").red(), e);
+            }
+        }
+    }
+
+    // One line per model, so the tradeoffs can be read at a glance
+    println!();
+    let width = results.iter().map(|r| r.model.len()).max().unwrap_or(0);
+    for (result, (usage_label, cost_label)) in results.iter().zip(&rows) {
+        let status = match &result.outcome {
+            Ok(_) => style("✓").green(),
+            Err(_) => style("✗").red(),
+        };
+        let line = format!(
+            "{:<width$}  {:>6}  {:<24}  {}",
+            result.model,
+            format!("{:.1}s", result.elapsed.as_secs_f64()),
+            usage_label,
+            cost_label.as_deref().unwrap_or(""),
+            width = width
+        );
+        println!("{} {}", status, line.trim_end());
+    }
+    Ok(())
+}
+
+/// Generates one model's message, timing the whole request.
+async fn bench_model(
+    client: &Client,
+    api_key: &str,
+    model: &str,
+    diff: String,
+    files: &str,
+    options: &GenerationOptions,
+) -> BenchResult {
+    // Each model may have a different context window
+    let diff = fit_diff_to_context(client, model, diff, files, options.verbose).await;
+    let started = Instant::now();
+    let generated = stream_commit_message(
+        client,
+        api_key,
+        model,
+        &diff,
+        files,
+        &ProgressBar::hidden(),
+        options,
+    )
+    .await;
+    BenchResult {
+        model: model.to_string(),
+        elapsed: started.elapsed(),
+        outcome: generated
+            .map(|generated| (generated.message, generated.usage))
+            .map_err(|e| e.to_string()),
+    }
+}
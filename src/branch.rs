@@ -11,12 +11,33 @@
 //! Generated branch names follow the pattern: `<type>/<scope>-<description>`
 //!
 //! Examples: `feat/auth-login`, `fix/ui-button-style`, `refactor/api-client`
+//!
+//! # Declined Suggestions
+//!
+//! When a suggestion from branch analysis is declined, it is remembered per
+//! repository (in `.git/`) for [`DECLINE_WINDOW_SECS`], and the same name is
+//! not offered again on the same branch until then.
 
+use indicatif::ProgressBar;
 use regex_lite::Regex;
 use reqwest::Client;
-use serde::Deserialize;
+use serde::{Deserialize, Serialize};
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use crate::api::{
+    api_error, api_post, send_with_retry, ChatRequest, Message, NonStreamResponse,
+    ProviderPreference,
+};
+use crate::config::{BranchOverflowStrategy, BranchStrictness, BranchSuggestionMode};
+use crate::emoji::strip_emoji_prefix;
+use crate::format::commit_type;
+use crate::git::get_git_path;
 
-use crate::api::{ChatRequest, Message, NonStreamResponse, OPENROUTER_API_URL};
+/// File inside the git directory that remembers declined suggestions.
+pub const DECLINED_BRANCHES_FILE: &str = "COMMITTER_DECLINED_BRANCHES.json";
+
+/// How long a declined suggestion is held back.
+pub const DECLINE_WINDOW_SECS: u64 = 12 * 60 * 60;
 
 /// Branches that should never receive direct commits.
 pub const PROTECTED_BRANCHES: &[&str] =
@@ -75,6 +96,64 @@ pub enum BranchAction {
     Skip,
 }
 
+/// A branch suggestion the user turned down.
+#[derive(Serialize, Deserialize)]
+struct DeclinedBranch {
+    /// Branch the user chose to stay on.
+    branch: String,
+    /// The suggested branch name.
+    suggested: String,
+    /// Seconds since the Unix epoch when it was declined.
+    declined_at: u64,
+}
+
+fn now_secs() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.as_secs())
+        .unwrap_or(0)
+}
+
+/// Loads declined suggestions that are still within the window.
+fn load_declined(path: &std::path::Path) -> Vec<DeclinedBranch> {
+    let Ok(contents) = std::fs::read_to_string(path) else {
+        return Vec::new();
+    };
+    let declined: Vec<DeclinedBranch> = serde_json::from_str(&contents).unwrap_or_default();
+    let now = now_secs();
+    declined
+        .into_iter()
+        .filter(|d| now.saturating_sub(d.declined_at) < DECLINE_WINDOW_SECS)
+        .collect()
+}
+
+/// Returns true if `suggested` was declined on `branch` within the window.
+pub async fn was_recently_declined(branch: &str, suggested: &str) -> bool {
+    let Some(path) = get_git_path(DECLINED_BRANCHES_FILE).await else {
+        return false;
+    };
+    load_declined(&path)
+        .iter()
+        .any(|d| d.branch == branch && d.suggested == suggested)
+}
+
+/// Remembers that `suggested` was declined while on `branch` (best-effort).
+pub async fn record_declined_branch(branch: &str, suggested: &str) {
+    let Some(path) = get_git_path(DECLINED_BRANCHES_FILE).await else {
+        return;
+    };
+    let mut declined = load_declined(&path);
+    declined.retain(|d| !(d.branch == branch && d.suggested == suggested));
+    declined.push(DeclinedBranch {
+        branch: branch.to_string(),
+        suggested: suggested.to_string(),
+        declined_at: now_secs(),
+    });
+    if let Ok(json) = serde_json::to_string_pretty(&declined) {
+        let _ = std::fs::write(&path, json);
+    }
+}
+
 /// Converts text to a kebab-case slug suitable for branch names.
 ///
 /// Filters out common filler words and limits to `max_words`.
@@ -103,12 +182,81 @@ pub fn slugify(text: &str, max_words: usize) -> String {
         .collect()
 }
 
+/// Returns the ticket reference in a branch name, e.g. `PROJ-123` in
+/// `feat/PROJ-123-login`.
+///
+/// Uses the first capture group of `pattern` if it has one, otherwise the
+/// whole match. Returns `None` if the branch names no ticket.
+pub fn ticket_from_branch(branch: &str, pattern: &Regex) -> Option<String> {
+    let captures = pattern.captures(branch)?;
+    let ticket = captures.get(1).or_else(|| captures.get(0))?.as_str();
+    (!ticket.is_empty()).then(|| ticket.to_string())
+}
+
+/// Cuts a name to at most `max_len` characters, at a `-` or `/` boundary
+/// where one exists so no word is cut in half.
+fn truncate_branch(name: &str, max_len: usize) -> String {
+    if name.len() <= max_len {
+        return name.to_string();
+    }
+
+    let mut end = max_len;
+    while !name.is_char_boundary(end) {
+        end -= 1;
+    }
+    let cut = if name[end..].starts_with(['-', '/']) {
+        &name[..end]
+    } else {
+        // Back up to the last word boundary, unless only the type prefix would remain
+        let prefix_end = name.find('/').map_or(0, |i| i + 1);
+        match name[..end].rfind('-') {
+            Some(i) if i > prefix_end => &name[..i],
+            _ => &name[..end],
+        }
+    };
+    cut.trim_end_matches(['-', '/']).to_string()
+}
+
+/// Shortens a suggested branch name to fit `max_len`.
+///
+/// [`BranchOverflowStrategy::Abbreviate`] keeps `type/` and the scope (the
+/// first word after it) and reduces the rest of the description to its two
+/// leading non-filler words, e.g. `feat/auth-refresh-the-token-with-backoff`
+/// becomes `feat/auth-refresh-token`. Whatever still doesn't fit is
+/// truncated at a word boundary.
+pub fn fit_branch_name(name: &str, max_len: usize, strategy: BranchOverflowStrategy) -> String {
+    if name.len() <= max_len {
+        return name.to_string();
+    }
+
+    let name = match strategy {
+        BranchOverflowStrategy::Truncate => name.to_string(),
+        BranchOverflowStrategy::Abbreviate => {
+            let (prefix, slug) = match name.split_once('/') {
+                Some((prefix, slug)) => (format!("{}/", prefix), slug),
+                None => (String::new(), name),
+            };
+            let mut words = slug.split('-').filter(|w| !w.is_empty());
+            let scope = words.next().unwrap_or_default();
+            let key_words: Vec<&str> = words
+                .filter(|w| !FILLER_WORDS.contains(w))
+                .take(2)
+                .collect();
+            let mut parts = vec![scope];
+            parts.extend(key_words);
+            format!("{}{}", prefix, parts.join("-"))
+        }
+    };
+
+    truncate_branch(&name, max_len)
+}
+
 /// Generates a branch name from a commit message without LLM.
 ///
 /// Parses conventional commit format to extract type/scope, falling back
 /// to `feat/<slug>` if parsing fails.
 pub fn generate_fallback_branch(commit_message: &str) -> String {
-    let first_line = commit_message.lines().next().unwrap_or(commit_message);
+    let first_line = strip_emoji_prefix(commit_message.lines().next().unwrap_or(commit_message));
 
     let re = Regex::new(r"^([a-z]+)(?:\(([^)]+)\))?:\s*(.+)$").unwrap();
     if let Some(caps) = re.captures(first_line) {
@@ -127,9 +275,57 @@ pub fn generate_fallback_branch(commit_message: &str) -> String {
     }
 }
 
+/// Makes a suggested branch's `<type>/` prefix agree with the commit's type.
+///
+/// Models sometimes suggest `feat/...` for a `fix:` commit. When the message
+/// has a conventional type, the branch's leading lowercase segment is
+/// replaced with it (`feat/ui-scroll` becomes `fix/ui-scroll`), or it's
+/// prepended when the name has no type prefix. Without a type in the
+/// message, the branch is returned as is.
+pub fn align_branch_type(branch: &str, commit_message: &str) -> String {
+    let Some(commit_type) = commit_type(commit_message) else {
+        return branch.to_string();
+    };
+    let rest = match branch.split_once('/') {
+        Some((prefix, rest))
+            if !prefix.is_empty() && prefix.chars().all(|c| c.is_ascii_lowercase()) =>
+        {
+            rest
+        }
+        _ => branch,
+    };
+    format!("{}/{}", commit_type, rest)
+}
+
+/// The branch analysis prompt rules that depend on `strictness`.
+///
+/// Returns the scope rule (rule 2) and the mismatch rules (rules 4 and 5).
+fn strictness_rules(strictness: BranchStrictness) -> (&'static str, &'static str) {
+    match strictness {
+        BranchStrictness::Strict => (
+            r#"2. The commit scope/module MUST relate to the branch name. Example: branch "feat/auth-login" should only have auth-related commits, NOT unrelated features like "feat(db): add migration""#,
+            r#"4. If the commit introduces a NEW scope/module not mentioned in the branch name, flag as MISMATCH
+5. Be STRICT: when in doubt, flag as mismatch. It's better to suggest a new branch than pollute an existing one with unrelated work"#,
+        ),
+        BranchStrictness::Balanced => (
+            r#"2. The commit should relate to the branch's feature. Example: branch "feat/auth-login" can have auth and session commits, NOT unrelated features like "feat(db): add migration""#,
+            r#"4. A NEW scope/module is fine when it's part of the same feature or directly supports it; flag as MISMATCH when it's separate work
+5. Be BALANCED: flag a mismatch when the commit is clearly unrelated, not just because its scope differs from the branch name"#,
+        ),
+        BranchStrictness::Lenient => (
+            r#"2. Branches cover broad efforts: related modules, supporting refactors, tests, docs, and chores for the same work all belong"#,
+            r#"4. Only flag as MISMATCH when the commit has nothing to do with the branch's purpose
+5. Be LENIENT: when in doubt, it matches. Suggest a new branch only for obviously unrelated work"#,
+        ),
+    }
+}
+
 /// Analyzes whether a commit belongs on the current branch using LLM.
 ///
-/// Returns analysis with match status, reason, and suggested branch name.
+/// `strictness` sets how readily a commit is flagged as a mismatch. Returns
+/// analysis with match status, reason, and suggested branch name, whose type
+/// prefix is kept in line with the commit (see [`align_branch_type`]).
+#[allow(clippy::too_many_arguments)]
 pub async fn analyze_branch_alignment(
     client: &Client,
     api_key: &str,
@@ -138,7 +334,14 @@ pub async fn analyze_branch_alignment(
     commit_message: &str,
     files_changed: &str,
     recent_commits: &str,
+    provider: Option<&ProviderPreference>,
+    max_retries: u32,
+    spinner: &ProgressBar,
+    temperature: Option<f32>,
+    max_tokens: Option<u32>,
+    strictness: BranchStrictness,
 ) -> Result<BranchAnalysis, Box<dyn std::error::Error>> {
+    let (scope_rule, mismatch_rules) = strictness_rules(strictness);
     let prompt = format!(
         r#"You are a git branch analyzer. Determine if the current commit belongs on this branch.
 
@@ -155,10 +358,9 @@ NEW COMMIT MESSAGE:
 
 ANALYSIS RULES:
 1. Protected branches (main, master, develop, dev, staging, production) - NEVER match, always suggest a feature branch
-2. The commit scope/module MUST relate to the branch name. Example: branch "feat/auth-login" should only have auth-related commits, NOT unrelated features like "feat(db): add migration"
+{scope_rule}
 3. Different commit TYPES (feat, fix, refactor, docs, test) on the SAME feature are fine - e.g., feat/auth can have "feat(auth): add login" then "fix(auth): handle edge case" then "docs(auth): add comments"
-4. If the commit introduces a NEW scope/module not mentioned in the branch name, flag as MISMATCH
-5. Be STRICT: when in doubt, flag as mismatch. It's better to suggest a new branch than pollute an existing one with unrelated work
+{mismatch_rules}
 
 BRANCH NAMING CONVENTION: <type>/<scope>-<short-description>
 Examples: feat/auth-refresh-token, fix/ui-chat-scroll, refactor/server-ws-reconnect
@@ -170,28 +372,26 @@ Respond with ONLY valid JSON:
 
     let request = ChatRequest {
         model: model.to_string(),
-        messages: vec![Message {
-            role: "user".to_string(),
-            content: prompt,
-        }],
+        messages: vec![Message::user(prompt)],
         stream: false,
-        provider: None,
+        provider: provider.cloned(),
+        response_format: None,
+        temperature,
+        max_tokens,
+        stream_options: None,
     };
 
-    let response = client
-        .post(OPENROUTER_API_URL)
-        .header("Authorization", format!("Bearer {}", api_key))
-        .header("Content-Type", "application/json")
-        .header("X-Title", "Committer")
-        .header("HTTP-Referer", "https://github.com/Nolanneff/commiter")
-        .json(&request)
-        .send()
-        .await?;
+    let response = send_with_retry(
+        api_post(client, api_key).json(&request),
+        max_retries,
+        spinner,
+    )
+    .await?;
 
     if !response.status().is_success() {
         let status = response.status();
         let body = response.text().await.unwrap_or_default();
-        return Err(format!("API error ({}): {}", status, body).into());
+        return Err(api_error(status, &body, provider).into());
     }
 
     let response_body: NonStreamResponse = response.json().await?;
@@ -207,20 +407,53 @@ Respond with ONLY valid JSON:
     let content = content.strip_suffix("```").unwrap_or(content);
     let content = content.trim();
 
-    let analysis: BranchAnalysis = serde_json::from_str(content)
+    let mut analysis: BranchAnalysis = serde_json::from_str(content)
         .map_err(|e| format!("Failed to parse branch analysis: {} - raw: {}", e, content))?;
+    analysis.suggested_branch = analysis
+        .suggested_branch
+        .map(|branch| align_branch_type(&branch, commit_message));
 
     Ok(analysis)
 }
 
+/// Suggests a branch name for a commit message per `mode`.
+///
+/// Only [`BranchSuggestionMode::Llm`] can fail; `Fallback` never makes a
+/// request.
+pub async fn suggest_branch(
+    client: &Client,
+    api_key: &str,
+    model: &str,
+    commit_message: &str,
+    provider: Option<&ProviderPreference>,
+    mode: BranchSuggestionMode,
+) -> Result<String, Box<dyn std::error::Error + Send + Sync>> {
+    match mode {
+        BranchSuggestionMode::Fallback => Ok(generate_fallback_branch(commit_message)),
+        BranchSuggestionMode::Llm => {
+            generate_branch_suggestion(client, api_key, model, commit_message, provider).await
+        }
+        BranchSuggestionMode::LlmWithFallback => {
+            Ok(
+                generate_branch_suggestion(client, api_key, model, commit_message, provider)
+                    .await
+                    .unwrap_or_else(|_| generate_fallback_branch(commit_message)),
+            )
+        }
+    }
+}
+
 /// Generates a branch name suggestion using LLM.
 ///
-/// Falls back to [`generate_fallback_branch`] on error.
+/// The name's type prefix is made to match the commit's type.
+/// Callers fall back to [`generate_fallback_branch`] on error (see
+/// [`suggest_branch`]).
 pub async fn generate_branch_suggestion(
     client: &Client,
     api_key: &str,
     model: &str,
     commit_message: &str,
+    provider: Option<&ProviderPreference>,
 ) -> Result<String, Box<dyn std::error::Error + Send + Sync>> {
     let prompt = format!(
         r#"Given this commit message, suggest an appropriate git branch name.
@@ -243,23 +476,16 @@ Respond with ONLY the branch name, nothing else."#
 
     let request = ChatRequest {
         model: model.to_string(),
-        messages: vec![Message {
-            role: "user".to_string(),
-            content: prompt,
-        }],
+        messages: vec![Message::user(prompt)],
         stream: false,
-        provider: None,
+        provider: provider.cloned(),
+        response_format: None,
+        temperature: None,
+        max_tokens: None,
+        stream_options: None,
     };
 
-    let response = client
-        .post(OPENROUTER_API_URL)
-        .header("Authorization", format!("Bearer {}", api_key))
-        .header("Content-Type", "application/json")
-        .header("X-Title", "Committer")
-        .header("HTTP-Referer", "https://github.com/nolancui/committer")
-        .json(&request)
-        .send()
-        .await?;
+    let response = api_post(client, api_key).json(&request).send().await?;
 
     if !response.status().is_success() {
         return Err(format!("API request failed: {}", response.status()).into());
@@ -272,11 +498,84 @@ Respond with ONLY the branch name, nothing else."#
         .map(|c| c.message.content.clone())
         .unwrap_or_default();
 
-    let branch_name = content.trim().to_string();
+    let branch_name = content.trim();
 
     if branch_name.is_empty() {
         return Err("Empty branch name returned".into());
     }
 
-    Ok(branch_name)
+    Ok(align_branch_type(branch_name, commit_message))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::Config;
+
+    fn issue_pattern() -> Regex {
+        Regex::new(&Config::default().issue_pattern).unwrap()
+    }
+
+    #[test]
+    fn branch_without_a_ticket_has_none() {
+        assert_eq!(
+            ticket_from_branch("feat/login-form", &issue_pattern()),
+            None
+        );
+        assert_eq!(ticket_from_branch("main", &issue_pattern()), None);
+    }
+
+    #[test]
+    fn first_ticket_in_the_branch_wins() {
+        assert_eq!(
+            ticket_from_branch("feat/ABC-12-and-XYZ-9", &issue_pattern()).as_deref(),
+            Some("ABC-12")
+        );
+    }
+
+    #[test]
+    fn ticket_pattern_capture_group_is_used() {
+        let pattern = Regex::new(r"issue-(\d+)").unwrap();
+        assert_eq!(
+            ticket_from_branch("fix/issue-42-crash", &pattern).as_deref(),
+            Some("42")
+        );
+    }
+
+    #[test]
+    fn align_branch_type_replaces_a_mismatched_type() {
+        assert_eq!(
+            align_branch_type("feat/ui-scroll", "fix(ui): handle crash on scroll"),
+            "fix/ui-scroll"
+        );
+        assert_eq!(
+            align_branch_type("docs/setup", "✨ feat: add setup wizard"),
+            "feat/setup"
+        );
+    }
+
+    #[test]
+    fn align_branch_type_adds_a_missing_type() {
+        assert_eq!(
+            align_branch_type("ui-scroll", "fix: handle crash"),
+            "fix/ui-scroll"
+        );
+        // A prefix that isn't a lowercase type is part of the name
+        assert_eq!(
+            align_branch_type("PROJ-1/ui-scroll", "fix: handle crash"),
+            "fix/PROJ-1/ui-scroll"
+        );
+    }
+
+    #[test]
+    fn align_branch_type_keeps_matching_and_untyped_cases() {
+        assert_eq!(
+            align_branch_type("fix/ui-scroll", "fix(ui): handle crash"),
+            "fix/ui-scroll"
+        );
+        assert_eq!(
+            align_branch_type("feat/ui-scroll", "Handle crash on scroll"),
+            "feat/ui-scroll"
+        );
+    }
 }
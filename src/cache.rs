@@ -0,0 +1,222 @@
+//! Cache of generated commit messages.
+//!
+//! Running Committer again on the same staged changes (after cancelling,
+//! say) reuses the message from the last run instead of paying for a new
+//! one. Entries are keyed by a SHA-256 of the model and everything sent in
+//! the prompt ([`cache_key`]), kept in a JSON file next to the config file,
+//! and expire after `cache_ttl_secs`. Regenerating from the review prompt
+//! always asks the model again.
+//!
+//! Skip the cache for one run with `--no-cache`; wipe it with
+//! `committer cache clear`.
+
+use ring::digest::{digest, SHA256};
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::path::PathBuf;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use crate::config::config_dir;
+
+/// Default time a cached message stays usable.
+pub const DEFAULT_CACHE_TTL_SECS: u64 = 60 * 60;
+
+/// A cached message.
+#[derive(Serialize, Deserialize)]
+struct CacheEntry {
+    /// Seconds since the Unix epoch when the message was generated.
+    created_at: u64,
+    message: String,
+}
+
+/// Generated messages on disk, keyed by [`cache_key`].
+pub struct MessageCache {
+    path: PathBuf,
+    ttl_secs: u64,
+    /// Current time in seconds since the Unix epoch.
+    clock: fn() -> u64,
+}
+
+/// Returns the path to the message cache file.
+pub fn message_cache_path() -> PathBuf {
+    config_dir().join("message_cache.json")
+}
+
+fn now_secs() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.as_secs())
+        .unwrap_or(0)
+}
+
+/// Returns the cache key for a generation: a hex SHA-256 of the model, diff,
+/// file list, and prompt notes.
+pub fn cache_key(model: &str, diff: &str, files: &str, prompt_notes: &[String]) -> String {
+    // Length prefixes keep parts from running into each other
+    let mut input = String::new();
+    for part in [model, diff, files]
+        .into_iter()
+        .chain(prompt_notes.iter().map(String::as_str))
+    {
+        input.push_str(&format!("{}:{}", part.len(), part));
+    }
+    digest(&SHA256, input.as_bytes())
+        .as_ref()
+        .iter()
+        .map(|byte| format!("{:02x}", byte))
+        .collect()
+}
+
+impl MessageCache {
+    /// Opens the cache in the config directory.
+    pub fn new(ttl_secs: u64) -> Self {
+        Self::with_clock(message_cache_path(), ttl_secs, now_secs)
+    }
+
+    /// Opens the cache at `path`, telling time with `clock`.
+    pub fn with_clock(path: PathBuf, ttl_secs: u64, clock: fn() -> u64) -> Self {
+        Self {
+            path,
+            ttl_secs,
+            clock,
+        }
+    }
+
+    fn load(&self) -> BTreeMap<String, CacheEntry> {
+        std::fs::read_to_string(&self.path)
+            .ok()
+            .and_then(|contents| serde_json::from_str(&contents).ok())
+            .unwrap_or_default()
+    }
+
+    fn is_fresh(&self, entry: &CacheEntry, now: u64) -> bool {
+        now.saturating_sub(entry.created_at) < self.ttl_secs
+    }
+
+    /// Returns the message cached under `key`, if it hasn't expired.
+    pub fn get(&self, key: &str) -> Option<String> {
+        let now = (self.clock)();
+        self.load()
+            .remove(key)
+            .filter(|entry| self.is_fresh(entry, now))
+            .map(|entry| entry.message)
+    }
+
+    /// Caches `message` under `key`, dropping expired entries.
+    pub fn put(&self, key: &str, message: &str) -> Result<(), Box<dyn std::error::Error>> {
+        if self.ttl_secs == 0 {
+            return Ok(());
+        }
+        let now = (self.clock)();
+        let mut entries = self.load();
+        entries.retain(|_, entry| self.is_fresh(entry, now));
+        entries.insert(
+            key.to_string(),
+            CacheEntry {
+                created_at: now,
+                message: message.to_string(),
+            },
+        );
+        if let Some(parent) = self.path.parent() {
+            std::fs::create_dir_all(parent)?;
+        }
+        std::fs::write(&self.path, serde_json::to_string(&entries)?)?;
+        Ok(())
+    }
+
+    /// Removes every cached message. Returns false if there was no cache.
+    pub fn clear(&self) -> Result<bool, Box<dyn std::error::Error>> {
+        match std::fs::remove_file(&self.path) {
+            Ok(()) => Ok(true),
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => Ok(false),
+            Err(e) => Err(e.into()),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_support::TempDir;
+    use std::cell::Cell;
+
+    thread_local! {
+        /// The time each test's fake clock reports.
+        static NOW: Cell<u64> = const { Cell::new(1_000) };
+    }
+
+    fn fake_now() -> u64 {
+        NOW.with(Cell::get)
+    }
+
+    fn advance(secs: u64) {
+        NOW.with(|now| now.set(now.get() + secs));
+    }
+
+    fn cache(dir: &TempDir, ttl_secs: u64) -> MessageCache {
+        MessageCache::with_clock(dir.path().join("cache.json"), ttl_secs, fake_now)
+    }
+
+    #[test]
+    fn hit_inside_the_ttl_and_miss_after_it() {
+        let dir = TempDir::new("cache-ttl");
+        let cache = cache(&dir, 60);
+        cache.put("key", "fix: handle crash").unwrap();
+
+        advance(59);
+        assert_eq!(cache.get("key").as_deref(), Some("fix: handle crash"));
+        assert_eq!(cache.get("other"), None);
+        advance(1);
+        assert_eq!(cache.get("key"), None);
+    }
+
+    #[test]
+    fn zero_ttl_disables_the_cache() {
+        let dir = TempDir::new("cache-off");
+        let cache = cache(&dir, 0);
+        cache.put("key", "fix: handle crash").unwrap();
+        assert_eq!(cache.get("key"), None);
+        assert!(!dir.path().join("cache.json").exists());
+    }
+
+    #[test]
+    fn put_prunes_expired_entries() {
+        let dir = TempDir::new("cache-prune");
+        let cache = cache(&dir, 60);
+        cache.put("old", "fix: old").unwrap();
+        advance(30);
+        cache.put("recent", "fix: recent").unwrap();
+        advance(40);
+        cache.put("new", "fix: new").unwrap();
+
+        let entries = cache.load();
+        assert!(!entries.contains_key("old"));
+        assert!(entries.contains_key("recent"));
+        assert!(entries.contains_key("new"));
+    }
+
+    #[test]
+    fn cache_key_changes_with_every_input() {
+        let notes = vec!["Use imperative mood".to_string()];
+        let key = cache_key("model", "diff", "files", &notes);
+        assert_eq!(key, cache_key("model", "diff", "files", &notes));
+        assert_eq!(key.len(), 64);
+
+        assert_ne!(key, cache_key("other", "diff", "files", &notes));
+        assert_ne!(key, cache_key("model", "diff2", "files", &notes));
+        assert_ne!(key, cache_key("model", "diff", "files2", &notes));
+        assert_ne!(key, cache_key("model", "diff", "files", &[]));
+    }
+
+    #[test]
+    fn cache_key_parts_cannot_run_into_each_other() {
+        assert_ne!(
+            cache_key("ab", "c", "files", &[]),
+            cache_key("a", "bc", "files", &[])
+        );
+        assert_ne!(
+            cache_key("model", "diff", "", &["notes".to_string()]),
+            cache_key("model", "diff", "notes", &[])
+        );
+    }
+}
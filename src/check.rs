@@ -0,0 +1,167 @@
+//! Offline checks of the staged changes for `--check`.
+//!
+//! Meant for the pre-commit stage of a hook framework: nothing is generated
+//! and no request is made. The staged diff is scanned for leftover merge
+//! conflict markers and for strings that look like credentials, and the
+//! requirements for generating a message (an API key) are checked.
+//!
+//! Each problem is printed as one line, `kind: location (detail)`:
+//!
+//! ```text
+//! conflict-marker: src/lib.rs:42
+//! secret: deploy/dev.env:3 (AWS access key)
+//! no-api-key
+//! ```
+
+use regex_lite::Regex;
+use serde::Serialize;
+use tokio::process::Command;
+
+use crate::git::log_command;
+
+/// Credential formats the secret scan recognizes, as (description, pattern).
+const SECRET_PATTERNS: &[(&str, &str)] = &[
+    ("AWS access key", r"\b(?:AKIA|ASIA)[0-9A-Z]{16}\b"),
+    ("private key", r"-----BEGIN [A-Z ]*PRIVATE KEY-----"),
+    ("GitHub token", r"\bgh[pousr]_[A-Za-z0-9]{36,}\b"),
+    ("GitHub token", r"\bgithub_pat_[A-Za-z0-9_]{60,}\b"),
+    ("OpenRouter API key", r"\bsk-or-v1-[0-9a-f]{64}\b"),
+    ("API key", r"\bsk-(?:ant-|proj-)?[A-Za-z0-9_-]{32,}\b"),
+    ("Slack token", r"\bxox[abprs]-[A-Za-z0-9-]{10,}\b"),
+];
+
+/// Line prefixes git writes around conflicting hunks.
+const CONFLICT_MARKERS: &[&str] = &["<<<<<<< ", ">>>>>>> "];
+
+/// A reason the staged changes shouldn't be committed yet.
+#[derive(Debug, Serialize)]
+pub struct Problem {
+    /// Short machine-readable name, e.g. `conflict-marker`.
+    pub kind: &'static str,
+    /// File the problem is in, if it's in one.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub path: Option<String>,
+    /// Line in the staged version of `path`.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub line: Option<usize>,
+    /// Extra detail, e.g. which kind of secret matched.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub detail: Option<String>,
+}
+
+impl Problem {
+    /// A problem with the repository or setup rather than a specific line.
+    pub fn general(kind: &'static str) -> Self {
+        Self {
+            kind,
+            path: None,
+            line: None,
+            detail: None,
+        }
+    }
+}
+
+impl std::fmt::Display for Problem {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", self.kind)?;
+        match (&self.path, self.line) {
+            (Some(path), Some(line)) => write!(f, ": {}:{}", path, line)?,
+            (Some(path), None) => write!(f, ": {}", path)?,
+            _ => {}
+        }
+        if let Some(detail) = &self.detail {
+            write!(f, " ({})", detail)?;
+        }
+        Ok(())
+    }
+}
+
+/// Returns the full staged diff with no context lines.
+///
+/// Unlike the diff sent for generation, nothing is excluded or truncated, so
+/// a secret in a lock file or a huge diff is still found.
+pub async fn get_check_diff(verbose: bool) -> Result<String, Box<dyn std::error::Error>> {
+    let args = [
+        "diff",
+        "--staged",
+        "--no-color",
+        "--no-ext-diff",
+        "--unified=0",
+    ];
+    let output = Command::new("git").args(args).output().await;
+    log_command(verbose, "git", &args, &output);
+    let output = output?;
+
+    if !output.status.success() {
+        let stderr = String::from_utf8_lossy(&output.stderr);
+        return Err(format!("git diff failed: {}", stderr).into());
+    }
+
+    Ok(String::from_utf8_lossy(&output.stdout).to_string())
+}
+
+/// Scans the added lines of a diff for conflict markers and secrets.
+pub fn scan_diff(diff: &str) -> Vec<Problem> {
+    let secrets: Vec<(&str, Regex)> = SECRET_PATTERNS
+        .iter()
+        .map(|(name, pattern)| (*name, Regex::new(pattern).expect("valid secret pattern")))
+        .collect();
+
+    let mut problems = Vec::new();
+    let mut path: Option<String> = None;
+    let mut line_number = 0;
+    // Between "diff --git" and the first hunk, where "+++" names the file
+    let mut in_header = false;
+
+    for line in diff.lines() {
+        if line.starts_with("diff --git ") {
+            in_header = true;
+            continue;
+        }
+        if in_header {
+            if let Some(target) = line.strip_prefix("+++ ") {
+                let target = target.trim_matches('"');
+                path = target.strip_prefix("b/").map(str::to_string);
+            }
+            if !line.starts_with("@@ ") {
+                continue;
+            }
+            in_header = false;
+        }
+        if let Some(header) = line.strip_prefix("@@ ") {
+            // "@@ -a,b +c,d @@": added lines start at c
+            line_number = header
+                .split_whitespace()
+                .find_map(|range| range.strip_prefix('+'))
+                .and_then(|range| range.split(',').next())
+                .and_then(|start| start.parse().ok())
+                .unwrap_or(0);
+            continue;
+        }
+        let Some(added) = line.strip_prefix('+') else {
+            if line.starts_with(' ') {
+                line_number += 1;
+            }
+            continue;
+        };
+
+        let location = |kind, detail| Problem {
+            kind,
+            path: path.clone(),
+            line: Some(line_number),
+            detail,
+        };
+        if CONFLICT_MARKERS
+            .iter()
+            .any(|marker| added.starts_with(marker))
+        {
+            problems.push(location("conflict-marker", None));
+        }
+        if let Some((name, _)) = secrets.iter().find(|(_, regex)| regex.is_match(added)) {
+            problems.push(location("secret", Some(name.to_string())));
+        }
+        line_number += 1;
+    }
+
+    problems
+}
@@ -8,8 +8,16 @@
 //! - Default (no subcommand): Generate and create a commit
 //! - `config`: Manage persistent configuration
 //! - `pr`: Generate and create a pull request
+//! - `history`: Show recently generated messages
 
 use clap::{Parser, Subcommand};
+use std::path::PathBuf;
+
+use crate::clipboard::CopyTarget;
+use crate::config::{
+    BranchOverflowStrategy, BranchSkipAction, BranchStrictness, BranchSuggestionMode,
+    SubjectOverflow, TicketPlacement,
+};
 
 /// Main CLI structure for Committer.
 #[derive(Parser)]
@@ -23,18 +31,277 @@ pub struct Cli {
     #[arg(short = 'y', long)]
     pub yes: bool,
 
+    /// Auto-commit only if the message looks confident enough
+    ///
+    /// Uses auto_accept_threshold, or requires a full score if it isn't set.
+    /// Messages below it go to the usual review.
+    #[arg(long, conflicts_with = "yes")]
+    pub yes_if_match: bool,
+
     /// Just print the message, don't commit
     #[arg(short, long)]
     pub dry_run: bool,
 
+    /// Print nothing when there is nothing to commit or nothing staged
+    ///
+    /// The exit code still tells the outcomes apart (0 and 3).
+    #[arg(short, long)]
+    pub quiet: bool,
+
+    /// Print the result as a single JSON object instead of console output
+    ///
+    /// Reports the message, model, tokens, branch, suggested branch, and
+    /// whether it was committed; nothing-to-commit outcomes are
+    /// `{"status": "..."}`. Never prompts: it commits like --yes.
+    #[arg(
+        long,
+        conflicts_with_all = [
+            "quiet", "interactive_regenerate", "refine", "select_files", "copy", "stats",
+            "count"
+        ]
+    )]
+    pub json: bool,
+
+    /// Time limit in seconds for each API request (overrides config; 0 disables)
+    #[arg(long, value_name = "SECS")]
+    pub timeout: Option<u64>,
+
+    /// Sampling temperature, e.g. 0.2 for steadier messages (overrides config)
+    #[arg(long, value_name = "TEMP")]
+    pub temperature: Option<f32>,
+
+    /// Cap on generated tokens per request (overrides config)
+    #[arg(long, value_name = "N")]
+    pub max_tokens: Option<u32>,
+
+    /// Read and write this config file instead of the default one
+    #[arg(long, global = true, value_name = "PATH")]
+    pub config: Option<PathBuf>,
+
+    /// Read the OpenRouter API key from a file (overrides the env var and config)
+    #[arg(long, value_name = "PATH")]
+    pub api_key_file: Option<PathBuf>,
+
+    /// Generate N messages and pick one to review (disables streaming)
+    ///
+    /// Each candidate is a separate request, so N of them cost N times as
+    /// much. Identical candidates are merged; a higher --temperature gives more
+    /// variety. With -y, or without a terminal, the first one is used.
+    #[arg(
+        long,
+        value_name = "N",
+        default_value_t = 1,
+        value_parser = clap::value_parser!(u8).range(1..=10),
+        conflicts_with_all = ["from_recovery", "print_branch"]
+    )]
+    pub candidates: u8,
+
+    /// Estimate prompt tokens and cost without calling the API
+    #[arg(long, conflicts_with_all = ["from_recovery", "interactive_regenerate", "print_branch"])]
+    pub count: bool,
+
+    /// Print subject/body length metrics (with --dry-run)
+    #[arg(long, requires = "dry_run")]
+    pub stats: bool,
+
+    /// Check the message against Conventional Commits before committing
+    ///
+    /// Invalid messages are sent back for review instead of being committed
+    /// (with -y, the run fails and the message is saved for --from-recovery).
+    #[arg(long)]
+    pub lint: bool,
+
     /// Include unstaged changes
     #[arg(short, long)]
     pub all: bool,
 
+    /// Add a Signed-off-by trailer using your git identity
+    #[arg(short = 's', long)]
+    pub signoff: bool,
+
+    /// GPG-sign the commit (git commit -S)
+    #[arg(short = 'S', long)]
+    pub sign: bool,
+
+    /// Skip the pre-commit and commit-msg hooks (git commit --no-verify)
+    #[arg(long)]
+    pub no_verify: bool,
+
+    /// Generate a new message even if one is cached for these changes
+    #[arg(long)]
+    pub no_cache: bool,
+
+    /// Credit a pair with a Co-authored-by trailer (repeatable)
+    ///
+    /// A name or email alone is completed from the commit history.
+    #[arg(long = "co-author", value_name = "NAME <EMAIL>")]
+    pub co_authors: Vec<String>,
+
+    /// Always write a bulleted body summarizing the key changes
+    #[arg(long)]
+    pub body: bool,
+
+    /// Prefix the subject with the gitmoji for its type (e.g. ✨ feat: ...)
+    #[arg(long)]
+    pub emoji: bool,
+
+    /// Show the diff below the message when editing it, like `git commit -v`
+    #[arg(long)]
+    pub verbose_editor: bool,
+
+    /// Keep the spinner up while generating and print the message once done
+    #[arg(long)]
+    pub no_stream_display: bool,
+
+    /// Copy the commit message to the clipboard afterwards (--copy=sha for the SHA)
+    ///
+    /// A dry run copies the message it would commit. Without a clipboard
+    /// (e.g. over SSH) this only warns.
+    #[arg(
+        long,
+        value_enum,
+        value_name = "WHAT",
+        num_args = 0..=1,
+        require_equals = true,
+        default_missing_value = "message"
+    )]
+    pub copy: Option<CopyTarget>,
+
+    /// Render the message into this template (overrides `template` in config)
+    #[arg(long, value_name = "PATH")]
+    pub template_file: Option<PathBuf>,
+
+    /// Keep at most N body lines, dropping trailing bullets (0 removes the body)
+    #[arg(long, value_name = "N")]
+    pub max_body_lines: Option<usize>,
+
+    /// Fold the staged changes into an earlier commit, keeping its message
+    ///
+    /// Creates a `fixup!` commit for REF and runs a non-interactive
+    /// `git rebase --autosquash`. The rebase is aborted if it hits conflicts.
+    #[arg(long, value_name = "REF", conflicts_with_all = ["amend", "print_branch", "branch", "auto_branch"])]
+    pub squash_into: Option<String>,
+
+    /// Fetch before checking whether the branch is behind its upstream
+    #[arg(long)]
+    pub fetch: bool,
+
+    /// Stage only working-tree hunks with a changed line matching REGEX
+    ///
+    /// Already-staged changes are kept. If the run ends without committing,
+    /// the index is restored to its previous state.
+    #[arg(long, value_name = "REGEX", conflicts_with_all = ["all", "amend"])]
+    pub hunk_filter: Option<String>,
+
+    /// Choose the files to commit from a list of all changed files
+    ///
+    /// Stages exactly the chosen files (whole files, unstaging the rest).
+    /// If the run ends without committing, the index is restored to its
+    /// previous state.
+    #[arg(long, conflicts_with_all = ["all", "amend", "hunk_filter"])]
+    pub select_files: bool,
+
+    /// Write the message from your own description of the change
+    ///
+    /// The description is treated as authoritative; the diff (trimmed to a
+    /// short excerpt) is used to pick the type and scope and check details.
+    #[arg(
+        long,
+        visible_alias = "context",
+        value_name = "DESCRIPTION",
+        conflicts_with_all = ["squash_into", "from_recovery"]
+    )]
+    pub from: Option<String>,
+
+    /// Reformat a message you wrote instead of writing a new one, then exit
+    ///
+    /// For commit-msg hooks: reads the message from FILE and rewrites it, or
+    /// reads stdin and prints the result with "-" (the default). A message
+    /// already in Conventional Commits form is left alone; otherwise it's
+    /// reworded as little as possible, keeping its trailers. Nothing is
+    /// committed, and the message is left as written if refining fails.
+    #[arg(
+        long,
+        value_name = "FILE",
+        num_args = 0..=1,
+        default_missing_value = "-",
+        conflicts_with_all = [
+            "from", "from_recovery", "amend", "squash_into", "print_branch", "branch",
+            "auto_branch", "interactive_regenerate", "dry_run", "check"
+        ]
+    )]
+    pub refine: Option<PathBuf>,
+
+    /// Generate a message for a diff read from stdin and print it
+    ///
+    /// For editor integrations and scripts: the diff needn't match the
+    /// index, so nothing is committed unless --yes and --repo are both
+    /// given, which commit what's staged in that repository.
+    #[arg(
+        long,
+        conflicts_with_all = [
+            "amend", "all", "squash_into", "hunk_filter", "select_files", "refine",
+            "check", "allow_empty", "interactive_regenerate"
+        ]
+    )]
+    pub stdin_diff: bool,
+
+    /// With --stdin-diff, stdin starts with the file list (one per line, as
+    /// from git diff --name-status), followed by the diff
+    #[arg(long, requires = "stdin_diff")]
+    pub files_from_stdin: bool,
+
+    /// Run in this repository instead of the current directory
+    #[arg(long, value_name = "PATH")]
+    pub repo: Option<PathBuf>,
+
+    /// Commit even if nothing is staged (e.g. to trigger CI)
+    ///
+    /// With nothing staged, the message is generated from --from/--context,
+    /// or is "chore: empty commit" without one.
+    #[arg(long, conflicts_with_all = ["amend", "squash_into"])]
+    pub allow_empty: bool,
+
+    /// Run even while a rebase, merge, cherry-pick, or am is in progress
+    #[arg(long)]
+    pub force: bool,
+
+    /// Check the staged changes for conflict markers and secrets, then exit
+    ///
+    /// Offline and generates nothing, for pre-commit hooks. Prints one line
+    /// per problem (nothing if all is well) and exits 1 if there are any.
+    #[arg(long)]
+    pub check: bool,
+
+    /// Commit with the message saved by a dry run, or a cancelled or failed run
+    ///
+    /// Skips generation. The message still goes through the usual review
+    /// and formatting; a warning is shown if the staged changes differ from
+    /// the ones it was generated for.
+    #[arg(long, conflicts_with_all = ["squash_into", "print_branch"])]
+    pub from_recovery: bool,
+
+    /// Force the conventional-commit scope, e.g. `feat(<SCOPE>): ...`
+    #[arg(long, value_name = "SCOPE")]
+    pub assume_scope: Option<String>,
+
+    /// Regenerate the last commit's message and amend it, folding in any staged changes
+    ///
+    /// The message is generated from HEAD~1 against the index, i.e. the last
+    /// commit plus whatever is staged now (combine with --all to stage
+    /// everything first). The resulting tree is what `git commit --amend`
+    /// would record.
+    #[arg(long)]
+    pub amend: bool,
+
     /// Override model for this run
     #[arg(short, long)]
     pub model: Option<String>,
 
+    /// Route only to this OpenRouter provider (e.g. "DeepInfra"), with no fallbacks
+    #[arg(long, value_name = "NAME")]
+    pub provider_only: Option<String>,
+
     /// Interactive branch suggestion on mismatch [y/n/e]
     #[arg(short = 'b', long)]
     pub branch: bool,
@@ -43,6 +310,27 @@ pub struct Cli {
     #[arg(short = 'B', long)]
     pub auto_branch: bool,
 
+    /// Skip branch analysis this run, even with -b/-B from an alias
+    ///
+    /// Whichever of --branch, --auto-branch, and --no-branch comes last wins.
+    #[arg(long, overrides_with_all = ["branch", "auto_branch"])]
+    pub no_branch: bool,
+
+    /// Cancel instead of committing when declining the branch offered by 'b'
+    #[arg(long)]
+    pub no_commit_on_branch_skip: bool,
+
+    /// Refine the message by typing feedback, regenerating until you accept
+    ///
+    /// Each critique is sent along with the earlier responses, so the model
+    /// sees the whole conversation. Accepting commits the message.
+    #[arg(long, conflicts_with_all = ["yes", "print_branch", "squash_into"])]
+    pub interactive_regenerate: bool,
+
+    /// Print only the suggested branch name for the staged changes, then exit
+    #[arg(long, conflicts_with_all = ["branch", "auto_branch", "amend"])]
+    pub print_branch: bool,
+
     /// Show detailed operation logs (excluded files, truncation, etc.)
     #[arg(short = 'v', long)]
     pub verbose: bool,
@@ -58,6 +346,44 @@ pub enum Commands {
     },
     /// Generate and create a pull request
     Pr(PrArgs),
+    /// List available models, or the configured model aliases
+    Models {
+        /// List `model_aliases` from config instead
+        #[arg(long)]
+        aliases: bool,
+    },
+    /// Compare models on the staged changes, without committing
+    Bench(BenchArgs),
+    /// Show recently generated messages (requires `history` enabled)
+    History {
+        /// Number of entries to show
+        #[arg(short = 'n', long, default_value_t = 10)]
+        limit: usize,
+    },
+    /// Manage the cache of generated messages
+    Cache {
+        #[command(subcommand)]
+        action: CacheAction,
+    },
+}
+
+/// Arguments for the `bench` subcommand.
+#[derive(Parser)]
+pub struct BenchArgs {
+    /// Models or aliases to compare (default: `bench_models` from config)
+    pub models: Vec<String>,
+
+    /// How many models to query at once
+    #[arg(short, long, default_value_t = 4, value_name = "N")]
+    pub jobs: usize,
+
+    /// Show detailed operation logs
+    #[arg(short = 'v', long)]
+    pub verbose: bool,
+
+    /// Read the OpenRouter API key from a file (overrides the env var and config)
+    #[arg(long, value_name = "PATH")]
+    pub api_key_file: Option<PathBuf>,
 }
 
 /// Arguments for the `pr` subcommand.
@@ -75,24 +401,85 @@ pub struct PrArgs {
     #[arg(short = 'D', long)]
     pub draft: bool,
 
+    /// Request a review from a user, on top of default_reviewers (repeatable)
+    #[arg(long = "reviewer", value_name = "USER")]
+    pub reviewers: Vec<String>,
+
+    /// Add a label, on top of default_labels (repeatable)
+    #[arg(long = "label", value_name = "NAME")]
+    pub labels: Vec<String>,
+
+    /// Regenerate the title/body of the branch's open PR instead of creating one
+    #[arg(long, conflicts_with = "draft")]
+    pub update: bool,
+
     /// Override base branch (default: auto-detect)
     #[arg(short, long)]
     pub base: Option<String>,
 
+    /// Pick the base branch from a list of likely candidates
+    ///
+    /// The list is also offered when detection can only guess. Without a
+    /// terminal, or with --yes, the base is detected as usual.
+    #[arg(long, conflicts_with = "base")]
+    pub interactive_base: bool,
+
+    /// Write the description from the branch's commit messages, not the diff
+    ///
+    /// The model groups the commits by type; the diff isn't sent, which is
+    /// faster and cheaper when the commits are already well written.
+    #[arg(long)]
+    pub fill_from_commits: bool,
+
+    /// Keep the spinner up while generating and print the PR content once done
+    #[arg(long)]
+    pub no_stream_display: bool,
+
+    /// Skip git hooks when committing uncommitted changes first
+    #[arg(long)]
+    pub no_verify: bool,
+
     /// Show detailed operation logs
     #[arg(short = 'v', long)]
     pub verbose: bool,
 
+    /// Read the OpenRouter API key from a file (overrides the env var and config)
+    #[arg(long, value_name = "PATH")]
+    pub api_key_file: Option<PathBuf>,
+
     /// Override model for this run
     #[arg(short, long)]
     pub model: Option<String>,
+
+    /// Route only to this OpenRouter provider (e.g. "DeepInfra"), with no fallbacks
+    #[arg(long, value_name = "NAME")]
+    pub provider_only: Option<String>,
+
+    /// Remote to push the branch to (default: origin)
+    #[arg(long, value_name = "REMOTE")]
+    pub head_remote: Option<String>,
+
+    /// Remote hosting the repository the PR targets (default: upstream if present)
+    #[arg(long, value_name = "REMOTE")]
+    pub base_remote_name: Option<String>,
+}
+
+/// Cache subcommand actions.
+#[derive(Subcommand)]
+pub enum CacheAction {
+    /// Remove all cached messages
+    Clear,
 }
 
 /// Configuration subcommand actions.
 #[derive(Subcommand)]
 pub enum ConfigAction {
     /// Show current configuration
-    Show,
+    Show {
+        /// Also show where each value comes from (default, global, env)
+        #[arg(long)]
+        effective: bool,
+    },
     /// Set auto-commit behavior
     AutoCommit {
         /// true or false
@@ -103,14 +490,249 @@ pub enum ConfigAction {
         /// true or false
         value: String,
     },
-    /// Set default model
+    /// What to do after choosing 'b' and then staying on the current branch
+    BranchSkipAction {
+        /// prompt (ask again), commit (commit here), or cancel
+        #[arg(value_enum)]
+        value: BranchSkipAction,
+    },
+    /// Limit suggested branch names to N characters ("none" removes the limit)
+    BranchMaxLength {
+        /// Number of characters, or "none"
+        value: String,
+    },
+    /// How branch names over branch-max-length are shortened
+    BranchOverflowStrategy {
+        /// truncate (cut at a word boundary) or abbreviate (keep two key words)
+        #[arg(value_enum)]
+        value: BranchOverflowStrategy,
+    },
+    /// How branch names are suggested
+    BranchSuggestionMode {
+        /// llm, fallback (local, no request), or llm-with-fallback
+        #[arg(value_enum)]
+        value: BranchSuggestionMode,
+    },
+    /// How readily branch analysis flags a commit as belonging elsewhere
+    BranchStrictness {
+        /// strict (any new scope), balanced (clearly separate work), or lenient
+        #[arg(value_enum)]
+        value: BranchStrictness,
+    },
+    /// Regex matching a ticket reference in branch names (e.g. PROJ-123)
+    IssuePattern {
+        /// Pattern; its first capture group is the ticket if it has one
+        value: String,
+    },
+    /// Template the message is rendered into, with {type}, {scope}, {subject}, ... ("none" disables)
+    Template {
+        /// Template text, e.g. "[{ticket}] {type}({scope}): {subject}"
+        value: String,
+    },
+    /// Where the ticket from the branch name goes in the commit message
+    TicketInSubject {
+        /// off, after-type (feat: PROJ-1 x), start (PROJ-1 feat: x), end (feat: x (PROJ-1)), or trailer (Refs: PROJ-1)
+        #[arg(value_enum)]
+        value: TicketPlacement,
+    },
+    /// Set default model (pick from OpenRouter's list when no value is given)
     Model {
         /// Model identifier (e.g., x-ai/grok-4.1-fast:free)
-        value: String,
+        value: Option<String>,
     },
     /// Enable verbose operation logs by default
     Verbose {
         /// true or false
         value: String,
     },
+    /// Request commit messages as structured JSON output
+    StructuredOutput {
+        /// true or false
+        value: String,
+    },
+    /// Limit generated bodies to N lines ("none" removes the limit)
+    MaxBodyLines {
+        /// Number of lines, or "none"
+        value: String,
+    },
+    /// Cap the final message size, cutting the body ("none" removes the limit)
+    MaxMessageBytes {
+        /// Number of bytes, or "none"
+        value: String,
+    },
+    /// Commit messages scoring at least N% without review ("none" disables)
+    AutoAcceptThreshold {
+        /// Percentage from 0 to 100, or "none"
+        value: String,
+    },
+    /// Prefix subjects with the gitmoji for their type
+    Emoji {
+        /// true or false
+        value: String,
+    },
+    /// Check messages against Conventional Commits before committing
+    Lint {
+        /// true or false
+        value: String,
+    },
+    /// Maximum subject length, used by on-too-long, lint, and --stats
+    SubjectMaxLen {
+        /// Number of characters
+        value: usize,
+    },
+    /// Base branch for PRs when the remote's default can't be found ("none" unsets)
+    DefaultBase {
+        /// Branch name (e.g., "develop"), or "none"
+        value: String,
+    },
+    /// Code host for `committer pr` ("auto" detects it from the remote)
+    Forge {
+        /// github, gitlab, or auto
+        value: String,
+    },
+    /// What to do with a generated subject over subject-max-len
+    OnTooLong {
+        /// regenerate (ask for a shorter one), truncate (cut at a word), or warn
+        #[arg(value_enum)]
+        value: SubjectOverflow,
+    },
+    /// Wrap generated body lines at N columns
+    WrapBodyAt {
+        /// Number of columns (0 disables wrapping)
+        value: usize,
+    },
+    /// Always write a bulleted body, not just for multi-part changes
+    Body {
+        /// true or false
+        value: String,
+    },
+    /// GPG-sign every commit (not needed if git's commit.gpgsign is set)
+    Sign {
+        /// true or false
+        value: String,
+    },
+    /// Add a Signed-off-by trailer to every commit
+    Signoff {
+        /// true or false
+        value: String,
+    },
+    /// Record generated messages in the history file
+    History {
+        /// true or false
+        value: String,
+    },
+    /// Warn before committing on a detached HEAD or stale branch
+    WarnIfBehind {
+        /// true or false
+        value: String,
+    },
+    /// Editor for reviewing messages ("none" uses git's editor)
+    Editor {
+        /// Editor command (e.g., "code --wait"), or "none"
+        value: String,
+    },
+    /// Show the staged diff below the message when editing it
+    VerboseEditor {
+        /// true or false
+        value: String,
+    },
+    /// Print generated text token by token as it streams
+    ShowStream {
+        /// true or false
+        value: String,
+    },
+    /// Initial size of the response stream buffer
+    StreamBufferSize {
+        /// Size in bytes (e.g., 8192)
+        value: usize,
+    },
+    /// Time limit for each API request
+    Timeout {
+        /// Seconds (0 disables the limit)
+        value: u64,
+    },
+    /// How long a generated message is reused for the same changes
+    CacheTtl {
+        /// Seconds (0 disables the cache)
+        value: u64,
+    },
+    /// Sampling temperature for generation ("none" uses the provider default)
+    Temperature {
+        /// Temperature (typically 0.0-2.0), or "none"
+        value: String,
+    },
+    /// Cap on generated tokens per request ("none" uses the provider default)
+    MaxTokens {
+        /// Number of tokens, or "none"
+        value: String,
+    },
+    /// Chat completions endpoint, e.g. a local Ollama server ("default" for OpenRouter)
+    BaseUrl {
+        /// URL such as http://localhost:11434/v1/chat/completions, or "default"
+        value: String,
+    },
+    /// Retries for rate-limited or unavailable API responses
+    MaxRetries {
+        /// Number of retries (0 disables retrying)
+        value: u32,
+    },
+    /// Command the diff is piped through before reaching the model ("none" disables)
+    DiffPreprocessor {
+        /// Shell command reading the diff on stdin (e.g., "./scripts/redact.sh")
+        value: String,
+    },
+    /// Attach a git note with generation metadata to each commit
+    AttachNotes {
+        /// true or false
+        value: String,
+    },
+    /// Add Committer-Model and Committer-Version trailers to generated messages
+    StampMetadata {
+        /// true or false
+        value: String,
+    },
+    /// Notes ref used by attach-notes
+    NotesRef {
+        /// Ref name (e.g., refs/notes/committer)
+        value: String,
+    },
+    /// Model to retry with when the main model refuses ("none" retries the same model)
+    FallbackModel {
+        /// Model identifier, or "none"
+        value: String,
+    },
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn parse(args: &[&str]) -> Result<Cli, clap::Error> {
+        Cli::try_parse_from(std::iter::once("committer").chain(args.iter().copied()))
+    }
+
+    #[test]
+    fn json_rejects_flags_that_print_or_prompt() {
+        for flag in [
+            &["--copy"][..],
+            &["--copy=sha"],
+            &["--stats"],
+            &["--count"],
+            &["--quiet"],
+            &["--interactive-regenerate"],
+            &["--select-files"],
+        ] {
+            let args: Vec<&str> = ["--json"].iter().chain(flag).copied().collect();
+            let error = parse(&args)
+                .err()
+                .unwrap_or_else(|| panic!("{:?} parsed", args));
+            assert_eq!(error.kind(), clap::error::ErrorKind::ArgumentConflict);
+        }
+    }
+
+    #[test]
+    fn json_combines_with_dry_run_and_print_branch() {
+        assert!(parse(&["--json", "--dry-run"]).is_ok());
+        assert!(parse(&["--json", "--print-branch"]).is_ok());
+    }
 }
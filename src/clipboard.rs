@@ -0,0 +1,85 @@
+//! Copying to the system clipboard for `--copy`.
+//!
+//! No clipboard library is linked; the platform's own tool is run with the
+//! text on stdin, trying each in turn until one works:
+//!
+//! - macOS: `pbcopy`
+//! - Windows and WSL: `clip.exe`
+//! - Wayland: `wl-copy`; X11: `xclip`, then `xsel`
+//!
+//! Headless sessions (SSH, CI, containers) usually have none of these, so
+//! callers report a failed copy as a warning rather than an error.
+
+use std::process::Stdio;
+use tokio::io::AsyncWriteExt;
+use tokio::process::Command;
+
+/// What `--copy` puts on the clipboard.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, clap::ValueEnum)]
+pub enum CopyTarget {
+    /// The final commit message, trailers included.
+    Message,
+    /// The new commit's SHA.
+    Sha,
+}
+
+/// Clipboard tools to try on this platform, as (program, arguments).
+fn clipboard_commands() -> Vec<(&'static str, &'static [&'static str])> {
+    if cfg!(target_os = "macos") {
+        return vec![("pbcopy", &[])];
+    }
+    if cfg!(windows) {
+        return vec![("clip.exe", &[])];
+    }
+
+    let mut commands: Vec<(&str, &[&str])> = Vec::new();
+    if std::env::var_os("WAYLAND_DISPLAY").is_some() {
+        commands.push(("wl-copy", &[]));
+    }
+    if std::env::var_os("DISPLAY").is_some() {
+        commands.push(("xclip", &["-selection", "clipboard"]));
+        commands.push(("xsel", &["--clipboard", "--input"]));
+    }
+    // WSL can reach the Windows clipboard
+    commands.push(("clip.exe", &[]));
+    commands
+}
+
+/// Copies `text` to the clipboard, returning the name of the tool used.
+///
+/// Fails if no clipboard tool is installed or every one fails, e.g. with no
+/// display to talk to.
+pub async fn copy_to_clipboard(text: &str) -> Result<&'static str, String> {
+    let mut last_error = None;
+    for (program, args) in clipboard_commands() {
+        // xclip and wl-copy keep serving the selection in the background, so
+        // only stdin is piped: waiting on their output would never finish
+        let spawned = Command::new(program)
+            .args(args)
+            .stdin(Stdio::piped())
+            .stdout(Stdio::null())
+            .stderr(Stdio::null())
+            .spawn();
+        let mut child = match spawned {
+            Ok(child) => child,
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => continue,
+            Err(e) => {
+                last_error = Some(format!("{} could not start: {}", program, e));
+                continue;
+            }
+        };
+
+        if let Some(mut stdin) = child.stdin.take() {
+            let _ = stdin.write_all(text.as_bytes()).await;
+        }
+        match child.wait().await {
+            Ok(status) if status.success() => return Ok(program),
+            Ok(status) => last_error = Some(format!("{} failed ({})", program, status)),
+            Err(e) => last_error = Some(format!("{} failed: {}", program, e)),
+        }
+    }
+
+    Err(last_error.unwrap_or_else(|| {
+        "no clipboard tool found (pbcopy, clip.exe, wl-copy, xclip, or xsel)".to_string()
+    }))
+}
@@ -0,0 +1,575 @@
+//! Commit message assembly shared by every commit path.
+//!
+//! Every commit Committer creates — auto-commit, interactive, commit after
+//! branch creation, and the PR flow's "commit first" — goes through
+//! [`CommitPolicy::commit`], so configured trailers are applied the same way
+//! no matter how the commit was reached.
+//!
+//! The pipeline has two stages:
+//!
+//! - [`CommitPolicy::prepare`]: shapes the generated message before the user
+//!   reviews it (e.g. spacing normalization, body trimming, forced scope,
+//!   the message template, the branch's ticket, `body_by_type = "never"`
+//!   removal, the gitmoji prefix, subject truncation, body wrapping)
+//! - [`CommitPolicy::finalize`]: applied at commit time (e.g. trailers, then
+//!   the `max_message_bytes` ceiling)
+//!
+//! Generation context shared by both paths comes from [`collect_prompt_notes`].
+//! With `lint` enabled, a message must pass [`CommitPolicy::lint`] to be
+//! committed. A message whose commit fails is saved for `--from-recovery`.
+//!
+//! # Trailers
+//!
+//! - `Signed-off-by`: added when `signoff` is enabled (`--signoff` or config)
+//! - `Co-authored-by`: one per `co_authors` entry and `--co-author` flag
+//! - `Refs`: the ticket from the branch name, with `ticket_in_subject = "trailer"`
+//! - `Committer-Model`, `Committer-Version`: from [`metadata_trailers`], with
+//!   `stamp_metadata` enabled
+
+use regex_lite::Regex;
+use std::collections::BTreeMap;
+
+use crate::branch::ticket_from_branch;
+use crate::config::{BodyMode, Config, SubjectOverflow, TicketPlacement};
+use crate::convention::RepoConvention;
+use crate::emoji::add_emoji;
+use crate::format::{
+    append_trailers, commit_scope, commit_type, message_stats, normalize_spacing, place_ticket,
+    remove_scope, set_scope, trim_body, trim_to_bytes, truncate_subject, wrap_commit_message,
+    SUBJECT_MAX_WIDTH,
+};
+use crate::git::{
+    get_current_branch, get_submodule_commit_subject, parse_submodule_updates, run_git_commit,
+    short_sha, CommitOptions, RevertedCommit, SubmoduleUpdate,
+};
+use crate::identity::{require_own_identity, resolve_identity};
+use crate::lint::{validate_conventional, LintRules};
+use crate::recovery::save_recovery;
+use crate::templates::{render_template, uses_placeholder, CommitParts};
+
+/// Gathers extra prompt guidance for a diff.
+///
+/// Includes the repo's declared commit rules, the configured per-type body
+/// rules, and a description of any submodule pointer changes, which
+/// otherwise appear to the model as bare SHAs.
+pub async fn collect_prompt_notes(
+    diff: &str,
+    convention: Option<&RepoConvention>,
+    config: &Config,
+) -> Vec<String> {
+    let mut notes = Vec::new();
+
+    if let Some(convention) = convention {
+        notes.push(convention.prompt_note());
+    }
+
+    let body_rules: Vec<String> = config
+        .body_by_type
+        .iter()
+        .filter_map(|(commit_type, mode)| match mode {
+            BodyMode::Always => Some(format!("- {} commits: always include a body", commit_type)),
+            BodyMode::Never => Some(format!(
+                "- {} commits: subject line only, no body",
+                commit_type
+            )),
+            BodyMode::Auto => None,
+        })
+        .collect();
+    // A repo's own header-max-length is already in its rules
+    let declared_max = convention.and_then(|c| c.header_max_length);
+    if declared_max.is_none() && config.subject_max_len != SUBJECT_MAX_WIDTH {
+        notes.push(format!(
+            "Keep the first line at most {} characters.",
+            config.subject_max_len
+        ));
+    }
+
+    if !body_rules.is_empty() {
+        notes.push(format!(
+            "Body rules by commit type (choose the type first, then follow its rule):\n{}",
+            body_rules.join("\n")
+        ));
+    }
+
+    let updates = parse_submodule_updates(diff);
+    if !updates.is_empty() {
+        let mut lines = vec![
+            "Submodule pointer changes (describe these as dependency bumps, e.g. \"chore(deps): bump <path> to <sha>\"):".to_string(),
+        ];
+        for update in &updates {
+            let line = match (&update.old_sha, &update.new_sha) {
+                (Some(old), Some(new)) => {
+                    let subject = get_submodule_commit_subject(&update.path, new).await;
+                    format!(
+                        "- {} moved from {} to {}{}",
+                        update.path,
+                        short_sha(old),
+                        short_sha(new),
+                        subject
+                            .map(|s| format!(" (now at: \"{}\")", s))
+                            .unwrap_or_default()
+                    )
+                }
+                (None, Some(new)) => format!("- {} added at {}", update.path, short_sha(new)),
+                (Some(_), None) => format!("- {} removed", update.path),
+                (None, None) => continue,
+            };
+            lines.push(line);
+        }
+        notes.push(lines.join("\n"));
+    }
+
+    notes
+}
+
+/// Builds a commit message for submodule pointer changes without the LLM.
+pub fn submodule_fallback_message(updates: &[SubmoduleUpdate]) -> String {
+    let describe = |update: &SubmoduleUpdate| match (&update.old_sha, &update.new_sha) {
+        (_, None) => format!("remove {}", update.path),
+        (None, Some(new)) => format!("add {} at {}", update.path, short_sha(new)),
+        (Some(_), Some(new)) => format!("bump {} to {}", update.path, short_sha(new)),
+    };
+
+    match updates {
+        [single] => format!("chore(deps): {}", describe(single)),
+        _ => {
+            let bullets: Vec<String> = updates
+                .iter()
+                .map(|update| format!("- {}", describe(update)))
+                .collect();
+            format!(
+                "chore(deps): update {} submodules\n\n{}",
+                updates.len(),
+                bullets.join("\n")
+            )
+        }
+    }
+}
+
+/// Message for `--allow-empty` commits made without a description.
+pub const EMPTY_COMMIT_MESSAGE: &str = "chore: empty commit";
+
+/// Diff size limit when the author describes the change with `--from`.
+///
+/// The description carries the intent, so the diff only needs to be enough
+/// to pick the type and scope and to check details.
+pub const DESCRIBED_DIFF_CHARS: usize = 20_000;
+
+/// Prompt guidance that makes the author's own description authoritative.
+pub fn description_prompt_note(description: &str) -> String {
+    format!(
+        "The author describes this change as: \"{}\"\n\
+         Write the message from this description: it is authoritative for what changed and why. \
+         Use the diff only to choose the type and scope and to get names and details right, \
+         and don't add changes the description leaves out unless they're significant.",
+        description.trim()
+    )
+}
+
+/// Prompt guidance for `--body`: a bulleted body even for small changes.
+///
+/// Per-type `body_by_type = "never"` rules still win; their bodies are
+/// removed by [`CommitPolicy::prepare`] anyway.
+pub const BODY_PROMPT_NOTE: &str = "Always include a body, even for a single change: the subject \
+     line, a blank line, then \"-\" bullet points summarizing the key changes (unless a body rule \
+     above says subject line only).";
+
+/// Builds the standard message for reverting a commit, without the LLM.
+///
+/// `revert: <original subject>` followed by git's own
+/// `This reverts commit <sha>.` line.
+pub fn revert_message(reverted: &RevertedCommit) -> String {
+    format!(
+        "revert: {}\n\nThis reverts commit {}.",
+        reverted.subject, reverted.sha
+    )
+}
+
+/// Prompt guidance for staged changes that revert a commit.
+pub fn revert_prompt_note(reverted: &RevertedCommit) -> String {
+    format!(
+        "These changes revert commit {} (\"{}\"). Describe them as a revert, not as new work: \
+         use the subject \"revert: {}\" and include the line \"This reverts commit {}.\" in the body.",
+        reverted.sha, reverted.subject, reverted.subject, reverted.sha
+    )
+}
+
+/// Prompt guidance for a subject the ticket is added to afterwards.
+pub fn ticket_prompt_note(ticket: &str) -> String {
+    format!(
+        "This work is for ticket {}. Don't write the ticket in the message; it's added \
+         automatically.",
+        ticket
+    )
+}
+
+/// True if a generated message has the standard revert form for `reverted`.
+pub fn is_revert_message(message: &str, reverted: &RevertedCommit) -> bool {
+    commit_type(message) == Some("revert")
+        && message.contains(&format!("This reverts commit {}", reverted.sha))
+}
+
+/// Trailers recording that a message was generated by `model` (`stamp_metadata`).
+///
+/// They're added at commit time with the other trailers, so editing the
+/// message during review can't drop them.
+pub fn metadata_trailers(model: &str) -> Vec<String> {
+    vec![
+        format!("Committer-Model: {}", model),
+        format!("Committer-Version: {}", env!("CARGO_PKG_VERSION")),
+    ]
+}
+
+/// Per-run overrides for [`CommitPolicy::from_config`], usually from CLI flags.
+#[derive(Default)]
+pub struct PolicyOverrides {
+    /// Force a `Signed-off-by` trailer even if disabled in config.
+    pub signoff: bool,
+    /// Co-authors credited in addition to the configured ones.
+    pub co_authors: Vec<String>,
+    /// Override the configured body line limit.
+    pub max_body_lines: Option<usize>,
+    /// Log commit-time adjustments even if `verbose` is off in config.
+    pub verbose: bool,
+    /// Scope forced onto the generated subject.
+    pub scope: Option<String>,
+    /// Template that replaces the configured one.
+    pub template: Option<String>,
+    /// Add the gitmoji prefix even if `emoji` is off in config.
+    pub emoji: bool,
+}
+
+/// Message policy applied to every commit in a run.
+#[derive(Default)]
+pub struct CommitPolicy {
+    /// Trailer lines appended to every message (e.g. `Signed-off-by: ...`).
+    pub trailers: Vec<String>,
+    /// Maximum number of body lines kept from the generated message.
+    pub max_body_lines: Option<usize>,
+    /// Scope that replaces whatever scope the model chose.
+    pub scope: Option<String>,
+    /// Scopes the subject may use (see `convention`); others are dropped.
+    pub allowed_scopes: Option<Vec<String>>,
+    /// Message template the generated message is rendered into.
+    pub template: Option<String>,
+    /// Emoji overrides, when subjects get their type's emoji.
+    pub emoji: Option<BTreeMap<String, String>>,
+    /// Ticket from the branch name, when `ticket_in_subject` or the
+    /// template places one.
+    pub ticket: Option<String>,
+    /// Where `ticket` goes in the subject.
+    pub ticket_placement: TicketPlacement,
+    /// Commit types whose body is always removed.
+    pub bodyless_types: Vec<String>,
+    /// Maximum size of the final message, trailers included.
+    pub max_message_bytes: Option<usize>,
+    /// Maximum subject width; see [`CommitPolicy::subject_too_long`].
+    pub subject_max_len: usize,
+    /// Cut subjects over `subject_max_len` at a word boundary.
+    pub truncate_subject: bool,
+    /// Column at which body lines are wrapped (0 disables wrapping).
+    pub wrap_body_at: usize,
+    /// Conventional Commits rules a message must pass to be committed.
+    pub lint_rules: Option<LintRules>,
+    /// Report adjustments made at commit time on stderr.
+    pub verbose: bool,
+}
+
+impl CommitPolicy {
+    /// Builds the policy from config and per-run overrides.
+    ///
+    /// Fails if sign-off is requested but no git identity is configured, if a
+    /// co-author isn't a valid `Name <email>`, or if the ticket is needed and
+    /// `issue_pattern` isn't a valid regex.
+    /// A branch without a ticket just leaves the subject alone.
+    pub async fn from_config(
+        config: &Config,
+        overrides: &PolicyOverrides,
+    ) -> Result<Self, Box<dyn std::error::Error>> {
+        let mut trailers = Vec::new();
+
+        if overrides.signoff || config.signoff {
+            let identity = require_own_identity("Sign-off").await?;
+            trailers.push(format!("Signed-off-by: {}", identity));
+        }
+
+        for co_author in config.co_authors.iter().chain(&overrides.co_authors) {
+            let identity = resolve_identity(co_author)
+                .await
+                .map_err(|e| format!("Invalid co-author: {}", e))?;
+            let trailer = format!("Co-authored-by: {}", identity);
+            if !trailers.contains(&trailer) {
+                trailers.push(trailer);
+            }
+        }
+
+        let template = overrides
+            .template
+            .clone()
+            .or_else(|| config.template.clone());
+        let wants_ticket = config.ticket_in_subject != TicketPlacement::Off
+            || template
+                .as_deref()
+                .is_some_and(|template| uses_placeholder(template, "ticket"));
+        let ticket = if wants_ticket {
+            let pattern = Regex::new(&config.issue_pattern)
+                .map_err(|e| format!("Invalid issue_pattern '{}': {}", config.issue_pattern, e))?;
+            get_current_branch()
+                .await
+                .ok()
+                .and_then(|branch| ticket_from_branch(&branch, &pattern))
+        } else {
+            None
+        };
+        let template_has_ticket = template
+            .as_deref()
+            .is_some_and(|template| uses_placeholder(template, "ticket"));
+        if let (Some(ticket), TicketPlacement::Trailer, false) =
+            (&ticket, config.ticket_in_subject, template_has_ticket)
+        {
+            trailers.push(format!("Refs: {}", ticket));
+        }
+
+        Ok(Self {
+            trailers,
+            max_body_lines: overrides.max_body_lines.or(config.max_body_lines),
+            scope: overrides.scope.clone(),
+            // Set by the caller from the repo's commit rules
+            allowed_scopes: None,
+            template,
+            emoji: (overrides.emoji || config.emoji).then(|| config.emoji_by_type.clone()),
+            ticket,
+            ticket_placement: config.ticket_in_subject,
+            bodyless_types: config
+                .body_by_type
+                .iter()
+                .filter(|(_, mode)| **mode == BodyMode::Never)
+                .map(|(commit_type, _)| commit_type.clone())
+                .collect(),
+            max_message_bytes: config.max_message_bytes,
+            // The caller lowers this to the repo's header-max-length, if it has one
+            subject_max_len: config.subject_max_len,
+            truncate_subject: config.on_too_long == SubjectOverflow::Truncate,
+            wrap_body_at: config.wrap_body_at,
+            // Set by the caller when linting is enabled
+            lint_rules: None,
+            verbose: overrides.verbose || config.verbose,
+        })
+    }
+
+    /// Returns the message's scope if `allowed_scopes` doesn't include it.
+    ///
+    /// A scope forced with `--assume-scope` is checked up front, so this is
+    /// `None` whenever one is set.
+    pub fn rejected_scope<'a>(&self, message: &'a str) -> Option<&'a str> {
+        let allowed = self.allowed_scopes.as_ref()?;
+        if self.scope.is_some() {
+            return None;
+        }
+        commit_scope(message).filter(|scope| !allowed.iter().any(|a| a == scope))
+    }
+
+    /// Shapes a freshly generated message before it is shown for review.
+    pub fn prepare(&self, message: &str) -> String {
+        let message = normalize_spacing(message);
+        let message = match &self.scope {
+            Some(scope) => set_scope(&message, scope),
+            None => message,
+        };
+        let message = match self.rejected_scope(&message) {
+            Some(_) => remove_scope(&message),
+            None => message,
+        };
+        let bodyless = commit_type(&message)
+            .is_some_and(|commit_type| self.bodyless_types.iter().any(|t| t == commit_type));
+        let message = match &self.template {
+            Some(template) => render_template(
+                template,
+                &CommitParts::parse(&message, self.ticket.as_deref()),
+            ),
+            None => message,
+        };
+        // A template with {ticket} has already placed it
+        let ticket_placed = self
+            .template
+            .as_deref()
+            .is_some_and(|template| uses_placeholder(template, "ticket"));
+        let message = match &self.ticket {
+            Some(ticket) if !ticket_placed => place_ticket(&message, ticket, self.ticket_placement),
+            _ => message,
+        };
+        let message = match &self.emoji {
+            Some(overrides) => add_emoji(&message, overrides),
+            None => message,
+        };
+        let message = if self.truncate_subject {
+            truncate_subject(&message, self.subject_max_len)
+        } else {
+            message
+        };
+        // Wrapped first, so max_body_lines counts the lines as committed
+        let message = wrap_commit_message(&message, self.wrap_body_at);
+        match self.max_body_lines {
+            _ if bodyless => trim_body(&message, 0),
+            Some(max_lines) => trim_body(&message, max_lines),
+            None => message,
+        }
+    }
+
+    /// True if the subject is wider than `subject_max_len`.
+    pub fn subject_too_long(&self, message: &str) -> bool {
+        !message_stats(message).subject_ok(self.subject_max_len)
+    }
+
+    /// Returns the message exactly as it will be committed.
+    pub fn finalize(&self, message: &str) -> String {
+        // Normalized again here because the user may have edited the message
+        let message = append_trailers(&normalize_spacing(message), &self.trailers);
+        let Some(max_bytes) = self.max_message_bytes else {
+            return message;
+        };
+
+        let trimmed = trim_to_bytes(&message, max_bytes);
+        if self.verbose && trimmed != message {
+            eprintln!(
+                "— Message is {} bytes; body cut to fit max_message_bytes ({})",
+                message.len(),
+                max_bytes
+            );
+        }
+        if self.verbose && trimmed.len() > max_bytes {
+            eprintln!("— Subject and trailers alone exceed max_message_bytes");
+        }
+        trimmed
+    }
+
+    /// Checks `message` against the lint rules, if linting is enabled.
+    pub fn lint(&self, message: &str) -> Result<(), Vec<String>> {
+        match &self.lint_rules {
+            Some(rules) => validate_conventional(message, rules),
+            None => Ok(()),
+        }
+    }
+
+    /// Finalizes the message and commits it, returning the committed message.
+    ///
+    /// A message that fails [`CommitPolicy::lint`] isn't committed.
+    pub async fn commit(
+        &self,
+        message: &str,
+        options: &CommitOptions,
+    ) -> Result<String, Box<dyn std::error::Error>> {
+        if let Err(violations) = self.lint(message) {
+            save_recovery(message).await;
+            return Err(format!("Message fails lint: {}", violations.join("; ")).into());
+        }
+        let final_message = self.finalize(message);
+        if let Err(e) = run_git_commit(&final_message, options).await {
+            // Keep the reviewed message so a rejected commit (e.g. by a hook) isn't lost
+            save_recovery(message).await;
+            return Err(e);
+        }
+        Ok(final_message)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::cli::Cli;
+    use clap::Parser;
+
+    async fn policy_for(args: &[&str]) -> CommitPolicy {
+        let cli = Cli::try_parse_from(args).unwrap();
+        let overrides = PolicyOverrides {
+            co_authors: cli.co_authors,
+            ..Default::default()
+        };
+        CommitPolicy::from_config(&Config::default(), &overrides)
+            .await
+            .unwrap()
+    }
+
+    #[tokio::test]
+    async fn each_co_author_flag_adds_a_trailer_line() {
+        let policy = policy_for(&[
+            "committer",
+            "--co-author",
+            "Ada Lovelace <ada@example.com>",
+            "--co-author",
+            "Grace Hopper <grace@example.com>",
+        ])
+        .await;
+
+        assert_eq!(
+            policy.finalize("feat: add parser"),
+            "feat: add parser\n\n\
+             Co-authored-by: Ada Lovelace <ada@example.com>\n\
+             Co-authored-by: Grace Hopper <grace@example.com>"
+        );
+    }
+
+    #[tokio::test]
+    async fn repeated_co_authors_are_credited_once() {
+        let policy = policy_for(&[
+            "committer",
+            "--co-author",
+            "Ada Lovelace <ada@example.com>",
+            "--co-author",
+            " Ada Lovelace  <ada@example.com> ",
+        ])
+        .await;
+
+        assert_eq!(
+            policy.trailers,
+            ["Co-authored-by: Ada Lovelace <ada@example.com>"]
+        );
+    }
+
+    #[test]
+    fn every_commit_through_a_policy_gets_the_sign_off() {
+        let sign_off = "Signed-off-by: Ada Lovelace <ada@example.com>";
+        let policy = CommitPolicy {
+            trailers: vec![sign_off.to_string()],
+            ..Default::default()
+        };
+        let messages = [
+            "feat(api): add pagination",
+            "fix(ui): handle scroll\n\n- Clamp offset",
+            "docs: explain setup\n\nSigned-off-by: Ada Lovelace <ada@example.com>",
+        ];
+
+        for message in messages {
+            let finalized = policy.finalize(message);
+            assert_eq!(finalized.matches(sign_off).count(), 1, "{}", finalized);
+            assert!(finalized.ends_with(sign_off));
+        }
+    }
+
+    #[tokio::test]
+    async fn committing_through_a_policy_records_the_sign_off() {
+        let repo = crate::test_support::TestRepo::new("policy-sign-off");
+        repo.git(&["config", "user.name", "Ada Lovelace"]);
+        repo.git(&["config", "user.email", "ada@example.com"]);
+        repo.git(&["config", "commit.gpgsign", "false"]);
+        repo.write("b.txt", "two\n");
+        repo.git(&["add", "b.txt"]);
+
+        let sign_off = "Signed-off-by: Ada Lovelace <ada@example.com>";
+        let policy = CommitPolicy {
+            trailers: vec![sign_off.to_string()],
+            ..Default::default()
+        };
+        let options = CommitOptions {
+            no_verify: true,
+            dir: Some(repo.path().to_path_buf()),
+            ..Default::default()
+        };
+        policy.commit("feat: add b", &options).await.unwrap();
+
+        assert_eq!(
+            repo.git(&["log", "-1", "--format=%B"]),
+            format!("feat: add b\n\n{}", sign_off)
+        );
+    }
+}
@@ -4,8 +4,10 @@
 //! `~/.config/committer/config.toml`. It provides:
 //!
 //! - [`Config`] struct with all user preferences
-//! - Functions to [`load_config`] and [`save_config`]
+//! - Functions to [`load_config`] and [`save_config`], with [`parse_config`]
+//!   reporting unknown keys and invalid values instead of dropping them
 //! - API key retrieval via [`get_api_key`]
+//! - Per-field origin lookup via [`ConfigSources`] (`config show --effective`)
 //!
 //! # Example
 //!
@@ -17,16 +19,229 @@
 //! save_config(&config).unwrap();
 //! ```
 
+use console::style;
 use serde::{Deserialize, Serialize};
-use std::path::PathBuf;
+use std::collections::{BTreeMap, HashMap, HashSet};
+use std::path::{Path, PathBuf};
+use std::sync::OnceLock;
+
+/// What to do when the user picks `b` (create branch first) but then
+/// chooses to stay on the current branch.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize, clap::ValueEnum)]
+#[serde(rename_all = "lowercase")]
+pub enum BranchSkipAction {
+    /// Show the commit prompt again (without the branch option).
+    #[default]
+    Prompt,
+    /// Commit on the current branch immediately.
+    Commit,
+    /// Cancel without committing.
+    Cancel,
+}
+
+impl BranchSkipAction {
+    /// Returns the lowercase name used in the config file.
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            BranchSkipAction::Prompt => "prompt",
+            BranchSkipAction::Commit => "commit",
+            BranchSkipAction::Cancel => "cancel",
+        }
+    }
+}
+
+/// How a suggested branch name longer than `branch_max_length` is shortened.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize, clap::ValueEnum)]
+#[serde(rename_all = "lowercase")]
+pub enum BranchOverflowStrategy {
+    /// Cut the name at the last whole word that fits.
+    #[default]
+    Truncate,
+    /// Keep the type and scope, and shorten the description to two key words.
+    Abbreviate,
+}
+
+impl BranchOverflowStrategy {
+    /// Returns the lowercase name used in the config file.
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            BranchOverflowStrategy::Truncate => "truncate",
+            BranchOverflowStrategy::Abbreviate => "abbreviate",
+        }
+    }
+}
+
+/// What happens to a generated subject longer than `subject_max_len`.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize, clap::ValueEnum)]
+#[serde(rename_all = "lowercase")]
+pub enum SubjectOverflow {
+    /// Ask the model once to shorten it, then warn if it's still too long.
+    Regenerate,
+    /// Cut it at the last whole word that fits.
+    Truncate,
+    /// Keep it and print a warning.
+    #[default]
+    Warn,
+}
+
+impl SubjectOverflow {
+    /// Returns the lowercase name used in the config file.
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            SubjectOverflow::Regenerate => "regenerate",
+            SubjectOverflow::Truncate => "truncate",
+            SubjectOverflow::Warn => "warn",
+        }
+    }
+}
+
+/// Code host that `committer pr` opens pull requests on.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, clap::ValueEnum)]
+#[serde(rename_all = "lowercase")]
+pub enum ForgeKind {
+    /// GitHub, through the GitHub CLI.
+    Github,
+    /// GitLab, through its merge request API.
+    Gitlab,
+}
+
+impl ForgeKind {
+    /// Returns the lowercase name used in the config file.
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            ForgeKind::Github => "github",
+            ForgeKind::Gitlab => "gitlab",
+        }
+    }
+}
+
+/// How branch names are suggested when creating a branch.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize, clap::ValueEnum)]
+#[serde(rename_all = "kebab-case")]
+pub enum BranchSuggestionMode {
+    /// Ask the model; fail if the request fails.
+    Llm,
+    /// Derive the name from the commit message locally, with no request.
+    Fallback,
+    /// Ask the model, deriving the name locally if the request fails.
+    #[default]
+    LlmWithFallback,
+}
+
+impl BranchSuggestionMode {
+    /// Returns the kebab-case name used in the config file.
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            BranchSuggestionMode::Llm => "llm",
+            BranchSuggestionMode::Fallback => "fallback",
+            BranchSuggestionMode::LlmWithFallback => "llm-with-fallback",
+        }
+    }
+}
+
+/// How readily branch analysis flags a commit as not belonging on the branch.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize, clap::ValueEnum)]
+#[serde(rename_all = "lowercase")]
+pub enum BranchStrictness {
+    /// Any scope not in the branch name is a mismatch.
+    #[default]
+    Strict,
+    /// Closely related scopes fit; clearly separate work is a mismatch.
+    Balanced,
+    /// Only obviously unrelated work is a mismatch.
+    Lenient,
+}
+
+impl BranchStrictness {
+    /// Returns the lowercase name used in the config file.
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            BranchStrictness::Strict => "strict",
+            BranchStrictness::Balanced => "balanced",
+            BranchStrictness::Lenient => "lenient",
+        }
+    }
+}
+
+/// Where the ticket from the branch name goes in the commit message.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize, clap::ValueEnum)]
+#[serde(rename_all = "kebab-case")]
+pub enum TicketPlacement {
+    /// Leave the subject alone.
+    #[default]
+    Off,
+    /// After the type and scope: `feat: PROJ-123 add login`.
+    AfterType,
+    /// Before everything: `PROJ-123 feat: add login`.
+    Start,
+    /// At the end, in parentheses: `feat: add login (PROJ-123)`.
+    End,
+    /// In a `Refs: PROJ-123` trailer, leaving the subject alone.
+    Trailer,
+}
+
+impl TicketPlacement {
+    /// Returns the kebab-case name used in the config file.
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            TicketPlacement::Off => "off",
+            TicketPlacement::AfterType => "after-type",
+            TicketPlacement::Start => "start",
+            TicketPlacement::End => "end",
+            TicketPlacement::Trailer => "trailer",
+        }
+    }
+}
+
+/// Whether commits of a given type get a body.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum BodyMode {
+    /// Always write a body.
+    Always,
+    /// Never write a body; one generated anyway is removed.
+    Never,
+    /// Let the model decide.
+    #[default]
+    Auto,
+}
+
+impl BodyMode {
+    /// Returns the lowercase name used in the config file.
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            BodyMode::Always => "always",
+            BodyMode::Never => "never",
+            BodyMode::Auto => "auto",
+        }
+    }
+}
 
 /// Default LLM model used for commit message generation.
 pub const DEFAULT_MODEL: &str = "google/gemini-3-flash-preview";
 
+/// Default initial capacity, in bytes, of the response stream buffer.
+pub const DEFAULT_STREAM_BUFFER_SIZE: usize = 8 * 1024;
+
+/// Default number of retries for a transient API failure.
+pub const DEFAULT_MAX_RETRIES: u32 = 3;
+
+/// Default time limit, in seconds, for a single API request.
+pub const DEFAULT_TIMEOUT_SECS: u64 = 60;
+
+/// Default notes ref for `attach_notes`.
+pub const DEFAULT_NOTES_REF: &str = "refs/notes/committer";
+
+/// Default commit types `lint` allows.
+pub const DEFAULT_LINT_TYPES: &[&str] = &[
+    "build", "chore", "ci", "docs", "feat", "fix", "perf", "refactor", "revert", "style", "test",
+];
+
 /// User configuration for Committer.
 ///
 /// All fields have sensible defaults and are optional in the config file.
 #[derive(Debug, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
 pub struct Config {
     /// Skip confirmation prompts and commit automatically.
     #[serde(default)]
@@ -36,6 +251,10 @@ pub struct Config {
     #[serde(default)]
     pub commit_after_branch: bool,
 
+    /// Behavior when skipping branch creation after choosing `b`.
+    #[serde(default)]
+    pub branch_skip_action: BranchSkipAction,
+
     /// LLM model identifier for OpenRouter (e.g., "anthropic/claude-sonnet-4").
     #[serde(default = "default_model")]
     pub model: String,
@@ -43,56 +262,801 @@ pub struct Config {
     /// Enable detailed logging of operations.
     #[serde(default)]
     pub verbose: bool,
+
+    /// Add a `Signed-off-by` trailer to every commit.
+    #[serde(default)]
+    pub signoff: bool,
+
+    /// GPG-sign every commit (`git commit -S`).
+    #[serde(default)]
+    pub sign: bool,
+
+    /// People credited with a `Co-authored-by` trailer on every commit.
+    #[serde(default)]
+    pub co_authors: Vec<String>,
+
+    /// Scopes generated subjects may use; a repo's `.committer-scopes` wins.
+    #[serde(default)]
+    pub valid_scopes: Vec<String>,
+
+    /// Always ask for a bulleted body, not only for multi-part changes.
+    #[serde(default)]
+    pub body: bool,
+
+    /// Maximum number of body lines to keep; extra bullets are dropped with a note.
+    #[serde(default)]
+    pub max_body_lines: Option<usize>,
+
+    /// Maximum size in bytes of the final message; the body is cut to fit.
+    #[serde(default)]
+    pub max_message_bytes: Option<usize>,
+
+    /// Commit messages whose confidence score (0-100) reaches this without
+    /// review; see `lint::assess_confidence`.
+    #[serde(default)]
+    pub auto_accept_threshold: Option<u8>,
+
+    /// Check messages against Conventional Commits before committing.
+    #[serde(default)]
+    pub lint: bool,
+
+    /// Commit types `lint` allows; a repo's declared `type-enum` wins.
+    #[serde(default = "default_lint_types")]
+    pub lint_types: Vec<String>,
+
+    /// Maximum subject length in characters; a repo's `header-max-length` wins.
+    #[serde(default = "default_subject_max_len")]
+    pub subject_max_len: usize,
+
+    /// What to do with a generated subject over `subject_max_len`.
+    #[serde(default)]
+    pub on_too_long: SubjectOverflow,
+
+    /// Column at which generated body lines are wrapped (0 disables wrapping).
+    #[serde(default = "default_wrap_body_at")]
+    pub wrap_body_at: usize,
+
+    /// Request commit messages as schema-constrained JSON (falls back to text).
+    #[serde(default)]
+    pub structured_output: bool,
+
+    /// Record generated messages and their outcomes in the history file.
+    #[serde(default)]
+    pub history: bool,
+
+    /// Remote the PR branch is pushed to (e.g., "origin").
+    #[serde(default)]
+    pub head_remote: Option<String>,
+
+    /// Remote hosting the repository PRs are opened against (e.g., "upstream").
+    #[serde(default)]
+    pub base_remote: Option<String>,
+
+    /// Base branch for PRs when the remote's default can't be found.
+    #[serde(default)]
+    pub default_base: Option<String>,
+
+    /// Reviewers requested on every PR created.
+    #[serde(default)]
+    pub default_reviewers: Vec<String>,
+
+    /// Labels added to every PR created.
+    #[serde(default)]
+    pub default_labels: Vec<String>,
+
+    /// Code host for PRs; detected from the PR's remote when unset.
+    #[serde(default)]
+    pub forge: Option<ForgeKind>,
+
+    /// Warn before committing on a detached HEAD or a branch behind its upstream.
+    #[serde(default = "default_true")]
+    pub warn_if_behind: bool,
+
+    /// Editor for reviewing messages; defaults to the one `git commit` would use.
+    #[serde(default)]
+    pub editor: Option<String>,
+
+    /// Show the staged diff below a scissors line when editing a message.
+    #[serde(default)]
+    pub verbose_editor: bool,
+
+    /// Print generated text token by token; when off, the response is still
+    /// streamed but only the finished text is shown.
+    #[serde(default = "default_true")]
+    pub show_stream: bool,
+
+    /// Initial capacity, in bytes, of the buffer used to read streamed responses.
+    #[serde(default = "default_stream_buffer_size")]
+    pub stream_buffer_size: usize,
+
+    /// Time limit in seconds for each API request, response included (0 disables).
+    #[serde(default = "default_timeout_secs")]
+    pub timeout_secs: u64,
+
+    /// Seconds a generated message is reused for the same changes (0 disables).
+    #[serde(default = "default_cache_ttl_secs")]
+    pub cache_ttl_secs: u64,
+
+    /// Sampling temperature for commit messages and branch analysis.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub temperature: Option<f32>,
+
+    /// Cap on generated tokens for commit messages and branch analysis.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub max_tokens: Option<u32>,
+
+    /// Chat completions endpoint; any OpenAI-compatible server works.
+    #[serde(default = "default_base_url")]
+    pub base_url: String,
+
+    /// OpenRouter API key, used when `OPENROUTER_API_KEY` isn't set.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub api_key: Option<String>,
+
+    /// File holding the OpenRouter API key, e.g. a mounted Docker or
+    /// Kubernetes secret. Checked before `api_key`.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub api_key_file: Option<PathBuf>,
+
+    /// Retries for rate-limited (429) or unavailable (5xx) API responses (0 disables).
+    #[serde(default = "default_max_retries")]
+    pub max_retries: u32,
+
+    /// Shell command the diff is piped through before it reaches the model.
+    #[serde(default)]
+    pub diff_preprocessor: Option<String>,
+
+    /// Model to retry with when the main model refuses to write a message.
+    #[serde(default)]
+    pub fallback_model: Option<String>,
+
+    /// PR base branch per path glob (e.g. `"services/payments/**" = "payments-main"`).
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub base_branch_rules: BTreeMap<String, String>,
+
+    /// Body behavior per commit type (e.g. `docs = "never"`); unlisted types are `auto`.
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub body_by_type: BTreeMap<String, BodyMode>,
+
+    /// Prefix subjects with the gitmoji for their type.
+    #[serde(default)]
+    pub emoji: bool,
+
+    /// Emoji per commit type, overriding the defaults (`""` disables one).
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub emoji_by_type: BTreeMap<String, String>,
+
+    /// Maximum length of suggested branch names.
+    #[serde(default)]
+    pub branch_max_length: Option<usize>,
+
+    /// How suggested branch names over `branch_max_length` are shortened.
+    #[serde(default)]
+    pub branch_overflow_strategy: BranchOverflowStrategy,
+
+    /// How branch names are suggested (model, local fallback, or both).
+    #[serde(default)]
+    pub branch_suggestion_mode: BranchSuggestionMode,
+
+    /// How readily branch analysis suggests moving a commit to a new branch.
+    #[serde(default)]
+    pub branch_strictness: BranchStrictness,
+
+    /// Regex matching a ticket reference in branch names, e.g. `PROJ-123`.
+    #[serde(default = "default_issue_pattern", alias = "ticket_pattern")]
+    pub issue_pattern: String,
+
+    /// Where the ticket from the branch name is put in the message.
+    #[serde(default, alias = "ticket_placement")]
+    pub ticket_in_subject: TicketPlacement,
+
+    /// Template the generated message is rendered into (see `templates`).
+    #[serde(default)]
+    pub template: Option<String>,
+
+    /// Attach a git note with generation metadata to each commit.
+    #[serde(default)]
+    pub attach_notes: bool,
+
+    /// Notes ref used by `attach_notes`.
+    #[serde(default = "default_notes_ref")]
+    pub notes_ref: String,
+
+    /// Add `Committer-Model` and `Committer-Version` trailers to generated
+    /// messages, so AI-assisted commits can be identified from the log.
+    #[serde(default)]
+    pub stamp_metadata: bool,
+
+    /// Headers added to every API request (e.g. `Helicone-Auth` for a gateway).
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub extra_headers: BTreeMap<String, String>,
+
+    /// Models `committer bench` compares when none are given.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub bench_models: Vec<String>,
+
+    /// Short names for models (e.g. `fast = "x-ai/grok-4.1-fast:free"`).
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub model_aliases: BTreeMap<String, String>,
+}
+
+impl Config {
+    /// Resolves a model alias to its model id.
+    ///
+    /// Names that aren't aliases are returned as is, as literal model ids.
+    pub fn resolve_model(&self, name: &str) -> String {
+        self.model_aliases
+            .get(name)
+            .cloned()
+            .unwrap_or_else(|| name.to_string())
+    }
 }
 
 fn default_model() -> String {
     DEFAULT_MODEL.to_string()
 }
 
+fn default_true() -> bool {
+    true
+}
+
+fn default_stream_buffer_size() -> usize {
+    DEFAULT_STREAM_BUFFER_SIZE
+}
+
+fn default_issue_pattern() -> String {
+    "[A-Z][A-Z0-9]+-[0-9]+".to_string()
+}
+
+fn default_base_url() -> String {
+    crate::api::OPENROUTER_API_URL.to_string()
+}
+
+fn default_timeout_secs() -> u64 {
+    DEFAULT_TIMEOUT_SECS
+}
+
+fn default_cache_ttl_secs() -> u64 {
+    crate::cache::DEFAULT_CACHE_TTL_SECS
+}
+
+fn default_max_retries() -> u32 {
+    DEFAULT_MAX_RETRIES
+}
+
+fn default_notes_ref() -> String {
+    DEFAULT_NOTES_REF.to_string()
+}
+
+fn default_lint_types() -> Vec<String> {
+    DEFAULT_LINT_TYPES.iter().map(|t| t.to_string()).collect()
+}
+
+fn default_subject_max_len() -> usize {
+    crate::format::SUBJECT_MAX_WIDTH
+}
+
+fn default_wrap_body_at() -> usize {
+    crate::format::BODY_MAX_WIDTH
+}
+
 impl Default for Config {
     fn default() -> Self {
         Self {
             auto_commit: false,
             commit_after_branch: false,
+            branch_skip_action: BranchSkipAction::default(),
             model: default_model(),
             verbose: false,
+            signoff: false,
+            sign: false,
+            co_authors: Vec::new(),
+            valid_scopes: Vec::new(),
+            body: false,
+            max_body_lines: None,
+            max_message_bytes: None,
+            auto_accept_threshold: None,
+            lint: false,
+            lint_types: default_lint_types(),
+            subject_max_len: default_subject_max_len(),
+            on_too_long: SubjectOverflow::default(),
+            wrap_body_at: default_wrap_body_at(),
+            structured_output: false,
+            history: false,
+            head_remote: None,
+            base_remote: None,
+            default_base: None,
+            default_reviewers: Vec::new(),
+            default_labels: Vec::new(),
+            forge: None,
+            warn_if_behind: true,
+            editor: None,
+            verbose_editor: false,
+            show_stream: true,
+            stream_buffer_size: default_stream_buffer_size(),
+            timeout_secs: default_timeout_secs(),
+            cache_ttl_secs: default_cache_ttl_secs(),
+            temperature: None,
+            max_tokens: None,
+            base_url: default_base_url(),
+            api_key: None,
+            api_key_file: None,
+            max_retries: default_max_retries(),
+            diff_preprocessor: None,
+            fallback_model: None,
+            base_branch_rules: BTreeMap::new(),
+            body_by_type: BTreeMap::new(),
+            emoji: false,
+            emoji_by_type: BTreeMap::new(),
+            branch_max_length: None,
+            branch_overflow_strategy: BranchOverflowStrategy::default(),
+            branch_suggestion_mode: BranchSuggestionMode::default(),
+            branch_strictness: BranchStrictness::default(),
+            issue_pattern: default_issue_pattern(),
+            ticket_in_subject: TicketPlacement::default(),
+            template: None,
+            attach_notes: false,
+            stamp_metadata: false,
+            notes_ref: default_notes_ref(),
+            extra_headers: BTreeMap::new(),
+            model_aliases: BTreeMap::new(),
+            bench_models: Vec::new(),
         }
     }
 }
 
-/// Returns the path to the configuration file.
+/// Config file chosen with `--config`, set once at startup.
+static CONFIG_PATH_OVERRIDE: OnceLock<PathBuf> = OnceLock::new();
+
+/// Makes [`config_path`] return `path` for the rest of the process.
 ///
-/// Typically `~/.config/committer/config.toml` on Linux/macOS.
-pub fn config_path() -> PathBuf {
+/// Called once, before any config is loaded; later calls are ignored.
+pub fn set_config_path(path: PathBuf) {
+    let _ = CONFIG_PATH_OVERRIDE.set(path);
+}
+
+/// Returns committer's directory for its config, cache, and history files.
+///
+/// Typically `~/.config/committer` on Linux/macOS. `--config` doesn't move
+/// it, so profiles share the models cache and history.
+pub fn config_dir() -> PathBuf {
     dirs::config_dir()
         .unwrap_or_else(|| PathBuf::from("."))
         .join("committer")
-        .join("config.toml")
+}
+
+/// Returns the path to the configuration file.
+///
+/// The `--config` path if given (see [`set_config_path`]), otherwise
+/// `config.toml` in [`config_dir`].
+pub fn config_path() -> PathBuf {
+    match CONFIG_PATH_OVERRIDE.get() {
+        Some(path) => path.clone(),
+        None => config_dir().join("config.toml"),
+    }
 }
 
 /// Loads configuration from disk, returning defaults if file doesn't exist.
+///
+/// Problems are reported on stderr rather than failing the run: unknown keys
+/// are skipped with a warning (and kept by [`save_config`]), and a file that
+/// can't be parsed is replaced by the defaults with the parse error shown.
 pub fn load_config() -> Config {
     let path = config_path();
-    if path.exists() {
-        let contents = std::fs::read_to_string(&path).unwrap_or_default();
-        toml::from_str(&contents).unwrap_or_default()
-    } else {
-        Config::default()
+    if !path.exists() {
+        return Config::default();
+    }
+
+    let contents = match std::fs::read_to_string(&path) {
+        Ok(contents) => contents,
+        Err(e) => {
+            eprintln!(
+                "{} Could not read {} ({}); using defaults",
+                style("⚠").yellow(),
+                path.display(),
+                e
+            );
+            return Config::default();
+        }
+    };
+
+    match parse_config(&contents) {
+        Ok((config, unknown)) => {
+            for key in unknown {
+                eprintln!(
+                    "{} Ignoring unknown key '{}' in {}",
+                    style("⚠").yellow(),
+                    key,
+                    path.display()
+                );
+            }
+            config
+        }
+        Err(e) => {
+            eprintln!(
+                "{} Invalid config in {}; using defaults",
+                style("⚠").yellow(),
+                path.display()
+            );
+            for line in e.to_string().trim_end().lines() {
+                eprintln!("  {}", line);
+            }
+            Config::default()
+        }
+    }
+}
+
+/// Parses config file contents.
+///
+/// Unknown keys (usually typos) are dropped and returned, so one bad key
+/// doesn't discard the rest of the file. Any other problem, such as invalid
+/// TOML or a value of the wrong type, is an error.
+pub fn parse_config(contents: &str) -> Result<(Config, Vec<String>), toml::de::Error> {
+    // Parsing the text directly keeps line numbers in the error
+    let mut error = match toml::from_str(contents) {
+        Ok(config) => return Ok((config, Vec::new())),
+        Err(e) => e,
+    };
+
+    let mut table: toml::Table = contents.parse()?;
+    let mut unknown = Vec::new();
+    while let Some(key) = unknown_field(&error) {
+        if table.remove(&key).is_none() {
+            break;
+        }
+        unknown.push(key);
+        match toml::Value::Table(table.clone()).try_into() {
+            Ok(config) => return Ok((config, unknown)),
+            Err(e) => error = e,
+        }
     }
+
+    Err(error)
+}
+
+/// Returns the key named by an "unknown field" error.
+fn unknown_field(error: &toml::de::Error) -> Option<String> {
+    let rest = error.message().strip_prefix("unknown field `")?;
+    rest.split('`').next().map(str::to_string)
 }
 
 /// Saves configuration to disk, creating parent directories if needed.
+///
+/// Refuses to overwrite a config file that doesn't parse, since the loaded
+/// configuration is then just the defaults. Unknown keys in the existing file
+/// are written back unchanged, so a typo isn't lost by `config` commands.
 pub fn save_config(config: &Config) -> Result<(), Box<dyn std::error::Error>> {
     let path = config_path();
+    let mut contents = toml::to_string_pretty(config)?;
+    if let Ok(existing) = std::fs::read_to_string(&path) {
+        let Ok((_, unknown)) = parse_config(&existing) else {
+            return Err(format!(
+                "Not overwriting {}: fix or remove the invalid config first",
+                path.display()
+            )
+            .into());
+        };
+        contents = keep_unknown_keys(contents, &existing, &unknown)?;
+    }
     if let Some(parent) = path.parent() {
         std::fs::create_dir_all(parent)?;
     }
-    let contents = toml::to_string_pretty(config)?;
     std::fs::write(&path, contents)?;
     Ok(())
 }
 
-/// Retrieves the OpenRouter API key from the `OPENROUTER_API_KEY` environment variable.
-pub fn get_api_key() -> Option<String> {
-    std::env::var("OPENROUTER_API_KEY").ok()
+/// Adds the `unknown` keys of `existing` to freshly serialized config text.
+///
+/// Plain values go first and tables last, so neither ends up inside one of
+/// the config's own tables.
+fn keep_unknown_keys(
+    contents: String,
+    existing: &str,
+    unknown: &[String],
+) -> Result<String, toml::ser::Error> {
+    let Ok(mut table) = existing.parse::<toml::Table>() else {
+        return Ok(contents);
+    };
+    let mut values = toml::Table::new();
+    let mut tables = toml::Table::new();
+    for key in unknown {
+        let Some(value) = table.remove(key) else {
+            continue;
+        };
+        let is_table = match &value {
+            toml::Value::Table(_) => true,
+            toml::Value::Array(items) => !items.is_empty() && items.iter().all(|v| v.is_table()),
+            _ => false,
+        };
+        if is_table {
+            tables.insert(key.clone(), value);
+        } else {
+            values.insert(key.clone(), value);
+        }
+    }
+
+    let mut kept = toml::to_string_pretty(&values)?;
+    kept.push_str(&contents);
+    if !tables.is_empty() {
+        kept.push('\n');
+        kept.push_str(&toml::to_string_pretty(&tables)?);
+    }
+    Ok(kept)
+}
+
+/// Where an effective configuration value came from.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ConfigSource {
+    /// Built-in default; not set anywhere.
+    Default,
+    /// Set in the global config file.
+    Global,
+    /// Set by an environment variable.
+    Env,
+    /// Set by a file in the repository, e.g. `.committer-scopes`.
+    Repo,
+    /// Overridden by a command-line flag for this run.
+    Cli,
+}
+
+impl ConfigSource {
+    /// Returns the lowercase tag shown by `config show --effective`.
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            ConfigSource::Default => "default",
+            ConfigSource::Global => "global",
+            ConfigSource::Env => "env",
+            ConfigSource::Repo => "repo",
+            ConfigSource::Cli => "cli",
+        }
+    }
+}
+
+/// Records which config keys are explicitly set, to report value origins.
+pub struct ConfigSources {
+    global_keys: HashSet<String>,
+    /// Keys whose value was replaced after loading, and by what.
+    overrides: HashMap<String, ConfigSource>,
+}
+
+impl ConfigSources {
+    /// Reads the keys present in the global config file.
+    pub fn load() -> Self {
+        let global_keys = std::fs::read_to_string(config_path())
+            .ok()
+            .and_then(|contents| contents.parse::<toml::Table>().ok())
+            .map(|table| table.keys().cloned().collect())
+            .unwrap_or_default();
+        Self {
+            global_keys,
+            overrides: HashMap::new(),
+        }
+    }
+
+    /// Records that a key's value was replaced by the repository or a flag.
+    pub fn set(&mut self, key: &str, source: ConfigSource) {
+        self.overrides.insert(key.to_string(), source);
+    }
+
+    /// Returns the origin of a config key's effective value.
+    pub fn of(&self, key: &str) -> ConfigSource {
+        if let Some(source) = self.overrides.get(key) {
+            *source
+        } else if self.global_keys.contains(key) {
+            ConfigSource::Global
+        } else {
+            ConfigSource::Default
+        }
+    }
+}
+
+/// Where [`find_api_key`] found the API key.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ApiKeySource {
+    /// The `OPENROUTER_API_KEY` environment variable.
+    Env,
+    /// The file named by `OPENROUTER_API_KEY_FILE`.
+    EnvFile,
+    /// The file named by `api_key_file` in the config file.
+    ConfigFile,
+    /// `api_key` in the config file.
+    Config,
+}
+
+impl ApiKeySource {
+    /// Returns the label shown by `config show`.
+    pub fn label(&self) -> &'static str {
+        match self {
+            ApiKeySource::Env => "[set via env]",
+            ApiKeySource::EnvFile => "[read from OPENROUTER_API_KEY_FILE]",
+            ApiKeySource::ConfigFile => "[read from api_key_file]",
+            ApiKeySource::Config => "[set in config]",
+        }
+    }
+
+    /// Returns where the setting that supplied the key lives.
+    pub fn config_source(&self) -> ConfigSource {
+        match self {
+            ApiKeySource::Env | ApiKeySource::EnvFile => ConfigSource::Env,
+            ApiKeySource::ConfigFile | ApiKeySource::Config => ConfigSource::Global,
+        }
+    }
+}
+
+/// A place an API key can come from: the key itself, or a file holding it.
+enum KeyRef<'a> {
+    Value(Option<String>),
+    File(Option<&'a Path>),
+}
+
+/// Retrieves the OpenRouter API key.
+///
+/// `key_file` (`--api-key-file`) wins over everything; otherwise see
+/// [`find_api_key`]. Fails if a key file that's consulted can't be read.
+pub fn get_api_key(
+    config: &Config,
+    key_file: Option<&Path>,
+) -> Result<Option<String>, Box<dyn std::error::Error>> {
+    api_key_from(config, key_file, &|name| std::env::var_os(name))
+}
+
+/// Reads an environment variable; swapped out in tests.
+type EnvLookup<'a> = &'a dyn Fn(&str) -> Option<std::ffi::OsString>;
+
+/// [`get_api_key`], reading the environment through `env`.
+fn api_key_from(
+    config: &Config,
+    key_file: Option<&Path>,
+    env: EnvLookup,
+) -> Result<Option<String>, Box<dyn std::error::Error>> {
+    if let Some(key) = read_key(KeyRef::File(key_file))? {
+        return Ok(Some(key));
+    }
+    Ok(find_api_key_from(config, env)?.map(|(key, _)| key))
+}
+
+/// Finds the API key in the environment or config, and where it came from.
+///
+/// Sources, highest precedence first: the `OPENROUTER_API_KEY` environment
+/// variable, the file named by `OPENROUTER_API_KEY_FILE` (the `*_FILE`
+/// convention of Docker secrets), `api_key_file`, then `api_key`. Values are
+/// trimmed and blank ones skipped. A key file is only read if every source
+/// before it is unset, and fails the lookup if it can't be read.
+pub fn find_api_key(
+    config: &Config,
+) -> Result<Option<(String, ApiKeySource)>, Box<dyn std::error::Error>> {
+    find_api_key_from(config, &|name| std::env::var_os(name))
+}
+
+/// [`find_api_key`], reading the environment through `env`.
+fn find_api_key_from(
+    config: &Config,
+    env: EnvLookup,
+) -> Result<Option<(String, ApiKeySource)>, Box<dyn std::error::Error>> {
+    let env_file = env("OPENROUTER_API_KEY_FILE").map(PathBuf::from);
+    let sources = [
+        (
+            ApiKeySource::Env,
+            KeyRef::Value(env("OPENROUTER_API_KEY").and_then(|key| key.into_string().ok())),
+        ),
+        (ApiKeySource::EnvFile, KeyRef::File(env_file.as_deref())),
+        (
+            ApiKeySource::ConfigFile,
+            KeyRef::File(config.api_key_file.as_deref()),
+        ),
+        (ApiKeySource::Config, KeyRef::Value(config.api_key.clone())),
+    ];
+
+    for (source, key) in sources {
+        if let Some(key) = read_key(key)? {
+            return Ok(Some((key, source)));
+        }
+    }
+    Ok(None)
+}
+
+/// Returns the trimmed key from `key`, or `None` if it's unset or blank.
+fn read_key(key: KeyRef) -> Result<Option<String>, String> {
+    let key = match key {
+        KeyRef::Value(value) => value,
+        KeyRef::File(None) => None,
+        KeyRef::File(Some(path)) => Some(
+            std::fs::read_to_string(path)
+                .map_err(|e| format!("Failed to read API key file '{}': {}", path.display(), e))?,
+        ),
+    };
+    Ok(key
+        .map(|key| key.trim().to_string())
+        .filter(|key| !key.is_empty()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_support::TempDir;
+    use std::collections::HashMap;
+    use std::ffi::OsString;
+
+    #[test]
+    fn api_key_sources_follow_precedence() {
+        let dir = TempDir::new("api-key");
+        let cli_file = dir.write("cli", "from-cli\n");
+        let env_file = dir.write("env-file", "  from-env-file  ");
+        let config_file = dir.write("config-file", "from-config-file");
+
+        let mut config = Config {
+            api_key: Some("from-config".to_string()),
+            api_key_file: Some(config_file.clone()),
+            ..Config::default()
+        };
+        let mut env: HashMap<&str, OsString> = HashMap::from([
+            ("OPENROUTER_API_KEY", "from-env".into()),
+            ("OPENROUTER_API_KEY_FILE", env_file.clone().into()),
+        ]);
+        let key = |config: &Config, cli: Option<&Path>, env: &HashMap<&str, OsString>| {
+            api_key_from(config, cli, &|name| env.get(name).cloned()).unwrap()
+        };
+
+        assert_eq!(
+            key(&config, Some(&cli_file), &env).as_deref(),
+            Some("from-cli")
+        );
+        assert_eq!(key(&config, None, &env).as_deref(), Some("from-env"));
+        env.remove("OPENROUTER_API_KEY");
+        assert_eq!(key(&config, None, &env).as_deref(), Some("from-env-file"));
+        env.remove("OPENROUTER_API_KEY_FILE");
+        assert_eq!(
+            key(&config, None, &env).as_deref(),
+            Some("from-config-file")
+        );
+        config.api_key_file = None;
+        assert_eq!(key(&config, None, &env).as_deref(), Some("from-config"));
+        config.api_key = None;
+        assert_eq!(key(&config, None, &env), None);
+    }
+
+    #[test]
+    fn blank_api_key_sources_are_skipped() {
+        let config = Config {
+            api_key: Some("from-config".to_string()),
+            ..Config::default()
+        };
+        let env = |name: &str| (name == "OPENROUTER_API_KEY").then(|| OsString::from("   "));
+        assert_eq!(
+            find_api_key_from(&config, &env).unwrap(),
+            Some(("from-config".to_string(), ApiKeySource::Config))
+        );
+    }
+
+    #[test]
+    fn saving_keeps_unknown_keys() {
+        let existing = "modle = \"typo/model\"\nauto_commit = true\n\n[extra]\nkey = 1\n";
+        let (config, unknown) = parse_config(existing).unwrap();
+        assert_eq!(unknown, ["modle", "extra"]);
+
+        let contents = toml::to_string_pretty(&config).unwrap();
+        let saved = keep_unknown_keys(contents, existing, &unknown).unwrap();
+
+        let (reloaded, unknown) = parse_config(&saved).unwrap();
+        assert!(reloaded.auto_commit);
+        assert_eq!(unknown, ["modle", "extra"]);
+        let table: toml::Table = saved.parse().unwrap();
+        assert_eq!(table["modle"].as_str(), Some("typo/model"));
+        assert_eq!(table["extra"]["key"].as_integer(), Some(1));
+    }
+
+    #[test]
+    fn config_sources_prefer_overrides_to_the_global_file() {
+        let mut sources = ConfigSources {
+            global_keys: HashSet::from(["model".to_string(), "lint".to_string()]),
+            overrides: HashMap::new(),
+        };
+        sources.set("model", ConfigSource::Cli);
+        sources.set("valid_scopes", ConfigSource::Repo);
+
+        assert_eq!(sources.of("model"), ConfigSource::Cli);
+        assert_eq!(sources.of("valid_scopes"), ConfigSource::Repo);
+        assert_eq!(sources.of("lint"), ConfigSource::Global);
+        assert_eq!(sources.of("body"), ConfigSource::Default);
+    }
 }
@@ -0,0 +1,349 @@
+//! Detection of a repository's declared commit conventions.
+//!
+//! Many repositories already declare their commit rules for commitlint or
+//! gitlint. Committer reads these files (best-effort) so generated messages
+//! follow the repo's own rules without duplicating them in Committer config:
+//!
+//! - **commitlint**: `.commitlintrc`, `.commitlintrc.json`, `.commitlintrc.yaml`,
+//!   `.commitlintrc.yml`, `.commitlintrc.js`, `.commitlintrc.cjs`,
+//!   `commitlint.config.js`, `commitlint.config.cjs`, `commitlint.config.mjs` —
+//!   `type-enum`, `scope-enum`, `header-max-length`
+//! - **gitlint**: `.gitlint` — `[title-max-length] line-length` and
+//!   `[contrib-title-conventional-commits] types`
+//!
+//! Rules are extracted with patterns rather than by evaluating JavaScript, so
+//! computed configs are not understood. Unrecognized files are ignored silently.
+//!
+//! A scope registry narrows the scopes further: [`SCOPES_FILE`] in the
+//! repository root, or `valid_scopes` in config. [`load_commit_rules`] merges
+//! it with the convention file, replacing any `scope-enum`.
+
+use regex_lite::Regex;
+use std::path::Path;
+
+/// Candidate convention files, in lookup order.
+pub const CONVENTION_FILES: &[&str] = &[
+    ".commitlintrc",
+    ".commitlintrc.json",
+    ".commitlintrc.yaml",
+    ".commitlintrc.yml",
+    ".commitlintrc.js",
+    ".commitlintrc.cjs",
+    "commitlint.config.js",
+    "commitlint.config.cjs",
+    "commitlint.config.mjs",
+    ".gitlint",
+];
+
+/// File in the repository root listing its valid scopes, one per line.
+pub const SCOPES_FILE: &str = ".committer-scopes";
+
+/// Commit rules declared by the repository.
+#[derive(Debug, Default, Clone)]
+pub struct RepoConvention {
+    /// File the rules were read from.
+    pub source: String,
+    /// Allowed commit types, if restricted.
+    pub types: Option<Vec<String>>,
+    /// Allowed scopes, if restricted.
+    pub scopes: Option<Vec<String>>,
+    /// Maximum header (subject line) length.
+    pub header_max_length: Option<usize>,
+}
+
+impl RepoConvention {
+    /// True if no rules were found.
+    pub fn is_empty(&self) -> bool {
+        self.types.is_none() && self.scopes.is_none() && self.header_max_length.is_none()
+    }
+
+    /// True if the scope is allowed (always true when scopes aren't restricted).
+    pub fn allows_scope(&self, scope: &str) -> bool {
+        self.scopes
+            .as_ref()
+            .is_none_or(|scopes| scopes.iter().any(|s| s == scope))
+    }
+
+    /// Renders the rules as prompt guidance for the model.
+    pub fn prompt_note(&self) -> String {
+        let mut lines = vec![format!("This repository's commit rules ({}):", self.source)];
+        if let Some(types) = &self.types {
+            lines.push(format!("- Allowed types: {}", types.join(", ")));
+        }
+        if let Some(scopes) = &self.scopes {
+            lines.push(format!(
+                "- Allowed scopes: {} (use one of these, or no scope)",
+                scopes.join(", ")
+            ));
+        }
+        if let Some(max) = self.header_max_length {
+            lines.push(format!("- First line must be at most {} characters", max));
+        }
+        lines.join("\n")
+    }
+}
+
+/// Loads the first recognized convention file in the repository root.
+///
+/// Returns `None` if no file exists or none declares recognizable rules.
+pub fn load_repo_convention(repo_root: &Path) -> Option<RepoConvention> {
+    CONVENTION_FILES.iter().find_map(|name| {
+        let contents = std::fs::read_to_string(repo_root.join(name)).ok()?;
+        let mut convention = if *name == ".gitlint" {
+            parse_gitlint(&contents)
+        } else if name.ends_with(".yaml") || name.ends_with(".yml") {
+            parse_commitlint_yaml(&contents)
+        } else {
+            let convention = parse_commitlint(&contents);
+            // An extensionless `.commitlintrc` may be YAML as well as JSON
+            if convention.is_empty() && *name == ".commitlintrc" {
+                parse_commitlint_yaml(&contents)
+            } else {
+                convention
+            }
+        };
+        convention.source = name.to_string();
+        (!convention.is_empty()).then_some(convention)
+    })
+}
+
+/// Loads the repository's commit rules with its scope registry applied.
+///
+/// The registry is [`SCOPES_FILE`] if the repository has one, otherwise
+/// `valid_scopes` from config; either replaces the convention file's scopes.
+/// Returns `None` if there are no rules at all.
+pub fn load_commit_rules(
+    repo_root: Option<&Path>,
+    valid_scopes: &[String],
+) -> Option<RepoConvention> {
+    let convention = repo_root.and_then(load_repo_convention);
+    let registry = repo_root
+        .and_then(load_scopes_file)
+        .map(|scopes| (SCOPES_FILE, scopes))
+        .or_else(|| (!valid_scopes.is_empty()).then(|| ("valid_scopes", valid_scopes.to_vec())));
+    let Some((registry_source, scopes)) = registry else {
+        return convention;
+    };
+
+    let mut convention = convention.unwrap_or_default();
+    convention.source = if convention.source.is_empty() {
+        registry_source.to_string()
+    } else {
+        format!("{}, {}", convention.source, registry_source)
+    };
+    convention.scopes = Some(scopes);
+    Some(convention)
+}
+
+/// Reads [`SCOPES_FILE`]: one scope per line, `#` starts a comment.
+pub fn load_scopes_file(repo_root: &Path) -> Option<Vec<String>> {
+    let contents = std::fs::read_to_string(repo_root.join(SCOPES_FILE)).ok()?;
+    let scopes: Vec<String> = contents
+        .lines()
+        .map(|line| line.split('#').next().unwrap_or_default().trim())
+        .filter(|scope| !scope.is_empty())
+        .map(str::to_string)
+        .collect();
+    (!scopes.is_empty()).then_some(scopes)
+}
+
+/// Returns the allowed scope closest to `scope`, if one is a likely intent.
+///
+/// Matching ignores case. A scope within a few edits (`atuh` for `auth`), or
+/// one containing or contained in an allowed scope (`api` for `api-client`),
+/// counts as close; the fewest edits wins.
+pub fn closest_scope<'a>(scope: &str, allowed: &'a [String]) -> Option<&'a str> {
+    let scope = scope.to_lowercase();
+    allowed
+        .iter()
+        .filter_map(|candidate| {
+            let lower = candidate.to_lowercase();
+            let distance = edit_distance(&scope, &lower);
+            let max_distance = (scope.chars().count().max(lower.chars().count()) / 3).max(1);
+            let close =
+                distance <= max_distance || lower.contains(&scope) || scope.contains(&lower);
+            close.then_some((distance, candidate.as_str()))
+        })
+        .min_by_key(|(distance, _)| *distance)
+        .map(|(_, candidate)| candidate)
+}
+
+/// Levenshtein distance between two strings, counted in characters.
+fn edit_distance(a: &str, b: &str) -> usize {
+    let b: Vec<char> = b.chars().collect();
+    let mut previous: Vec<usize> = (0..=b.len()).collect();
+    for (i, a_char) in a.chars().enumerate() {
+        let mut current = vec![i + 1];
+        for (j, b_char) in b.iter().enumerate() {
+            let substitution = previous[j] + usize::from(a_char != *b_char);
+            current.push(substitution.min(previous[j + 1] + 1).min(current[j] + 1));
+        }
+        previous = current;
+    }
+    previous[b.len()]
+}
+
+/// Extracts the values of a commitlint enum rule like `[2, "always", ["a", "b"]]`.
+fn commitlint_enum(contents: &str, rule: &str) -> Option<Vec<String>> {
+    let pattern = format!(
+        r#"['"]?{}['"]?\s*:\s*\[\s*\d\s*,\s*['"]always['"]\s*,\s*\[([^\]]*)\]"#,
+        rule
+    );
+    let re = Regex::new(&pattern).ok()?;
+    let list = re.captures(contents)?.get(1)?.as_str();
+
+    let item_re = Regex::new(r#"['"]([^'"]+)['"]"#).ok()?;
+    let values: Vec<String> = item_re
+        .captures_iter(list)
+        .filter_map(|caps| caps.get(1).map(|m| m.as_str().to_string()))
+        .collect();
+
+    (!values.is_empty()).then_some(values)
+}
+
+/// Parses commitlint rules from JSON, YAML flow style, or a JS config.
+fn parse_commitlint(contents: &str) -> RepoConvention {
+    let header_re =
+        Regex::new(r#"['"]?header-max-length['"]?\s*:\s*\[\s*\d\s*,\s*['"]always['"]\s*,\s*(\d+)"#)
+            .unwrap();
+
+    RepoConvention {
+        types: commitlint_enum(contents, "type-enum"),
+        scopes: commitlint_enum(contents, "scope-enum"),
+        header_max_length: header_re
+            .captures(contents)
+            .and_then(|caps| caps.get(1))
+            .and_then(|m| m.as_str().parse().ok()),
+        ..Default::default()
+    }
+}
+
+/// Parses commitlint rules from a YAML config, in block or flow style.
+fn parse_commitlint_yaml(contents: &str) -> RepoConvention {
+    let enum_rule = |rule| match yaml_rule(contents, rule).as_slice() {
+        [_, when, values @ ..] if when == "always" && !values.is_empty() => Some(values.to_vec()),
+        _ => None,
+    };
+    let header_max_length = match yaml_rule(contents, "header-max-length").as_slice() {
+        [_, when, max] if when == "always" => max.parse().ok(),
+        _ => None,
+    };
+
+    RepoConvention {
+        types: enum_rule("type-enum"),
+        scopes: enum_rule("scope-enum"),
+        header_max_length,
+        ..Default::default()
+    }
+}
+
+/// Flattens a YAML rule's value, e.g. `[2, always, [a, b]]` written in block
+/// or flow style, into its scalars: `2`, `always`, `a`, `b`.
+fn yaml_rule(contents: &str, rule: &str) -> Vec<String> {
+    let key_re = Regex::new(&format!(r#"^(\s*)['"]?{}['"]?\s*:(.*)$"#, rule)).unwrap();
+    let mut lines = contents.lines();
+    let Some((indent, first)) = lines.by_ref().find_map(|line| {
+        let caps = key_re.captures(line)?;
+        Some((caps[1].len(), caps[2].to_string()))
+    }) else {
+        return Vec::new();
+    };
+
+    // The value runs on until the next line indented no deeper than the key
+    let rest = lines
+        .take_while(|line| line.trim().is_empty() || line.len() - line.trim_start().len() > indent);
+    std::iter::once(first.as_str())
+        .chain(rest)
+        .flat_map(|line| {
+            let line = line.split(" #").next().unwrap_or_default();
+            let line = line.trim_start_matches(|c: char| c == '-' || c.is_whitespace());
+            line.split([',', '[', ']'])
+                .map(|item| item.trim().trim_matches(['"', '\'']).to_string())
+                .filter(|item| !item.is_empty())
+                .collect::<Vec<_>>()
+        })
+        .collect()
+}
+
+/// Parses the gitlint INI format.
+fn parse_gitlint(contents: &str) -> RepoConvention {
+    let mut convention = RepoConvention::default();
+    let mut section = String::new();
+
+    for line in contents.lines() {
+        let line = line.trim();
+        if line.starts_with('#') || line.starts_with(';') || line.is_empty() {
+            continue;
+        }
+        if let Some(name) = line.strip_prefix('[').and_then(|l| l.strip_suffix(']')) {
+            section = name.trim().to_string();
+            continue;
+        }
+        let Some((key, value)) = line.split_once('=') else {
+            continue;
+        };
+        let (key, value) = (key.trim(), value.trim());
+
+        match (section.as_str(), key) {
+            ("title-max-length" | "T1", "line-length") => {
+                convention.header_max_length = value.parse().ok();
+            }
+            ("contrib-title-conventional-commits" | "CT1", "types") => {
+                let types: Vec<String> = value
+                    .split(',')
+                    .map(|t| t.trim().to_string())
+                    .filter(|t| !t.is_empty())
+                    .collect();
+                if !types.is_empty() {
+                    convention.types = Some(types);
+                }
+            }
+            _ => {}
+        }
+    }
+
+    convention
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn commitlint_yaml_block_style_is_parsed() {
+        let convention = parse_commitlint_yaml(
+            "extends:\n  - '@commitlint/config-conventional'\nrules:\n  type-enum:\n    - 2\n    - always\n    - - feat\n      - fix # no chores\n  scope-enum:\n    - 2\n    - always\n    - [api, 'ui']\n  header-max-length: [2, always, 72]\n",
+        );
+        assert_eq!(
+            convention.types,
+            Some(vec!["feat".to_string(), "fix".to_string()])
+        );
+        assert_eq!(
+            convention.scopes,
+            Some(vec!["api".to_string(), "ui".to_string()])
+        );
+        assert_eq!(convention.header_max_length, Some(72));
+    }
+
+    #[test]
+    fn commitlint_yaml_rules_that_are_off_or_never_are_ignored() {
+        let convention =
+            parse_commitlint_yaml("rules:\n  type-enum: [0]\n  scope-enum: [2, never, [deps]]\n");
+        assert!(convention.is_empty());
+    }
+
+    #[test]
+    fn commitlintrc_yml_is_detected() {
+        let dir = crate::test_support::TempDir::new("commitlintrc-yml");
+        dir.write(
+            ".commitlintrc.yml",
+            "rules:\n  type-enum: [2, always, [feat, fix]]\n",
+        );
+        let convention = load_repo_convention(dir.path()).unwrap();
+        assert_eq!(convention.source, ".commitlintrc.yml");
+        assert_eq!(
+            convention.types,
+            Some(vec!["feat".to_string(), "fix".to_string()])
+        );
+    }
+}
@@ -0,0 +1,84 @@
+//! Gitmoji prefixes for `emoji` mode.
+//!
+//! With `--emoji` (or `emoji = true`), the subject gets the emoji for its
+//! conventional-commit type: `feat: add login` becomes `✨ feat: add login`.
+//! [`TYPE_EMOJI`] holds the defaults; `[emoji_by_type]` in the config file
+//! overrides or adds entries, and an empty string turns one off.
+//!
+//! Subject parsing elsewhere (types, scopes, branch names) skips a leading
+//! emoji via [`strip_emoji_prefix`], so prefixed messages are understood the
+//! same as plain ones.
+
+use std::collections::BTreeMap;
+
+/// Default emoji for each conventional-commit type, from gitmoji.
+pub const TYPE_EMOJI: &[(&str, &str)] = &[
+    ("feat", "✨"),
+    ("fix", "🐛"),
+    ("docs", "📝"),
+    ("style", "🎨"),
+    ("refactor", "♻️"),
+    ("perf", "⚡️"),
+    ("test", "✅"),
+    ("build", "📦️"),
+    ("ci", "👷"),
+    ("chore", "🔧"),
+    ("revert", "⏪️"),
+];
+
+/// Returns the default emoji for a commit type.
+pub fn emoji_for_type(commit_type: &str) -> Option<&'static str> {
+    TYPE_EMOJI
+        .iter()
+        .find(|(t, _)| *t == commit_type)
+        .map(|(_, emoji)| *emoji)
+}
+
+/// Returns the subject without a leading emoji or `:shortcode:`.
+///
+/// `✨ feat: x` and `:sparkles: feat: x` both become `feat: x`; other
+/// subjects are returned unchanged.
+pub fn strip_emoji_prefix(subject: &str) -> &str {
+    let trimmed = subject.trim_start();
+    let Some((prefix, rest)) = trimmed.split_once(' ') else {
+        return subject;
+    };
+    let is_emoji = !prefix.is_empty()
+        && prefix
+            .chars()
+            .all(|c| !c.is_ascii() && !c.is_alphanumeric());
+    let is_shortcode = prefix.len() > 2
+        && prefix.starts_with(':')
+        && prefix.ends_with(':')
+        && prefix[1..prefix.len() - 1]
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || c == '_' || c == '-');
+    if is_emoji || is_shortcode {
+        rest.trim_start()
+    } else {
+        subject
+    }
+}
+
+/// Prepends the emoji for the message's type to its subject.
+///
+/// `overrides` ([`emoji_by_type`](crate::config::Config::emoji_by_type))
+/// wins over [`TYPE_EMOJI`]. Messages without a conventional type, without an
+/// emoji for it, or that already start with an emoji are returned unchanged.
+pub fn add_emoji(message: &str, overrides: &BTreeMap<String, String>) -> String {
+    let subject = message.lines().next().unwrap_or_default();
+    if strip_emoji_prefix(subject) != subject {
+        return message.to_string();
+    }
+    let Some(commit_type) = crate::format::commit_type(message) else {
+        return message.to_string();
+    };
+    let emoji = match overrides.get(commit_type) {
+        Some(emoji) => emoji.trim(),
+        None => emoji_for_type(commit_type).unwrap_or_default(),
+    };
+    if emoji.is_empty() {
+        return message.to_string();
+    }
+    format!("{} {}", emoji, message.trim_start())
+}
@@ -0,0 +1,96 @@
+//! Per-repository diff exclusions from `.committerignore`.
+//!
+//! A `.committerignore` file in the repository root uses gitignore syntax to
+//! list paths whose diffs are left out of the generation prompt. The files
+//! are still committed; only the model stops seeing their contents.
+//!
+//! Patterns are combined with the built-in [`EXCLUDED_FROM_DIFF`] list.
+//! A negated pattern (`!Cargo.lock`) re-includes a built-in exclusion.
+//!
+//! [`EXCLUDED_FROM_DIFF`]: crate::git::EXCLUDED_FROM_DIFF
+
+use ignore::gitignore::{Gitignore, GitignoreBuilder};
+use ignore::Match;
+use std::path::{Path, PathBuf};
+use std::sync::OnceLock;
+
+/// Name of the ignore file in the repository root.
+pub const COMMITTERIGNORE_FILE: &str = ".committerignore";
+
+/// Why a file's diff is excluded.
+pub enum Exclusion {
+    /// Matched a built-in pattern.
+    BuiltIn,
+    /// Matched the given `.committerignore` pattern.
+    Ignored(String),
+}
+
+/// The result of checking a path against `.committerignore`.
+pub enum IgnoreMatch {
+    /// No pattern matched.
+    None,
+    /// Excluded by the given pattern.
+    Ignore(String),
+    /// Re-included by a negated pattern.
+    Include,
+}
+
+/// Parses `.committerignore` from the repository root.
+///
+/// Returns `None` if the file is missing or has no usable patterns. Invalid
+/// lines are skipped with a warning rather than failing the run.
+pub fn load_committerignore(repo_root: &Path) -> Option<Gitignore> {
+    let path = repo_root.join(COMMITTERIGNORE_FILE);
+    if !path.is_file() {
+        return None;
+    }
+
+    let mut builder = GitignoreBuilder::new(repo_root);
+    if let Some(err) = builder.add(&path) {
+        eprintln!(
+            "— Ignoring invalid {} entries: {}",
+            COMMITTERIGNORE_FILE, err
+        );
+    }
+
+    match builder.build() {
+        Ok(matcher) if !matcher.is_empty() => Some(matcher),
+        Ok(_) => None,
+        Err(err) => {
+            eprintln!("— Failed to read {}: {}", COMMITTERIGNORE_FILE, err);
+            None
+        }
+    }
+}
+
+/// Returns the current repository's `.committerignore`, loaded once per run.
+fn committerignore() -> Option<&'static Gitignore> {
+    static MATCHER: OnceLock<Option<Gitignore>> = OnceLock::new();
+
+    MATCHER
+        .get_or_init(|| {
+            // Called from synchronous diff filtering, so this can't use the async git helpers
+            let output = std::process::Command::new("git")
+                .args(["rev-parse", "--show-toplevel"])
+                .output()
+                .ok()
+                .filter(|output| output.status.success())?;
+            let root = PathBuf::from(String::from_utf8_lossy(&output.stdout).trim());
+            load_committerignore(&root)
+        })
+        .as_ref()
+}
+
+/// Checks a repo-relative path against `.committerignore`.
+pub fn check_committerignore(path: &str) -> IgnoreMatch {
+    let Some(matcher) = committerignore() else {
+        return IgnoreMatch::None;
+    };
+
+    // Directory patterns like `docs/` must also match the files inside them
+    match matcher.matched_path_or_any_parents(path, false) {
+        Match::None => IgnoreMatch::None,
+        Match::Ignore(glob) => IgnoreMatch::Ignore(glob.original().to_string()),
+        Match::Whitelist(_) => IgnoreMatch::Include,
+    }
+}
@@ -0,0 +1,19 @@
+//! Process exit codes.
+//!
+//! Scripts can rely on these to tell outcomes apart without parsing output:
+//!
+//! | Code | Meaning |
+//! |------|---------|
+//! | [`SUCCESS`] (0) | Committed, nothing to commit, or cancelled by the user |
+//! | [`FAILURE`] (1) | An error, including errors returned from `main` |
+//! | 2 | Invalid command-line usage (reported by clap) |
+//! | [`NOTHING_STAGED`] (3) | Changes exist, but none are staged |
+
+/// The run finished normally.
+pub const SUCCESS: i32 = 0;
+
+/// The run failed.
+pub const FAILURE: i32 = 1;
+
+/// There are unstaged changes but nothing is staged.
+pub const NOTHING_STAGED: i32 = 3;
@@ -0,0 +1,470 @@
+//! Code hosts that `committer pr` can open pull requests on.
+//!
+//! Each host implements [`Forge`]:
+//!
+//! - [`GitHub`]: through the GitHub CLI (`gh`), which handles authentication
+//! - [`GitLab`]: through the merge request REST API, with a token from
+//!   `GITLAB_TOKEN`
+//!
+//! The host is `forge` from config, or detected from the PR's target remote
+//! by [`detect_forge`]. A new host needs a [`ForgeKind`] variant, an
+//! implementation, and a case in [`build_forge`].
+
+use futures::future::{FutureExt, LocalBoxFuture};
+use reqwest::{Client, StatusCode};
+use std::time::Duration;
+use tokio::process::Command;
+
+use crate::config::{Config, ForgeKind};
+use crate::git::{get_remote_host, log_command};
+use crate::pr::CrossRepoTarget;
+
+/// Environment variable holding the GitLab access token (`api` scope).
+pub const GITLAB_TOKEN_ENV: &str = "GITLAB_TOKEN";
+
+/// Environment variable naming the GitLab API root, as set in GitLab CI.
+pub const GITLAB_API_URL_ENV: &str = "CI_API_V4_URL";
+
+/// A code host that pull requests can be opened on.
+pub trait Forge {
+    /// What the host calls a pull request, e.g. "PR" or "MR".
+    fn request_name(&self) -> &'static str;
+
+    /// Opens a pull request from the current branch into `base`, returning
+    /// its URL.
+    fn create_pr<'a>(
+        &'a self,
+        title: &'a str,
+        body: &'a str,
+        base: &'a str,
+        draft: bool,
+    ) -> LocalBoxFuture<'a, Result<String, Box<dyn std::error::Error>>>;
+
+    /// Requests reviews from `reviewers` and adds `labels` to the pull
+    /// request at `url`.
+    ///
+    /// Returns a warning for each one that couldn't be applied, leaving the
+    /// request itself in place.
+    fn add_reviewers_and_labels<'a>(
+        &'a self,
+        url: &'a str,
+        reviewers: &'a [String],
+        labels: &'a [String],
+    ) -> LocalBoxFuture<'a, Vec<String>>;
+}
+
+/// Picks the forge for a remote: `forge` from config, or a guess from the
+/// remote's host (GitLab if it mentions `gitlab`, otherwise GitHub).
+pub async fn detect_forge(config: &Config, remote: &str, verbose: bool) -> ForgeKind {
+    if let Some(kind) = config.forge {
+        return kind;
+    }
+    let kind = match get_remote_host(remote, verbose).await {
+        Some(host) if host.contains("gitlab") => ForgeKind::Gitlab,
+        _ => ForgeKind::Github,
+    };
+    if verbose {
+        eprintln!("— Forge: {} (from remote '{}')", kind.as_str(), remote);
+    }
+    kind
+}
+
+/// Sets up the forge of `kind`, failing early if it can't be used.
+///
+/// `base_remote` receives the pull request and `head_remote` holds the
+/// branch; they differ in the fork workflow, where `cross_repo` is set.
+pub async fn build_forge(
+    kind: ForgeKind,
+    config: &Config,
+    branch: &str,
+    base_remote: &str,
+    head_remote: &str,
+    cross_repo: Option<CrossRepoTarget>,
+    verbose: bool,
+) -> Result<Box<dyn Forge>, Box<dyn std::error::Error>> {
+    match kind {
+        ForgeKind::Github => {
+            check_gh_installed(verbose).await?;
+            Ok(Box::new(GitHub {
+                cross_repo,
+                verbose,
+            }))
+        }
+        ForgeKind::Gitlab => {
+            let token = std::env::var(GITLAB_TOKEN_ENV)
+                .ok()
+                .map(|token| token.trim().to_string())
+                .filter(|token| !token.is_empty())
+                .ok_or_else(|| {
+                    format!(
+                        "No GitLab token found.\n\
+                         Set {} to a personal access token with the api scope",
+                        GITLAB_TOKEN_ENV
+                    )
+                })?;
+            let api_url = match std::env::var(GITLAB_API_URL_ENV) {
+                Ok(url) if !url.trim().is_empty() => url.trim().trim_end_matches('/').to_string(),
+                _ => {
+                    let host = get_remote_host(base_remote, verbose)
+                        .await
+                        .ok_or_else(|| format!("Could not read the host of '{}'", base_remote))?;
+                    format!("https://{}/api/v4", host)
+                }
+            };
+            let project = remote_project(base_remote, verbose).await?;
+            let source_project = match &cross_repo {
+                Some(_) => Some(remote_project(head_remote, verbose).await?),
+                None => None,
+            };
+            let timeout = match config.timeout_secs {
+                0 => None,
+                secs => Some(Duration::from_secs(secs)),
+            };
+            let mut client = Client::builder();
+            if let Some(timeout) = timeout {
+                client = client.timeout(timeout);
+            }
+            Ok(Box::new(GitLab {
+                client: client.build()?,
+                api_url,
+                token,
+                project,
+                source_project,
+                branch: branch.to_string(),
+                verbose,
+            }))
+        }
+    }
+}
+
+/// Returns the `group/project` path of a remote's repository.
+async fn remote_project(remote: &str, verbose: bool) -> Result<String, Box<dyn std::error::Error>> {
+    let url = crate::git::get_remote_url(remote, verbose)
+        .await
+        .ok_or_else(|| format!("Remote '{}' not found", remote))?;
+    Ok(crate::pr::repo_slug_from_url(&url)
+        .ok_or_else(|| format!("Could not parse repository from '{}'", url))?)
+}
+
+/// Checks if the GitHub CLI (`gh`) is installed.
+pub async fn check_gh_installed(verbose: bool) -> Result<(), Box<dyn std::error::Error>> {
+    let output = Command::new("gh").args(["--version"]).output().await;
+    log_command(verbose, "gh", &["--version"], &output);
+
+    match output {
+        Ok(o) if o.status.success() => Ok(()),
+        _ => Err("GitHub CLI (gh) is not installed.\n\
+                 Install it from: https://cli.github.com/\n\
+                 Then run: gh auth login"
+            .into()),
+    }
+}
+
+/// GitHub, through the GitHub CLI.
+pub struct GitHub {
+    /// Target repository when the branch lives in a fork.
+    pub cross_repo: Option<CrossRepoTarget>,
+    pub verbose: bool,
+}
+
+impl Forge for GitHub {
+    fn request_name(&self) -> &'static str {
+        "PR"
+    }
+
+    /// Creates the PR with `gh pr create`.
+    ///
+    /// With `cross_repo` set, the PR is opened against that repository with
+    /// an `owner:branch` head and its base branch.
+    fn create_pr<'a>(
+        &'a self,
+        title: &'a str,
+        body: &'a str,
+        base: &'a str,
+        draft: bool,
+    ) -> LocalBoxFuture<'a, Result<String, Box<dyn std::error::Error>>> {
+        async move {
+            let mut args = vec!["pr", "create", "--title", title, "--body", body];
+            if draft {
+                args.push("--draft");
+            }
+            match &self.cross_repo {
+                Some(target) => args.extend([
+                    "--repo",
+                    target.repo.as_str(),
+                    "--head",
+                    target.head.as_str(),
+                    "--base",
+                    target.base.as_str(),
+                ]),
+                None => args.extend(["--base", base]),
+            }
+
+            let output = Command::new("gh").args(&args).output().await;
+            log_command(self.verbose, "gh", &args, &output);
+            let output = output?;
+
+            if !output.status.success() {
+                let stderr = String::from_utf8_lossy(&output.stderr);
+                if stderr.contains("auth") {
+                    return Err(format!(
+                        "GitHub authentication failed.\nRun: gh auth login\n\nError: {}",
+                        stderr
+                    )
+                    .into());
+                }
+                return Err(format!("Failed to create PR: {}", stderr).into());
+            }
+
+            // gh pr create outputs the PR URL on success
+            let url = String::from_utf8_lossy(&output.stdout).trim().to_string();
+            Ok(url)
+        }
+        .boxed_local()
+    }
+
+    /// Adds each reviewer and label with its own `gh pr edit`, since one
+    /// unknown name would fail a combined call.
+    fn add_reviewers_and_labels<'a>(
+        &'a self,
+        url: &'a str,
+        reviewers: &'a [String],
+        labels: &'a [String],
+    ) -> LocalBoxFuture<'a, Vec<String>> {
+        async move {
+            let edits = reviewers
+                .iter()
+                .map(|reviewer| ("--add-reviewer", "request review from", reviewer))
+                .chain(
+                    labels
+                        .iter()
+                        .map(|label| ("--add-label", "add label", label)),
+                );
+
+            let mut warnings = Vec::new();
+            for (flag, action, name) in edits {
+                let args = ["pr", "edit", url, flag, name.as_str()];
+                let output = Command::new("gh").args(args).output().await;
+                log_command(self.verbose, "gh", &args, &output);
+                let error = match output {
+                    Ok(output) if output.status.success() => continue,
+                    Ok(output) => String::from_utf8_lossy(&output.stderr).trim().to_string(),
+                    Err(e) => e.to_string(),
+                };
+                warnings.push(format!("Couldn't {} '{}': {}", action, name, error));
+            }
+            warnings
+        }
+        .boxed_local()
+    }
+}
+
+/// GitLab, through the merge request API.
+pub struct GitLab {
+    client: Client,
+    /// API root, e.g. `https://gitlab.com/api/v4`.
+    api_url: String,
+    token: String,
+    /// `group/project` path the merge request is opened in.
+    project: String,
+    /// Fork holding the branch, when it isn't `project`.
+    source_project: Option<String>,
+    branch: String,
+    verbose: bool,
+}
+
+/// The fields of a GitLab project, user, or merge request that Committer reads.
+#[derive(serde::Deserialize)]
+struct GitLabObject {
+    id: Option<u64>,
+    web_url: Option<String>,
+}
+
+/// The body of a failed GitLab API request.
+#[derive(serde::Deserialize)]
+struct GitLabError {
+    /// Error details; a string or a list, depending on the endpoint.
+    message: Option<serde_json::Value>,
+    error: Option<String>,
+}
+
+impl GitLab {
+    /// URL of an endpoint under a project.
+    fn project_url(&self, project: &str, path: &str) -> String {
+        format!(
+            "{}/projects/{}{}",
+            self.api_url,
+            project.replace('/', "%2F"),
+            path
+        )
+    }
+
+    /// Sends a request and decodes the response, turning API errors into
+    /// readable messages.
+    async fn send<T: serde::de::DeserializeOwned>(
+        &self,
+        request: reqwest::RequestBuilder,
+    ) -> Result<T, Box<dyn std::error::Error>> {
+        let response = request.header("PRIVATE-TOKEN", &self.token).send().await?;
+        let status = response.status();
+        if self.verbose {
+            eprintln!("— GitLab API: {} {}", status.as_u16(), response.url());
+        }
+        let text = response.text().await?;
+
+        if status.is_success() {
+            return serde_json::from_str(&text)
+                .map_err(|_| "Unexpected response from the GitLab API".into());
+        }
+        let detail = serde_json::from_str::<GitLabError>(&text)
+            .ok()
+            .and_then(|r| match r.message {
+                Some(serde_json::Value::String(message)) => Some(message),
+                Some(serde_json::Value::Array(messages)) => Some(
+                    messages
+                        .iter()
+                        .filter_map(|m| m.as_str())
+                        .collect::<Vec<_>>()
+                        .join("; "),
+                ),
+                Some(other) => Some(other.to_string()),
+                None => r.error,
+            })
+            .unwrap_or_else(|| text.trim().to_string());
+        match status {
+            StatusCode::UNAUTHORIZED | StatusCode::FORBIDDEN => Err(format!(
+                "GitLab authentication failed.\n\
+                 Check that {} is a token with the api scope.\n\nError: {}",
+                GITLAB_TOKEN_ENV, detail
+            )
+            .into()),
+            _ => Err(format!("GitLab API error ({}): {}", status.as_u16(), detail).into()),
+        }
+    }
+}
+
+impl Forge for GitLab {
+    fn request_name(&self) -> &'static str {
+        "MR"
+    }
+
+    /// Creates the merge request, marking it draft with the `Draft:` prefix.
+    ///
+    /// From a fork, it's created in the fork with the target project's id.
+    fn create_pr<'a>(
+        &'a self,
+        title: &'a str,
+        body: &'a str,
+        base: &'a str,
+        draft: bool,
+    ) -> LocalBoxFuture<'a, Result<String, Box<dyn std::error::Error>>> {
+        async move {
+            let title = if draft {
+                format!("Draft: {}", title)
+            } else {
+                title.to_string()
+            };
+            let mut payload = serde_json::json!({
+                "source_branch": self.branch,
+                "target_branch": base,
+                "title": title,
+                "description": body,
+            });
+
+            let source = match &self.source_project {
+                Some(source) => {
+                    let target: GitLabObject = self
+                        .send(self.client.get(self.project_url(&self.project, "")))
+                        .await?;
+                    let id = target
+                        .id
+                        .ok_or("GitLab didn't return the target project's id")?;
+                    payload["target_project_id"] = id.into();
+                    source
+                }
+                None => &self.project,
+            };
+
+            let created: GitLabObject = self
+                .send(
+                    self.client
+                        .post(self.project_url(source, "/merge_requests"))
+                        .json(&payload),
+                )
+                .await?;
+            created
+                .web_url
+                .ok_or_else(|| "GitLab didn't return the merge request's URL".into())
+        }
+        .boxed_local()
+    }
+
+    /// Looks up each reviewer's user id, then sets reviewers and labels in
+    /// one update. Labels that don't exist yet are created by GitLab.
+    fn add_reviewers_and_labels<'a>(
+        &'a self,
+        url: &'a str,
+        reviewers: &'a [String],
+        labels: &'a [String],
+    ) -> LocalBoxFuture<'a, Vec<String>> {
+        async move {
+            let mut warnings = Vec::new();
+            // The merge request's URL ends in its project-scoped id
+            let Some(iid) = url
+                .rsplit('/')
+                .next()
+                .and_then(|iid| iid.parse::<u64>().ok())
+            else {
+                warnings.push(format!("Couldn't read the merge request id from {}", url));
+                return warnings;
+            };
+
+            let mut reviewer_ids = Vec::new();
+            for reviewer in reviewers {
+                let users: Result<Vec<GitLabObject>, _> = self
+                    .send(
+                        self.client
+                            .get(format!("{}/users", self.api_url))
+                            .query(&[("username", reviewer)]),
+                    )
+                    .await;
+                match users.map(|users| users.into_iter().find_map(|user| user.id)) {
+                    Ok(Some(id)) if reviewer_ids.contains(&id) => {}
+                    Ok(Some(id)) => reviewer_ids.push(id),
+                    Ok(None) => warnings.push(format!(
+                        "Couldn't request review from '{}': no such GitLab user",
+                        reviewer
+                    )),
+                    Err(e) => warnings.push(format!(
+                        "Couldn't request review from '{}': {}",
+                        reviewer, e
+                    )),
+                }
+            }
+            if reviewer_ids.is_empty() && labels.is_empty() {
+                return warnings;
+            }
+
+            let mut payload = serde_json::json!({});
+            if !reviewer_ids.is_empty() {
+                payload["reviewer_ids"] = reviewer_ids.into();
+            }
+            if !labels.is_empty() {
+                payload["add_labels"] = labels.join(",").into();
+            }
+            let path = format!("/merge_requests/{}", iid);
+            let updated: Result<GitLabObject, _> = self
+                .send(
+                    self.client
+                        .put(self.project_url(&self.project, &path))
+                        .json(&payload),
+                )
+                .await;
+            if let Err(e) = updated {
+                warnings.push(format!("Couldn't add reviewers and labels: {}", e));
+            }
+            warnings
+        }
+        .boxed_local()
+    }
+}
@@ -0,0 +1,749 @@
+//! Commit message formatting and measurement.
+//!
+//! This module provides pure helpers that inspect and assemble commit messages:
+//!
+//! - [`message_stats`]: Measure subject width and body line lengths
+//! - [`append_trailers`]: Add `Token: value` trailer lines to a message
+//! - [`trim_body`]: Limit body length without cutting bullets mid-sentence
+//! - [`set_scope`]: Force the scope of a conventional-commit subject
+//! - [`place_ticket`]: Put a ticket reference at a fixed place in the subject
+//! - [`normalize_spacing`]: Enforce `subject\n\nbody` and single blank lines
+//! - [`looks_like_refusal`]: Detect a model declining instead of answering
+//! - [`commit_type`]: Read the type of a conventional-commit subject
+//! - [`trim_to_bytes`]: Enforce a total message size, cutting only the body
+//! - [`normalize_line_endings`]: Convert CRLF to LF and tidy trailing whitespace
+//!   (or only convert, with [`convert_line_endings`])
+//! - [`add_reference_diff`] / [`strip_scissors`]: Show the diff below a
+//!   scissors line while editing, as `git commit -v` does
+//! - [`wrap_commit_message`]: Wrap long body lines at word boundaries
+//! - [`truncate_subject`]: Cut an overlong subject at a word boundary
+//!
+//! Widths are measured in terminal columns rather than bytes, so wide
+//! characters (CJK, emoji) count the way they render in `git log`.
+
+use console::measure_text_width;
+
+use crate::config::TicketPlacement;
+use crate::emoji::strip_emoji_prefix;
+
+/// Maximum subject line width, matching the limit given to the model.
+pub const SUBJECT_MAX_WIDTH: usize = 72;
+
+/// Maximum body line width before a line is considered too long.
+pub const BODY_MAX_WIDTH: usize = 72;
+
+/// Length metrics for a commit message.
+pub struct MessageStats {
+    /// Display width of the first line.
+    pub subject_width: usize,
+    /// Number of non-blank lines after the subject.
+    pub body_lines: usize,
+    /// Display width of the longest body line.
+    pub longest_body_line: usize,
+}
+
+impl MessageStats {
+    /// True if the subject fits within `limit` columns.
+    pub fn subject_ok(&self, limit: usize) -> bool {
+        self.subject_width <= limit
+    }
+
+    /// True if every body line fits within [`BODY_MAX_WIDTH`].
+    pub fn body_ok(&self) -> bool {
+        self.longest_body_line <= BODY_MAX_WIDTH
+    }
+}
+
+/// Computes length metrics for a commit message.
+pub fn message_stats(message: &str) -> MessageStats {
+    let mut lines = message.lines();
+    let subject_width = lines.next().map(measure_text_width).unwrap_or(0);
+
+    let body: Vec<&str> = lines.filter(|line| !line.trim().is_empty()).collect();
+
+    MessageStats {
+        subject_width,
+        body_lines: body.len(),
+        longest_body_line: body
+            .iter()
+            .map(|line| measure_text_width(line))
+            .max()
+            .unwrap_or(0),
+    }
+}
+
+/// Returns true if the line looks like a git trailer (`Token: value`).
+pub fn is_trailer_line(line: &str) -> bool {
+    match line.split_once(": ") {
+        Some((token, value)) => {
+            !token.is_empty()
+                && !value.trim().is_empty()
+                && token.chars().all(|c| c.is_ascii_alphanumeric() || c == '-')
+        }
+        None => false,
+    }
+}
+
+/// Appends trailer lines to a message, skipping any already present.
+///
+/// New trailers join an existing trailer block at the end of the message,
+/// or start a new paragraph separated by a blank line.
+pub fn append_trailers(message: &str, trailers: &[String]) -> String {
+    let missing: Vec<&str> = trailers
+        .iter()
+        .map(|t| t.trim())
+        .filter(|t| !message.lines().any(|line| line.trim() == *t))
+        .collect();
+
+    if missing.is_empty() {
+        return message.to_string();
+    }
+
+    let mut result = message.trim_end().to_string();
+    // The subject can look like a trailer ("fix: x"), so a block needs a preceding paragraph
+    let has_trailer_block = result
+        .rsplit_once("\n\n")
+        .is_some_and(|(_, last)| last.lines().all(is_trailer_line));
+
+    result.push_str(if has_trailer_block { "\n" } else { "\n\n" });
+    result.push_str(&missing.join("\n"));
+    result
+}
+
+/// Splits a message into subject, body, and trailing trailer block.
+///
+/// The trailer block is the last paragraph of the body when every line in it
+/// is a trailer. Any part may be empty.
+pub fn split_message(message: &str) -> (&str, &str, &str) {
+    let message = message.trim();
+    let (subject, rest) = message.split_once('\n').unwrap_or((message, ""));
+    let rest = rest.trim();
+
+    if let Some((body, last)) = rest.rsplit_once("\n\n") {
+        if last.lines().all(is_trailer_line) {
+            return (subject.trim(), body.trim(), last.trim());
+        }
+    } else if !rest.is_empty() && rest.lines().all(is_trailer_line) {
+        return (subject.trim(), "", rest);
+    }
+
+    (subject.trim(), rest, "")
+}
+
+/// Joins subject, body, and trailers with blank lines, skipping empty parts.
+pub fn join_message(subject: &str, body: &str, trailers: &str) -> String {
+    [subject, body, trailers]
+        .iter()
+        .filter(|part| !part.is_empty())
+        .copied()
+        .collect::<Vec<_>>()
+        .join("\n\n")
+}
+
+/// Normalizes blank lines in a message.
+///
+/// Guarantees exactly one blank line between the subject and body, collapses
+/// runs of blank lines to one, and strips trailing whitespace. Paragraph
+/// breaks, list indentation, and line order are otherwise preserved.
+pub fn normalize_spacing(message: &str) -> String {
+    let mut lines = message
+        .lines()
+        .map(str::trim_end)
+        .skip_while(|line| line.is_empty());
+
+    let Some(subject) = lines.next() else {
+        return String::new();
+    };
+
+    let mut result = subject.trim_start().to_string();
+    let mut pending_blank = true;
+    for line in lines {
+        if line.is_empty() {
+            pending_blank = true;
+            continue;
+        }
+        result.push('\n');
+        if pending_blank {
+            result.push('\n');
+            pending_blank = false;
+        }
+        result.push_str(line);
+    }
+
+    result
+}
+
+/// Converts CRLF and lone CR line endings to LF and tidies trailing whitespace.
+///
+/// Trailing whitespace is stripped from every line and trailing blank lines
+/// are dropped, leaving exactly one final newline. This is applied to text
+/// coming back from an editor, which on Windows is often saved with CRLF.
+pub fn normalize_line_endings(text: &str) -> String {
+    let mut result: String = convert_line_endings(text)
+        .lines()
+        .map(str::trim_end)
+        .collect::<Vec<_>>()
+        .join("\n")
+        .trim_end()
+        .to_string();
+    result.push('\n');
+    result
+}
+
+/// Converts CRLF and lone CR line endings to LF, changing nothing else.
+///
+/// Used for Markdown, where trailing spaces are hard line breaks.
+pub fn convert_line_endings(text: &str) -> String {
+    text.replace("\r\n", "\n").replace('\r', "\n")
+}
+
+/// Line separating a message being edited from reference text below it,
+/// the same one `git commit -v` uses.
+pub const SCISSORS_LINE: &str = "# ------------------------ >8 ------------------------";
+
+/// Appends `diff` below a scissors line, for reference while editing.
+///
+/// [`strip_scissors`] removes it again.
+pub fn add_reference_diff(message: &str, diff: &str) -> String {
+    format!(
+        "{}\n\n{}\n\
+         # Do not modify or remove the line above.\n\
+         # Everything below it will be ignored.\n\
+         {}",
+        message.trim_end(),
+        SCISSORS_LINE,
+        diff
+    )
+}
+
+/// Drops the scissors line and everything below it.
+///
+/// Text without a scissors line is returned unchanged.
+pub fn strip_scissors(text: &str) -> &str {
+    let mut offset = 0;
+    for line in text.split_inclusive('\n') {
+        if line.trim_end() == SCISSORS_LINE {
+            return &text[..offset];
+        }
+        offset += line.len();
+    }
+    text
+}
+
+/// Splits a `type(scope)!: description` subject into type, `!`, and description.
+///
+/// The scope is optional and discarded, and so is a leading emoji. Returns
+/// `None` if the subject isn't in conventional-commit form.
+fn split_conventional_subject(subject: &str) -> Option<(&str, &str, &str)> {
+    let (header, description) = strip_emoji_prefix(subject).split_once(": ")?;
+    let (header, breaking) = match header.strip_suffix('!') {
+        Some(header) => (header, "!"),
+        None => (header, ""),
+    };
+    let commit_type = match header.split_once('(') {
+        Some((commit_type, scope)) if scope.ends_with(')') => commit_type,
+        Some(_) => return None,
+        None => header,
+    };
+    if commit_type.is_empty() || !commit_type.chars().all(|c| c.is_ascii_alphanumeric()) {
+        return None;
+    }
+    Some((commit_type, breaking, description))
+}
+
+/// Returns the type of a conventional-commit message (`feat` in `feat(x): y`).
+pub fn commit_type(message: &str) -> Option<&str> {
+    let subject = message.trim_start().lines().next()?;
+    split_conventional_subject(subject).map(|(commit_type, _, _)| commit_type)
+}
+
+/// Returns the scope of a conventional-commit message (`x` in `feat(x): y`).
+pub fn commit_scope(message: &str) -> Option<&str> {
+    let subject = message.trim_start().lines().next()?;
+    split_conventional_subject(subject)?;
+    let (header, _) = strip_emoji_prefix(subject).split_once(": ")?;
+    let (_, scope) = header.split_once('(')?;
+    let scope = scope.trim_end_matches('!').strip_suffix(')')?.trim();
+    (!scope.is_empty()).then_some(scope)
+}
+
+/// Replaces the conventional-commit scope in the subject line.
+///
+/// `feat: x` becomes `feat(scope): x` and `fix(old)!: x` becomes
+/// `fix(scope)!: x`. Subjects not in `type(scope): description` form are
+/// returned unchanged.
+pub fn set_scope(message: &str, scope: &str) -> String {
+    replace_scope(message, Some(scope))
+}
+
+/// Removes the conventional-commit scope: `fix(old)!: x` becomes `fix!: x`.
+pub fn remove_scope(message: &str) -> String {
+    replace_scope(message, None)
+}
+
+/// Sets or removes the scope for [`set_scope`] and [`remove_scope`].
+fn replace_scope(message: &str, scope: Option<&str>) -> String {
+    let message = message.trim();
+    let (subject, rest) = match message.split_once('\n') {
+        Some((subject, rest)) => (subject, Some(rest)),
+        None => (message, None),
+    };
+
+    let Some((commit_type, breaking, description)) = split_conventional_subject(subject) else {
+        return message.to_string();
+    };
+
+    let emoji = &subject[..subject.len() - strip_emoji_prefix(subject).len()];
+    let scope = scope
+        .map(|scope| format!("({})", scope))
+        .unwrap_or_default();
+    let subject = format!(
+        "{}{}{}{}: {}",
+        emoji, commit_type, scope, breaking, description
+    );
+    match rest {
+        Some(rest) => format!("{}\n{}", subject, rest),
+        None => subject,
+    }
+}
+
+/// Puts `ticket` in the subject line at `placement`.
+///
+/// Any copy of the ticket already in the subject (bare, or as `[PROJ-1]`,
+/// `(PROJ-1)` or `PROJ-1:`) is removed first, so the ticket appears exactly
+/// once. `after-type` puts it at the start of subjects that aren't in
+/// conventional-commit form, and `trailer` only removes it (the trailer is
+/// added at commit time). [`TicketPlacement::Off`] returns the message
+/// unchanged.
+pub fn place_ticket(message: &str, ticket: &str, placement: TicketPlacement) -> String {
+    if placement == TicketPlacement::Off {
+        return message.to_string();
+    }
+    let message = message.trim();
+    let (subject, rest) = match message.split_once('\n') {
+        Some((subject, rest)) => (subject, Some(rest)),
+        None => (message, None),
+    };
+
+    let subject: Vec<&str> = subject
+        .split_whitespace()
+        .filter(|word| word.trim_matches(|c| "[]():,".contains(c)) != ticket)
+        .collect();
+    let subject = subject.join(" ");
+
+    let subject = match placement {
+        TicketPlacement::AfterType if split_conventional_subject(&subject).is_some() => {
+            let (header, description) = subject.split_once(": ").unwrap_or_default();
+            format!("{}: {} {}", header, ticket, description)
+        }
+        TicketPlacement::End => format!("{} ({})", subject, ticket),
+        TicketPlacement::Trailer => subject,
+        _ => format!("{} {}", ticket, subject),
+    };
+    match rest {
+        Some(rest) => format!("{}\n{}", subject, rest),
+        None => subject,
+    }
+}
+
+/// Responses longer than this are never treated as refusals.
+const REFUSAL_MAX_CHARS: usize = 400;
+
+/// Lowercase phrases that open or make up a typical refusal.
+const REFUSAL_PHRASES: &[&str] = &[
+    "i can't",
+    "i cannot",
+    "i can not",
+    "i won't",
+    "i will not",
+    "i'm sorry",
+    "i am sorry",
+    "i apologize",
+    "i'm unable",
+    "i am unable",
+    "i'm not able",
+    "i am not able",
+    "unable to assist",
+    "unable to help",
+    "as an ai",
+];
+
+/// Returns true if a response looks like the model declining the request.
+///
+/// A refusal is short, doesn't start with a conventional-commit subject, and
+/// contains an apologetic phrase like "I can't help with that". Real commit
+/// messages that merely mention such a phrase in the body are not matched.
+pub fn looks_like_refusal(message: &str) -> bool {
+    let message = message.trim();
+    if message.is_empty() || message.chars().count() > REFUSAL_MAX_CHARS {
+        return false;
+    }
+
+    let subject = message.lines().next().unwrap_or_default();
+    if split_conventional_subject(subject).is_some() {
+        return false;
+    }
+
+    let lower = message.to_lowercase().replace('\u{2019}', "'");
+    REFUSAL_PHRASES.iter().any(|phrase| lower.contains(phrase))
+}
+
+/// Note appended to a body cut by [`trim_to_bytes`].
+pub const BYTES_TRUNCATION_NOTE: &str = "[... body truncated to fit size limit ...]";
+
+/// Limits the whole message to `max_bytes`, trimming only the body.
+///
+/// The body is cut at a line boundary where possible and followed by
+/// [`BYTES_TRUNCATION_NOTE`]. The subject and trailers are never changed, so
+/// if they alone exceed the limit the result is just subject and trailers.
+pub fn trim_to_bytes(message: &str, max_bytes: usize) -> String {
+    let message = message.trim();
+    if message.len() <= max_bytes {
+        return message.to_string();
+    }
+
+    let (subject, body, trailers) = split_message(message);
+    let skeleton = join_message(subject, BYTES_TRUNCATION_NOTE, trailers);
+    // Whatever is left once the subject, trailers, and note fit, plus the "\n" after kept lines
+    let Some(budget) = max_bytes.checked_sub(skeleton.len() + 1) else {
+        return join_message(subject, "", trailers);
+    };
+
+    let mut kept = String::new();
+    for line in body.lines() {
+        if kept.len() + line.len() + 1 > budget {
+            if kept.is_empty() {
+                // A single oversized line: keep as much as fits, on a char boundary
+                let mut end = budget.min(line.len());
+                while !line.is_char_boundary(end) {
+                    end -= 1;
+                }
+                kept.push_str(&line[..end]);
+                kept.push('\n');
+            }
+            break;
+        }
+        kept.push_str(line);
+        kept.push('\n');
+    }
+
+    let kept = kept.trim_end();
+    if kept.is_empty() {
+        return join_message(subject, BYTES_TRUNCATION_NOTE, trailers);
+    }
+    join_message(
+        subject,
+        &format!("{}\n{}", kept, BYTES_TRUNCATION_NOTE),
+        trailers,
+    )
+}
+
+/// Returns true if the line starts a list item (`-`, `*`, or `1.`).
+fn is_list_item(line: &str) -> bool {
+    let line = line.trim_start();
+    line.starts_with("- ")
+        || line.starts_with("* ")
+        || line
+            .split_once(". ")
+            .is_some_and(|(n, _)| !n.is_empty() && n.chars().all(|c| c.is_ascii_digit()))
+}
+
+/// Trims the body to at most `max_lines` lines, keeping the first items.
+///
+/// The body is cut only between items (a bullet with its continuation lines,
+/// or a paragraph), never mid-sentence, and a note records how many items
+/// were dropped. Trailers and the subject are always kept. With
+/// `max_lines == 0` the body is removed entirely.
+pub fn trim_body(message: &str, max_lines: usize) -> String {
+    let (subject, body, trailers) = split_message(message);
+
+    // Group lines into items: a list item or paragraph plus its continuation lines
+    let mut items: Vec<Vec<&str>> = Vec::new();
+    let mut new_item = true;
+    for line in body.lines() {
+        if line.trim().is_empty() {
+            new_item = true;
+            continue;
+        }
+        if new_item || is_list_item(line) {
+            items.push(Vec::new());
+            new_item = false;
+        }
+        if let Some(item) = items.last_mut() {
+            item.push(line);
+        }
+    }
+
+    let total_lines: usize = items.iter().map(Vec::len).sum();
+    if total_lines <= max_lines {
+        return message.trim().to_string();
+    }
+
+    if max_lines == 0 {
+        return join_message(subject, "", trailers);
+    }
+
+    let mut kept: Vec<String> = Vec::new();
+    let mut used = 0;
+    let mut kept_items = 0;
+    for item in &items {
+        // Always keep the first item whole rather than cutting it
+        if kept_items > 0 && used + item.len() > max_lines {
+            break;
+        }
+        used += item.len();
+        kept_items += 1;
+        kept.push(item.join("\n"));
+    }
+
+    let dropped = items.len() - kept_items;
+    let all_bullets = items.iter().all(|item| is_list_item(item[0]));
+    let separator = if all_bullets { "\n" } else { "\n\n" };
+    let mut trimmed_body = kept.join(separator);
+    if dropped > 0 {
+        let noun = if dropped == 1 { "change" } else { "changes" };
+        trimmed_body.push_str(&format!("{}- ...and {} more {}", separator, dropped, noun));
+    }
+
+    join_message(subject, &trimmed_body, trailers)
+}
+
+/// Wraps body lines wider than `width` columns at word boundaries.
+///
+/// The subject and trailers are left alone, as are fenced and indented code
+/// lines. Existing line breaks are kept. A wrapped list item's continuation
+/// lines are indented to line up with its text, and a word wider than
+/// `width` gets a line of its own rather than being split. With `width == 0`
+/// the message is returned unchanged.
+pub fn wrap_commit_message(message: &str, width: usize) -> String {
+    let (subject, body, trailers) = split_message(message);
+    if width == 0 || body.lines().all(|line| measure_text_width(line) <= width) {
+        return message.to_string();
+    }
+
+    let mut lines = Vec::new();
+    let mut in_fence = false;
+    for line in body.lines() {
+        if line.trim_start().starts_with("```") {
+            in_fence = !in_fence;
+            lines.push(line.to_string());
+        } else if in_fence || is_indented_code(line) || measure_text_width(line) <= width {
+            lines.push(line.to_string());
+        } else {
+            lines.extend(wrap_line(line, width));
+        }
+    }
+
+    join_message(subject, &lines.join("\n"), trailers)
+}
+
+/// Returns true for a line indented as code (four spaces or a tab).
+fn is_indented_code(line: &str) -> bool {
+    (line.starts_with("    ") || line.starts_with('\t')) && !is_list_item(line)
+}
+
+/// Breaks one line into lines of at most `width` columns where possible.
+fn wrap_line(line: &str, width: usize) -> Vec<String> {
+    let text = line.trim_start();
+    let indent = &line[..line.len() - text.len()];
+    let (marker, text) = match text.split_once(' ') {
+        Some((marker, rest)) if is_list_item(line) => (marker, rest),
+        _ => ("", text),
+    };
+    let first = if marker.is_empty() {
+        indent.to_string()
+    } else {
+        format!("{}{} ", indent, marker)
+    };
+    let hanging = " ".repeat(measure_text_width(&first));
+
+    let mut lines = Vec::new();
+    let mut current = first;
+    let mut empty = true;
+    for word in text.split_whitespace() {
+        if !empty && measure_text_width(&current) + 1 + measure_text_width(word) > width {
+            lines.push(std::mem::replace(&mut current, hanging.clone()));
+            empty = true;
+        }
+        if !empty {
+            current.push(' ');
+        }
+        current.push_str(word);
+        empty = false;
+    }
+    lines.push(current);
+    lines
+}
+
+/// Shortens the subject to at most `max_width` columns.
+///
+/// The cut is made at the last word boundary that fits, never inside the
+/// `type(scope): ` prefix, and punctuation left dangling is removed. A
+/// description with no boundary in reach is cut mid-word. The body and
+/// trailers are kept.
+pub fn truncate_subject(message: &str, max_width: usize) -> String {
+    let (subject, rest) = message.split_once('\n').unwrap_or((message, ""));
+    if measure_text_width(subject) <= max_width {
+        return message.to_string();
+    }
+
+    let mut end = 0;
+    let mut used = 0;
+    let mut buf = [0u8; 4];
+    for (i, c) in subject.char_indices() {
+        let char_width = measure_text_width(c.encode_utf8(&mut buf));
+        if used + char_width > max_width {
+            break;
+        }
+        used += char_width;
+        end = i + c.len_utf8();
+    }
+    let fits = &subject[..end];
+
+    let description_start = subject.find(": ").map_or(0, |i| i + 2);
+    let cut = match fits.rfind(' ') {
+        _ if subject[end..].starts_with(' ') => fits,
+        Some(i) if i > description_start => &fits[..i],
+        _ => fits,
+    };
+    let cut = cut.trim_end_matches(|c: char| c.is_whitespace() || ",;:-".contains(c));
+
+    if rest.is_empty() {
+        cut.to_string()
+    } else {
+        format!("{}\n{}", cut, rest)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn normalize_spacing_adds_the_missing_blank_line_after_the_subject() {
+        assert_eq!(
+            normalize_spacing("feat: add login\n- Add form\n- Add route"),
+            "feat: add login\n\n- Add form\n- Add route"
+        );
+    }
+
+    #[test]
+    fn normalize_spacing_collapses_blank_runs_and_strips_padding() {
+        let message =
+            "\n\n  fix: handle timeout   \n\n\n\n- Retry once  \n\t\n\n- Log failures\n\n\n";
+        assert_eq!(
+            normalize_spacing(message),
+            "fix: handle timeout\n\n- Retry once\n\n- Log failures"
+        );
+    }
+
+    #[test]
+    fn normalize_spacing_keeps_list_indentation() {
+        let message = "docs: explain setup\n\n- Install:\n  - cargo build\n    continued";
+        assert_eq!(normalize_spacing(message), message);
+    }
+
+    #[test]
+    fn normalize_spacing_of_blank_input_is_empty() {
+        assert_eq!(normalize_spacing(" \n\n \t\n"), "");
+    }
+
+    #[test]
+    fn place_ticket_as_trailer_only_removes_it_from_the_subject() {
+        assert_eq!(
+            place_ticket(
+                "fix: [PROJ-1] handle crash\n\n- Guard",
+                "PROJ-1",
+                TicketPlacement::Trailer
+            ),
+            "fix: handle crash\n\n- Guard"
+        );
+        assert_eq!(
+            place_ticket("fix: handle crash", "PROJ-1", TicketPlacement::Trailer),
+            "fix: handle crash"
+        );
+    }
+
+    #[test]
+    fn trim_to_bytes_keeps_subject_and_trailers_of_a_huge_body() {
+        let body: String = (0..20_000)
+            .map(|i| format!("- change number {} in the generated body\n", i))
+            .collect();
+        let message = format!(
+            "feat(api): add pagination\n\n{}\nSigned-off-by: Ada <ada@example.com>\nRefs: PROJ-42",
+            body
+        );
+
+        let trimmed = trim_to_bytes(&message, 4096);
+
+        assert!(trimmed.len() <= 4096);
+        let (subject, body, trailers) = split_message(&trimmed);
+        assert_eq!(subject, "feat(api): add pagination");
+        assert_eq!(
+            trailers,
+            "Signed-off-by: Ada <ada@example.com>\nRefs: PROJ-42"
+        );
+        assert!(body.starts_with("- change number 0 in the generated body"));
+        assert!(body.ends_with(BYTES_TRUNCATION_NOTE));
+    }
+
+    #[test]
+    fn trim_to_bytes_leaves_small_messages_alone() {
+        let message = "fix: handle empty input\n\n- Return early";
+        assert_eq!(trim_to_bytes(message, 4096), message);
+    }
+
+    #[test]
+    fn trim_to_bytes_cuts_a_single_long_line_on_a_char_boundary() {
+        let message = format!("docs: translate\n\n{}", "é".repeat(5_000));
+        let trimmed = trim_to_bytes(&message, 200);
+        assert!(trimmed.len() <= 200);
+        assert!(trimmed.starts_with("docs: translate\n\né"));
+    }
+
+    #[test]
+    fn normalize_line_endings_tidies_a_crlf_edited_message() {
+        let edited =
+            "fix: handle crlf  \r\n\r\n- Strip carriage returns\t\r\n- Keep order\r\r\n\r\n";
+        assert_eq!(
+            normalize_line_endings(edited),
+            "fix: handle crlf\n\n- Strip carriage returns\n- Keep order\n"
+        );
+    }
+
+    #[test]
+    fn convert_line_endings_keeps_markdown_hard_breaks() {
+        let edited = "Title\r\n\r\nFirst line  \r\nsecond line\r\n";
+        assert_eq!(
+            convert_line_endings(edited),
+            "Title\n\nFirst line  \nsecond line\n"
+        );
+    }
+
+    #[test]
+    fn wrap_commit_message_gives_a_long_word_its_own_line() {
+        let url = "https://example.com/a/very/long/path/that/cannot/be/split";
+        let message = format!("docs: link guide\n\nSee the guide at {} for details", url);
+        assert_eq!(
+            wrap_commit_message(&message, 20),
+            format!("docs: link guide\n\nSee the guide at\n{}\nfor details", url)
+        );
+    }
+
+    #[test]
+    fn wrap_commit_message_keeps_existing_line_breaks() {
+        let message = "fix: handle retries\n\nShort line.\n\n- Retry the request once more when the server times out\n- Log it";
+        assert_eq!(
+            wrap_commit_message(message, 30),
+            "fix: handle retries\n\nShort line.\n\n- Retry the request once more\n  when the server times out\n- Log it"
+        );
+    }
+
+    #[test]
+    fn wrap_commit_message_leaves_narrow_messages_and_zero_width_alone() {
+        let message = "feat: add login\n\n- Add form\n\nSigned-off-by: Ada <ada@example.com>";
+        assert_eq!(wrap_commit_message(message, 72), message);
+        let wide = format!("feat: add login\n\n{}", "word ".repeat(40));
+        assert_eq!(wrap_commit_message(&wide, 0), wide);
+    }
+}
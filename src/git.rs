@@ -6,15 +6,28 @@
 //! - **Diff filtering**: Excludes lock files, minified code, build artifacts
 //! - **Diff truncation**: Limits size to stay within LLM token limits
 //! - **Status queries**: [`get_staged_files`], [`get_uncommitted_changes`]
-//! - **Branch operations**: [`get_current_branch`], [`create_and_switch_branch`]
+//! - **Branch operations**: [`get_current_branch`], [`create_and_switch_branch`],
+//!   [`roll_back_new_branch`]
 //! - **Commit operations**: [`run_git_commit`], [`stage_all_changes`]
-//! - **Push operations**: [`push_branch_with_spinner`]
+//! - **Amend support**: [`get_last_commit_diff`], [`get_last_commit_files`]
+//! - **Fixup support**: [`resolve_fixup_target`], [`autosquash_into`]
+//! - **Revert detection**: [`find_reverted_commit`]
+//! - **Remote operations**: [`push_branch_with_spinner`], [`get_remote_url`],
+//!   [`get_remote_host`], [`fetch_with_spinner`], [`get_upstream_state`]
 //!
 //! # Diff Filtering
 //!
 //! Files matching [`EXCLUDED_FROM_DIFF`] patterns are automatically removed
 //! from diffs to reduce noise and token usage. This includes lock files,
-//! minified code, and build directories.
+//! minified code, and build directories. A `.committerignore` file in the
+//! repository root adds more patterns (see [`crate::exclude`]).
+//!
+//! A configured `diff_preprocessor` command can then rewrite the diff (e.g. to
+//! redact tokens) via [`run_diff_preprocessor`].
+//!
+//! Control characters in diffs (escape sequences, NULs from binaries saved as
+//! text) are replaced by [`sanitize_diff`] so they can't corrupt the terminal
+//! or confuse the model.
 //!
 //! # Size Limits
 //!
@@ -23,8 +36,15 @@
 
 use console::{style, Term};
 use indicatif::{ProgressBar, ProgressStyle};
+use std::path::{Path, PathBuf};
+use std::process::Stdio;
+use tokio::io::AsyncWriteExt;
 use tokio::process::Command;
 
+use crate::branch::PROTECTED_BRANCHES;
+use crate::exclude::{check_committerignore, Exclusion, IgnoreMatch, COMMITTERIGNORE_FILE};
+use crate::format::split_message;
+
 /// File patterns excluded from diffs to reduce noise.
 pub const EXCLUDED_FROM_DIFF: &[&str] = &[
     // Lock files
@@ -56,8 +76,26 @@ pub const EXCLUDED_FROM_DIFF: &[&str] = &[
 /// room for the prompt and response.
 pub const MAX_DIFF_CHARS: usize = 300_000;
 
-/// Checks if a file should be excluded from the diff based on [`EXCLUDED_FROM_DIFF`] patterns.
+/// Checks if a file should be excluded from the diff.
+///
+/// Combines [`EXCLUDED_FROM_DIFF`] with the repository's `.committerignore`.
 pub fn should_exclude_from_diff(filename: &str) -> bool {
+    exclusion_reason(filename).is_some()
+}
+
+/// Returns why a file is excluded from the diff, or `None` if it isn't.
+///
+/// A negated `.committerignore` pattern overrides the built-in list.
+pub fn exclusion_reason(filename: &str) -> Option<Exclusion> {
+    match check_committerignore(filename) {
+        IgnoreMatch::Ignore(pattern) => Some(Exclusion::Ignored(pattern)),
+        IgnoreMatch::Include => None,
+        IgnoreMatch::None => is_builtin_exclusion(filename).then_some(Exclusion::BuiltIn),
+    }
+}
+
+/// Checks a file against the built-in [`EXCLUDED_FROM_DIFF`] patterns.
+fn is_builtin_exclusion(filename: &str) -> bool {
     EXCLUDED_FROM_DIFF.iter().any(|pattern| {
         if pattern.ends_with('/') {
             // Directory pattern - check if file is inside this directory
@@ -82,9 +120,48 @@ fn extract_filename_from_diff_header(header: &str) -> Option<&str> {
         .and_then(|rest| rest.split(" b/").next())
 }
 
-/// Removes excluded files from a diff based on [`EXCLUDED_FROM_DIFF`] patterns.
+/// Replaces control characters in a diff with visible placeholders.
+///
+/// ASCII control characters become their Unicode control picture (`␛` for
+/// ESC, `␀` for NUL) and C1 controls become `\u{FFFD}`. Newlines, tabs, and the
+/// `\r` of CRLF line endings are kept. In verbose mode, reports how many
+/// characters were replaced.
+pub fn sanitize_diff(diff: &str, verbose: bool) -> String {
+    let mut replaced = 0;
+    let mut sanitized = String::with_capacity(diff.len());
+    let mut chars = diff.chars().peekable();
+    while let Some(c) = chars.next() {
+        let kept = match c {
+            '\n' | '\t' => true,
+            '\r' => chars.peek() == Some(&'\n'),
+            c => !c.is_control(),
+        };
+        if kept {
+            sanitized.push(c);
+            continue;
+        }
+        replaced += 1;
+        sanitized.push(match c as u32 {
+            code @ 0..=0x1f => char::from_u32(0x2400 + code).unwrap_or('\u{FFFD}'),
+            0x7f => '\u{2421}',
+            _ => '\u{FFFD}',
+        });
+    }
+
+    if verbose && replaced > 0 {
+        eprintln!(
+            "— Replaced {} control character{} in the diff with placeholders",
+            replaced,
+            if replaced == 1 { "" } else { "s" }
+        );
+    }
+    sanitized
+}
+
+/// Removes excluded files from a diff (see [`should_exclude_from_diff`]).
 ///
-/// In verbose mode, prints excluded files to stderr.
+/// In verbose mode, prints excluded files to stderr, with the
+/// `.committerignore` pattern that matched where there is one.
 pub fn filter_excluded_diffs(diff: &str, verbose: bool) -> String {
     if diff.is_empty() {
         return diff.to_string();
@@ -97,13 +174,13 @@ pub fn filter_excluded_diffs(diff: &str, verbose: bool) -> String {
 
     let first = chunks.remove(0);
     let mut file_diffs: Vec<String> = vec![];
-    let mut excluded_files: Vec<String> = vec![];
+    let mut excluded_files: Vec<(String, Exclusion)> = vec![];
 
     if !first.is_empty() {
         if let Some(filename) = extract_filename_from_diff_header(&format!("diff --git {}", first))
         {
-            if should_exclude_from_diff(filename) {
-                excluded_files.push(filename.to_string());
+            if let Some(reason) = exclusion_reason(filename) {
+                excluded_files.push((filename.to_string(), reason));
             } else {
                 file_diffs.push(first.to_string());
             }
@@ -115,8 +192,8 @@ pub fn filter_excluded_diffs(diff: &str, verbose: bool) -> String {
     for chunk in chunks {
         let full_header = format!("diff --git {}", chunk);
         if let Some(filename) = extract_filename_from_diff_header(&full_header) {
-            if should_exclude_from_diff(filename) {
-                excluded_files.push(filename.to_string());
+            if let Some(reason) = exclusion_reason(filename) {
+                excluded_files.push((filename.to_string(), reason));
             } else {
                 file_diffs.push(format!("\ndiff --git {}", chunk));
             }
@@ -125,40 +202,138 @@ pub fn filter_excluded_diffs(diff: &str, verbose: bool) -> String {
 
     if verbose && !excluded_files.is_empty() {
         eprintln!("— Excluded from diff ({} files):", excluded_files.len());
-        for file in &excluded_files {
-            eprintln!("    {}", file);
+        for (file, reason) in &excluded_files {
+            match reason {
+                Exclusion::BuiltIn => eprintln!("    {}", file),
+                Exclusion::Ignored(pattern) => {
+                    eprintln!("    {} ({}: {})", file, COMMITTERIGNORE_FILE, pattern)
+                }
+            }
         }
     }
 
     file_diffs.join("")
 }
 
-/// Truncates a diff to fit within token limits while preserving useful context.
+/// Pipes a diff through a user command and returns its stdout.
+///
+/// The command runs through the shell (`sh -c`, or `cmd /C` on Windows) from
+/// the current directory. Any failure is an error: the unprocessed diff is
+/// never used in its place.
+pub async fn run_diff_preprocessor(
+    command: &str,
+    diff: &str,
+    verbose: bool,
+) -> Result<String, Box<dyn std::error::Error>> {
+    if verbose {
+        eprintln!("— Running diff_preprocessor: {}", command);
+    }
+
+    let (shell, flag) = if cfg!(windows) {
+        ("cmd", "/C")
+    } else {
+        ("sh", "-c")
+    };
+    let mut child = Command::new(shell)
+        .args([flag, command])
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .spawn()
+        .map_err(|e| format!("diff_preprocessor '{}' could not start: {}", command, e))?;
+
+    // Write concurrently with reading, so a large diff can't fill both pipes and deadlock
+    let mut stdin = child
+        .stdin
+        .take()
+        .ok_or("diff_preprocessor stdin unavailable")?;
+    let input = diff.as_bytes().to_vec();
+    let writer = tokio::spawn(async move {
+        let result = stdin.write_all(&input).await;
+        drop(stdin);
+        result
+    });
+
+    let output = child.wait_with_output().await?;
+    // A command that exits without reading all of stdin is fine if it succeeded
+    let _ = writer.await;
+
+    if !output.status.success() {
+        let stderr = String::from_utf8_lossy(&output.stderr);
+        return Err(format!(
+            "diff_preprocessor '{}' failed ({}): {}",
+            command,
+            output.status,
+            stderr.trim()
+        )
+        .into());
+    }
+
+    let processed = String::from_utf8(output.stdout)
+        .map_err(|_| format!("diff_preprocessor '{}' produced invalid UTF-8", command))?;
+    let processed = sanitize_diff(&processed, verbose);
+    if verbose {
+        eprintln!(
+            "— diff_preprocessor: {} → {} chars",
+            diff.len(),
+            processed.len()
+        );
+    }
+    Ok(processed)
+}
+
+/// Truncates a diff to [`MAX_DIFF_CHARS`] while preserving useful context.
 /// Keeps the beginning (file headers, context) and end (recent changes).
 pub fn truncate_diff(diff: &str, verbose: bool) -> String {
-    if diff.len() <= MAX_DIFF_CHARS {
+    truncate_diff_to(diff, MAX_DIFF_CHARS, verbose)
+}
+
+/// Largest char boundary in `text` at or before byte `index`.
+fn floor_char_boundary(text: &str, index: usize) -> usize {
+    let mut index = index.min(text.len());
+    while !text.is_char_boundary(index) {
+        index -= 1;
+    }
+    index
+}
+
+/// Smallest char boundary in `text` at or after byte `index`.
+fn ceil_char_boundary(text: &str, index: usize) -> usize {
+    let mut index = index.min(text.len());
+    while !text.is_char_boundary(index) {
+        index += 1;
+    }
+    index
+}
+
+/// Truncates a diff to at most `max_chars`, keeping whole files where possible.
+///
+/// Limits are in bytes. When even the first file doesn't fit, the diff's
+/// start and end are kept instead, cut on char boundaries so multi-byte
+/// characters are never split.
+pub fn truncate_diff_to(diff: &str, max_chars: usize, verbose: bool) -> String {
+    if diff.len() <= max_chars {
         return diff.to_string();
     }
 
     // Split into file chunks to truncate more intelligently
     let mut chunks: Vec<&str> = diff.split("\ndiff --git ").collect();
 
-    if chunks.is_empty() {
+    if chunks[0].len() + 200 > max_chars {
         // Fallback: simple truncation with middle cut
-        let keep_each = MAX_DIFF_CHARS / 2;
-        let start = &diff[..keep_each];
-        let end = &diff[diff.len() - keep_each..];
+        let keep_each = max_chars / 2;
+        let start = &diff[..floor_char_boundary(diff, keep_each)];
+        let end = &diff[ceil_char_boundary(diff, diff.len() - keep_each)..];
+        let removed = diff.len() - start.len() - end.len();
         if verbose {
             eprintln!(
                 "— Diff truncated: {} chars removed (fallback mode)",
-                diff.len() - MAX_DIFF_CHARS
+                removed
             );
         }
         return format!(
             "{}\n\n[... {} characters truncated ...]\n\n{}",
-            start,
-            diff.len() - MAX_DIFF_CHARS,
-            end
+            start, removed, end
         );
     }
 
@@ -177,7 +352,7 @@ pub fn truncate_diff(diff: &str, verbose: bool) -> String {
     for file_diff in &file_diffs {
         let chunk_len = file_diff.len();
         // Reserve space for truncation notice
-        if total_len + chunk_len + 200 > MAX_DIFF_CHARS {
+        if total_len + chunk_len + 200 > max_chars {
             break;
         }
         result.push_str(file_diff);
@@ -192,7 +367,7 @@ pub fn truncate_diff(diff: &str, verbose: bool) -> String {
                 "— Diff truncated: showing {}/{} files ({} KB limit)",
                 included,
                 file_diffs.len(),
-                MAX_DIFF_CHARS / 1024
+                max_chars / 1024
             );
         }
         result.push_str(&format!(
@@ -205,9 +380,131 @@ pub fn truncate_diff(diff: &str, verbose: bool) -> String {
     result
 }
 
+/// Annotates `--name-status` output, marking files excluded from the diff.
+///
+/// Returns the annotated lines and the number of excluded files.
+fn annotate_excluded_files(name_status: &str) -> (Vec<String>, usize) {
+    let mut excluded_count = 0;
+
+    let annotated = name_status
+        .lines()
+        .map(|line| {
+            let parts: Vec<&str> = line.splitn(2, '\t').collect();
+            if parts.len() == 2 {
+                let filename = parts[1];
+                if should_exclude_from_diff(filename) {
+                    excluded_count += 1;
+                    format!("{}\t{} [excluded from diff]", parts[0], filename)
+                } else {
+                    line.to_string()
+                }
+            } else {
+                line.to_string()
+            }
+        })
+        .collect();
+
+    (annotated, excluded_count)
+}
+
+/// A submodule whose recorded commit changed in a diff.
+pub struct SubmoduleUpdate {
+    /// Path of the submodule in the superproject.
+    pub path: String,
+    /// Previously recorded commit (`None` if the submodule was added).
+    pub old_sha: Option<String>,
+    /// Newly recorded commit (`None` if the submodule was removed).
+    pub new_sha: Option<String>,
+}
+
+/// Abbreviates a commit SHA to 7 characters.
+pub fn short_sha(sha: &str) -> &str {
+    &sha[..sha.len().min(7)]
+}
+
+/// Finds submodule pointer changes (`Subproject commit` lines) in a diff.
+pub fn parse_submodule_updates(diff: &str) -> Vec<SubmoduleUpdate> {
+    let mut updates = Vec::new();
+
+    for chunk in diff.split("diff --git ").skip(1) {
+        let header = format!("diff --git {}", chunk);
+        let Some(path) = extract_filename_from_diff_header(&header) else {
+            continue;
+        };
+
+        let mut old_sha = None;
+        let mut new_sha = None;
+        for line in chunk.lines() {
+            if let Some(sha) = line.strip_prefix("-Subproject commit ") {
+                old_sha = Some(sha.trim().trim_end_matches("-dirty").to_string());
+            } else if let Some(sha) = line.strip_prefix("+Subproject commit ") {
+                new_sha = Some(sha.trim().trim_end_matches("-dirty").to_string());
+            }
+        }
+
+        if old_sha.is_some() || new_sha.is_some() {
+            updates.push(SubmoduleUpdate {
+                path: path.to_string(),
+                old_sha,
+                new_sha,
+            });
+        }
+    }
+
+    updates
+}
+
+/// Returns true if every file in the diff is a submodule pointer change.
+pub fn is_submodule_only_diff(diff: &str) -> bool {
+    let files = diff.split("diff --git ").skip(1).count();
+    files > 0 && parse_submodule_updates(diff).len() == files
+}
+
+/// Returns the subject of a commit inside a submodule, if it's checked out.
+///
+/// `path` comes from the diff, so it's relative to the repository root, not
+/// the directory committer runs in.
+pub async fn get_submodule_commit_subject(path: &str, sha: &str) -> Option<String> {
+    submodule_commit_subject_from(Path::new("."), path, sha).await
+}
+
+async fn submodule_commit_subject_from(dir: &Path, path: &str, sha: &str) -> Option<String> {
+    let root = Command::new("git")
+        .arg("-C")
+        .arg(dir)
+        .args(["rev-parse", "--show-toplevel"])
+        .output()
+        .await
+        .ok()?;
+    if !root.status.success() {
+        return None;
+    }
+    let submodule = PathBuf::from(String::from_utf8_lossy(&root.stdout).trim()).join(path);
+
+    let output = Command::new("git")
+        .arg("-C")
+        .arg(submodule)
+        .args(["log", "-1", "--format=%s", sha])
+        .output()
+        .await
+        .ok()?;
+
+    if !output.status.success() {
+        return None;
+    }
+
+    let subject = String::from_utf8_lossy(&output.stdout).trim().to_string();
+    if subject.is_empty() {
+        None
+    } else {
+        Some(subject)
+    }
+}
+
 /// Retrieves the git diff, filtered and truncated for LLM consumption.
 ///
-/// Applies [`filter_excluded_diffs`] and [`truncate_diff`] automatically.
+/// Applies [`sanitize_diff`], [`filter_excluded_diffs`], and [`truncate_diff`]
+/// automatically.
 pub async fn get_git_diff(
     staged_only: bool,
     verbose: bool,
@@ -225,7 +522,7 @@ pub async fn get_git_diff(
         return Err(format!("git diff failed: {}", stderr).into());
     }
 
-    let diff = String::from_utf8_lossy(&output.stdout).to_string();
+    let diff = sanitize_diff(&String::from_utf8_lossy(&output.stdout), verbose);
     let filtered_diff = filter_excluded_diffs(&diff, verbose);
     Ok(truncate_diff(&filtered_diff, verbose))
 }
@@ -245,25 +542,7 @@ pub async fn get_staged_files(verbose: bool) -> Result<String, Box<dyn std::erro
     }
 
     let raw_output = String::from_utf8_lossy(&output.stdout).to_string();
-    let mut excluded_count = 0;
-
-    let annotated: Vec<String> = raw_output
-        .lines()
-        .map(|line| {
-            let parts: Vec<&str> = line.splitn(2, '\t').collect();
-            if parts.len() == 2 {
-                let filename = parts[1];
-                if should_exclude_from_diff(filename) {
-                    excluded_count += 1;
-                    format!("{}\t{} [excluded from diff]", parts[0], filename)
-                } else {
-                    line.to_string()
-                }
-            } else {
-                line.to_string()
-            }
-        })
-        .collect();
+    let (annotated, excluded_count) = annotate_excluded_files(&raw_output);
 
     if verbose {
         let total = annotated.len();
@@ -276,159 +555,946 @@ pub async fn get_staged_files(verbose: bool) -> Result<String, Box<dyn std::erro
     Ok(annotated.join("\n"))
 }
 
-/// Creates a git commit with the given message.
-pub async fn run_git_commit(message: &str) -> Result<(), Box<dyn std::error::Error>> {
-    let output = Command::new("git")
-        .args(["commit", "-m", message])
+/// Lists the files a diff touches, like [`get_staged_files`] does for the index.
+///
+/// For diffs that don't come from git (`--stdin-diff`). Statuses come from
+/// each section's header: `A` for new files, `D` for deleted ones, `R` for
+/// renames, and `M` otherwise.
+pub fn files_from_diff(diff: &str) -> String {
+    let mut files: Vec<(char, &str)> = Vec::new();
+    for line in diff.lines() {
+        if let Some(paths) = line.strip_prefix("diff --git ") {
+            let path = paths.rsplit_once(" b/").map_or(paths, |(_, path)| path);
+            files.push(('M', path));
+        } else if let Some((status, _)) = files.last_mut() {
+            if line.starts_with("new file mode") {
+                *status = 'A';
+            } else if line.starts_with("deleted file mode") {
+                *status = 'D';
+            } else if line.starts_with("rename to ") {
+                *status = 'R';
+            }
+        }
+    }
+    let name_status: Vec<String> = files
+        .iter()
+        .map(|(status, path)| format!("{}\t{}", status, path))
+        .collect();
+    annotate_file_list(&name_status.join("\n"))
+}
+
+/// Marks the files in a `--name-status` list that are excluded from the diff.
+pub fn annotate_file_list(name_status: &str) -> String {
+    annotate_excluded_files(name_status).0.join("\n")
+}
+
+/// Object ID of git's empty tree, used as the diff base for root commits.
+const EMPTY_TREE_SHA: &str = "4b825dc642cb6eb9a060e54bf8d69288fbee4904";
+
+/// True if `HEAD` is a merge commit (has a second parent).
+pub async fn head_is_merge() -> bool {
+    Command::new("git")
+        .args(["rev-parse", "--verify", "-q", "HEAD^2"])
+        .output()
+        .await
+        .is_ok_and(|output| output.status.success())
+}
+
+/// Returns the revision the amended commit will be diffed against.
+///
+/// This is `HEAD~1`, or the empty tree when `HEAD` is a root commit.
+async fn get_amend_base() -> Result<String, Box<dyn std::error::Error>> {
+    let head = Command::new("git")
+        .args(["rev-parse", "--verify", "-q", "HEAD"])
         .output()
         .await?;
 
-    if !output.status.success() {
-        let stderr = String::from_utf8_lossy(&output.stderr);
-        return Err(format!("git commit failed: {}", stderr).into());
+    if !head.status.success() {
+        return Err("Nothing to amend: the repository has no commits yet".into());
     }
 
-    Ok(())
+    let parent = Command::new("git")
+        .args(["rev-parse", "--verify", "-q", "HEAD~1"])
+        .output()
+        .await?;
+
+    if parent.status.success() {
+        Ok(String::from_utf8_lossy(&parent.stdout).trim().to_string())
+    } else {
+        Ok(EMPTY_TREE_SHA.to_string())
+    }
 }
 
-/// Stages all changes (tracked and untracked) via `git add -A`.
-pub async fn stage_all_changes() -> Result<(), Box<dyn std::error::Error>> {
-    let output = Command::new("git").args(["add", "-A"]).output().await?;
+/// Retrieves the diff an amended commit would contain, filtered and truncated.
+///
+/// Compares `HEAD~1` against the index, so the result covers the last
+/// commit's changes plus anything newly staged. If the last commit was
+/// empty, only the newly staged changes appear.
+pub async fn get_last_commit_diff(verbose: bool) -> Result<String, Box<dyn std::error::Error>> {
+    let base = get_amend_base().await?;
+
+    let output = Command::new("git")
+        .args(["diff", "--cached", &base])
+        .output()
+        .await?;
 
     if !output.status.success() {
         let stderr = String::from_utf8_lossy(&output.stderr);
-        return Err(format!("git add failed: {}", stderr).into());
+        return Err(format!("git diff failed: {}", stderr).into());
     }
 
-    Ok(())
+    let diff = sanitize_diff(&String::from_utf8_lossy(&output.stdout), verbose);
+    let filtered_diff = filter_excluded_diffs(&diff, verbose);
+    Ok(truncate_diff(&filtered_diff, verbose))
 }
 
-/// Returns the name of the current git branch.
-pub async fn get_current_branch() -> Result<String, Box<dyn std::error::Error>> {
+/// Returns the files an amended commit would contain, with status (M/A/D).
+///
+/// Like [`get_last_commit_diff`], compares `HEAD~1` against the index.
+pub async fn get_last_commit_files(verbose: bool) -> Result<String, Box<dyn std::error::Error>> {
+    let base = get_amend_base().await?;
+
     let output = Command::new("git")
-        .args(["rev-parse", "--abbrev-ref", "HEAD"])
+        .args(["diff", "--cached", "--name-status", &base])
         .output()
         .await?;
 
     if !output.status.success() {
         let stderr = String::from_utf8_lossy(&output.stderr);
-        return Err(format!("git rev-parse failed: {}", stderr).into());
+        return Err(format!("git diff --name-status failed: {}", stderr).into());
     }
 
-    Ok(String::from_utf8_lossy(&output.stdout).trim().to_string())
+    let raw_output = String::from_utf8_lossy(&output.stdout).to_string();
+    let (annotated, excluded_count) = annotate_excluded_files(&raw_output);
+
+    if verbose {
+        eprintln!(
+            "— Amended commit files: {} total, {} excluded from diff",
+            annotated.len(),
+            excluded_count
+        );
+    }
+
+    Ok(annotated.join("\n"))
 }
 
-/// Creates a new branch and switches to it.
-pub async fn create_and_switch_branch(branch_name: &str) -> Result<(), Box<dyn std::error::Error>> {
+/// An earlier commit that staged changes will be folded into.
+pub struct FixupTarget {
+    /// Full SHA of the target commit.
+    pub sha: String,
+    /// Subject line of the target commit.
+    pub subject: String,
+    /// True if the target has no parent (the root commit).
+    pub is_root: bool,
+}
+
+/// Resolves a ref to a commit that can be fixed up from `HEAD`.
+///
+/// Fails if the ref doesn't name a commit, or the commit isn't an ancestor of
+/// (or equal to) `HEAD`.
+pub async fn resolve_fixup_target(
+    reference: &str,
+) -> Result<FixupTarget, Box<dyn std::error::Error>> {
     let output = Command::new("git")
-        .args(["checkout", "-b", branch_name])
+        .args(["log", "-1", "--format=%H%x00%P%x00%s", reference, "--"])
         .output()
         .await?;
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    let mut fields = stdout.trim_end().splitn(3, '\0');
+    let (Some(sha), Some(parents), Some(subject)) = (fields.next(), fields.next(), fields.next())
+    else {
+        return Err(format!("'{}' is not a commit", reference).into());
+    };
+    if !output.status.success() || sha.is_empty() {
+        return Err(format!("'{}' is not a commit", reference).into());
+    }
 
-    if !output.status.success() {
-        let stderr = String::from_utf8_lossy(&output.stderr);
-        return Err(format!("git checkout -b failed: {}", stderr).into());
+    let is_ancestor = Command::new("git")
+        .args(["merge-base", "--is-ancestor", sha, "HEAD"])
+        .status()
+        .await?
+        .success();
+    if !is_ancestor {
+        return Err(format!("'{}' is not an ancestor of HEAD", reference).into());
     }
 
-    Ok(())
+    Ok(FixupTarget {
+        sha: sha.to_string(),
+        subject: subject.to_string(),
+        is_root: parents.is_empty(),
+    })
 }
 
-/// Returns the subject lines of recent commits (for branch analysis context).
-pub async fn get_recent_commits(limit: usize) -> Result<String, Box<dyn std::error::Error>> {
+/// Folds `fixup!` commits into `target` with a non-interactive autosquash rebase.
+///
+/// Local changes are stashed around the rebase. If the rebase fails (e.g. on
+/// a conflict), it is aborted so the branch is left exactly as before.
+pub async fn autosquash_into(target: &FixupTarget) -> Result<(), Box<dyn std::error::Error>> {
+    let base = format!("{}^", target.sha);
+    let mut args = vec!["rebase", "-i", "--autosquash", "--autostash"];
+    if target.is_root {
+        args.push("--root");
+    } else {
+        args.push(&base);
+    }
+
+    // Accept the generated todo list as-is and never stop for a message edit
     let output = Command::new("git")
-        .args(["log", "--oneline", &format!("-{}", limit), "--format=%s"])
+        .args(&args)
+        .env("GIT_SEQUENCE_EDITOR", "true")
+        .env("GIT_EDITOR", "true")
         .output()
         .await?;
 
     if !output.status.success() {
-        return Ok(String::new());
+        let _ = Command::new("git")
+            .args(["rebase", "--abort"])
+            .output()
+            .await;
+        // Keep the actual failure, not the progress output and resolution hints
+        let stderr = String::from_utf8_lossy(&output.stderr);
+        let reason: Vec<&str> = stderr
+            .split(['\n', '\r'])
+            .map(|line| line.trim_start_matches(|c: char| !c.is_ascii_alphabetic()))
+            .filter(|line| {
+                line.starts_with("error:")
+                    || line.starts_with("fatal:")
+                    || line.starts_with("CONFLICT")
+            })
+            .collect();
+        let reason = if reason.is_empty() {
+            stderr.trim().to_string()
+        } else {
+            reason.join("; ")
+        };
+        return Err(format!("git rebase failed: {}", reason).into());
     }
 
-    Ok(String::from_utf8_lossy(&output.stdout).trim().to_string())
+    Ok(())
 }
 
-/// Check if a branch exists and has a merge base with HEAD
-pub async fn branch_has_merge_base(branch: &str) -> bool {
-    let output = Command::new("git")
-        .args(["merge-base", branch, "HEAD"])
-        .output()
-        .await;
+/// How many recent commits are checked for one the staged changes undo.
+pub const REVERT_SEARCH_DEPTH: usize = 10;
 
-    matches!(output, Ok(o) if o.status.success())
+/// A commit whose changes the staged changes undo.
+pub struct RevertedCommit {
+    /// Full SHA of the reverted commit.
+    pub sha: String,
+    /// Subject line of the reverted commit.
+    pub subject: String,
 }
 
-/// Try to get the default branch from the cached remote HEAD reference
-pub async fn get_cached_remote_head() -> Option<String> {
+/// Computes the stable patch ID of a diff, ignoring whitespace and line numbers.
+async fn patch_id(diff: &[u8]) -> Option<String> {
+    let mut child = Command::new("git")
+        .args(["patch-id", "--stable"])
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::null())
+        .spawn()
+        .ok()?;
+
+    if let Some(mut stdin) = child.stdin.take() {
+        stdin.write_all(diff).await.ok()?;
+    }
+
+    let output = child.wait_with_output().await.ok()?;
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    stdout.split_whitespace().next().map(str::to_string)
+}
+
+/// Finds the commit that the staged changes revert, if any.
+///
+/// Uses `REVERT_HEAD` while a `git revert --no-commit` (or a conflicted
+/// revert) is in progress. Otherwise compares the patch ID of the staged
+/// diff with the inverse of each of the last [`REVERT_SEARCH_DEPTH`]
+/// commits, which catches reverts made by hand or with `git apply -R`.
+pub async fn find_reverted_commit(verbose: bool) -> Option<RevertedCommit> {
+    let args = ["rev-parse", "-q", "--verify", "REVERT_HEAD"];
+    let output = Command::new("git").args(args).output().await;
+    log_command(verbose, "git", &args, &output);
+
+    let sha = match output {
+        Ok(output) if output.status.success() => {
+            String::from_utf8_lossy(&output.stdout).trim().to_string()
+        }
+        _ => find_inverted_commit(verbose).await?,
+    };
+
     let output = Command::new("git")
-        .args(["symbolic-ref", "refs/remotes/origin/HEAD"])
+        .args(["log", "-1", "--format=%s", &sha])
         .output()
         .await
         .ok()?;
-
     if !output.status.success() {
         return None;
     }
 
-    let full_ref = String::from_utf8_lossy(&output.stdout).trim().to_string();
-    // refs/remotes/origin/main -> main
-    full_ref
-        .strip_prefix("refs/remotes/origin/")
-        .map(|s| s.to_string())
+    Some(RevertedCommit {
+        sha,
+        subject: String::from_utf8_lossy(&output.stdout).trim().to_string(),
+    })
 }
 
-/// Query the remote directly for its default branch (works with any git remote)
-pub async fn get_remote_default_branch() -> Option<String> {
-    let output = Command::new("git")
-        .args(["ls-remote", "--symref", "origin", "HEAD"])
+/// Returns the recent commit whose inverse matches the staged diff exactly.
+async fn find_inverted_commit(verbose: bool) -> Option<String> {
+    let staged = Command::new("git")
+        .args([
+            "diff",
+            "--cached",
+            "--no-color",
+            "--no-ext-diff",
+            "--src-prefix=a/",
+            "--dst-prefix=b/",
+        ])
         .output()
         .await
         .ok()?;
-
-    if !output.status.success() {
+    if staged.stdout.is_empty() {
         return None;
     }
-
-    // Output format: "ref: refs/heads/main\tHEAD\n..."
-    let stdout = String::from_utf8_lossy(&output.stdout);
-    for line in stdout.lines() {
-        if line.starts_with("ref: refs/heads/") && line.contains("HEAD") {
-            // Extract branch name between "ref: refs/heads/" and the tab
-            if let Some(rest) = line.strip_prefix("ref: refs/heads/") {
-                if let Some(branch) = rest.split('\t').next() {
-                    return Some(branch.to_string());
-                }
-            }
+    let staged_id = patch_id(&staged.stdout).await?;
+
+    let depth = format!("-{}", REVERT_SEARCH_DEPTH);
+    let args = ["log", &depth, "--no-merges", "--format=%H"];
+    let output = Command::new("git").args(args).output().await;
+    log_command(verbose, "git", &args, &output);
+    let output = output.ok()?;
+
+    for sha in String::from_utf8_lossy(&output.stdout).lines() {
+        // -R swaps the prefixes too, so swap them back to match the staged diff
+        let inverse = Command::new("git")
+            .args([
+                "diff-tree",
+                "-p",
+                "-R",
+                "--no-color",
+                "--no-ext-diff",
+                "--src-prefix=b/",
+                "--dst-prefix=a/",
+                "--root",
+                sha,
+            ])
+            .output()
+            .await
+            .ok()?;
+        if patch_id(&inverse.stdout).await.as_deref() == Some(staged_id.as_str()) {
+            return Some(sha.to_string());
         }
     }
+
     None
 }
 
-/// Checks if an 'upstream' remote exists (for fork workflows).
-pub async fn get_upstream_remote() -> Result<Option<String>, Box<dyn std::error::Error>> {
-    // Check if 'upstream' remote exists (common fork workflow)
-    let output = Command::new("git")
-        .args(["remote", "get-url", "upstream"])
-        .output()
-        .await?;
+/// Options controlling how [`run_git_commit`] invokes `git commit`.
+#[derive(Default)]
+pub struct CommitOptions {
+    /// Replace the last commit (`--amend`) instead of creating a new one.
+    pub amend: bool,
+    /// Record the commit even if nothing is staged (`--allow-empty`).
+    pub allow_empty: bool,
+    /// GPG-sign the commit (`-S`). See [`sign_flag_needed`].
+    pub sign: bool,
+    /// Skip the pre-commit and commit-msg hooks (`--no-verify`).
+    pub no_verify: bool,
+    /// Print the `git commit` invocation to stderr before running it.
+    pub verbose: bool,
+    /// Directory to run `git commit` in; the current directory when unset.
+    pub dir: Option<PathBuf>,
+}
 
-    if output.status.success() {
+/// Builds the `git commit` arguments for a message (the message comes last).
+fn commit_args<'a>(message: &'a str, options: &CommitOptions) -> Vec<&'a str> {
+    let mut args = vec!["commit"];
+    if options.amend {
+        args.push("--amend");
+    }
+    if options.allow_empty {
+        args.push("--allow-empty");
+    }
+    if options.sign {
+        args.push("-S");
+    }
+    if options.no_verify {
+        args.push("--no-verify");
+    }
+    args.extend(["-m", message]);
+    args
+}
+
+/// Quotes an argument for display in a POSIX shell command line.
+fn shell_quote(arg: &str) -> String {
+    if !arg.is_empty()
+        && arg
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || "-_./=:@+,".contains(c))
+    {
+        return arg.to_string();
+    }
+    format!("'{}'", arg.replace('\'', "'\\''"))
+}
+
+/// Logs an external command and its outcome to stderr when `verbose` is set.
+///
+/// Multi-line or long arguments (e.g. a PR body) are shown as a length.
+pub fn log_command<S: AsRef<str>>(
+    verbose: bool,
+    program: &str,
+    args: &[S],
+    output: &std::io::Result<std::process::Output>,
+) {
+    if !verbose {
+        return;
+    }
+
+    let command: Vec<String> = args
+        .iter()
+        .map(|arg| {
+            let arg = arg.as_ref();
+            if arg.contains('\n') || arg.len() > 80 {
+                format!("<{} chars>", arg.len())
+            } else {
+                shell_quote(arg)
+            }
+        })
+        .collect();
+    let outcome = match output {
+        Ok(output) if output.status.success() => "ok".to_string(),
+        Ok(output) => {
+            let stderr = String::from_utf8_lossy(&output.stderr);
+            match stderr.lines().map(str::trim).find(|line| !line.is_empty()) {
+                Some(line) => format!("failed ({}): {}", output.status, line),
+                None => format!("failed ({})", output.status),
+            }
+        }
+        Err(e) => format!("could not run: {}", e),
+    };
+    eprintln!("— $ {} {}: {}", program, command.join(" "), outcome);
+}
+
+/// Renders the `git commit` command [`run_git_commit`] would run.
+///
+/// The message is abbreviated to its subject, a body line count, and any
+/// trailers, so flags and trailers can be checked at a glance.
+pub fn describe_commit_command(message: &str, options: &CommitOptions) -> String {
+    let (subject, body, trailers) = split_message(message);
+    let mut summary = subject.to_string();
+    let body_lines = body.lines().filter(|line| !line.trim().is_empty()).count();
+    if body_lines > 0 {
+        summary.push_str(&format!(" [+{} body lines]", body_lines));
+    }
+    for trailer in trailers.lines() {
+        summary.push_str(&format!(" [{}]", trailer.trim()));
+    }
+
+    let args = commit_args(message, options);
+    let flags = &args[..args.len() - 1];
+    let mut command = vec!["git".to_string()];
+    command.extend(flags.iter().map(|arg| shell_quote(arg)));
+    command.push(shell_quote(&summary));
+    command.join(" ")
+}
+
+/// Creates a git commit with the given message.
+pub async fn run_git_commit(
+    message: &str,
+    options: &CommitOptions,
+) -> Result<(), Box<dyn std::error::Error>> {
+    if options.verbose {
+        eprintln!("— Running: {}", describe_commit_command(message, options));
+    }
+
+    let args = commit_args(message, options);
+    let mut command = Command::new("git");
+    if let Some(dir) = &options.dir {
+        command.current_dir(dir);
+    }
+    let output = command.args(&args).output().await?;
+
+    if !output.status.success() {
+        let stderr = String::from_utf8_lossy(&output.stderr);
+        let mut error = format!("git commit failed: {}", stderr.trim_end());
+        if stderr.contains("failed to sign") {
+            error.push_str(
+                "\nSigning needs a key git can use: set user.signingkey, and make sure \
+                 gpg can prompt for its passphrase (e.g. export GPG_TTY=$(tty))",
+            );
+        }
+        return Err(error.into());
+    }
+
+    Ok(())
+}
+
+/// Whether `-S` should be passed for a `requested` signed commit.
+///
+/// With `commit.gpgsign` set git already signs every commit, so the flag is
+/// left out rather than passed twice.
+pub async fn sign_flag_needed(requested: bool) -> bool {
+    if !requested {
+        return false;
+    }
+    let signs_by_default = get_git_config("commit.gpgsign").await.is_some_and(|value| {
+        matches!(
+            value.to_ascii_lowercase().as_str(),
+            "true" | "yes" | "on" | "1"
+        )
+    });
+    !signs_by_default
+}
+
+/// Returns the full message of the `HEAD` commit.
+pub async fn get_head_message() -> Option<String> {
+    let output = Command::new("git")
+        .args(["log", "-1", "--format=%B"])
+        .output()
+        .await
+        .ok()?;
+
+    if !output.status.success() {
+        return None;
+    }
+
+    Some(
+        String::from_utf8_lossy(&output.stdout)
+            .trim_end()
+            .to_string(),
+    )
+}
+
+/// Returns the full SHA of `HEAD`, if the repository has any commits.
+pub async fn get_head_sha() -> Option<String> {
+    let output = Command::new("git")
+        .args(["rev-parse", "HEAD"])
+        .output()
+        .await
+        .ok()?;
+
+    if !output.status.success() {
+        return None;
+    }
+
+    Some(String::from_utf8_lossy(&output.stdout).trim().to_string())
+}
+
+/// Reads a single git config value, returning `None` if unset or empty.
+pub async fn get_git_config(key: &str) -> Option<String> {
+    let output = Command::new("git")
+        .args(["config", "--get", key])
+        .output()
+        .await
+        .ok()?;
+
+    if !output.status.success() {
+        return None;
+    }
+
+    let value = String::from_utf8_lossy(&output.stdout).trim().to_string();
+    if value.is_empty() {
+        None
+    } else {
+        Some(value)
+    }
+}
+
+/// Stages all changes (tracked and untracked) via `git add -A`.
+pub async fn stage_all_changes() -> Result<(), Box<dyn std::error::Error>> {
+    let output = Command::new("git").args(["add", "-A"]).output().await?;
+
+    if !output.status.success() {
+        let stderr = String::from_utf8_lossy(&output.stderr);
+        return Err(format!("git add failed: {}", stderr).into());
+    }
+
+    Ok(())
+}
+
+/// Returns the top-level directory of the current repository.
+pub async fn get_repo_root() -> Option<PathBuf> {
+    let output = Command::new("git")
+        .args(["rev-parse", "--show-toplevel"])
+        .output()
+        .await
+        .ok()?;
+
+    if !output.status.success() {
+        return None;
+    }
+
+    Some(PathBuf::from(
+        String::from_utf8_lossy(&output.stdout).trim().to_string(),
+    ))
+}
+
+/// Returns the path of a file inside the git directory (`git rev-parse --git-path`).
+pub async fn get_git_path(name: &str) -> Option<PathBuf> {
+    let output = Command::new("git")
+        .args(["rev-parse", "--git-path", name])
+        .output()
+        .await
+        .ok()?;
+
+    if !output.status.success() {
+        return None;
+    }
+
+    Some(PathBuf::from(
+        String::from_utf8_lossy(&output.stdout).trim().to_string(),
+    ))
+}
+
+/// Returns the name of the current git branch.
+pub async fn get_current_branch() -> Result<String, Box<dyn std::error::Error>> {
+    let output = Command::new("git")
+        .args(["rev-parse", "--abbrev-ref", "HEAD"])
+        .output()
+        .await?;
+
+    if !output.status.success() {
+        let stderr = String::from_utf8_lossy(&output.stderr);
+        return Err(format!("git rev-parse failed: {}", stderr).into());
+    }
+
+    Ok(String::from_utf8_lossy(&output.stdout).trim().to_string())
+}
+
+/// A branch created by this run, and where the run started.
+pub struct NewBranch {
+    /// Name of the created branch.
+    pub name: String,
+    /// Branch the run started on, or `HEAD` if it was detached.
+    pub original: String,
+    /// Commit the branch was created at (`None` in a repo without commits).
+    pub head: Option<String>,
+}
+
+/// Creates a new branch and switches to it.
+///
+/// Returns what's needed to undo the switch with [`roll_back_new_branch`].
+pub async fn create_and_switch_branch(
+    branch_name: &str,
+) -> Result<NewBranch, Box<dyn std::error::Error>> {
+    let original = get_current_branch().await?;
+    let head = get_head_sha().await;
+
+    let output = Command::new("git")
+        .args(["checkout", "-b", branch_name])
+        .output()
+        .await?;
+
+    if !output.status.success() {
+        let stderr = String::from_utf8_lossy(&output.stderr);
+        return Err(format!("git checkout -b failed: {}", stderr).into());
+    }
+
+    Ok(NewBranch {
+        name: branch_name.to_string(),
+        original,
+        head,
+    })
+}
+
+/// Switches back to where the run started and deletes the new branch.
+///
+/// Only allowed while the new branch has no commits of its own, so nothing
+/// is lost; staged changes stay staged because both point at one commit.
+pub async fn roll_back_new_branch(branch: &NewBranch) -> Result<(), Box<dyn std::error::Error>> {
+    if get_head_sha().await != branch.head {
+        return Err(format!("'{}' has new commits; leaving it in place", branch.name).into());
+    }
+
+    match (&branch.head, branch.original.as_str()) {
+        // Unborn branches have no ref to delete; just point HEAD back
+        (None, original) => {
+            let target = format!("refs/heads/{}", original);
+            return run_git_checked(&["symbolic-ref", "HEAD", &target]).await;
+        }
+        (Some(sha), "HEAD") => run_git_checked(&["checkout", "--detach", sha]).await?,
+        (Some(_), original) => run_git_checked(&["checkout", original]).await?,
+    }
+    run_git_checked(&["branch", "-D", &branch.name]).await
+}
+
+/// Runs a git command, failing with its stderr if it exits unsuccessfully.
+async fn run_git_checked(args: &[&str]) -> Result<(), Box<dyn std::error::Error>> {
+    let output = Command::new("git").args(args).output().await?;
+    if !output.status.success() {
+        let stderr = String::from_utf8_lossy(&output.stderr);
+        return Err(format!("git {} failed: {}", args[0], stderr.trim()).into());
+    }
+    Ok(())
+}
+
+/// Returns the subject lines of recent commits (for branch analysis context).
+pub async fn get_recent_commits(limit: usize) -> Result<String, Box<dyn std::error::Error>> {
+    let output = Command::new("git")
+        .args(["log", "--oneline", &format!("-{}", limit), "--format=%s"])
+        .output()
+        .await?;
+
+    if !output.status.success() {
+        return Ok(String::new());
+    }
+
+    Ok(String::from_utf8_lossy(&output.stdout).trim().to_string())
+}
+
+/// Check if a branch exists and has a merge base with HEAD
+pub async fn branch_has_merge_base(branch: &str, verbose: bool) -> bool {
+    let args = ["merge-base", branch, "HEAD"];
+    let output = Command::new("git").args(args).output().await;
+    log_command(verbose, "git", &args, &output);
+
+    matches!(output, Ok(o) if o.status.success())
+}
+
+/// Returns local and remote branches that look long-lived.
+///
+/// A branch is long-lived if its name, without the remote, is one of the
+/// [`PROTECTED_BRANCHES`] or starts with `release/`. A remote branch is left
+/// out when a local branch has the same name.
+pub async fn get_long_lived_branches(verbose: bool) -> Vec<String> {
+    let args = [
+        "for-each-ref",
+        "--format=%(refname)",
+        "refs/heads",
+        "refs/remotes",
+    ];
+    let output = Command::new("git").args(args).output().await;
+    log_command(verbose, "git", &args, &output);
+    let Ok(output) = output else {
+        return Vec::new();
+    };
+    let stdout = String::from_utf8_lossy(&output.stdout);
+
+    let is_long_lived =
+        |name: &str| PROTECTED_BRANCHES.contains(&name) || name.starts_with("release/");
+    let local: Vec<&str> = stdout
+        .lines()
+        .filter_map(|line| line.strip_prefix("refs/heads/"))
+        .filter(|name| is_long_lived(name))
+        .collect();
+    let remote = stdout
+        .lines()
+        .filter_map(|line| line.strip_prefix("refs/remotes/"))
+        .filter(|short| {
+            short
+                .split_once('/')
+                .is_some_and(|(_, name)| is_long_lived(name) && !local.contains(&name))
+        });
+
+    local
+        .iter()
+        .copied()
+        .chain(remote)
+        .map(str::to_string)
+        .collect()
+}
+
+/// Try to get the default branch from the cached remote HEAD reference
+pub async fn get_cached_remote_head(remote: &str, verbose: bool) -> Option<String> {
+    let head_ref = format!("refs/remotes/{}/HEAD", remote);
+    let args = ["symbolic-ref", head_ref.as_str()];
+    let output = Command::new("git").args(args).output().await;
+    log_command(verbose, "git", &args, &output);
+    let output = output.ok()?;
+
+    if !output.status.success() {
+        return None;
+    }
+
+    let full_ref = String::from_utf8_lossy(&output.stdout).trim().to_string();
+    // refs/remotes/origin/main -> main
+    full_ref
+        .strip_prefix(&format!("refs/remotes/{}/", remote))
+        .map(|s| s.to_string())
+}
+
+/// Branch names [`detect_default_base`] looks for on the remote, in order of preference.
+const DEFAULT_BASE_NAMES: &[&str] = &["main", "master", "develop"];
+
+/// Finds the base branch from what git already knows about `remote`.
+///
+/// Tries, in order: the remote's cached HEAD (`refs/remotes/<remote>/HEAD`),
+/// the remote's `main`, `master`, and `develop` branches, then
+/// `default_base`. Only branches sharing history with HEAD count; when
+/// several remote branches do, the one HEAD diverged from most recently
+/// wins. Makes no network requests.
+pub async fn detect_default_base(
+    remote: &str,
+    default_base: Option<&str>,
+    verbose: bool,
+) -> Option<String> {
+    if let Some(branch) = get_cached_remote_head(remote, verbose).await {
+        let remote_branch = format!("{}/{}", remote, branch);
+        if branch_has_merge_base(&remote_branch, verbose).await {
+            if verbose {
+                eprintln!("— Base branch detection: cached {}/HEAD ref", remote);
+            }
+            return Some(remote_branch);
+        }
+    }
+
+    // Fewest commits since the merge base means the most recent divergence
+    let mut best: Option<(String, usize)> = None;
+    for name in DEFAULT_BASE_NAMES {
+        let candidate = format!("{}/{}", remote, name);
+        if !branch_has_merge_base(&candidate, verbose).await {
+            continue;
+        }
+        let Some(distance) = commits_since_merge_base(&candidate, verbose).await else {
+            continue;
+        };
+        if verbose {
+            eprintln!(
+                "— Base candidate {}: {} commit(s) since merge base",
+                candidate, distance
+            );
+        }
+        if best.as_ref().is_none_or(|(_, best)| distance < *best) {
+            best = Some((candidate, distance));
+        }
+    }
+    if let Some((branch, _)) = best {
+        if verbose {
+            eprintln!(
+                "— Base branch detection: closest of {}'s common branches",
+                remote
+            );
+        }
+        return Some(branch);
+    }
+
+    let default_base = default_base?;
+    for branch in [
+        default_base.to_string(),
+        format!("{}/{}", remote, default_base),
+    ] {
+        if branch_has_merge_base(&branch, verbose).await {
+            if verbose {
+                eprintln!("— Base branch detection: default_base from config");
+            }
+            return Some(branch);
+        }
+    }
+    None
+}
+
+/// Counts the commits on HEAD that `branch` doesn't have.
+async fn commits_since_merge_base(branch: &str, verbose: bool) -> Option<usize> {
+    let range = format!("{}..HEAD", branch);
+    let args = ["rev-list", "--count", range.as_str()];
+    let output = Command::new("git").args(args).output().await;
+    log_command(verbose, "git", &args, &output);
+    let output = output.ok().filter(|output| output.status.success())?;
+    String::from_utf8_lossy(&output.stdout).trim().parse().ok()
+}
+
+/// Query the remote directly for its default branch (works with any git remote)
+pub async fn get_remote_default_branch(remote: &str, verbose: bool) -> Option<String> {
+    let args = ["ls-remote", "--symref", remote, "HEAD"];
+    let output = Command::new("git").args(args).output().await;
+    log_command(verbose, "git", &args, &output);
+    let output = output.ok()?;
+
+    if !output.status.success() {
+        return None;
+    }
+
+    // Output format: "ref: refs/heads/main\tHEAD\n..."
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    for line in stdout.lines() {
+        if line.starts_with("ref: refs/heads/") && line.contains("HEAD") {
+            // Extract branch name between "ref: refs/heads/" and the tab
+            if let Some(rest) = line.strip_prefix("ref: refs/heads/") {
+                if let Some(branch) = rest.split('\t').next() {
+                    return Some(branch.to_string());
+                }
+            }
+        }
+    }
+    None
+}
+
+/// Checks if an 'upstream' remote exists (for fork workflows).
+pub async fn get_upstream_remote(
+    verbose: bool,
+) -> Result<Option<String>, Box<dyn std::error::Error>> {
+    // Check if 'upstream' remote exists (common fork workflow)
+    let args = ["remote", "get-url", "upstream"];
+    let output = Command::new("git").args(args).output().await;
+    log_command(verbose, "git", &args, &output);
+    let output = output?;
+
+    if output.status.success() {
         return Ok(Some("upstream".to_string()));
     }
     Ok(None)
 }
 
+/// Returns the fetch URL configured for a remote, if the remote exists.
+pub async fn get_remote_url(remote: &str, verbose: bool) -> Option<String> {
+    let args = ["remote", "get-url", remote];
+    let output = Command::new("git").args(args).output().await;
+    log_command(verbose, "git", &args, &output);
+    let output = output.ok()?;
+
+    if !output.status.success() {
+        return None;
+    }
+
+    let url = String::from_utf8_lossy(&output.stdout).trim().to_string();
+    if url.is_empty() {
+        None
+    } else {
+        Some(url)
+    }
+}
+
+/// Returns the host of a remote's URL, e.g. `gitlab.com`.
+pub async fn get_remote_host(remote: &str, verbose: bool) -> Option<String> {
+    remote_host_from_url(&get_remote_url(remote, verbose).await?)
+}
+
+/// Extracts the host from a git remote URL, without user or port.
+///
+/// Handles `https://[user@]host[:port]/path`, `ssh://git@host[:port]/path`,
+/// and scp-like `git@host:path`. Returns `None` for local paths.
+pub fn remote_host_from_url(url: &str) -> Option<String> {
+    let url = url.trim();
+    let authority = match url.split_once("://") {
+        Some((_, rest)) => rest.split('/').next()?,
+        // scp-like syntax needs a colon before any slash
+        None => match url.split_once(':') {
+            Some((authority, _)) if !authority.contains('/') => authority,
+            _ => return None,
+        },
+    };
+    let host = authority.rsplit('@').next()?;
+    let host = match host.rsplit_once(':') {
+        Some((host, port)) if port.chars().all(|c| c.is_ascii_digit()) => host,
+        _ => host,
+    };
+    if host.is_empty() {
+        None
+    } else {
+        Some(host.to_lowercase())
+    }
+}
+
 /// Returns true if the branch needs to be pushed to origin.
-pub async fn branch_needs_push(branch: &str) -> bool {
+pub async fn branch_needs_push(branch: &str, verbose: bool) -> bool {
     // Check if branch has upstream tracking
-    let output = Command::new("git")
-        .args(["rev-parse", "--abbrev-ref", &format!("{}@{{u}}", branch)])
-        .output()
-        .await;
+    let upstream = format!("{}@{{u}}", branch);
+    let args = ["rev-parse", "--abbrev-ref", upstream.as_str()];
+    let output = Command::new("git").args(args).output().await;
+    log_command(verbose, "git", &args, &output);
 
     match output {
         Ok(o) if o.status.success() => {
             // Has upstream, check if we're ahead
             let status = Command::new("git").args(["status", "-sb"]).output().await;
+            log_command(verbose, "git", &["status", "-sb"], &status);
             if let Ok(s) = status {
                 let out = String::from_utf8_lossy(&s.stdout);
                 out.contains("ahead")
@@ -448,6 +1514,73 @@ pub struct UncommittedChanges {
     pub unstaged: Vec<String>,
 }
 
+/// What a commit run has to work with, according to one status snapshot.
+#[derive(Debug, PartialEq, Eq)]
+pub enum StagedState {
+    /// No changes at all.
+    NothingToCommit,
+    /// Changes exist, but none are staged.
+    NothingStaged,
+    /// Something is staged, even if its diff looks empty (e.g. only
+    /// excluded files or mode changes).
+    Staged,
+}
+
+impl UncommittedChanges {
+    /// Parses `git status --porcelain` output.
+    pub fn parse(status: &str) -> Self {
+        let mut staged = Vec::new();
+        let mut unstaged = Vec::new();
+
+        for line in status.lines() {
+            if line.len() < 3 {
+                continue;
+            }
+            let index_status = line.chars().next().unwrap_or(' ');
+            let worktree_status = line.chars().nth(1).unwrap_or(' ');
+            let file = &line[3..];
+
+            // Staged changes (index has modifications)
+            if index_status != ' ' && index_status != '?' {
+                staged.push(format!("  {} {}", index_status, file));
+            }
+            // Unstaged changes (worktree has modifications) or untracked
+            if worktree_status != ' ' {
+                unstaged.push(format!("  {} {}", worktree_status, file));
+            }
+        }
+
+        Self { staged, unstaged }
+    }
+
+    /// Decides whether there is anything staged to commit.
+    ///
+    /// Only the status counts, never the diff: a staged change can have an
+    /// empty diff once excluded files are filtered out.
+    pub fn staged_state(&self) -> StagedState {
+        if !self.staged.is_empty() {
+            StagedState::Staged
+        } else if self.unstaged.is_empty() {
+            StagedState::NothingToCommit
+        } else {
+            StagedState::NothingStaged
+        }
+    }
+}
+
+/// Stands in for the diff when staged changes have none to show.
+pub const NO_TEXTUAL_DIFF: &str =
+    "[No textual diff: staged files are excluded from the diff or have no content changes]";
+
+/// Returns `diff`, or [`NO_TEXTUAL_DIFF`] if nothing textual survived filtering.
+pub fn diff_or_placeholder(diff: String) -> String {
+    if diff.trim().is_empty() {
+        NO_TEXTUAL_DIFF.to_string()
+    } else {
+        diff
+    }
+}
+
 /// Returns lists of staged and unstaged changes.
 pub async fn get_uncommitted_changes() -> Result<UncommittedChanges, Box<dyn std::error::Error>> {
     let output = Command::new("git")
@@ -459,41 +1592,280 @@ pub async fn get_uncommitted_changes() -> Result<UncommittedChanges, Box<dyn std
         return Err("Failed to get git status".into());
     }
 
-    let status = String::from_utf8_lossy(&output.stdout);
-    let mut staged = Vec::new();
-    let mut unstaged = Vec::new();
+    Ok(UncommittedChanges::parse(&String::from_utf8_lossy(
+        &output.stdout,
+    )))
+}
 
-    for line in status.lines() {
-        if line.len() < 3 {
-            continue;
+/// The staged changes as of one `git write-tree`.
+///
+/// The staged diff, the staged file list, and the staged half of the status
+/// are all read against the written tree rather than the live index, so
+/// staging from another terminal mid-run can't make them disagree. Unstaged
+/// files still come from a separate `git status`; they can only tell
+/// "nothing staged" from "nothing to commit" when the tree has no changes.
+pub struct IndexTree {
+    /// `HEAD`'s tree, or the empty tree before the first commit.
+    base: String,
+    /// The index, written as a tree.
+    tree: String,
+}
+
+impl IndexTree {
+    /// Writes the index as a tree. Fails while the index has conflicts.
+    pub async fn write() -> Result<Self, Box<dyn std::error::Error>> {
+        let (tree, base) = tokio::join!(
+            git_stdout(&["write-tree"]),
+            git_stdout(&["rev-parse", "--verify", "-q", "HEAD^{tree}"])
+        );
+        Ok(Self {
+            base: base.map_or_else(|_| EMPTY_TREE_SHA.to_string(), |sha| sha.trim().to_string()),
+            tree: tree?.trim().to_string(),
+        })
+    }
+
+    /// The staged diff, filtered and truncated like [`get_git_diff`].
+    pub async fn diff(&self, verbose: bool) -> Result<String, Box<dyn std::error::Error>> {
+        let diff = git_stdout(&["diff", &self.base, &self.tree]).await?;
+        let diff = sanitize_diff(&diff, verbose);
+        let filtered_diff = filter_excluded_diffs(&diff, verbose);
+        Ok(truncate_diff(&filtered_diff, verbose))
+    }
+
+    /// The staged files with their status, annotated like [`get_staged_files`].
+    pub async fn files(&self, verbose: bool) -> Result<String, Box<dyn std::error::Error>> {
+        let name_status = self.name_status().await?;
+        let (annotated, excluded_count) = annotate_excluded_files(&name_status);
+        if verbose {
+            eprintln!(
+                "— Staged files: {} total, {} excluded from diff",
+                annotated.len(),
+                excluded_count
+            );
+        }
+        Ok(annotated.join("\n"))
+    }
+
+    /// The uncommitted changes, with the staged ones taken from the tree.
+    pub async fn status(&self) -> Result<UncommittedChanges, Box<dyn std::error::Error>> {
+        let (name_status, changes) = tokio::join!(self.name_status(), get_uncommitted_changes());
+        let mut changes = changes?;
+        changes.staged = name_status?
+            .lines()
+            .filter_map(|line| line.split_once('\t'))
+            .map(|(status, path)| {
+                let status = status.chars().next().unwrap_or('M');
+                format!("  {} {}", status, path.replace('\t', " -> "))
+            })
+            .collect();
+        Ok(changes)
+    }
+
+    async fn name_status(&self) -> Result<String, Box<dyn std::error::Error>> {
+        git_stdout(&["diff", "--name-status", &self.base, &self.tree]).await
+    }
+}
+
+/// Runs a git command and returns its stdout, failing with its stderr.
+async fn git_stdout(args: &[&str]) -> Result<String, Box<dyn std::error::Error>> {
+    let output = Command::new("git").args(args).output().await?;
+    if !output.status.success() {
+        let stderr = String::from_utf8_lossy(&output.stderr);
+        return Err(format!("git {} failed: {}", args[0], stderr.trim()).into());
+    }
+    Ok(String::from_utf8_lossy(&output.stdout).into_owned())
+}
+
+/// A changed file, as offered by `--select-files`.
+pub struct ChangedFile {
+    /// Path relative to the repository root.
+    pub path: String,
+    /// True if the file has staged changes.
+    pub staged: bool,
+}
+
+/// Runs a git command that lists NUL-separated paths.
+async fn list_paths(args: &[&str]) -> Result<Vec<String>, Box<dyn std::error::Error>> {
+    let output = Command::new("git").args(args).output().await?;
+    if !output.status.success() {
+        let stderr = String::from_utf8_lossy(&output.stderr);
+        return Err(format!("git {} failed: {}", args[0], stderr.trim()).into());
+    }
+    Ok(String::from_utf8_lossy(&output.stdout)
+        .split('\0')
+        .filter(|path| !path.is_empty())
+        .map(str::to_string)
+        .collect())
+}
+
+/// Returns every changed file: staged, unstaged, and untracked.
+///
+/// Renames are listed as their two paths, so each side can be staged or
+/// unstaged on its own.
+pub async fn get_changed_files() -> Result<Vec<ChangedFile>, Box<dyn std::error::Error>> {
+    let staged = list_paths(&["diff", "--staged", "--name-only", "--no-renames", "-z"]).await?;
+    let unstaged = list_paths(&["diff", "--name-only", "--no-renames", "-z"]).await?;
+    let untracked = list_paths(&["ls-files", "--others", "--exclude-standard", "-z"]).await?;
+
+    let mut files: Vec<ChangedFile> = staged
+        .into_iter()
+        .map(|path| ChangedFile { path, staged: true })
+        .collect();
+    for path in unstaged.into_iter().chain(untracked) {
+        if !files.iter().any(|file| file.path == path) {
+            files.push(ChangedFile {
+                path,
+                staged: false,
+            });
         }
-        let index_status = line.chars().next().unwrap_or(' ');
-        let worktree_status = line.chars().nth(1).unwrap_or(' ');
-        let file = &line[3..];
+    }
+    files.sort_by(|a, b| a.path.cmp(&b.path));
+
+    Ok(files)
+}
 
-        // Staged changes (index has modifications)
-        if index_status != ' ' && index_status != '?' {
-            staged.push(format!("  {} {}", index_status, file));
+/// A multi-step git operation that's stopped partway, waiting for the user.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum GitOperation {
+    Rebase,
+    /// `git am`, which shares `rebase-apply` with the legacy rebase backend.
+    Am,
+    CherryPick,
+    Merge,
+}
+
+impl GitOperation {
+    /// The git subcommand that continues or aborts the operation.
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Self::Rebase => "rebase",
+            Self::Am => "am",
+            Self::CherryPick => "cherry-pick",
+            Self::Merge => "merge",
         }
-        // Unstaged changes (worktree has modifications) or untracked
-        if worktree_status != ' ' {
-            let status_char = if worktree_status == '?' {
-                '?'
-            } else {
-                worktree_status
-            };
-            unstaged.push(format!("  {} {}", status_char, file));
+    }
+}
+
+/// Returns the operation in progress in the repository, if any.
+///
+/// Detected from the state files git leaves in the git directory, e.g.
+/// `rebase-merge/` or `CHERRY_PICK_HEAD`. A pending revert isn't reported:
+/// committing `git revert --no-commit` is supported (see
+/// [`find_reverted_commit`]).
+pub async fn get_operation_in_progress() -> Option<GitOperation> {
+    let exists = |path: Option<PathBuf>| path.is_some_and(|path| path.exists());
+
+    if exists(get_git_path("rebase-merge").await) {
+        return Some(GitOperation::Rebase);
+    }
+    if exists(get_git_path("rebase-apply").await) {
+        return Some(if exists(get_git_path("rebase-apply/applying").await) {
+            GitOperation::Am
+        } else {
+            GitOperation::Rebase
+        });
+    }
+    for (file, operation) in [
+        ("CHERRY_PICK_HEAD", GitOperation::CherryPick),
+        ("MERGE_HEAD", GitOperation::Merge),
+    ] {
+        if exists(get_git_path(file).await) {
+            return Some(operation);
         }
     }
+    None
+}
+
+/// How the current branch relates to its upstream tracking branch.
+pub enum UpstreamState {
+    /// `HEAD` is not on a branch.
+    Detached,
+    /// The branch has no upstream configured.
+    NoUpstream,
+    /// The branch tracks `upstream`, which has `behind` commits not in `HEAD`.
+    Tracking { upstream: String, behind: usize },
+}
+
+/// Compares `HEAD` with its upstream using local tracking refs only.
+pub async fn get_upstream_state() -> UpstreamState {
+    let branch = Command::new("git")
+        .args(["symbolic-ref", "-q", "HEAD"])
+        .output()
+        .await;
+    if !branch.is_ok_and(|output| output.status.success()) {
+        return UpstreamState::Detached;
+    }
+
+    let upstream = Command::new("git")
+        .args([
+            "rev-parse",
+            "--abbrev-ref",
+            "--symbolic-full-name",
+            "@{upstream}",
+        ])
+        .output()
+        .await;
+    let upstream = match upstream {
+        Ok(output) if output.status.success() => {
+            String::from_utf8_lossy(&output.stdout).trim().to_string()
+        }
+        _ => return UpstreamState::NoUpstream,
+    };
+
+    let behind = Command::new("git")
+        .args(["rev-list", "--count", "HEAD..@{upstream}"])
+        .output()
+        .await
+        .ok()
+        .filter(|output| output.status.success())
+        .and_then(|output| String::from_utf8_lossy(&output.stdout).trim().parse().ok())
+        .unwrap_or(0);
+
+    UpstreamState::Tracking { upstream, behind }
+}
+
+/// Fetches the current branch's default remote with a progress spinner.
+pub async fn fetch_with_spinner() -> Result<(), Box<dyn std::error::Error>> {
+    let term = Term::stdout();
+    let _ = term.hide_cursor();
+
+    let spinner = ProgressBar::new_spinner();
+    spinner.set_style(
+        ProgressStyle::default_spinner()
+            .tick_strings(&["⠋", "⠙", "⠹", "⠸", "⠼", "⠴", "⠦", "⠧", "⠇", "⠏"])
+            .template("{spinner:.cyan} Fetching upstream...")
+            .unwrap(),
+    );
+    spinner.enable_steady_tick(std::time::Duration::from_millis(80));
 
-    Ok(UncommittedChanges { staged, unstaged })
+    let output = Command::new("git")
+        .args(["fetch", "--quiet"])
+        .output()
+        .await?;
+
+    spinner.finish_and_clear();
+    let _ = term.show_cursor();
+
+    if !output.status.success() {
+        let stderr = String::from_utf8_lossy(&output.stderr);
+        return Err(format!("git fetch failed: {}", stderr.trim()).into());
+    }
+
+    Ok(())
 }
 
-/// Pushes the branch to origin with a progress spinner.
+/// Pushes the branch to the given remote with a progress spinner.
 ///
 /// Skips if branch is already up-to-date with upstream.
-pub async fn push_branch_with_spinner(branch: &str) -> Result<(), Box<dyn std::error::Error>> {
-    if !branch_needs_push(branch).await {
+pub async fn push_branch_with_spinner(
+    branch: &str,
+    remote: &str,
+    verbose: bool,
+) -> Result<(), Box<dyn std::error::Error>> {
+    if !branch_needs_push(branch, verbose).await {
+        if verbose {
+            eprintln!("— Branch is up to date with its upstream; not pushing");
+        }
         return Ok(());
     }
 
@@ -504,25 +1876,28 @@ pub async fn push_branch_with_spinner(branch: &str) -> Result<(), Box<dyn std::e
     spinner.set_style(
         ProgressStyle::default_spinner()
             .tick_strings(&["⠋", "⠙", "⠹", "⠸", "⠼", "⠴", "⠦", "⠧", "⠇", "⠏"])
-            .template("{spinner:.cyan} Pushing branch to origin...")
+            .template(&format!(
+                "{{spinner:.cyan}} Pushing branch to {}...",
+                remote
+            ))
             .unwrap(),
     );
     spinner.enable_steady_tick(std::time::Duration::from_millis(80));
 
-    let push_output = Command::new("git")
-        .args(["push", "-u", "origin", branch])
-        .output()
-        .await?;
+    let args = ["push", "-u", remote, branch];
+    let push_output = Command::new("git").args(args).output().await;
 
     spinner.finish_and_clear();
     let _ = term.show_cursor();
+    log_command(verbose, "git", &args, &push_output);
+    let push_output = push_output?;
 
     if !push_output.status.success() {
         let stderr = String::from_utf8_lossy(&push_output.stderr);
         return Err(format!("Failed to push branch: {}", stderr).into());
     }
 
-    println!("{} Pushed branch to origin", style("✓").green());
+    println!("{} Pushed branch to {}", style("✓").green(), remote);
     Ok(())
 }
 
@@ -531,27 +1906,32 @@ pub async fn get_branch_diff(
     base: &str,
     verbose: bool,
 ) -> Result<String, Box<dyn std::error::Error>> {
-    let output = Command::new("git")
-        .args(["diff", &format!("{}...HEAD", base)])
-        .output()
-        .await?;
+    let range = format!("{}...HEAD", base);
+    let args = ["diff", range.as_str()];
+    let output = Command::new("git").args(args).output().await;
+    log_command(verbose, "git", &args, &output);
+    let output = output?;
 
     if !output.status.success() {
         let stderr = String::from_utf8_lossy(&output.stderr);
         return Err(format!("git diff failed: {}", stderr).into());
     }
 
-    let diff = String::from_utf8_lossy(&output.stdout).to_string();
+    let diff = sanitize_diff(&String::from_utf8_lossy(&output.stdout), verbose);
     let filtered_diff = filter_excluded_diffs(&diff, verbose);
     Ok(truncate_diff(&filtered_diff, verbose))
 }
 
 /// Returns commit subjects between base branch and HEAD.
-pub async fn get_branch_commits(base: &str) -> Result<Vec<String>, Box<dyn std::error::Error>> {
-    let output = Command::new("git")
-        .args(["log", &format!("{}..HEAD", base), "--format=%s"])
-        .output()
-        .await?;
+pub async fn get_branch_commits(
+    base: &str,
+    verbose: bool,
+) -> Result<Vec<String>, Box<dyn std::error::Error>> {
+    let range = format!("{}..HEAD", base);
+    let args = ["log", range.as_str(), "--format=%s"];
+    let output = Command::new("git").args(args).output().await;
+    log_command(verbose, "git", &args, &output);
+    let output = output?;
 
     if !output.status.success() {
         let stderr = String::from_utf8_lossy(&output.stderr);
@@ -567,15 +1947,82 @@ pub async fn get_branch_commits(base: &str) -> Result<Vec<String>, Box<dyn std::
     Ok(commits)
 }
 
+/// Returns the full messages of the non-merge commits since `base`, oldest first.
+pub async fn get_branch_commit_messages(
+    base: &str,
+    verbose: bool,
+) -> Result<Vec<String>, Box<dyn std::error::Error>> {
+    let range = format!("{}..HEAD", base);
+    let args = [
+        "log",
+        range.as_str(),
+        "--no-merges",
+        "--reverse",
+        "--format=%B%x00",
+    ];
+    let output = Command::new("git").args(args).output().await;
+    log_command(verbose, "git", &args, &output);
+    let output = output?;
+
+    if !output.status.success() {
+        let stderr = String::from_utf8_lossy(&output.stderr);
+        return Err(format!("git log failed: {}", stderr).into());
+    }
+
+    Ok(String::from_utf8_lossy(&output.stdout)
+        .split('\0')
+        .map(|message| message.trim().to_string())
+        .filter(|message| !message.is_empty())
+        .collect())
+}
+
+/// Returns the paths a PR would touch: changed since `base` plus staged.
+///
+/// With no `base`, only staged paths are returned.
+pub async fn get_changed_paths(base: Option<&str>, verbose: bool) -> Vec<String> {
+    let mut commands = vec![vec![
+        "diff".to_string(),
+        "--cached".to_string(),
+        "--name-only".to_string(),
+    ]];
+    if let Some(base) = base {
+        commands.push(vec![
+            "diff".to_string(),
+            "--name-only".to_string(),
+            format!("{}...HEAD", base),
+        ]);
+    }
+
+    let mut paths: Vec<String> = Vec::new();
+    for args in commands {
+        let output = Command::new("git").args(&args).output().await;
+        log_command(verbose, "git", &args, &output);
+        let Ok(output) = output else {
+            continue;
+        };
+        if !output.status.success() {
+            continue;
+        }
+        for path in String::from_utf8_lossy(&output.stdout).lines() {
+            if !path.is_empty() && !paths.iter().any(|p| p == path) {
+                paths.push(path.to_string());
+            }
+        }
+    }
+
+    paths
+}
+
 /// Returns files changed between base branch and HEAD with status.
 pub async fn get_pr_changed_files(
     base: &str,
     verbose: bool,
 ) -> Result<String, Box<dyn std::error::Error>> {
-    let output = Command::new("git")
-        .args(["diff", "--name-status", &format!("{}...HEAD", base)])
-        .output()
-        .await?;
+    let range = format!("{}...HEAD", base);
+    let args = ["diff", "--name-status", range.as_str()];
+    let output = Command::new("git").args(args).output().await;
+    log_command(verbose, "git", &args, &output);
+    let output = output?;
 
     if !output.status.success() {
         let stderr = String::from_utf8_lossy(&output.stderr);
@@ -583,25 +2030,7 @@ pub async fn get_pr_changed_files(
     }
 
     let raw_output = String::from_utf8_lossy(&output.stdout).to_string();
-    let mut excluded_count = 0;
-
-    let annotated: Vec<String> = raw_output
-        .lines()
-        .map(|line| {
-            let parts: Vec<&str> = line.splitn(2, '\t').collect();
-            if parts.len() == 2 {
-                let filename = parts[1];
-                if should_exclude_from_diff(filename) {
-                    excluded_count += 1;
-                    format!("{}\t{} [excluded from diff]", parts[0], filename)
-                } else {
-                    line.to_string()
-                }
-            } else {
-                line.to_string()
-            }
-        })
-        .collect();
+    let (annotated, excluded_count) = annotate_excluded_files(&raw_output);
 
     if verbose && excluded_count > 0 {
         eprintln!(
@@ -613,3 +2042,92 @@ pub async fn get_pr_changed_files(
 
     Ok(annotated.join("\n"))
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn truncate_diff_cuts_multibyte_text_on_char_boundaries() {
+        // 151 bytes to keep at each end falls inside a two-byte 'é'
+        let diff = "é".repeat(1000);
+        let truncated = truncate_diff_to(&diff, 302, false);
+        let (start, rest) = truncated.split_once("\n\n[... ").unwrap();
+        let (note, end) = rest.split_once(" ...]\n\n").unwrap();
+        assert_eq!(start, "é".repeat(75));
+        assert_eq!(end, "é".repeat(75));
+        assert_eq!(note, "1700 characters truncated");
+    }
+
+    #[test]
+    fn truncate_diff_never_splits_a_character() {
+        let diff = "日本🦀".repeat(200);
+        for max_chars in 200..240 {
+            let truncated = truncate_diff_to(&diff, max_chars, false);
+            let (start, _) = truncated.split_once("\n\n[... ").unwrap();
+            assert!(start.len() <= max_chars / 2);
+            assert!(diff.starts_with(start));
+        }
+    }
+
+    #[test]
+    fn staged_changes_with_an_empty_diff_are_still_staged() {
+        // Only a lock file staged; the diff filter drops it, leaving no text
+        let status = UncommittedChanges::parse("M  Cargo.lock\n?? notes.txt\n");
+        assert_eq!(status.staged, ["  M Cargo.lock"]);
+        assert_eq!(status.staged_state(), StagedState::Staged);
+        assert_eq!(diff_or_placeholder(" \n".to_string()), NO_TEXTUAL_DIFF);
+    }
+
+    #[test]
+    fn staged_state_without_staged_changes() {
+        assert_eq!(
+            UncommittedChanges::parse("").staged_state(),
+            StagedState::NothingToCommit
+        );
+        assert_eq!(
+            UncommittedChanges::parse(" M src/lib.rs\n?? new.rs\n").staged_state(),
+            StagedState::NothingStaged
+        );
+    }
+
+    #[test]
+    fn diff_or_placeholder_keeps_a_real_diff() {
+        let diff = "diff --git a/x b/x\n".to_string();
+        assert_eq!(diff_or_placeholder(diff.clone()), diff);
+    }
+
+    #[test]
+    fn truncate_diff_leaves_short_diffs_alone() {
+        assert_eq!(truncate_diff_to("é", 2, false), "é");
+    }
+
+    #[tokio::test]
+    async fn submodule_subject_resolves_from_a_subdirectory() {
+        let submodule = crate::test_support::TestRepo::new("submodule-src");
+        submodule.write("b.txt", "two\n");
+        submodule.git(&["add", "b.txt"]);
+        submodule.commit("feat: add b");
+        let sha = submodule.git(&["rev-parse", "HEAD"]);
+
+        let repo = crate::test_support::TestRepo::new("submodule-parent");
+        repo.git(&[
+            "-c",
+            "protocol.file.allow=always",
+            "submodule",
+            "add",
+            "-q",
+            submodule.path().to_str().unwrap(),
+            "vendor/sub",
+        ]);
+        let subdir = repo.write("docs/readme.md", "docs\n");
+        let subdir = subdir.parent().unwrap();
+
+        assert_eq!(
+            submodule_commit_subject_from(subdir, "vendor/sub", &sha)
+                .await
+                .as_deref(),
+            Some("feat: add b")
+        );
+    }
+}
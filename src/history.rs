@@ -0,0 +1,158 @@
+//! History of generated commit messages.
+//!
+//! When the `history` config option is enabled, each generated message is
+//! appended to a JSONL file next to the config file, together with the model
+//! used and what the user did with it (accepted, edited, or cancelled).
+//!
+//! The file is capped at [`MAX_HISTORY_BYTES`]; when it grows past the cap
+//! the oldest entries are dropped. View recent entries with
+//! `committer history`.
+
+use serde::{Deserialize, Serialize};
+use std::io::Write;
+use std::path::PathBuf;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use crate::config::{config_dir, Config};
+use crate::git::get_head_sha;
+
+/// Maximum size of the history file before old entries are dropped.
+pub const MAX_HISTORY_BYTES: u64 = 512 * 1024;
+
+/// What the user did with a generated message.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Outcome {
+    /// Committed exactly as generated.
+    Accepted,
+    /// Committed after editing.
+    Edited,
+    /// Not committed.
+    Cancelled,
+}
+
+impl Outcome {
+    /// Returns the lowercase label used in the history file.
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Outcome::Accepted => "accepted",
+            Outcome::Edited => "edited",
+            Outcome::Cancelled => "cancelled",
+        }
+    }
+}
+
+/// A single recorded generation.
+#[derive(Debug, Serialize, Deserialize)]
+pub struct HistoryEntry {
+    /// Seconds since the Unix epoch.
+    pub timestamp: u64,
+    /// Model that generated the message.
+    pub model: String,
+    /// Final message (as committed, or as generated if cancelled).
+    pub message: String,
+    /// What the user did with the message.
+    pub outcome: Outcome,
+    /// Commit SHA, if the message was committed.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub sha: Option<String>,
+}
+
+/// Returns the path to the history file.
+pub fn history_path() -> PathBuf {
+    config_dir().join("history.jsonl")
+}
+
+/// Records a generated message if history is enabled in config.
+///
+/// Looks up the new `HEAD` SHA for committed outcomes. Failures are reported
+/// as warnings and never abort the commit flow.
+pub async fn record_history(config: &Config, model: &str, message: &str, outcome: Outcome) {
+    if !config.history {
+        return;
+    }
+
+    let sha = match outcome {
+        Outcome::Cancelled => None,
+        _ => get_head_sha().await,
+    };
+
+    let entry = HistoryEntry {
+        timestamp: SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map(|d| d.as_secs())
+            .unwrap_or(0),
+        model: model.to_string(),
+        message: message.to_string(),
+        outcome,
+        sha,
+    };
+
+    if let Err(e) = append_history(&entry) {
+        eprintln!("— Failed to write history: {}", e);
+    }
+}
+
+/// Appends an entry to the history file, enforcing [`MAX_HISTORY_BYTES`].
+pub fn append_history(entry: &HistoryEntry) -> Result<(), Box<dyn std::error::Error>> {
+    let path = history_path();
+    if let Some(parent) = path.parent() {
+        std::fs::create_dir_all(parent)?;
+    }
+
+    let mut file = std::fs::OpenOptions::new()
+        .create(true)
+        .append(true)
+        .open(&path)?;
+    writeln!(file, "{}", serde_json::to_string(entry)?)?;
+
+    if file.metadata()?.len() > MAX_HISTORY_BYTES {
+        // Keep the newest half so trimming doesn't run on every append
+        let contents = std::fs::read_to_string(&path)?;
+        let lines: Vec<&str> = contents.lines().collect();
+        let keep = &lines[lines.len() / 2..];
+        std::fs::write(&path, format!("{}\n", keep.join("\n")))?;
+    }
+
+    Ok(())
+}
+
+/// Loads the most recent `limit` entries, oldest first.
+///
+/// Malformed lines are skipped.
+pub fn load_history(limit: usize) -> Vec<HistoryEntry> {
+    let contents = std::fs::read_to_string(history_path()).unwrap_or_default();
+    let entries: Vec<HistoryEntry> = contents
+        .lines()
+        .filter_map(|line| serde_json::from_str(line).ok())
+        .collect();
+
+    let skip = entries.len().saturating_sub(limit);
+    entries.into_iter().skip(skip).collect()
+}
+
+/// Formats a Unix timestamp as `YYYY-MM-DD HH:MM` (UTC).
+pub fn format_timestamp(secs: u64) -> String {
+    let days = (secs / 86_400) as i64;
+    let minutes_of_day = (secs % 86_400) / 60;
+
+    // Civil-from-days conversion (proleptic Gregorian calendar)
+    let z = days + 719_468;
+    let era = z.div_euclid(146_097);
+    let doe = z.rem_euclid(146_097);
+    let yoe = (doe - doe / 1460 + doe / 36_524 - doe / 146_096) / 365;
+    let doy = doe - (365 * yoe + yoe / 4 - yoe / 100);
+    let mp = (5 * doy + 2) / 153;
+    let day = doy - (153 * mp + 2) / 5 + 1;
+    let month = if mp < 10 { mp + 3 } else { mp - 9 };
+    let year = yoe + era * 400 + i64::from(month <= 2);
+
+    format!(
+        "{:04}-{:02}-{:02} {:02}:{:02}",
+        year,
+        month,
+        day,
+        minutes_of_day / 60,
+        minutes_of_day % 60
+    )
+}
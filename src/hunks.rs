@@ -0,0 +1,367 @@
+//! Partial staging for `--hunk-filter` and `--select-files`.
+//!
+//! For `--hunk-filter`, the working-tree diff is split into hunks, and every
+//! hunk with an added or removed line matching the pattern is applied to the
+//! index with `git apply --cached`. Changes that were already staged stay
+//! staged.
+//!
+//! For `--select-files`, [`stage_selected_files`] stages exactly the chosen
+//! files and unstages every other changed file.
+//!
+//! An [`IndexSnapshot`] is taken before anything is applied. If the run ends
+//! without creating a commit (cancel, dry run, or error), dropping the
+//! snapshot puts the index back exactly as it was.
+
+use regex_lite::Regex;
+use std::path::{Path, PathBuf};
+use std::process::Stdio;
+use tokio::io::AsyncWriteExt;
+use tokio::process::Command;
+
+use crate::git::ChangedFile;
+
+/// The diff of a single file, split into its header and hunks.
+pub struct FileDiff {
+    /// Lines from `diff --git` up to the first hunk.
+    pub header: String,
+    /// Each hunk, starting with its `@@` line.
+    pub hunks: Vec<String>,
+}
+
+/// Splits a unified diff into per-file headers and hunks.
+pub fn parse_file_diffs(diff: &str) -> Vec<FileDiff> {
+    let mut files: Vec<FileDiff> = Vec::new();
+
+    for line in diff.split_inclusive('\n') {
+        if line.starts_with("diff --git ") {
+            files.push(FileDiff {
+                header: line.to_string(),
+                hunks: Vec::new(),
+            });
+        } else if let Some(file) = files.last_mut() {
+            if line.starts_with("@@") {
+                file.hunks.push(line.to_string());
+            } else if let Some(hunk) = file.hunks.last_mut() {
+                hunk.push_str(line);
+            } else {
+                file.header.push_str(line);
+            }
+        }
+    }
+
+    files
+}
+
+/// Returns true if an added or removed line in the hunk matches the pattern.
+fn hunk_matches(hunk: &str, pattern: &Regex) -> bool {
+    hunk.lines().skip(1).any(|line| {
+        (line.starts_with('+') || line.starts_with('-')) && pattern.is_match(&line[1..])
+    })
+}
+
+/// Builds a patch containing only the hunks that match `pattern`.
+///
+/// Returns the patch and the number of hunks selected. Files without a
+/// matching hunk (including binary and mode-only changes) are left out.
+pub fn filter_hunks(diff: &str, pattern: &Regex) -> (String, usize) {
+    let mut patch = String::new();
+    let mut selected = 0;
+
+    for file in parse_file_diffs(diff) {
+        let hunks: Vec<&String> = file
+            .hunks
+            .iter()
+            .filter(|hunk| hunk_matches(hunk, pattern))
+            .collect();
+        if hunks.is_empty() {
+            continue;
+        }
+
+        selected += hunks.len();
+        patch.push_str(&file.header);
+        for hunk in hunks {
+            patch.push_str(hunk);
+        }
+    }
+
+    (patch, selected)
+}
+
+/// The index as it was before `--hunk-filter` or `--select-files` staged anything.
+///
+/// Restores the index on drop unless `HEAD` moved, i.e. unless a commit
+/// consumed the staged hunks.
+///
+/// `std::process::exit` skips the restore, so drop the snapshot explicitly
+/// before exiting.
+pub struct IndexSnapshot {
+    /// Repository the snapshot was taken in.
+    dir: PathBuf,
+    tree: String,
+    head: Option<String>,
+}
+
+/// Returns the commit `HEAD` points at in `dir`, or `None` before the first commit.
+fn head_in(dir: &Path) -> Option<String> {
+    std::process::Command::new("git")
+        .arg("-C")
+        .arg(dir)
+        .args(["rev-parse", "--verify", "-q", "HEAD"])
+        .output()
+        .ok()
+        .filter(|output| output.status.success())
+        .map(|output| String::from_utf8_lossy(&output.stdout).trim().to_string())
+}
+
+impl IndexSnapshot {
+    /// Records the current index as a tree object.
+    pub async fn take() -> Result<Self, Box<dyn std::error::Error>> {
+        Self::take_in(Path::new(".")).await
+    }
+
+    /// Records the index of the repository at `dir` as a tree object.
+    pub async fn take_in(dir: &Path) -> Result<Self, Box<dyn std::error::Error>> {
+        let output = Command::new("git")
+            .arg("-C")
+            .arg(dir)
+            .arg("write-tree")
+            .output()
+            .await?;
+        if !output.status.success() {
+            let stderr = String::from_utf8_lossy(&output.stderr);
+            return Err(format!("Failed to snapshot the index: {}", stderr.trim()).into());
+        }
+
+        Ok(Self {
+            dir: dir.to_path_buf(),
+            tree: String::from_utf8_lossy(&output.stdout).trim().to_string(),
+            head: head_in(dir),
+        })
+    }
+}
+
+impl Drop for IndexSnapshot {
+    fn drop(&mut self) {
+        // Runs during unwinding and early returns, so this has to be synchronous
+        if head_in(&self.dir) != self.head {
+            return;
+        }
+
+        let restored = std::process::Command::new("git")
+            .arg("-C")
+            .arg(&self.dir)
+            .args(["read-tree", &self.tree])
+            .status()
+            .is_ok_and(|status| status.success());
+        if !restored {
+            eprintln!(
+                "— Failed to restore the index; recover it with: git read-tree {}",
+                self.tree
+            );
+        }
+    }
+}
+
+/// Applies a patch to the index only, leaving the working tree untouched.
+async fn apply_to_index(patch: &str) -> Result<(), Box<dyn std::error::Error>> {
+    let mut child = Command::new("git")
+        .args(["apply", "--cached", "--whitespace=nowarn", "-"])
+        .stdin(Stdio::piped())
+        .stdout(Stdio::null())
+        .stderr(Stdio::piped())
+        .spawn()?;
+
+    if let Some(mut stdin) = child.stdin.take() {
+        stdin.write_all(patch.as_bytes()).await?;
+    }
+
+    let output = child.wait_with_output().await?;
+    if !output.status.success() {
+        let stderr = String::from_utf8_lossy(&output.stderr);
+        return Err(format!("git apply failed: {}", stderr.trim()).into());
+    }
+
+    Ok(())
+}
+
+/// Stages the working-tree hunks whose changed lines match `pattern`.
+///
+/// Returns the snapshot to restore from if the run doesn't commit. Fails
+/// without touching the index if the pattern is invalid or nothing matches.
+pub async fn stage_matching_hunks(
+    pattern: &str,
+    verbose: bool,
+) -> Result<IndexSnapshot, Box<dyn std::error::Error>> {
+    let pattern =
+        Regex::new(pattern).map_err(|e| format!("Invalid --hunk-filter pattern: {}", e))?;
+
+    // Fixed prefixes so `diff.noprefix` or external diff tools can't break the patch
+    let output = Command::new("git")
+        .args([
+            "diff",
+            "--no-color",
+            "--no-ext-diff",
+            "--src-prefix=a/",
+            "--dst-prefix=b/",
+        ])
+        .output()
+        .await?;
+    if !output.status.success() {
+        let stderr = String::from_utf8_lossy(&output.stderr);
+        return Err(format!("git diff failed: {}", stderr.trim()).into());
+    }
+    let diff = String::from_utf8_lossy(&output.stdout);
+
+    let (patch, selected) = filter_hunks(&diff, &pattern);
+    if selected == 0 {
+        return Err(format!("No unstaged hunks match '{}'", pattern.as_str()).into());
+    }
+
+    if verbose {
+        eprintln!("— Staging {} matching hunk(s):", selected);
+        for file in parse_file_diffs(&patch) {
+            for hunk in &file.hunks {
+                eprintln!(
+                    "    {} {}",
+                    file.header.lines().next().unwrap_or_default(),
+                    hunk.lines().next().unwrap_or_default()
+                );
+            }
+        }
+    }
+
+    let snapshot = IndexSnapshot::take().await?;
+    apply_to_index(&patch).await?;
+
+    Ok(snapshot)
+}
+
+/// Runs `git <args> -- <paths>`, doing nothing when `paths` is empty.
+async fn run_on_paths(args: &[&str], paths: &[&str]) -> Result<(), Box<dyn std::error::Error>> {
+    if paths.is_empty() {
+        return Ok(());
+    }
+
+    let output = Command::new("git")
+        .args(args)
+        .arg("--")
+        .args(paths)
+        .output()
+        .await?;
+    if !output.status.success() {
+        let stderr = String::from_utf8_lossy(&output.stderr);
+        return Err(format!("git {} failed: {}", args[0], stderr.trim()).into());
+    }
+
+    Ok(())
+}
+
+/// Stages every change in `selected` and unstages the rest of `files`.
+///
+/// Returns the snapshot to restore from if the run doesn't commit.
+pub async fn stage_selected_files(
+    files: &[ChangedFile],
+    selected: &[usize],
+    verbose: bool,
+) -> Result<IndexSnapshot, Box<dyn std::error::Error>> {
+    let (chosen, rest): (Vec<_>, Vec<_>) = files
+        .iter()
+        .enumerate()
+        .partition(|(i, _)| selected.contains(i));
+    let chosen: Vec<&str> = chosen.iter().map(|(_, file)| file.path.as_str()).collect();
+    let unstaged: Vec<&str> = rest
+        .iter()
+        .filter(|(_, file)| file.staged)
+        .map(|(_, file)| file.path.as_str())
+        .collect();
+
+    if verbose {
+        eprintln!(
+            "— Staging {} file(s), unstaging {}",
+            chosen.len(),
+            unstaged.len()
+        );
+    }
+
+    let snapshot = IndexSnapshot::take().await?;
+    run_on_paths(&["reset", "-q"], &unstaged).await?;
+    run_on_paths(&["add", "-A"], &chosen).await?;
+
+    Ok(snapshot)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_support::TestRepo;
+
+    const DIFF: &str = "\
+diff --git a/src/lib.rs b/src/lib.rs
+index 1111111..2222222 100644
+--- a/src/lib.rs
++++ b/src/lib.rs
+@@ -1,3 +1,3 @@
+ fn a() {
+-    old_a();
++    new_a();
+ }
+@@ -10,3 +10,3 @@
+ fn b() {
+-    old_b();
++    logging::debug();
+ }
+diff --git a/README.md b/README.md
+index 3333333..4444444 100644
+--- a/README.md
++++ b/README.md
+@@ -1 +1 @@
+-Old title
++New title
+";
+
+    #[test]
+    fn filter_hunks_keeps_only_matching_hunks_and_their_headers() {
+        let (patch, selected) = filter_hunks(DIFF, &Regex::new("logging").unwrap());
+        assert_eq!(selected, 1);
+        assert!(patch.starts_with("diff --git a/src/lib.rs b/src/lib.rs\n"));
+        assert!(patch.contains("+    logging::debug();"));
+        assert!(!patch.contains("new_a"));
+        assert!(!patch.contains("README.md"));
+    }
+
+    #[test]
+    fn filter_hunks_ignores_context_lines() {
+        let (patch, selected) = filter_hunks(DIFF, &Regex::new("fn a").unwrap());
+        assert_eq!(selected, 0);
+        assert!(patch.is_empty());
+    }
+
+    #[tokio::test]
+    async fn dropping_the_snapshot_restores_the_index() {
+        let repo = TestRepo::new("restore");
+        repo.write("a.txt", "two\n");
+        repo.git(&["add", "a.txt"]);
+
+        let snapshot = IndexSnapshot::take_in(repo.path()).await.unwrap();
+        repo.write("b.txt", "new\n");
+        repo.git(&["add", "b.txt"]);
+        repo.git(&["reset", "-q", "--", "a.txt"]);
+        assert_eq!(repo.staged(), "b.txt");
+
+        drop(snapshot);
+        assert_eq!(repo.staged(), "a.txt");
+    }
+
+    #[tokio::test]
+    async fn a_commit_keeps_the_snapshot_from_restoring() {
+        let repo = TestRepo::new("commit");
+        let snapshot = IndexSnapshot::take_in(repo.path()).await.unwrap();
+        repo.write("b.txt", "new\n");
+        repo.git(&["add", "b.txt"]);
+        repo.commit("add b");
+
+        drop(snapshot);
+        assert_eq!(repo.staged(), "");
+        assert_eq!(repo.git(&["ls-files"]), "a.txt\nb.txt");
+    }
+}
@@ -0,0 +1,292 @@
+//! Resolving `Name <email>` identities for trailers.
+//!
+//! Trailers that name a person (e.g. `Signed-off-by`) need a full
+//! `Name <email>`, but the input is often partial: just a name, just an
+//! email, or a git identity with only one of `user.name`/`user.email` set.
+//! [`resolve_identity`] fills the gaps where it can:
+//!
+//! - From `user.name`/`user.email` when the given part is the user's own
+//! - Otherwise from the most recent commit by a matching author, the way
+//!   `git commit --author=<pattern>` does
+//!
+//! Errors name what's missing, and only occur when no full identity can be
+//! formed. Input that's malformed rather than partial (an email without `@`,
+//! unbalanced angle brackets) is rejected up front.
+
+use tokio::process::Command;
+
+use crate::git::get_git_config;
+
+/// A possibly incomplete `Name <email>` identity.
+#[derive(Debug, Default, Clone, PartialEq, Eq)]
+pub struct Identity {
+    /// Display name, e.g. `Alice Smith`.
+    pub name: Option<String>,
+    /// Email address, without the angle brackets.
+    pub email: Option<String>,
+}
+
+impl Identity {
+    /// Parses `Name <email>`, `<email>`, a bare email, or a bare name.
+    pub fn parse(input: &str) -> Self {
+        let non_empty = |part: &str| {
+            let part = part.trim();
+            (!part.is_empty()).then(|| part.to_string())
+        };
+
+        let input = input.trim();
+        if let Some((name, rest)) = input.split_once('<') {
+            let email = rest.trim_end();
+            let email = email.strip_suffix('>').unwrap_or(email);
+            return Self {
+                name: non_empty(name),
+                email: non_empty(email),
+            };
+        }
+        if input.contains('@') && !input.contains(char::is_whitespace) {
+            Self {
+                name: None,
+                email: non_empty(input),
+            }
+        } else {
+            Self {
+                name: non_empty(input),
+                email: None,
+            }
+        }
+    }
+
+    /// Returns `Name <email>` if both parts are known.
+    pub fn complete(&self) -> Option<String> {
+        match (&self.name, &self.email) {
+            (Some(name), Some(email)) => Some(format!("{} <{}>", name, email)),
+            _ => None,
+        }
+    }
+
+    /// Names the missing parts, e.g. `an email`.
+    fn missing(&self) -> &'static str {
+        match (&self.name, &self.email) {
+            (None, None) => "a name and an email",
+            (None, Some(_)) => "a name",
+            (Some(_), None) => "an email",
+            (Some(_), Some(_)) => "nothing",
+        }
+    }
+}
+
+/// Reads the user's identity from `user.name` and `user.email`.
+async fn own_identity() -> Identity {
+    Identity {
+        name: get_git_config("user.name").await,
+        email: get_git_config("user.email").await,
+    }
+}
+
+/// Returns the user's own identity as `Name <email>`.
+///
+/// If only one of `user.name`/`user.email` is set, the other is taken from
+/// the user's most recent commit. `purpose` (e.g. "Sign-off") starts the
+/// error, which names the unset config keys.
+pub async fn require_own_identity(purpose: &str) -> Result<String, String> {
+    let own = own_identity().await;
+    if let Some(complete) = own.complete() {
+        return Ok(complete);
+    }
+    if let Some(given) = own.name.as_deref().or(own.email.as_deref()) {
+        if let Ok(identity) = resolve_identity(given).await {
+            return Ok(identity);
+        }
+    }
+
+    let unset: Vec<&str> = [
+        ("user.name", own.name.is_none()),
+        ("user.email", own.email.is_none()),
+    ]
+    .iter()
+    .filter(|(_, unset)| *unset)
+    .map(|(key, _)| *key)
+    .collect();
+    Err(format!(
+        "{} requires git {} to be configured",
+        purpose,
+        unset.join(" and ")
+    ))
+}
+
+/// Completes a possibly partial identity such as `alice` or `alice@example.com`.
+///
+/// A full `Name <email>` is returned as given. A part matching the user's own
+/// name or email is completed from git config; anything else is looked up in
+/// the history, taking the most recent matching commit author.
+pub async fn resolve_identity(input: &str) -> Result<String, String> {
+    if let Some(problem) = malformed(input) {
+        return Err(format!(
+            "'{}' is not a valid 'Name <email>': {}",
+            input.trim(),
+            problem
+        ));
+    }
+    let identity = Identity::parse(input);
+    if let Some(complete) = identity.complete() {
+        return Ok(complete);
+    }
+    let Some(given) = identity.name.clone().or_else(|| identity.email.clone()) else {
+        return Err("An identity needs a name and an email, e.g. 'Name <email>'".to_string());
+    };
+
+    if let Some(complete) = complete_from_own(&identity, &own_identity().await) {
+        return Ok(complete);
+    }
+
+    if let Some(author) = find_author(&given).await {
+        return Ok(author);
+    }
+    Err(format!(
+        "Can't resolve '{}' to 'Name <email>': {} is missing and no commit author matches",
+        input.trim(),
+        identity.missing()
+    ))
+}
+
+/// Completes `identity` from `own` if the part it has is the user's own.
+fn complete_from_own(identity: &Identity, own: &Identity) -> Option<String> {
+    let matches = |given: &Option<String>, own: &Option<String>| {
+        given
+            .as_deref()
+            .zip(own.as_deref())
+            .is_some_and(|(given, own)| given.eq_ignore_ascii_case(own))
+    };
+    if !matches(&identity.name, &own.name) && !matches(&identity.email, &own.email) {
+        return None;
+    }
+    Identity {
+        name: identity.name.clone().or_else(|| own.name.clone()),
+        email: identity.email.clone().or_else(|| own.email.clone()),
+    }
+    .complete()
+}
+
+/// Describes what's wrong with an identity that can't be parsed, if anything.
+fn malformed(input: &str) -> Option<&'static str> {
+    let input = input.trim();
+    let opens = input.matches('<').count();
+    let closes = input.matches('>').count();
+    if opens > 1 || closes > 1 || opens != closes {
+        return Some("unbalanced angle brackets");
+    }
+    if opens == 1 {
+        let (_, email) = input.split_once('<')?;
+        let Some(email) = email.strip_suffix('>') else {
+            return Some("text after the closing '>'");
+        };
+        return if email.trim().is_empty() {
+            Some("the email between '<' and '>' is empty")
+        } else if !email.contains('@') || email.contains(char::is_whitespace) {
+            Some("the email must look like user@host")
+        } else {
+            None
+        };
+    }
+    if input.contains('@') && input.contains(char::is_whitespace) {
+        return Some("put the email in angle brackets");
+    }
+    None
+}
+
+/// Returns the author of the most recent commit whose author matches `given`.
+///
+/// Matching is a case-insensitive substring search of the `Name <email>`
+/// string, as with `git log -F -i --author`.
+async fn find_author(given: &str) -> Option<String> {
+    let pattern = format!("--author={}", given);
+    let output = Command::new("git")
+        .args(["log", "-1", "-F", "-i", &pattern, "--format=%an <%ae>"])
+        .output()
+        .await
+        .ok()?;
+    if !output.status.success() {
+        return None;
+    }
+    let author = String::from_utf8_lossy(&output.stdout).trim().to_string();
+    Identity::parse(&author).complete()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn own() -> Identity {
+        Identity::parse("Alice Smith <alice@example.com>")
+    }
+
+    #[test]
+    fn parse_reads_partial_identities() {
+        assert_eq!(
+            Identity::parse("Alice Smith"),
+            Identity {
+                name: Some("Alice Smith".to_string()),
+                email: None,
+            }
+        );
+        assert_eq!(
+            Identity::parse("<alice@example.com>"),
+            Identity {
+                name: None,
+                email: Some("alice@example.com".to_string()),
+            }
+        );
+        assert_eq!(Identity::parse("  "), Identity::default());
+    }
+
+    #[test]
+    fn name_only_is_completed_with_the_own_email() {
+        let identity = Identity::parse("alice smith");
+        assert_eq!(
+            complete_from_own(&identity, &own()).as_deref(),
+            Some("alice smith <alice@example.com>")
+        );
+        assert_eq!(identity.missing(), "an email");
+    }
+
+    #[test]
+    fn email_only_is_completed_with_the_own_name() {
+        let identity = Identity::parse("ALICE@example.com");
+        assert_eq!(
+            complete_from_own(&identity, &own()).as_deref(),
+            Some("Alice Smith <ALICE@example.com>")
+        );
+        assert_eq!(identity.missing(), "a name");
+    }
+
+    #[test]
+    fn someone_elses_partial_identity_is_not_completed_from_config() {
+        let identity = Identity::parse("bob@example.com");
+        assert_eq!(complete_from_own(&identity, &own()), None);
+    }
+
+    #[test]
+    fn neither_part_cannot_be_completed() {
+        let identity = Identity::parse("");
+        assert_eq!(complete_from_own(&identity, &own()), None);
+        assert_eq!(identity.missing(), "a name and an email");
+    }
+
+    #[tokio::test]
+    async fn resolve_identity_rejects_empty_and_malformed_input() {
+        assert_eq!(
+            resolve_identity("").await,
+            Err("An identity needs a name and an email, e.g. 'Name <email>'".to_string())
+        );
+        assert!(resolve_identity("Alice <alice>").await.is_err());
+        assert!(resolve_identity("alice @example.com").await.is_err());
+    }
+
+    #[tokio::test]
+    async fn resolve_identity_keeps_a_full_identity() {
+        assert_eq!(
+            resolve_identity(" Bob <bob@example.com> ").await.as_deref(),
+            Ok("Bob <bob@example.com>")
+        );
+    }
+}
@@ -0,0 +1,128 @@
+//! Conventional Commits validation for `lint` mode.
+//!
+//! With `--lint` (or `lint = true`), a message is checked before it's
+//! committed: the subject must be `type(scope): description` with an allowed
+//! type, fit the subject length limit, and not end with a period. The types
+//! and limit come from the repo's commit rules when it declares them (see
+//! `convention`), otherwise from `lint_types` and `subject_max_len`.
+//!
+//! Messages git writes itself (`fixup!`, `squash!`, `amend!`, merges, and
+//! reverts) are not checked.
+//!
+//! [`assess_confidence`] builds on the same checks to score a message for
+//! `auto_accept_threshold`, which commits confident messages unreviewed.
+
+use crate::config::Config;
+use crate::convention::RepoConvention;
+use crate::format::{commit_scope, commit_type, looks_like_refusal, message_stats};
+
+/// Subject prefixes of messages git generates, which are never linted.
+const EXEMPT_PREFIXES: &[&str] = &["fixup! ", "squash! ", "amend! ", "Merge ", "Revert \""];
+
+/// What [`validate_conventional`] checks a message against.
+#[derive(Debug, Clone)]
+pub struct LintRules {
+    /// Allowed commit types.
+    pub types: Vec<String>,
+    /// Maximum subject length, in characters.
+    pub subject_max_len: usize,
+}
+
+impl LintRules {
+    /// Builds the rules from config; the repo's declared rules win.
+    pub fn new(config: &Config, convention: Option<&RepoConvention>) -> Self {
+        LintRules {
+            types: convention
+                .and_then(|c| c.types.clone())
+                .unwrap_or_else(|| config.lint_types.clone()),
+            subject_max_len: convention
+                .and_then(|c| c.header_max_length)
+                .unwrap_or(config.subject_max_len),
+        }
+    }
+}
+
+/// Checks that a message follows Conventional Commits under `rules`.
+///
+/// Returns every violation found, as a sentence for the user.
+pub fn validate_conventional(message: &str, rules: &LintRules) -> Result<(), Vec<String>> {
+    let subject = message.trim_start().lines().next().unwrap_or_default();
+    if EXEMPT_PREFIXES
+        .iter()
+        .any(|prefix| subject.starts_with(prefix))
+    {
+        return Ok(());
+    }
+
+    let mut violations = Vec::new();
+    match commit_type(message) {
+        Some(commit_type) if !rules.types.iter().any(|t| t == commit_type) => {
+            violations.push(format!(
+                "Type '{}' isn't allowed (use one of: {})",
+                commit_type,
+                rules.types.join(", ")
+            ));
+        }
+        Some(_) => {}
+        None => violations.push("Subject isn't in 'type(scope): description' form".to_string()),
+    }
+
+    let length = subject.chars().count();
+    if length > rules.subject_max_len {
+        violations.push(format!(
+            "Subject is {} characters, over the limit of {}",
+            length, rules.subject_max_len
+        ));
+    }
+
+    if subject.trim_end().ends_with('.') {
+        violations.push("Subject ends with a period".to_string());
+    }
+
+    if violations.is_empty() {
+        Ok(())
+    } else {
+        Err(violations)
+    }
+}
+
+/// How far a message can be trusted without review, from [`assess_confidence`].
+#[derive(Debug)]
+pub struct Confidence {
+    /// Score from 0 to 100.
+    pub score: u8,
+    /// What cost points, one sentence each.
+    pub shortfalls: Vec<String>,
+}
+
+/// Scores a message by a heuristic: half for passing [`validate_conventional`],
+/// a quarter each for having a scope and a body.
+///
+/// A message that looks like the model refusing scores 0.
+pub fn assess_confidence(message: &str, rules: &LintRules) -> Confidence {
+    if looks_like_refusal(message) {
+        return Confidence {
+            score: 0,
+            shortfalls: vec!["Looks like a refusal".to_string()],
+        };
+    }
+
+    let mut score = 0;
+    let mut shortfalls = Vec::new();
+    match validate_conventional(message, rules) {
+        Ok(()) => score += 50,
+        Err(violations) => shortfalls.extend(violations),
+    }
+    if commit_scope(message).is_some() {
+        score += 25;
+    } else {
+        shortfalls.push("No scope".to_string());
+    }
+    if message_stats(message).body_lines > 0 {
+        score += 25;
+    } else {
+        shortfalls.push("No body".to_string());
+    }
+
+    Confidence { score, shortfalls }
+}
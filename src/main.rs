@@ -12,11 +12,27 @@
 //! # Modules
 //!
 //! - [`api`]: OpenRouter API integration
+//! - [`bench`]: Model comparison on the staged changes
 //! - [`branch`]: Branch analysis and naming
+//! - [`cache`]: Generated message cache
 //! - [`cli`]: Command-line interface
+//! - [`commit`]: Commit message assembly
 //! - [`config`]: Configuration management
+//! - [`convention`]: Repo commit convention detection
+//! - [`emoji`]: Gitmoji subject prefixes
+//! - [`exclude`]: `.committerignore` diff exclusions
+//! - [`exit_code`]: Process exit codes
+//! - [`forge`]: Code hosts for pull requests
+//! - [`format`]: Commit message measurement
 //! - [`git`]: Git operations
+//! - [`history`]: Generated message history
+//! - [`hunks`]: Regex-based partial staging
+//! - [`lint`]: Conventional Commits validation
+//! - [`models`]: Model metadata and context budgets
+//! - [`notes`]: Generation metadata as git notes
 //! - [`pr`]: Pull request generation
+//! - [`recovery`]: Messages saved from runs that didn't commit
+//! - [`refine`]: Reformatting a message the author wrote (`--refine`)
 //! - [`ui`]: User interaction prompts
 //!
 //! # Quick Start
@@ -27,33 +43,94 @@
 //! committer
 //! ```
 
-use clap::Parser;
+use clap::{Parser, ValueEnum};
 use console::{style, Term};
-use indicatif::{ProgressBar, ProgressStyle};
-use reqwest::Client;
 use std::io::Write;
-use tokio::process::Command;
 
 mod api;
+mod bench;
 mod branch;
+mod cache;
+mod check;
 mod cli;
+mod clipboard;
+mod commit;
 mod config;
+mod convention;
+mod emoji;
+mod exclude;
+mod exit_code;
+mod forge;
+mod format;
 mod git;
+mod history;
+mod hunks;
+mod identity;
+mod lint;
+mod models;
+mod notes;
 mod pr;
+mod recovery;
+mod refine;
+mod templates;
+#[cfg(test)]
+mod test_support;
 mod ui;
 
-use api::stream_commit_message;
+use api::{
+    build_client, build_prompt, describe_headers, generate_candidates, set_api_url,
+    stream_commit_message, uses_openrouter, GenerationOptions, Message, ProviderPreference,
+    RequestTimeout, Usage, COMMIT_INSTRUCTIONS, OPENROUTER_API_URL,
+};
+use bench::handle_bench_command;
 use branch::{
-    analyze_branch_alignment, generate_branch_suggestion, generate_fallback_branch, BranchAction,
+    analyze_branch_alignment, fit_branch_name, generate_fallback_branch, record_declined_branch,
+    suggest_branch, was_recently_declined, BranchAction,
+};
+use cache::{cache_key, MessageCache};
+use check::{get_check_diff, scan_diff, Problem};
+use cli::{CacheAction, Cli, Commands, ConfigAction};
+use clipboard::{copy_to_clipboard, CopyTarget};
+use commit::{
+    collect_prompt_notes, description_prompt_note, is_revert_message, metadata_trailers,
+    revert_message, revert_prompt_note, submodule_fallback_message, ticket_prompt_note,
+    CommitPolicy, PolicyOverrides, BODY_PROMPT_NOTE, DESCRIBED_DIFF_CHARS, EMPTY_COMMIT_MESSAGE,
 };
-use cli::{Cli, Commands, ConfigAction};
-use config::{config_path, get_api_key, load_config, save_config};
+use config::{
+    config_path, find_api_key, get_api_key, load_config, save_config, set_config_path,
+    BranchSkipAction, BranchSuggestionMode, Config, ConfigSource, ConfigSources, ForgeKind,
+    SubjectOverflow, TicketPlacement,
+};
+use convention::{closest_scope, load_commit_rules, load_scopes_file};
+use format::{message_stats, set_scope};
 use git::{
-    create_and_switch_branch, get_current_branch, get_git_diff, get_recent_commits,
-    get_staged_files, run_git_commit, stage_all_changes,
+    annotate_file_list, autosquash_into, create_and_switch_branch, describe_commit_command,
+    diff_or_placeholder, fetch_with_spinner, files_from_diff, filter_excluded_diffs,
+    find_reverted_commit, get_changed_files, get_current_branch, get_head_message, get_head_sha,
+    get_last_commit_diff, get_last_commit_files, get_operation_in_progress, get_recent_commits,
+    get_repo_root, get_uncommitted_changes, get_upstream_state, head_is_merge,
+    is_submodule_only_diff, parse_submodule_updates, resolve_fixup_target, roll_back_new_branch,
+    run_diff_preprocessor, sanitize_diff, short_sha, sign_flag_needed, stage_all_changes,
+    truncate_diff, truncate_diff_to, CommitOptions, IndexTree, NewBranch, StagedState,
+    UpstreamState,
+};
+use history::{format_timestamp, load_history, record_history, Outcome};
+use hunks::{stage_matching_hunks, stage_selected_files};
+use lint::{assess_confidence, validate_conventional, LintRules};
+use models::{
+    estimate_tokens, fit_diff_to_context, get_model_info, get_models, OUTPUT_RESERVE_TOKENS,
 };
+use notes::attach_note;
 use pr::handle_pr_command;
-use ui::{prompt_branch_action, prompt_commit, CommitAction};
+use recovery::{clear_recovery, get_index_tree, load_recovery, save_recovery};
+use refine::{refine_prompt_note, RefineTarget};
+use ui::{
+    can_prompt, phase_message, phase_spinner, print_lint_violations, print_message_stats,
+    print_token_estimate, print_usage, prompt_branch_action, prompt_commit, prompt_continue_stale,
+    prompt_feedback, prompt_roll_back_branch, prompt_select_files, prompt_select_message,
+    prompt_select_model, prompt_use_scope, report_outcome, report_run, resolve_editor,
+    CommitAction, Feedback, OutputMode, StdinInput, NO_TTY_MESSAGE,
+};
 
 // ============================================================================
 // Main
@@ -62,58 +139,817 @@ use ui::{prompt_branch_action, prompt_commit, CommitAction};
 #[tokio::main(flavor = "current_thread")]
 async fn main() -> Result<(), Box<dyn std::error::Error>> {
     let cli = Cli::parse();
+    if let Some(repo) = &cli.repo {
+        if let Err(e) = std::env::set_current_dir(repo) {
+            eprintln!(
+                "{} Could not use '{}' as the repository: {}",
+                style("✗").red(),
+                repo.display(),
+                e
+            );
+            std::process::exit(exit_code::FAILURE);
+        }
+    }
+    if let Some(path) = &cli.config {
+        set_config_path(path.clone());
+    }
     let mut config = load_config();
+    set_api_url(config.base_url.clone());
 
     // Handle subcommands
     if let Some(command) = cli.command {
         match command {
             Commands::Config { action } => {
                 match action {
-                    ConfigAction::Show => {
+                    ConfigAction::Show { effective } => {
                         println!("{}", style("Configuration").bold());
                         println!("  {} {}", style("file:").dim(), config_path().display());
                         println!();
                         let bool_style = |v: bool| {
                             if v {
-                                style("true").green()
+                                style("true").green().to_string()
+                            } else {
+                                style("false").dim().to_string()
+                            }
+                        };
+                        let mut sources = ConfigSources::load();
+                        let api_key_source = find_api_key(&config);
+
+                        // The effective view also applies the repository's
+                        // scope registry and this run's flags
+                        if effective {
+                            let repo_root = get_repo_root().await;
+                            if let Some(scopes) = repo_root.as_deref().and_then(load_scopes_file) {
+                                config.valid_scopes = scopes;
+                                sources.set("valid_scopes", ConfigSource::Repo);
+                            }
+                            if let Some(model) = &cli.model {
+                                config.model = model.clone();
+                                sources.set("model", ConfigSource::Cli);
+                            }
+                            if let Some(secs) = cli.timeout {
+                                config.timeout_secs = secs;
+                                sources.set("timeout_secs", ConfigSource::Cli);
+                            }
+                            if cli.temperature.is_some() {
+                                config.temperature = cli.temperature;
+                                sources.set("temperature", ConfigSource::Cli);
+                            }
+                            if cli.max_tokens.is_some() {
+                                config.max_tokens = cli.max_tokens;
+                                sources.set("max_tokens", ConfigSource::Cli);
+                            }
+                            if cli.max_body_lines.is_some() {
+                                config.max_body_lines = cli.max_body_lines;
+                                sources.set("max_body_lines", ConfigSource::Cli);
+                            }
+                            for (key, flag, value) in [
+                                ("lint", cli.lint, &mut config.lint),
+                                ("body", cli.body, &mut config.body),
+                                ("signoff", cli.signoff, &mut config.signoff),
+                                ("sign", cli.sign, &mut config.sign),
+                                ("emoji", cli.emoji, &mut config.emoji),
+                            ] {
+                                if flag {
+                                    *value = true;
+                                    sources.set(key, ConfigSource::Cli);
+                                }
+                            }
+                        }
+
+                        let rows = [
+                            (
+                                "auto_commit",
+                                bool_style(config.auto_commit),
+                                sources.of("auto_commit"),
+                            ),
+                            (
+                                "commit_after_branch",
+                                bool_style(config.commit_after_branch),
+                                sources.of("commit_after_branch"),
+                            ),
+                            (
+                                "branch_skip_action",
+                                style(config.branch_skip_action.as_str())
+                                    .yellow()
+                                    .to_string(),
+                                sources.of("branch_skip_action"),
+                            ),
+                            (
+                                "branch_max_length",
+                                match config.branch_max_length {
+                                    Some(n) => style(n).yellow().to_string(),
+                                    None => style("none").dim().to_string(),
+                                },
+                                sources.of("branch_max_length"),
+                            ),
+                            (
+                                "branch_overflow_strategy",
+                                style(config.branch_overflow_strategy.as_str())
+                                    .yellow()
+                                    .to_string(),
+                                sources.of("branch_overflow_strategy"),
+                            ),
+                            (
+                                "branch_suggestion_mode",
+                                style(config.branch_suggestion_mode.as_str())
+                                    .yellow()
+                                    .to_string(),
+                                sources.of("branch_suggestion_mode"),
+                            ),
+                            (
+                                "branch_strictness",
+                                style(config.branch_strictness.as_str())
+                                    .yellow()
+                                    .to_string(),
+                                sources.of("branch_strictness"),
+                            ),
+                            (
+                                "issue_pattern",
+                                style(&config.issue_pattern).yellow().to_string(),
+                                sources.of("issue_pattern"),
+                            ),
+                            (
+                                "template",
+                                match &config.template {
+                                    Some(template) => style(template.trim().replace('\n', "\\n"))
+                                        .yellow()
+                                        .to_string(),
+                                    None => style("none").dim().to_string(),
+                                },
+                                sources.of("template"),
+                            ),
+                            (
+                                "ticket_in_subject",
+                                style(config.ticket_in_subject.as_str())
+                                    .yellow()
+                                    .to_string(),
+                                sources.of("ticket_in_subject"),
+                            ),
+                            ("verbose", bool_style(config.verbose), sources.of("verbose")),
+                            (
+                                "max_body_lines",
+                                match config.max_body_lines {
+                                    Some(n) => style(n).yellow().to_string(),
+                                    None => style("none").dim().to_string(),
+                                },
+                                sources.of("max_body_lines"),
+                            ),
+                            (
+                                "max_message_bytes",
+                                match config.max_message_bytes {
+                                    Some(n) => style(n).yellow().to_string(),
+                                    None => style("none").dim().to_string(),
+                                },
+                                sources.of("max_message_bytes"),
+                            ),
+                            (
+                                "auto_accept_threshold",
+                                match config.auto_accept_threshold {
+                                    Some(n) => style(format!("{}%", n)).yellow().to_string(),
+                                    None => style("none").dim().to_string(),
+                                },
+                                sources.of("auto_accept_threshold"),
+                            ),
+                            ("lint", bool_style(config.lint), sources.of("lint")),
+                            (
+                                "lint_types",
+                                style(config.lint_types.join(", ")).yellow().to_string(),
+                                sources.of("lint_types"),
+                            ),
+                            (
+                                "subject_max_len",
+                                style(config.subject_max_len).yellow().to_string(),
+                                sources.of("subject_max_len"),
+                            ),
+                            (
+                                "on_too_long",
+                                style(config.on_too_long.as_str()).yellow().to_string(),
+                                sources.of("on_too_long"),
+                            ),
+                            (
+                                "wrap_body_at",
+                                if config.wrap_body_at == 0 {
+                                    style("off").dim().to_string()
+                                } else {
+                                    style(config.wrap_body_at).yellow().to_string()
+                                },
+                                sources.of("wrap_body_at"),
+                            ),
+                            ("body", bool_style(config.body), sources.of("body")),
+                            ("signoff", bool_style(config.signoff), sources.of("signoff")),
+                            ("sign", bool_style(config.sign), sources.of("sign")),
+                            (
+                                "valid_scopes",
+                                if config.valid_scopes.is_empty() {
+                                    style("any").dim().to_string()
+                                } else {
+                                    style(config.valid_scopes.join(", ")).yellow().to_string()
+                                },
+                                sources.of("valid_scopes"),
+                            ),
+                            (
+                                "bench_models",
+                                if config.bench_models.is_empty() {
+                                    style("none").dim().to_string()
+                                } else {
+                                    style(config.bench_models.join(", ")).yellow().to_string()
+                                },
+                                sources.of("bench_models"),
+                            ),
+                            (
+                                "co_authors",
+                                if config.co_authors.is_empty() {
+                                    style("none").dim().to_string()
+                                } else {
+                                    style(config.co_authors.join(", ")).yellow().to_string()
+                                },
+                                sources.of("co_authors"),
+                            ),
+                            (
+                                "structured_output",
+                                bool_style(config.structured_output),
+                                sources.of("structured_output"),
+                            ),
+                            ("history", bool_style(config.history), sources.of("history")),
+                            (
+                                "attach_notes",
+                                bool_style(config.attach_notes),
+                                sources.of("attach_notes"),
+                            ),
+                            (
+                                "notes_ref",
+                                style(&config.notes_ref).yellow().to_string(),
+                                sources.of("notes_ref"),
+                            ),
+                            (
+                                "stamp_metadata",
+                                bool_style(config.stamp_metadata),
+                                sources.of("stamp_metadata"),
+                            ),
+                            (
+                                "warn_if_behind",
+                                bool_style(config.warn_if_behind),
+                                sources.of("warn_if_behind"),
+                            ),
+                            (
+                                "editor",
+                                match &config.editor {
+                                    Some(editor) => style(editor).yellow().to_string(),
+                                    None => style(format!("none (using {})", resolve_editor(None)))
+                                        .dim()
+                                        .to_string(),
+                                },
+                                sources.of("editor"),
+                            ),
+                            (
+                                "verbose_editor",
+                                bool_style(config.verbose_editor),
+                                sources.of("verbose_editor"),
+                            ),
+                            (
+                                "show_stream",
+                                bool_style(config.show_stream),
+                                sources.of("show_stream"),
+                            ),
+                            (
+                                "stream_buffer_size",
+                                style(config.stream_buffer_size).yellow().to_string(),
+                                sources.of("stream_buffer_size"),
+                            ),
+                            (
+                                "timeout_secs",
+                                style(config.timeout_secs).yellow().to_string(),
+                                sources.of("timeout_secs"),
+                            ),
+                            (
+                                "cache_ttl_secs",
+                                style(config.cache_ttl_secs).yellow().to_string(),
+                                sources.of("cache_ttl_secs"),
+                            ),
+                            (
+                                "temperature",
+                                match config.temperature {
+                                    Some(n) => style(n).yellow().to_string(),
+                                    None => style("none").dim().to_string(),
+                                },
+                                sources.of("temperature"),
+                            ),
+                            (
+                                "max_tokens",
+                                match config.max_tokens {
+                                    Some(n) => style(n).yellow().to_string(),
+                                    None => style("none").dim().to_string(),
+                                },
+                                sources.of("max_tokens"),
+                            ),
+                            (
+                                "base_url",
+                                style(&config.base_url).yellow().to_string(),
+                                sources.of("base_url"),
+                            ),
+                            (
+                                "max_retries",
+                                style(config.max_retries).yellow().to_string(),
+                                sources.of("max_retries"),
+                            ),
+                            (
+                                "base_branch_rules",
+                                if config.base_branch_rules.is_empty() {
+                                    style("none").dim().to_string()
+                                } else {
+                                    config
+                                        .base_branch_rules
+                                        .iter()
+                                        .map(|(glob, base)| format!("{} → {}", glob, base))
+                                        .collect::<Vec<_>>()
+                                        .join(", ")
+                                },
+                                sources.of("base_branch_rules"),
+                            ),
+                            (
+                                "head_remote",
+                                match &config.head_remote {
+                                    Some(remote) => style(remote).yellow().to_string(),
+                                    None => style("origin").dim().to_string(),
+                                },
+                                sources.of("head_remote"),
+                            ),
+                            (
+                                "base_remote",
+                                match &config.base_remote {
+                                    Some(remote) => style(remote).yellow().to_string(),
+                                    None => style("upstream if present").dim().to_string(),
+                                },
+                                sources.of("base_remote"),
+                            ),
+                            (
+                                "default_base",
+                                match &config.default_base {
+                                    Some(base) => style(base).yellow().to_string(),
+                                    None => style("none").dim().to_string(),
+                                },
+                                sources.of("default_base"),
+                            ),
+                            (
+                                "default_reviewers",
+                                if config.default_reviewers.is_empty() {
+                                    style("none").dim().to_string()
+                                } else {
+                                    style(config.default_reviewers.join(", "))
+                                        .yellow()
+                                        .to_string()
+                                },
+                                sources.of("default_reviewers"),
+                            ),
+                            (
+                                "default_labels",
+                                if config.default_labels.is_empty() {
+                                    style("none").dim().to_string()
+                                } else {
+                                    style(config.default_labels.join(", ")).yellow().to_string()
+                                },
+                                sources.of("default_labels"),
+                            ),
+                            (
+                                "forge",
+                                match config.forge {
+                                    Some(kind) => style(kind.as_str()).yellow().to_string(),
+                                    None => style("auto").dim().to_string(),
+                                },
+                                sources.of("forge"),
+                            ),
+                            (
+                                "body_by_type",
+                                if config.body_by_type.is_empty() {
+                                    style("auto").dim().to_string()
+                                } else {
+                                    config
+                                        .body_by_type
+                                        .iter()
+                                        .map(|(commit_type, mode)| {
+                                            format!("{} → {}", commit_type, mode.as_str())
+                                        })
+                                        .collect::<Vec<_>>()
+                                        .join(", ")
+                                },
+                                sources.of("body_by_type"),
+                            ),
+                            ("emoji", bool_style(config.emoji), sources.of("emoji")),
+                            (
+                                "emoji_by_type",
+                                if config.emoji_by_type.is_empty() {
+                                    style("defaults").dim().to_string()
+                                } else {
+                                    config
+                                        .emoji_by_type
+                                        .iter()
+                                        .map(|(commit_type, emoji)| {
+                                            format!("{} → {}", commit_type, emoji)
+                                        })
+                                        .collect::<Vec<_>>()
+                                        .join(", ")
+                                },
+                                sources.of("emoji_by_type"),
+                            ),
+                            (
+                                "extra_headers",
+                                if config.extra_headers.is_empty() {
+                                    style("none").dim().to_string()
+                                } else {
+                                    describe_headers(&config.extra_headers)
+                                },
+                                sources.of("extra_headers"),
+                            ),
+                            (
+                                "model_aliases",
+                                if config.model_aliases.is_empty() {
+                                    style("none").dim().to_string()
+                                } else {
+                                    config
+                                        .model_aliases
+                                        .iter()
+                                        .map(|(alias, model)| format!("{} → {}", alias, model))
+                                        .collect::<Vec<_>>()
+                                        .join(", ")
+                                },
+                                sources.of("model_aliases"),
+                            ),
+                            (
+                                "model",
+                                describe_model(&config, &config.model),
+                                sources.of("model"),
+                            ),
+                            (
+                                "diff_preprocessor",
+                                match &config.diff_preprocessor {
+                                    Some(command) => style(command).yellow().to_string(),
+                                    None => style("none").dim().to_string(),
+                                },
+                                sources.of("diff_preprocessor"),
+                            ),
+                            (
+                                "fallback_model",
+                                match &config.fallback_model {
+                                    Some(model) => describe_model(&config, model),
+                                    None => style("none").dim().to_string(),
+                                },
+                                sources.of("fallback_model"),
+                            ),
+                            (
+                                "api_key",
+                                match &api_key_source {
+                                    Ok(Some((_, source))) => {
+                                        style(source.label()).green().to_string()
+                                    }
+                                    Ok(None) => style("[not set]").red().to_string(),
+                                    Err(e) => style(format!("[{}]", e)).red().to_string(),
+                                },
+                                match &api_key_source {
+                                    Ok(Some((_, source))) => source.config_source(),
+                                    _ => ConfigSource::Default,
+                                },
+                            ),
+                        ];
+
+                        for (key, value, source) in rows {
+                            if effective {
+                                println!(
+                                    "  {} {} {}",
+                                    style(format!("{}:", key)).cyan(),
+                                    value,
+                                    style(format!("({})", source.as_str())).dim()
+                                );
                             } else {
-                                style("false").dim()
+                                println!("  {} {}", style(format!("{}:", key)).cyan(), value);
                             }
+                        }
+                    }
+                    ConfigAction::AutoCommit { value } => {
+                        config.auto_commit = value.parse().unwrap_or(false);
+                        save_config(&config)?;
+                        let val_style = if config.auto_commit {
+                            style("true").green()
+                        } else {
+                            style("false").dim()
                         };
                         println!(
-                            "  {} {}",
-                            style("auto_commit:").cyan(),
-                            bool_style(config.auto_commit)
+                            "{} {} set to {}",
+                            style("✓").green(),
+                            style("auto_commit").cyan(),
+                            val_style
                         );
+                    }
+                    ConfigAction::CommitAfterBranch { value } => {
+                        config.commit_after_branch = value.parse().unwrap_or(false);
+                        save_config(&config)?;
+                        let val_style = if config.commit_after_branch {
+                            style("true").green()
+                        } else {
+                            style("false").dim()
+                        };
                         println!(
-                            "  {} {}",
-                            style("commit_after_branch:").cyan(),
-                            bool_style(config.commit_after_branch)
+                            "{} {} set to {}",
+                            style("✓").green(),
+                            style("commit_after_branch").cyan(),
+                            val_style
                         );
+                    }
+                    ConfigAction::BranchSkipAction { value } => {
+                        config.branch_skip_action = value;
+                        save_config(&config)?;
                         println!(
-                            "  {} {}",
-                            style("verbose:").cyan(),
-                            bool_style(config.verbose)
+                            "{} {} set to {}",
+                            style("✓").green(),
+                            style("branch_skip_action").cyan(),
+                            style(config.branch_skip_action.as_str()).yellow()
                         );
+                    }
+                    ConfigAction::BranchMaxLength { value } => {
+                        config.branch_max_length = if value == "none" {
+                            None
+                        } else {
+                            match value.parse() {
+                                Ok(n) => Some(n),
+                                Err(_) => {
+                                    println!(
+                                        "{} Expected a number or \"none\", got '{}'",
+                                        style("✗").red(),
+                                        value
+                                    );
+                                    std::process::exit(exit_code::FAILURE);
+                                }
+                            }
+                        };
+                        save_config(&config)?;
                         println!(
-                            "  {} {}",
-                            style("model:").cyan(),
-                            style(&config.model).yellow()
+                            "{} {} set to {}",
+                            style("✓").green(),
+                            style("branch_max_length").cyan(),
+                            style(&value).yellow()
                         );
+                    }
+                    ConfigAction::BranchOverflowStrategy { value } => {
+                        config.branch_overflow_strategy = value;
+                        save_config(&config)?;
                         println!(
-                            "  {} {}",
-                            style("api_key:").cyan(),
-                            if std::env::var("OPENROUTER_API_KEY").is_ok() {
-                                style("[set via env]").green()
-                            } else {
-                                style("[not set]").red()
+                            "{} {} set to {}",
+                            style("✓").green(),
+                            style("branch_overflow_strategy").cyan(),
+                            style(config.branch_overflow_strategy.as_str()).yellow()
+                        );
+                    }
+                    ConfigAction::BranchSuggestionMode { value } => {
+                        config.branch_suggestion_mode = value;
+                        save_config(&config)?;
+                        println!(
+                            "{} {} set to {}",
+                            style("✓").green(),
+                            style("branch_suggestion_mode").cyan(),
+                            style(config.branch_suggestion_mode.as_str()).yellow()
+                        );
+                    }
+                    ConfigAction::BranchStrictness { value } => {
+                        config.branch_strictness = value;
+                        save_config(&config)?;
+                        println!(
+                            "{} {} set to {}",
+                            style("✓").green(),
+                            style("branch_strictness").cyan(),
+                            style(config.branch_strictness.as_str()).yellow()
+                        );
+                    }
+                    ConfigAction::IssuePattern { value } => {
+                        if let Err(e) = regex_lite::Regex::new(&value) {
+                            println!("{} Invalid pattern '{}': {}", style("✗").red(), value, e);
+                            std::process::exit(exit_code::FAILURE);
+                        }
+                        config.issue_pattern = value;
+                        save_config(&config)?;
+                        println!(
+                            "{} {} set to {}",
+                            style("✓").green(),
+                            style("issue_pattern").cyan(),
+                            style(&config.issue_pattern).yellow()
+                        );
+                    }
+                    ConfigAction::Template { value } => {
+                        config.template = if value == "none" { None } else { Some(value) };
+                        save_config(&config)?;
+                        println!(
+                            "{} {} set to {}",
+                            style("✓").green(),
+                            style("template").cyan(),
+                            style(config.template.as_deref().unwrap_or("none")).yellow()
+                        );
+                    }
+                    ConfigAction::TicketInSubject { value } => {
+                        config.ticket_in_subject = value;
+                        save_config(&config)?;
+                        println!(
+                            "{} {} set to {}",
+                            style("✓").green(),
+                            style("ticket_in_subject").cyan(),
+                            style(config.ticket_in_subject.as_str()).yellow()
+                        );
+                    }
+                    ConfigAction::WarnIfBehind { value } => {
+                        config.warn_if_behind = value.parse().unwrap_or(false);
+                        save_config(&config)?;
+                        let val_style = if config.warn_if_behind {
+                            style("true").green()
+                        } else {
+                            style("false").dim()
+                        };
+                        println!(
+                            "{} {} set to {}",
+                            style("✓").green(),
+                            style("warn_if_behind").cyan(),
+                            val_style
+                        );
+                    }
+                    ConfigAction::Editor { value } => {
+                        config.editor = if value == "none" { None } else { Some(value) };
+                        save_config(&config)?;
+                        println!(
+                            "{} {} set to {}",
+                            style("✓").green(),
+                            style("editor").cyan(),
+                            style(resolve_editor(config.editor.as_deref())).yellow()
+                        );
+                    }
+                    ConfigAction::VerboseEditor { value } => {
+                        config.verbose_editor = value.parse().unwrap_or(false);
+                        save_config(&config)?;
+                        let val_style = if config.verbose_editor {
+                            style("true").green()
+                        } else {
+                            style("false").dim()
+                        };
+                        println!(
+                            "{} {} set to {}",
+                            style("✓").green(),
+                            style("verbose_editor").cyan(),
+                            val_style
+                        );
+                    }
+                    ConfigAction::ShowStream { value } => {
+                        config.show_stream = value.parse().unwrap_or(true);
+                        save_config(&config)?;
+                        let val_style = if config.show_stream {
+                            style("true").green()
+                        } else {
+                            style("false").dim()
+                        };
+                        println!(
+                            "{} {} set to {}",
+                            style("✓").green(),
+                            style("show_stream").cyan(),
+                            val_style
+                        );
+                    }
+                    ConfigAction::StreamBufferSize { value } => {
+                        config.stream_buffer_size = value;
+                        save_config(&config)?;
+                        println!(
+                            "{} {} set to {}",
+                            style("✓").green(),
+                            style("stream_buffer_size").cyan(),
+                            style(config.stream_buffer_size).yellow()
+                        );
+                    }
+                    ConfigAction::Temperature { value } => {
+                        config.temperature = if value == "none" {
+                            None
+                        } else {
+                            match value.parse() {
+                                Ok(n) => Some(n),
+                                Err(_) => {
+                                    println!(
+                                        "{} Expected a number or \"none\", got '{}'",
+                                        style("✗").red(),
+                                        value
+                                    );
+                                    std::process::exit(exit_code::FAILURE);
+                                }
                             }
+                        };
+                        save_config(&config)?;
+                        println!(
+                            "{} {} set to {}",
+                            style("✓").green(),
+                            style("temperature").cyan(),
+                            style(&value).yellow()
                         );
                     }
-                    ConfigAction::AutoCommit { value } => {
-                        config.auto_commit = value.parse().unwrap_or(false);
+                    ConfigAction::MaxTokens { value } => {
+                        config.max_tokens = if value == "none" {
+                            None
+                        } else {
+                            match value.parse() {
+                                Ok(n) => Some(n),
+                                Err(_) => {
+                                    println!(
+                                        "{} Expected a number or \"none\", got '{}'",
+                                        style("✗").red(),
+                                        value
+                                    );
+                                    std::process::exit(exit_code::FAILURE);
+                                }
+                            }
+                        };
                         save_config(&config)?;
-                        let val_style = if config.auto_commit {
+                        println!(
+                            "{} {} set to {}",
+                            style("✓").green(),
+                            style("max_tokens").cyan(),
+                            style(&value).yellow()
+                        );
+                    }
+                    ConfigAction::BaseUrl { value } => {
+                        config.base_url = if value == "default" {
+                            OPENROUTER_API_URL.to_string()
+                        } else if reqwest::Url::parse(&value).is_ok() {
+                            value
+                        } else {
+                            println!("{} Not a valid URL: '{}'", style("✗").red(), value);
+                            std::process::exit(exit_code::FAILURE);
+                        };
+                        save_config(&config)?;
+                        println!(
+                            "{} {} set to {}",
+                            style("✓").green(),
+                            style("base_url").cyan(),
+                            style(&config.base_url).yellow()
+                        );
+                    }
+                    ConfigAction::MaxRetries { value } => {
+                        config.max_retries = value;
+                        save_config(&config)?;
+                        println!(
+                            "{} {} set to {}",
+                            style("✓").green(),
+                            style("max_retries").cyan(),
+                            style(config.max_retries).yellow()
+                        );
+                    }
+                    ConfigAction::Timeout { value } => {
+                        config.timeout_secs = value;
+                        save_config(&config)?;
+                        println!(
+                            "{} {} set to {}",
+                            style("✓").green(),
+                            style("timeout_secs").cyan(),
+                            style(config.timeout_secs).yellow()
+                        );
+                    }
+                    ConfigAction::CacheTtl { value } => {
+                        config.cache_ttl_secs = value;
+                        save_config(&config)?;
+                        println!(
+                            "{} {} set to {}",
+                            style("✓").green(),
+                            style("cache_ttl_secs").cyan(),
+                            style(config.cache_ttl_secs).yellow()
+                        );
+                    }
+                    ConfigAction::DiffPreprocessor { value } => {
+                        config.diff_preprocessor = if value == "none" { None } else { Some(value) };
+                        save_config(&config)?;
+                        println!(
+                            "{} {} set to {}",
+                            style("✓").green(),
+                            style("diff_preprocessor").cyan(),
+                            style(config.diff_preprocessor.as_deref().unwrap_or("none")).yellow()
+                        );
+                    }
+                    ConfigAction::FallbackModel { value } => {
+                        config.fallback_model = if value == "none" { None } else { Some(value) };
+                        save_config(&config)?;
+                        println!(
+                            "{} {} set to {}",
+                            style("✓").green(),
+                            style("fallback_model").cyan(),
+                            style(config.fallback_model.as_deref().unwrap_or("none")).yellow()
+                        );
+                    }
+                    ConfigAction::Model { value } => {
+                        config.model = match value {
+                            Some(value) => value,
+                            None => pick_model(&config).await?,
+                        };
+                        save_config(&config)?;
+                        println!(
+                            "{} {} set to {}",
+                            style("✓").green(),
+                            style("model").cyan(),
+                            describe_model(&config, &config.model)
+                        );
+                    }
+                    ConfigAction::Verbose { value } => {
+                        config.verbose = value.parse().unwrap_or(false);
+                        save_config(&config)?;
+                        let val_style = if config.verbose {
                             style("true").green()
                         } else {
                             style("false").dim()
@@ -121,166 +957,1454 @@ async fn main() -> Result<(), Box<dyn std::error::Error>> {
                         println!(
                             "{} {} set to {}",
                             style("✓").green(),
-                            style("auto_commit").cyan(),
+                            style("verbose").cyan(),
                             val_style
                         );
                     }
-                    ConfigAction::CommitAfterBranch { value } => {
-                        config.commit_after_branch = value.parse().unwrap_or(false);
+                    ConfigAction::MaxBodyLines { value } => {
+                        config.max_body_lines = if value == "none" {
+                            None
+                        } else {
+                            match value.parse() {
+                                Ok(n) => Some(n),
+                                Err(_) => {
+                                    println!(
+                                        "{} Expected a number or \"none\", got '{}'",
+                                        style("✗").red(),
+                                        value
+                                    );
+                                    std::process::exit(exit_code::FAILURE);
+                                }
+                            }
+                        };
                         save_config(&config)?;
-                        let val_style = if config.commit_after_branch {
+                        println!(
+                            "{} {} set to {}",
+                            style("✓").green(),
+                            style("max_body_lines").cyan(),
+                            style(&value).yellow()
+                        );
+                    }
+                    ConfigAction::MaxMessageBytes { value } => {
+                        config.max_message_bytes = if value == "none" {
+                            None
+                        } else {
+                            match value.parse() {
+                                Ok(n) => Some(n),
+                                Err(_) => {
+                                    println!(
+                                        "{} Expected a number or \"none\", got '{}'",
+                                        style("✗").red(),
+                                        value
+                                    );
+                                    std::process::exit(exit_code::FAILURE);
+                                }
+                            }
+                        };
+                        save_config(&config)?;
+                        println!(
+                            "{} {} set to {}",
+                            style("✓").green(),
+                            style("max_message_bytes").cyan(),
+                            style(&value).yellow()
+                        );
+                    }
+                    ConfigAction::AutoAcceptThreshold { value } => {
+                        config.auto_accept_threshold = if value == "none" {
+                            None
+                        } else {
+                            match value.parse() {
+                                Ok(n) if n <= 100 => Some(n),
+                                _ => {
+                                    println!(
+                                        "{} Expected a percentage from 0 to 100 or \"none\", got '{}'",
+                                        style("✗").red(),
+                                        value
+                                    );
+                                    std::process::exit(exit_code::FAILURE);
+                                }
+                            }
+                        };
+                        save_config(&config)?;
+                        println!(
+                            "{} {} set to {}",
+                            style("✓").green(),
+                            style("auto_accept_threshold").cyan(),
+                            style(&value).yellow()
+                        );
+                    }
+                    ConfigAction::Emoji { value } => {
+                        config.emoji = value.parse().unwrap_or(false);
+                        save_config(&config)?;
+                        let val_style = if config.emoji {
+                            style("true").green()
+                        } else {
+                            style("false").dim()
+                        };
+                        println!(
+                            "{} {} set to {}",
+                            style("✓").green(),
+                            style("emoji").cyan(),
+                            val_style
+                        );
+                    }
+                    ConfigAction::Lint { value } => {
+                        config.lint = value.parse().unwrap_or(false);
+                        save_config(&config)?;
+                        let val_style = if config.lint {
                             style("true").green()
                         } else {
-                            style("false").dim()
+                            style("false").dim()
+                        };
+                        println!(
+                            "{} {} set to {}",
+                            style("✓").green(),
+                            style("lint").cyan(),
+                            val_style
+                        );
+                    }
+                    ConfigAction::SubjectMaxLen { value } => {
+                        config.subject_max_len = value;
+                        save_config(&config)?;
+                        println!(
+                            "{} {} set to {}",
+                            style("✓").green(),
+                            style("subject_max_len").cyan(),
+                            style(config.subject_max_len).yellow()
+                        );
+                    }
+                    ConfigAction::DefaultBase { value } => {
+                        config.default_base = if value == "none" { None } else { Some(value) };
+                        save_config(&config)?;
+                        println!(
+                            "{} {} set to {}",
+                            style("✓").green(),
+                            style("default_base").cyan(),
+                            style(config.default_base.as_deref().unwrap_or("none")).yellow()
+                        );
+                    }
+                    ConfigAction::Forge { value } => {
+                        config.forge = if value == "auto" {
+                            None
+                        } else {
+                            match ForgeKind::from_str(&value, true) {
+                                Ok(kind) => Some(kind),
+                                Err(_) => {
+                                    println!(
+                                        "{} Expected github, gitlab, or \"auto\", got '{}'",
+                                        style("✗").red(),
+                                        value
+                                    );
+                                    std::process::exit(exit_code::FAILURE);
+                                }
+                            }
+                        };
+                        save_config(&config)?;
+                        println!(
+                            "{} {} set to {}",
+                            style("✓").green(),
+                            style("forge").cyan(),
+                            style(&value).yellow()
+                        );
+                    }
+                    ConfigAction::OnTooLong { value } => {
+                        config.on_too_long = value;
+                        save_config(&config)?;
+                        println!(
+                            "{} {} set to {}",
+                            style("✓").green(),
+                            style("on_too_long").cyan(),
+                            style(config.on_too_long.as_str()).yellow()
+                        );
+                    }
+                    ConfigAction::WrapBodyAt { value } => {
+                        config.wrap_body_at = value;
+                        save_config(&config)?;
+                        println!(
+                            "{} {} set to {}",
+                            style("✓").green(),
+                            style("wrap_body_at").cyan(),
+                            style(config.wrap_body_at).yellow()
+                        );
+                    }
+                    ConfigAction::Body { value } => {
+                        config.body = value.parse().unwrap_or(false);
+                        save_config(&config)?;
+                        let val_style = if config.body {
+                            style("true").green()
+                        } else {
+                            style("false").dim()
+                        };
+                        println!(
+                            "{} {} set to {}",
+                            style("✓").green(),
+                            style("body").cyan(),
+                            val_style
+                        );
+                    }
+                    ConfigAction::Sign { value } => {
+                        config.sign = value.parse().unwrap_or(false);
+                        save_config(&config)?;
+                        let val_style = if config.sign {
+                            style("true").green()
+                        } else {
+                            style("false").dim()
+                        };
+                        println!(
+                            "{} {} set to {}",
+                            style("✓").green(),
+                            style("sign").cyan(),
+                            val_style
+                        );
+                    }
+                    ConfigAction::Signoff { value } => {
+                        config.signoff = value.parse().unwrap_or(false);
+                        save_config(&config)?;
+                        let val_style = if config.signoff {
+                            style("true").green()
+                        } else {
+                            style("false").dim()
+                        };
+                        println!(
+                            "{} {} set to {}",
+                            style("✓").green(),
+                            style("signoff").cyan(),
+                            val_style
+                        );
+                    }
+                    ConfigAction::StructuredOutput { value } => {
+                        config.structured_output = value.parse().unwrap_or(false);
+                        save_config(&config)?;
+                        let val_style = if config.structured_output {
+                            style("true").green()
+                        } else {
+                            style("false").dim()
+                        };
+                        println!(
+                            "{} {} set to {}",
+                            style("✓").green(),
+                            style("structured_output").cyan(),
+                            val_style
+                        );
+                    }
+                    ConfigAction::AttachNotes { value } => {
+                        config.attach_notes = value.parse().unwrap_or(false);
+                        save_config(&config)?;
+                        let val_style = if config.attach_notes {
+                            style("true").green()
+                        } else {
+                            style("false").dim()
+                        };
+                        println!(
+                            "{} {} set to {}",
+                            style("✓").green(),
+                            style("attach_notes").cyan(),
+                            val_style
+                        );
+                    }
+                    ConfigAction::StampMetadata { value } => {
+                        config.stamp_metadata = value.parse().unwrap_or(false);
+                        save_config(&config)?;
+                        let val_style = if config.stamp_metadata {
+                            style("true").green()
+                        } else {
+                            style("false").dim()
+                        };
+                        println!(
+                            "{} {} set to {}",
+                            style("✓").green(),
+                            style("stamp_metadata").cyan(),
+                            val_style
+                        );
+                    }
+                    ConfigAction::NotesRef { value } => {
+                        config.notes_ref = value;
+                        save_config(&config)?;
+                        println!(
+                            "{} {} set to {}",
+                            style("✓").green(),
+                            style("notes_ref").cyan(),
+                            style(&config.notes_ref).yellow()
+                        );
+                    }
+                    ConfigAction::History { value } => {
+                        config.history = value.parse().unwrap_or(false);
+                        save_config(&config)?;
+                        let val_style = if config.history {
+                            style("true").green()
+                        } else {
+                            style("false").dim()
+                        };
+                        println!(
+                            "{} {} set to {}",
+                            style("✓").green(),
+                            style("history").cyan(),
+                            val_style
+                        );
+                    }
+                }
+                return Ok(());
+            }
+            Commands::Pr(args) => {
+                return handle_pr_command(args, &config).await;
+            }
+            Commands::Bench(args) => {
+                return handle_bench_command(args, &config).await;
+            }
+            Commands::Models { aliases: true } => {
+                if config.model_aliases.is_empty() {
+                    println!("{} No model aliases configured", style("—").dim());
+                    println!(
+                        "  {} Add them under [model_aliases] in {}",
+                        style("→").dim(),
+                        config_path().display()
+                    );
+                    return Ok(());
+                }
+                for (alias, model) in &config.model_aliases {
+                    println!("{} → {}", style(alias).cyan(), style(model).yellow());
+                }
+                return Ok(());
+            }
+            Commands::Models { aliases: false } => {
+                let client = build_client(&config.extra_headers, config.timeout_secs)?;
+                let Some(models) = get_models(&client, config.verbose).await else {
+                    println!("{} Could not fetch the models list", style("✗").red());
+                    std::process::exit(exit_code::FAILURE);
+                };
+                for info in models {
+                    match info.context_length {
+                        Some(context) => println!(
+                            "{} {}",
+                            info.id,
+                            style(format!("({} tokens)", context)).dim()
+                        ),
+                        None => println!("{}", info.id),
+                    }
+                }
+                return Ok(());
+            }
+            Commands::History { limit } => {
+                let entries = load_history(limit);
+                if entries.is_empty() {
+                    println!("{} No history recorded", style("—").dim());
+                    if !config.history {
+                        println!(
+                            "  {} Enable with: committer config history true",
+                            style("→").dim()
+                        );
+                    }
+                    return Ok(());
+                }
+                for entry in entries {
+                    let outcome = match entry.outcome {
+                        Outcome::Accepted => style(entry.outcome.as_str()).green(),
+                        Outcome::Edited => style(entry.outcome.as_str()).yellow(),
+                        Outcome::Cancelled => style(entry.outcome.as_str()).dim(),
+                    };
+                    let sha = entry
+                        .sha
+                        .as_deref()
+                        .map(|sha| &sha[..sha.len().min(7)])
+                        .unwrap_or("-------");
+                    println!(
+                        "{} {} {} {}",
+                        style(format_timestamp(entry.timestamp)).dim(),
+                        outcome,
+                        style(sha).yellow(),
+                        style(&entry.model).cyan()
+                    );
+                    println!("  {}", entry.message.lines().next().unwrap_or(""));
+                }
+                return Ok(());
+            }
+            Commands::Cache {
+                action: CacheAction::Clear,
+            } => {
+                if MessageCache::new(config.cache_ttl_secs).clear()? {
+                    println!("{} Message cache cleared", style("✓").green());
+                } else {
+                    println!("{} Message cache is already empty", style("—").dim());
+                }
+                return Ok(());
+            }
+        }
+    }
+
+    if cli.check {
+        let code = run_check(&cli, &config).await?;
+        std::process::exit(code);
+    }
+
+    // A message to reformat rather than replace; nothing is committed
+    let refine = match &cli.refine {
+        Some(path) => Some(RefineTarget::read(path)?),
+        None => None,
+    };
+    if refine
+        .as_ref()
+        .is_some_and(|refine| refine.message.is_empty())
+    {
+        return Ok(());
+    }
+    // A piped diff needn't match the index, so it's only committed on request
+    let stdin_commits = cli.stdin_diff && cli.yes && cli.repo.is_some();
+    let prints_message = cli.stdin_diff && !stdin_commits && !cli.print_branch;
+    // Modes whose stdout is only their result
+    let print_only = cli.print_branch
+        || refine.as_ref().is_some_and(RefineTarget::prints)
+        || prints_message
+        || cli.json;
+    // JSON output has no one to answer prompts
+    let yes = cli.yes || cli.json;
+
+    // Committing in the middle of these would interfere with the operation
+    if !cli.force && refine.is_none() && !prints_message {
+        if let Some(operation) = get_operation_in_progress().await {
+            println!(
+                "{} A git {} is in progress; refusing to commit",
+                style("✗").red(),
+                operation.as_str()
+            );
+            println!(
+                "  {} Finish it with 'git {} --continue' or 'git {} --abort', or pass --force",
+                style("→").dim(),
+                operation.as_str(),
+                operation.as_str()
+            );
+            std::process::exit(exit_code::FAILURE);
+        }
+    }
+
+    // Get API key
+    let api_key = match get_api_key(&config, cli.api_key_file.as_deref())? {
+        Some(key) => key,
+        // Estimating sends no generation request, so it works without a key
+        None if cli.count => String::new(),
+        // Local servers usually don't check one
+        None if !uses_openrouter() => String::new(),
+        None => {
+            println!("{} No API key found", style("✗").red());
+            println!(
+                "  {} Set OPENROUTER_API_KEY, api_key in the config file, or pass --api-key-file",
+                style("→").dim()
+            );
+            std::process::exit(exit_code::FAILURE);
+        }
+    };
+
+    // Stage all changes if requested
+    if cli.all {
+        stage_all_changes().await?;
+    }
+
+    // Determine verbose mode (CLI flag overrides config)
+    let verbose = cli.verbose || config.verbose;
+
+    let template = match &cli.template_file {
+        Some(path) => match std::fs::read_to_string(path) {
+            Ok(template) => Some(template),
+            Err(e) => {
+                println!(
+                    "{} Failed to read template file '{}': {}",
+                    style("✗").red(),
+                    path.display(),
+                    e
+                );
+                std::process::exit(exit_code::FAILURE);
+            }
+        },
+        None => None,
+    };
+
+    // Resolve trailers up front so a missing identity fails before generation
+    let overrides = PolicyOverrides {
+        signoff: cli.signoff,
+        co_authors: cli.co_authors.clone(),
+        max_body_lines: cli.max_body_lines,
+        scope: cli.assume_scope.clone(),
+        template,
+        emoji: cli.emoji,
+        verbose: cli.verbose,
+    };
+    let policy = match CommitPolicy::from_config(&config, &overrides).await {
+        Ok(policy) => policy,
+        Err(e) => {
+            println!("{} {}", style("✗").red(), e);
+            std::process::exit(exit_code::FAILURE);
+        }
+    };
+
+    let output_mode = OutputMode::from_flags(cli.quiet, cli.json);
+
+    let mode = RunMode {
+        refine,
+        stdin_commits,
+        prints_message,
+        print_only,
+        yes,
+    };
+    let code = run_commit(&cli, &config, &api_key, policy, mode, output_mode, verbose).await?;
+    if code != exit_code::SUCCESS {
+        std::process::exit(code);
+    }
+    Ok(())
+}
+
+/// How a commit run behaves, worked out from the flags before it starts.
+struct RunMode {
+    /// The message `--refine` reformats instead of generating one.
+    refine: Option<RefineTarget>,
+    /// Commit a piped `--stdin-diff` (only with `--yes` and `--repo`).
+    stdin_commits: bool,
+    /// Print the message for a piped diff instead of committing.
+    prints_message: bool,
+    /// Stdout carries only the result, e.g. a branch name or JSON.
+    print_only: bool,
+    /// Never prompt (`--yes`, or `--json`).
+    yes: bool,
+}
+
+/// Runs the commit flow once the policy is set up, returning the exit code.
+///
+/// The index snapshot taken by `--hunk-filter` or `--select-files` is owned
+/// here, so every return, including errors, restores the index unless the
+/// run committed. Exit only after this returns.
+async fn run_commit(
+    cli: &Cli,
+    config: &Config,
+    api_key: &str,
+    mut policy: CommitPolicy,
+    mode: RunMode,
+    output_mode: OutputMode,
+    verbose: bool,
+) -> Result<i32, Box<dyn std::error::Error>> {
+    let RunMode {
+        refine,
+        stdin_commits,
+        prints_message,
+        print_only,
+        yes,
+    } = mode;
+
+    // Stage matching hunks or the chosen files. The snapshot lives until this
+    // function returns, then restores the index unless we committed
+    let _index_snapshot = if let Some(pattern) = &cli.hunk_filter {
+        match stage_matching_hunks(pattern, verbose).await {
+            Ok(snapshot) => Some(snapshot),
+            Err(e) => {
+                println!("{} {}", style("✗").red(), e);
+                return Ok(exit_code::FAILURE);
+            }
+        }
+    } else if cli.select_files {
+        let files = match get_changed_files().await {
+            Ok(files) => files,
+            Err(e) => {
+                println!("{} {}", style("✗").red(), e);
+                return Ok(exit_code::FAILURE);
+            }
+        };
+        if files.is_empty() {
+            report_outcome(output_mode, "nothing_to_commit", || {
+                println!("{} Nothing to commit", style("✓").green());
+            });
+            return Ok(exit_code::SUCCESS);
+        }
+        // Nothing has been staged yet, so cancelling leaves the index untouched
+        let Some(selected) = prompt_select_files(&files) else {
+            println!("{} Cancelled", style("—").dim());
+            return Ok(exit_code::SUCCESS);
+        };
+        match stage_selected_files(&files, &selected, verbose).await {
+            Ok(snapshot) => Some(snapshot),
+            Err(e) => {
+                println!("{} {}", style("✗").red(), e);
+                return Ok(exit_code::FAILURE);
+            }
+        }
+    } else {
+        None
+    };
+
+    // A merge's diff against its first parent is the whole merged branch, which
+    // makes for a misleading message
+    if cli.amend && head_is_merge().await {
+        println!(
+            "{} HEAD is a merge commit; refusing to amend it",
+            style("✗").red()
+        );
+        println!(
+            "  {} Edit a merge message with 'git commit --amend' instead",
+            style("→").dim()
+        );
+        return Ok(exit_code::FAILURE);
+    }
+
+    // Get diff, file list, and status from one written index tree, so staging
+    // mid-run can't make them disagree. The status alone decides "nothing to
+    // commit" vs "nothing staged".
+    let (diff_result, files_result, status_result) = if cli.stdin_diff {
+        let (diff, files) = read_stdin_diff(cli.files_from_stdin, verbose)?;
+        let status = if stdin_commits {
+            get_uncommitted_changes().await.map(Some)
+        } else {
+            Ok(None)
+        };
+        (Ok(diff), Ok(files), status)
+    } else if cli.amend {
+        let (diff, files) = tokio::join!(
+            get_last_commit_diff(verbose),
+            get_last_commit_files(verbose)
+        );
+        (diff, files, Ok(None))
+    } else {
+        let index = IndexTree::write().await?;
+        let (diff, files, status) =
+            tokio::join!(index.diff(verbose), index.files(verbose), index.status());
+        (diff, files, status.map(Some))
+    };
+
+    let mut diff = diff_result?;
+    let files = files_result?;
+    let status = status_result?;
+
+    if cli.stdin_diff && diff.trim().is_empty() {
+        eprintln!("{} No diff on stdin", style("✗").red());
+        return Ok(exit_code::FAILURE);
+    }
+
+    if cli.amend && diff.trim().is_empty() {
+        report_outcome(output_mode, "nothing_to_amend", || {
+            println!(
+                "{} Nothing to amend: last commit is empty and nothing is staged",
+                style("✗").red()
+            );
+        });
+        return Ok(exit_code::FAILURE);
+    }
+
+    // `--allow-empty` turns "nothing staged" into an empty commit
+    let empty_commit = cli.allow_empty
+        && status
+            .as_ref()
+            .is_some_and(|status| status.staged.is_empty());
+
+    if let Some(status) = status.as_ref().filter(|_| !empty_commit) {
+        if let (Some(refine), true) = (&refine, status.staged.is_empty()) {
+            if verbose {
+                eprintln!("— Nothing staged to refine the message against; leaving it as is");
+            }
+            refine.keep()?;
+            return Ok(exit_code::SUCCESS);
+        }
+        match status.staged_state() {
+            StagedState::NothingToCommit => {
+                report_outcome(output_mode, "nothing_to_commit", || {
+                    println!("{} Nothing to commit", style("✓").green());
+                });
+                return Ok(exit_code::SUCCESS);
+            }
+            StagedState::NothingStaged => {
+                report_outcome(output_mode, "no_staged_changes", || {
+                    println!("{} No staged changes", style("⚠").yellow());
+                    println!("  {} Use 'git add' or --all", style("→").dim());
+                });
+                return Ok(exit_code::NOTHING_STAGED);
+            }
+            StagedState::Staged => {}
+        }
+    }
+
+    // Catch the "forgot to pull" case before spending a generation on it
+    if config.warn_if_behind && refine.is_none() && !prints_message {
+        if cli.fetch {
+            if let Err(e) = fetch_with_spinner().await {
+                println!("{} {}", style("⚠").yellow(), e);
+            }
+        }
+
+        let warning = match get_upstream_state().await {
+            UpstreamState::Detached => Some((
+                "HEAD is detached; the commit won't be on any branch".to_string(),
+                "Create or switch to a branch with 'git switch'".to_string(),
+            )),
+            UpstreamState::Tracking { upstream, behind } if behind > 0 => Some((
+                format!(
+                    "Branch is {} commit{} behind {}",
+                    behind,
+                    if behind == 1 { "" } else { "s" },
+                    upstream
+                ),
+                "You may want to pull or rebase first".to_string(),
+            )),
+            _ => None,
+        };
+
+        if let Some((warning, hint)) = warning {
+            // Non-interactive runs only warn, on stderr so scripted output stays clean
+            if yes || config.auto_commit || cli.dry_run || cli.print_branch {
+                eprintln!("{} {}", style("⚠").yellow(), warning);
+            } else if !can_prompt() {
+                println!("{} {}", style("✗").red(), NO_TTY_MESSAGE);
+                return Ok(exit_code::FAILURE);
+            } else if !prompt_continue_stale(&warning, &hint, &mut StdinInput) {
+                println!("{} Cancelled", style("—").dim());
+                return Ok(exit_code::SUCCESS);
+            }
+        }
+    }
+
+    // A saved message replaces generation; the staged changes may have moved on since
+    let recovered = if cli.from_recovery {
+        let Some(recovery) = load_recovery().await else {
+            println!("{} No saved message to recover", style("✗").red());
+            println!(
+                "  {} Messages are saved when a commit is cancelled or fails",
+                style("→").dim()
+            );
+            return Ok(exit_code::FAILURE);
+        };
+        if recovery.tree.is_some() && recovery.tree != get_index_tree().await {
+            let warning = "Staged changes differ from when this message was generated; \
+                           it may no longer match";
+            if yes || config.auto_commit || cli.dry_run {
+                eprintln!("{} {}", style("⚠").yellow(), warning);
+            } else {
+                println!("{} {}", style("⚠").yellow(), warning);
+            }
+        }
+        Some(recovery)
+    } else {
+        None
+    };
+    let head_before = get_head_sha().await;
+
+    // Squashing keeps the target's message, so there's nothing to generate
+    if let Some(reference) = &cli.squash_into {
+        let target = match resolve_fixup_target(reference).await {
+            Ok(target) => target,
+            Err(e) => {
+                println!("{} {}", style("✗").red(), e);
+                return Ok(exit_code::FAILURE);
+            }
+        };
+        let label = format!("{} {}", short_sha(&target.sha), target.subject);
+
+        if cli.dry_run {
+            println!(
+                "{} Would fold staged changes into {}",
+                style("→").dim(),
+                style(&label).cyan()
+            );
+            return Ok(exit_code::SUCCESS);
+        }
+
+        let fixup_message = format!("fixup! {}", target.subject);
+        let fixup_options = CommitOptions {
+            no_verify: cli.no_verify,
+            verbose,
+            ..Default::default()
+        };
+        policy.commit(&fixup_message, &fixup_options).await?;
+
+        if let Err(e) = autosquash_into(&target).await {
+            println!("{} Could not fold changes into {}", style("✗").red(), label);
+            println!("  {} {}", style("→").dim(), e);
+            println!(
+                "  {} The rebase was aborted; your changes are in the '{}' commit at HEAD",
+                style("→").dim(),
+                fixup_message
+            );
+            println!(
+                "  {} Resolve with 'git rebase -i --autosquash {}^' or undo with 'git reset --soft HEAD~1'",
+                style("→").dim(),
+                short_sha(&target.sha)
+            );
+            return Ok(exit_code::FAILURE);
+        }
+
+        println!(
+            "{} Folded into {}",
+            style("✓").green(),
+            style(&label).cyan()
+        );
+        return Ok(exit_code::SUCCESS);
+    }
+
+    if let Some(command) = &config.diff_preprocessor {
+        diff = run_diff_preprocessor(command, &diff, verbose).await?;
+    }
+    if cli.from.is_some() || refine.is_some() {
+        diff = truncate_diff_to(&diff, DESCRIBED_DIFF_CHARS, verbose);
+    }
+
+    if empty_commit {
+        diff = "[No changes: this is an empty commit]".to_string();
+    } else if diff.trim().is_empty() {
+        // Staged, but nothing textual survives filtering (e.g. only lock files)
+        if verbose {
+            eprintln!("— Staged changes have no textual diff; using file list only");
+        }
+        diff = diff_or_placeholder(diff);
+    }
+
+    // Determine which model to use
+    let model = &config.resolve_model(cli.model.as_deref().unwrap_or(&config.model));
+
+    // Pick up commit rules the repo declares for commitlint/gitlint, and its scope registry
+    let convention = load_commit_rules(get_repo_root().await.as_deref(), &config.valid_scopes);
+    if verbose {
+        if let Some(convention) = &convention {
+            eprintln!("— Commit conventions: {}", convention.source);
+        }
+    }
+    policy.allowed_scopes = convention.as_ref().and_then(|c| c.scopes.clone());
+    policy.lint_rules =
+        (cli.lint || config.lint).then(|| LintRules::new(config, convention.as_ref()));
+    if let Some(max) = convention.as_ref().and_then(|c| c.header_max_length) {
+        policy.subject_max_len = max;
+    }
+    if let Some(refine) = &refine {
+        let rules = LintRules::new(config, convention.as_ref());
+        if validate_conventional(&refine.message, &rules).is_ok() {
+            if verbose {
+                eprintln!("— Message already follows Conventional Commits; leaving it as is");
+            }
+            refine.keep()?;
+            return Ok(exit_code::SUCCESS);
+        }
+    }
+    // A recovered message came from an earlier run, maybe with another model,
+    // and the empty-commit message isn't generated at all
+    if config.stamp_metadata
+        && !cli.from_recovery
+        && refine.is_none()
+        && !(empty_commit && cli.from.is_none())
+    {
+        policy.trailers.extend(metadata_trailers(model));
+    }
+    let mut prompt_notes = collect_prompt_notes(&diff, convention.as_ref(), config).await;
+    if let Some(description) = &cli.from {
+        prompt_notes.push(description_prompt_note(description));
+    }
+    if let Some(refine) = &refine {
+        prompt_notes.push(refine_prompt_note(&refine.message));
+    }
+    if cli.body || config.body {
+        prompt_notes.push(BODY_PROMPT_NOTE.to_string());
+    }
+    if let Some(ticket) = &policy.ticket {
+        if verbose {
+            eprintln!("— Ticket from branch name: {}", ticket);
+        }
+        prompt_notes.push(ticket_prompt_note(ticket));
+    } else if verbose && config.ticket_in_subject != TicketPlacement::Off {
+        eprintln!("— No ticket in branch name; subject left as generated");
+    }
+
+    if let Some(scope) = &cli.assume_scope {
+        if let Some(convention) = convention.as_ref().filter(|c| !c.allows_scope(scope)) {
+            println!(
+                "{} Scope '{}' is not allowed by {} (allowed: {})",
+                style("✗").red(),
+                scope,
+                convention.source,
+                convention.scopes.as_deref().unwrap_or_default().join(", ")
+            );
+            return Ok(exit_code::FAILURE);
+        }
+        prompt_notes.push(format!(
+            "Use \"{}\" as the scope: type({}): description",
+            scope, scope
+        ));
+    }
+
+    // An inverse diff reads like new work to the model unless it knows what it undoes
+    let reverted = if cli.amend || empty_commit || recovered.is_some() {
+        None
+    } else {
+        find_reverted_commit(verbose).await
+    };
+    if let Some(reverted) = &reverted {
+        if verbose {
+            eprintln!(
+                "— Staged changes revert {} {}",
+                short_sha(&reverted.sha),
+                reverted.subject
+            );
+        }
+        prompt_notes.push(revert_prompt_note(reverted));
+    }
+
+    // Create HTTP client
+    let timeout_secs = cli.timeout.unwrap_or(config.timeout_secs);
+    let temperature = cli.temperature.or(config.temperature);
+    let max_tokens = cli.max_tokens.or(config.max_tokens);
+    let client = build_client(&config.extra_headers, timeout_secs)?;
+    if verbose && !config.extra_headers.is_empty() {
+        eprintln!(
+            "— Extra API headers: {}",
+            describe_headers(&config.extra_headers)
+        );
+    }
+    let provider = cli.provider_only.as_deref().map(ProviderPreference::only);
+
+    // The editor gets the whole diff, not the part that fits the model
+    let editor_diff = (cli.verbose_editor || config.verbose_editor).then(|| diff.clone());
+
+    // Fit the diff to the model's context window when its size is known
+    let diff = fit_diff_to_context(&client, model, diff, &files, verbose).await;
+
+    // Estimate from the fully assembled prompt and stop before any request
+    if cli.count {
+        let prompt_tokens = estimate_tokens(COMMIT_INSTRUCTIONS)
+            + estimate_tokens(&build_prompt(&diff, &files, &prompt_notes));
+        let info = get_model_info(&client, model, verbose).await;
+        // A configured cap is what the output can actually reach
+        let output_tokens = max_tokens.map_or(OUTPUT_RESERVE_TOKENS, |max| max as usize);
+        print_token_estimate(model, prompt_tokens, output_tokens, info.as_ref());
+        return Ok(exit_code::SUCCESS);
+    }
+
+    // Stream the commit message with spinner
+    // Cursor codes would end up in output meant for a pipe
+    let term = if print_only {
+        Term::stderr()
+    } else {
+        Term::stdout()
+    };
+    let spinner = phase_spinner(phase_message("Generating commit message", model));
+    let mut generation_options = GenerationOptions {
+        show_stream: !print_only,
+        show_tokens: config.show_stream && !cli.no_stream_display,
+        structured_output: config.structured_output,
+        verbose,
+        prompt_notes,
+        stream_buffer_size: config.stream_buffer_size,
+        provider: provider.clone(),
+        fallback_model: config
+            .fallback_model
+            .as_deref()
+            .map(|model| config.resolve_model(model)),
+        conversation: Vec::new(),
+        timeout_secs,
+        max_retries: config.max_retries,
+        temperature,
+        max_tokens,
+        // Notes record the tokens too
+        request_usage: cli.json || config.attach_notes,
+    };
+
+    // Branch analysis needs the message, but its git inputs can be read
+    // while the message is generated
+    let branch_inputs = async {
+        if cli.branch || cli.auto_branch {
+            Some(tokio::join!(get_current_branch(), get_recent_commits(5)))
+        } else {
+            None
+        }
+    };
+    // A message generated earlier for the same prompt is reused
+    let message_cache = MessageCache::new(config.cache_ttl_secs);
+    let cache_key = (!cli.no_cache && config.cache_ttl_secs > 0)
+        .then(|| cache_key(model, &diff, &files, &generation_options.prompt_notes));
+    let cached = cache_key.as_deref().and_then(|key| message_cache.get(key));
+    // Tokens spent on the message, for `--json` and notes
+    let run_usage = std::cell::Cell::new(None::<Usage>);
+    // Set when the fallback model wrote the message, for notes
+    let run_fallback_model = std::cell::Cell::new(None::<String>);
+
+    // `None` means a candidate wasn't picked
+    let generation = async {
+        Some(match recovered {
+            Some(recovery) => {
+                if generation_options.show_stream {
+                    println!();
+                    println!("{}", recovery.message);
+                }
+                Ok(recovery.message)
+            }
+            // Without a description there's nothing to generate from
+            None if empty_commit && cli.from.is_none() => {
+                if generation_options.show_stream {
+                    println!();
+                    println!("{}", EMPTY_COMMIT_MESSAGE);
+                }
+                Ok(EMPTY_COMMIT_MESSAGE.to_string())
+            }
+            None if cli.candidates > 1 => {
+                let _ = term.hide_cursor();
+                spinner.set_message(phase_message("Generating candidates", model));
+                spinner.enable_steady_tick(std::time::Duration::from_millis(80));
+                let result = generate_candidates(
+                    &client,
+                    api_key,
+                    model,
+                    &diff,
+                    &files,
+                    &spinner,
+                    &generation_options,
+                    cli.candidates.into(),
+                )
+                .await;
+                spinner.finish_and_clear();
+                let _ = term.show_cursor();
+
+                match result {
+                    Ok((candidates, usage)) => {
+                        report_usage(&client, model, usage, verbose).await;
+                        run_usage.set(usage);
+                        let interactive = !(yes || config.auto_commit) && term.is_term();
+                        let chosen = if candidates.len() > 1 && interactive {
+                            match prompt_select_message(&candidates) {
+                                CommitAction::Commit(chosen) => chosen,
+                                _ => return None,
+                            }
+                        } else {
+                            candidates[0].clone()
                         };
-                        println!(
-                            "{} {} set to {}",
-                            style("✓").green(),
-                            style("commit_after_branch").cyan(),
-                            val_style
-                        );
+                        if generation_options.show_stream {
+                            println!();
+                            println!("{}", chosen);
+                        }
+                        Ok(chosen)
                     }
-                    ConfigAction::Model { value } => {
-                        config.model = value;
-                        save_config(&config)?;
-                        println!(
-                            "{} {} set to {}",
-                            style("✓").green(),
-                            style("model").cyan(),
-                            style(&config.model).yellow()
-                        );
+                    Err(e) => Err(e),
+                }
+            }
+            None => {
+                if let Some(message) = cached {
+                    if verbose {
+                        eprintln!("— Reusing the message generated for these changes (cached)");
                     }
-                    ConfigAction::Verbose { value } => {
-                        config.verbose = value.parse().unwrap_or(false);
-                        save_config(&config)?;
-                        let val_style = if config.verbose {
-                            style("true").green()
-                        } else {
-                            style("false").dim()
-                        };
-                        println!(
-                            "{} {} set to {}",
-                            style("✓").green(),
-                            style("verbose").cyan(),
-                            val_style
-                        );
+                    if generation_options.show_stream {
+                        println!();
+                        println!("{}", message);
                     }
+                    return Some(Ok(message));
                 }
-                return Ok(());
+
+                let _ = term.hide_cursor();
+                spinner.enable_steady_tick(std::time::Duration::from_millis(80));
+
+                // Ensure spinner renders before starting API call
+                std::io::stdout().flush().ok();
+
+                let result = stream_commit_message(
+                    &client,
+                    api_key,
+                    model,
+                    &diff,
+                    &files,
+                    &spinner,
+                    &generation_options,
+                )
+                .await;
+                if let Ok(generated) = &result {
+                    report_usage(&client, model, generated.usage, verbose).await;
+                    run_usage.set(generated.usage);
+                    run_fallback_model.set(generated.fallback_model.clone());
+                    if let (Some(key), false) = (&cache_key, generated.message.is_empty()) {
+                        if let Err(e) = message_cache.put(key, &generated.message) {
+                            if verbose {
+                                eprintln!("— Could not cache the message: {}", e);
+                            }
+                        }
+                    }
+                }
+                result.map(|generated| generated.message)
             }
-            Commands::Pr(args) => {
-                return handle_pr_command(args, &config).await;
+        })
+    };
+    let (message_result, branch_inputs) = tokio::join!(generation, branch_inputs);
+    // The model that wrote the message
+    let mut message_model = run_fallback_model
+        .take()
+        .unwrap_or_else(|| model.to_string());
+    let Some(message_result) = message_result else {
+        println!("{} Cancelled", style("—").dim());
+        return Ok(exit_code::SUCCESS);
+    };
+
+    let _ = term.show_cursor();
+    // Reverts have a standard form; use it whenever the model doesn't
+    let message_result = match (&reverted, message_result) {
+        (Some(reverted), result)
+            if !result
+                .as_ref()
+                .is_ok_and(|m| is_revert_message(m, reverted)) =>
+        {
+            if verbose {
+                match &result {
+                    Err(e) => eprintln!(
+                        "— Generation failed ({}), using the standard revert message",
+                        e
+                    ),
+                    Ok(_) => eprintln!(
+                        "— Response isn't in revert form; using the standard revert message"
+                    ),
+                }
+            }
+            let fallback = revert_message(reverted);
+            if !print_only {
+                println!();
+                println!("{}", fallback);
             }
+            Ok(fallback)
         }
+        (_, result) => result,
+    };
+
+    // The author's message stands when it can't be refined
+    if let (Some(refine), Err(e)) = (&refine, &message_result) {
+        eprintln!(
+            "{} Couldn't refine the message ({}); leaving it as written",
+            style("⚠").yellow(),
+            e
+        );
+        refine.keep()?;
+        return Ok(exit_code::SUCCESS);
     }
 
-    // Get API key
-    let api_key = match get_api_key() {
-        Some(key) => key,
-        None => {
-            println!("{} No API key found", style("✗").red());
+    // A bare submodule bump gives the model little to go on; describe it locally
+    // rather than failing or committing an empty message
+    let mut generated = match message_result {
+        Ok(message) if !message.is_empty() || !is_submodule_only_diff(&diff) => message,
+        result if is_submodule_only_diff(&diff) => {
+            if let (Err(e), true) = (&result, verbose) {
+                eprintln!("— Generation failed ({}), using submodule fallback", e);
+            }
+            let fallback = submodule_fallback_message(&parse_submodule_updates(&diff));
+            if !print_only {
+                println!();
+                println!("{}", fallback);
+            }
+            fallback
+        }
+        Err(e) if e.is::<RequestTimeout>() => {
+            println!("{} {}", style("✗").red(), e);
             println!(
-                "  {} Set OPENROUTER_API_KEY environment variable",
+                "  {} Raise the limit with --timeout or 'committer config timeout <secs>'",
                 style("→").dim()
             );
-            std::process::exit(1);
+            return Ok(exit_code::FAILURE);
         }
+        result => result?,
     };
 
-    // Stage all changes if requested
-    if cli.all {
-        stage_all_changes().await?;
+    // Ask once for a shorter subject; the limit applies after the template and ticket
+    let prepared_width = message_stats(&policy.prepare(&generated)).subject_width;
+    if config.on_too_long == SubjectOverflow::Regenerate
+        && prepared_width > policy.subject_max_len
+        && !cli.from_recovery
+        && (!print_only || cli.json)
+    {
+        let overhead = prepared_width.saturating_sub(message_stats(&generated).subject_width);
+        let target = policy.subject_max_len.saturating_sub(overhead);
+        if !print_only {
+            println!(
+                "{} Subject is {} characters (limit {}); asking for a shorter one",
+                style("→").dim(),
+                prepared_width,
+                policy.subject_max_len
+            );
+        }
+        generation_options
+            .conversation
+            .push(Message::assistant(generated.as_str()));
+        generation_options.conversation.push(Message::user(format!(
+            "The first line is too long. Rewrite it in at most {} characters, keeping \
+             the rest of the message as it is.\n\n\
+             Reply with only the revised commit message.",
+            target
+        )));
+
+        let _ = term.hide_cursor();
+        let shorten_spinner = phase_spinner(phase_message("Shortening subject", model));
+        shorten_spinner.enable_steady_tick(std::time::Duration::from_millis(80));
+        let shortened = stream_commit_message(
+            &client,
+            api_key,
+            model,
+            &diff,
+            &files,
+            &shorten_spinner,
+            &generation_options,
+        )
+        .await;
+        let _ = term.show_cursor();
+        match shortened {
+            Ok(shortened) if !shortened.message.trim().is_empty() => {
+                report_usage(&client, model, shortened.usage, verbose).await;
+                run_usage.set(Usage::combine(run_usage.get(), shortened.usage));
+                generated = shortened.message;
+            }
+            result => {
+                if let (Err(e), true) = (&result, verbose) {
+                    eprintln!("— Shortening failed ({}), keeping the first message", e);
+                }
+                generation_options
+                    .conversation
+                    .truncate(generation_options.conversation.len() - 2);
+            }
+        }
     }
 
-    // Determine verbose mode (CLI flag overrides config)
-    let verbose = cli.verbose || config.verbose;
+    let rejected_scope = policy.rejected_scope(&generated).map(str::to_string);
+    let mut message = policy.prepare(&generated);
 
-    // Get diff and file list in parallel
-    let (diff_result, files_result) =
-        tokio::join!(get_git_diff(true, verbose), get_staged_files(verbose));
+    // prepare() drops a scope outside the allowed set; interactively, offer the closest one
+    if let (Some(rejected), false) = (&rejected_scope, print_only) {
+        let interactive = !(yes || config.auto_commit || cli.dry_run) && term.is_term();
+        let suggestion = policy
+            .allowed_scopes
+            .as_deref()
+            .and_then(|allowed| closest_scope(rejected, allowed));
+        match suggestion {
+            Some(suggestion)
+                if interactive && prompt_use_scope(rejected, suggestion, &mut StdinInput) =>
+            {
+                message = set_scope(&message, suggestion);
+            }
+            _ => println!(
+                "{} Dropped scope '{}', which isn't an allowed scope",
+                style("→").dim(),
+                rejected
+            ),
+        }
+    }
 
-    let diff = diff_result?;
-    let files = files_result?;
+    if !print_only && message != generated {
+        println!();
+        let kept_lines = message_stats(&message).body_lines;
+        if kept_lines < message_stats(&generated).body_lines {
+            println!("{} Trimmed to {} body lines:", style("→").dim(), kept_lines);
+        } else {
+            println!("{} Reformatted:", style("→").dim());
+        }
+        println!("{}", message);
+    }
 
-    if diff.trim().is_empty() {
-        // Check if there are any unstaged or untracked changes
-        let status_output = Command::new("git")
-            .args(["status", "--porcelain"])
-            .output()
-            .await?;
+    if let Some(refine) = &refine {
+        if message.is_empty() {
+            refine.keep()?;
+        } else {
+            refine.write(&message)?;
+        }
+        return Ok(exit_code::SUCCESS);
+    }
 
-        let status = String::from_utf8_lossy(&status_output.stdout);
+    if message.is_empty() {
+        spinner.finish_and_clear();
+        println!("{} Empty commit message generated", style("✗").red());
+        return Ok(exit_code::FAILURE);
+    }
 
-        if status.trim().is_empty() {
-            println!("{} Nothing to commit", style("✓").green());
-            std::process::exit(0);
+    if prints_message {
+        if output_mode == OutputMode::Json {
+            report_run(&message, model, run_usage.get(), None, None, false);
         } else {
-            println!("{} No staged changes", style("⚠").yellow());
-            println!("  {} Use 'git add' or --all", style("→").dim());
-            std::process::exit(1);
+            println!("{}", message);
         }
+        return Ok(exit_code::SUCCESS);
     }
 
-    // Determine which model to use
-    let model = cli.model.as_ref().unwrap_or(&config.model);
+    if !print_only && policy.subject_too_long(&message) {
+        println!(
+            "{} Subject is {} characters, over the limit of {}",
+            style("⚠").yellow(),
+            message_stats(&message).subject_width,
+            policy.subject_max_len
+        );
+    }
 
-    // Create HTTP client
-    let client = Client::builder().build()?;
+    // Conversational refinement: each critique is a new turn after the previous response
+    let mut refined = false;
+    if cli.interactive_regenerate {
+        let mut last_response = generated.clone();
+        loop {
+            match prompt_feedback() {
+                Feedback::Accept => {
+                    refined = true;
+                    break;
+                }
+                Feedback::Cancel => {
+                    println!("{} Cancelled", style("—").dim());
+                    record_history(config, model, &message, Outcome::Cancelled).await;
+                    save_recovery(&message).await;
+                    return Ok(exit_code::SUCCESS);
+                }
+                Feedback::Revise(feedback) => {
+                    generation_options
+                        .conversation
+                        .push(Message::assistant(last_response.as_str()));
+                    generation_options.conversation.push(Message::user(format!(
+                        "Revise the commit message based on this feedback: {}\n\n\
+                         Reply with only the revised commit message.",
+                        feedback
+                    )));
 
-    // Stream the commit message with spinner
-    let term = Term::stdout();
-    let _ = term.hide_cursor();
-
-    let spinner = ProgressBar::new_spinner();
-    spinner.set_style(
-        ProgressStyle::default_spinner()
-            .tick_strings(&["⠋", "⠙", "⠹", "⠸", "⠼", "⠴", "⠦", "⠧", "⠇", "⠏"])
-            .template("{spinner:.cyan} Generating commit message...")
-            .unwrap(),
-    );
-    spinner.enable_steady_tick(std::time::Duration::from_millis(80));
+                    let _ = term.hide_cursor();
+                    let revise_spinner =
+                        phase_spinner(phase_message("Revising commit message", model));
+                    revise_spinner.enable_steady_tick(std::time::Duration::from_millis(80));
+                    let revised = stream_commit_message(
+                        &client,
+                        api_key,
+                        model,
+                        &diff,
+                        &files,
+                        &revise_spinner,
+                        &generation_options,
+                    )
+                    .await;
+                    let _ = term.show_cursor();
+                    if let Ok(generated) = &revised {
+                        report_usage(&client, model, generated.usage, verbose).await;
+                        run_usage.set(Usage::combine(run_usage.get(), generated.usage));
+                        if !generated.message.trim().is_empty() {
+                            message_model = generated
+                                .fallback_model
+                                .clone()
+                                .unwrap_or_else(|| model.to_string());
+                        }
+                    }
+                    let revised = revised.map(|generated| generated.message);
 
-    // Ensure spinner renders before starting API call
-    std::io::stdout().flush().ok();
+                    match revised {
+                        Ok(revised) if !revised.trim().is_empty() => {
+                            message = policy.prepare(&revised);
+                            last_response = revised;
+                        }
+                        Ok(_) => {
+                            println!(
+                                "{} Empty response; keeping the previous message",
+                                style("⚠").yellow()
+                            );
+                            generation_options
+                                .conversation
+                                .truncate(generation_options.conversation.len() - 2);
+                        }
+                        Err(e) => {
+                            println!("{} {}", style("✗").red(), e);
+                            println!("  {} Keeping the previous message", style("→").dim());
+                            generation_options
+                                .conversation
+                                .truncate(generation_options.conversation.len() - 2);
+                        }
+                    }
+                }
+            }
+        }
+    }
 
-    let message_result =
-        stream_commit_message(&client, &api_key, model, &diff, &files, &spinner, verbose).await;
+    // An invalid message goes back for review; without one, the run fails
+    if let Err(violations) = policy.lint(&message) {
+        if !print_only {
+            print_lint_violations(&violations);
+        }
+        if !cli.dry_run && !cli.print_branch && (yes || config.auto_commit || refined) {
+            if output_mode == OutputMode::Json {
+                let branch = get_current_branch().await.ok();
+                report_run(
+                    &policy.finalize(&message),
+                    model,
+                    run_usage.get(),
+                    branch.as_deref(),
+                    None,
+                    false,
+                );
+            }
+            save_recovery(&message).await;
+            return Ok(exit_code::FAILURE);
+        }
+    }
 
-    let _ = term.show_cursor();
-    let message = message_result?;
+    // Every suggested name is held to `branch_max_length`
+    let fit_branch = |name: String| match config.branch_max_length {
+        Some(max_len) => fit_branch_name(&name, max_len, config.branch_overflow_strategy),
+        None => name,
+    };
 
-    if message.is_empty() {
-        spinner.finish_and_clear();
-        println!("{} Empty commit message generated", style("✗").red());
-        std::process::exit(1);
+    if cli.print_branch {
+        let branch = match suggest_branch(
+            &client,
+            api_key,
+            model,
+            &message,
+            provider.as_ref(),
+            config.branch_suggestion_mode,
+        )
+        .await
+        {
+            Ok(branch) => branch,
+            Err(e) => {
+                eprintln!("{} Branch name generation failed: {}", style("✗").red(), e);
+                return Ok(exit_code::FAILURE);
+            }
+        };
+        let branch = fit_branch(branch);
+        if output_mode == OutputMode::Json {
+            let current = get_current_branch().await.ok();
+            report_run(
+                &policy.finalize(&message),
+                model,
+                run_usage.get(),
+                current.as_deref(),
+                Some(&branch),
+                false,
+            );
+        } else {
+            println!("{}", branch);
+        }
+        return Ok(exit_code::SUCCESS);
     }
 
+    let commit_options = CommitOptions {
+        amend: cli.amend,
+        allow_empty: empty_commit,
+        sign: sign_flag_needed(cli.sign || config.sign).await,
+        no_verify: cli.no_verify,
+        verbose,
+        ..Default::default()
+    };
+    let branch_skip_action = if cli.no_commit_on_branch_skip {
+        BranchSkipAction::Cancel
+    } else {
+        config.branch_skip_action
+    };
+    let done_label = if cli.amend { "Amended" } else { "Committed" };
+
     // Track if branch was already handled via --branch or --auto-branch flags
     let mut branch_already_handled = false;
+    // A branch created this run is undone if its first commit fails
+    let mut new_branch: Option<NewBranch> = None;
+    let mut suggested_branch = None;
 
-    if cli.branch || cli.auto_branch {
-        let current_branch = get_current_branch().await?;
-        let recent_commits = get_recent_commits(5).await.unwrap_or_default();
+    if let Some((current_branch, recent_commits)) = branch_inputs {
+        let current_branch = current_branch?;
+        let recent_commits = recent_commits.unwrap_or_default();
 
         let _ = term.hide_cursor();
 
-        let branch_spinner = ProgressBar::new_spinner();
-        branch_spinner.set_style(
-            ProgressStyle::default_spinner()
-                .tick_strings(&["⠋", "⠙", "⠹", "⠸", "⠼", "⠴", "⠦", "⠧", "⠇", "⠏"])
-                .template("{spinner:.cyan} Analyzing branch alignment...")
-                .unwrap(),
-        );
+        let branch_spinner = phase_spinner(phase_message("Analyzing branch alignment", model));
         branch_spinner.enable_steady_tick(std::time::Duration::from_millis(120));
 
         let analysis = analyze_branch_alignment(
             &client,
-            &api_key,
+            api_key,
             model,
             &current_branch,
             &message,
             &files,
             &recent_commits,
+            provider.as_ref(),
+            config.max_retries,
+            &branch_spinner,
+            temperature,
+            max_tokens,
+            config.branch_strictness,
         )
         .await?;
 
@@ -292,24 +2416,58 @@ async fn main() -> Result<(), Box<dyn std::error::Error>> {
         }
 
         if !analysis.matches {
-            let suggested = analysis
-                .suggested_branch
-                .unwrap_or_else(|| generate_fallback_branch(&message));
+            // The fallback mode keeps names deterministic even when analysis suggests one
+            let suggested = fit_branch(match analysis.suggested_branch {
+                Some(name) if config.branch_suggestion_mode != BranchSuggestionMode::Fallback => {
+                    name
+                }
+                _ => generate_fallback_branch(&message),
+            });
 
-            if cli.auto_branch || cli.yes {
+            suggested_branch = Some(suggested.clone());
+            if cli.auto_branch || yes {
+                if !print_only {
+                    println!(
+                        "{} Branch '{}' → '{}' ({})",
+                        style("→").cyan(),
+                        style(&current_branch).dim(),
+                        style(&suggested).green(),
+                        style(&analysis.reason).dim()
+                    );
+                }
+                new_branch = Some(create_and_switch_branch(&suggested).await?);
+                branch_already_handled = true;
+            } else if was_recently_declined(&current_branch, &suggested).await {
+                // Don't nag with a name the user just turned down; 'b' still offers a branch
                 println!(
-                    "{} Branch '{}' → '{}' ({})",
-                    style("→").cyan(),
+                    "{} Continuing on '{}' ('{}' was declined recently)",
+                    style("→").dim(),
                     style(&current_branch).dim(),
+                    style(&suggested).dim()
+                );
+            } else if !can_prompt() {
+                // A dry run only previews, so the suggestion is shown rather than asked about
+                if !cli.dry_run {
+                    println!("{} {}", style("✗").red(), NO_TTY_MESSAGE);
+                    save_recovery(&message).await;
+                    return Ok(exit_code::FAILURE);
+                }
+                println!(
+                    "{} Suggested branch '{}' ({})",
+                    style("→").dim(),
                     style(&suggested).green(),
                     style(&analysis.reason).dim()
                 );
-                create_and_switch_branch(&suggested).await?;
-                branch_already_handled = true;
             } else {
-                match prompt_branch_action(&current_branch, &suggested, &analysis.reason, true) {
+                match prompt_branch_action(
+                    &current_branch,
+                    &suggested,
+                    &analysis.reason,
+                    true,
+                    &mut StdinInput,
+                ) {
                     BranchAction::Create(name) => {
-                        create_and_switch_branch(&name).await?;
+                        new_branch = Some(create_and_switch_branch(&name).await?);
                         println!(
                             "{} Switched to branch '{}'",
                             style("✓").green(),
@@ -323,6 +2481,7 @@ async fn main() -> Result<(), Box<dyn std::error::Error>> {
                             style("→").dim(),
                             style(&current_branch).dim()
                         );
+                        record_declined_branch(&current_branch, &suggested).await;
                         branch_already_handled = true;
                     }
                 }
@@ -330,26 +2489,136 @@ async fn main() -> Result<(), Box<dyn std::error::Error>> {
         }
     }
 
+    // With a threshold, a confident message skips the review
+    let auto_threshold = config
+        .auto_accept_threshold
+        .or(cli.yes_if_match.then_some(100));
+    let mut auto_accepted = false;
+    if let (Some(threshold), false) = (auto_threshold, yes || config.auto_commit || refined) {
+        let confidence = assess_confidence(&message, &LintRules::new(config, convention.as_ref()));
+        if confidence.score >= threshold {
+            auto_accepted = policy.lint(&message).is_ok();
+            if auto_accepted {
+                println!(
+                    "{} Confidence {}% (threshold {}%), accepting without review",
+                    style("→").dim(),
+                    confidence.score,
+                    threshold
+                );
+            }
+        } else {
+            println!(
+                "{} Confidence {}% is below the {}% threshold, so it needs review:",
+                style("→").dim(),
+                confidence.score,
+                threshold
+            );
+            for shortfall in &confidence.shortfalls {
+                println!("  {} {}", style("→").dim(), shortfall);
+            }
+        }
+    }
+
+    if cli.dry_run && output_mode == OutputMode::Json {
+        let branch = get_current_branch().await.ok();
+        report_run(
+            &policy.finalize(&message),
+            model,
+            run_usage.get(),
+            branch.as_deref(),
+            suggested_branch.as_deref(),
+            false,
+        );
+        save_recovery(&message).await;
+        return Ok(exit_code::SUCCESS);
+    }
+
     if cli.dry_run {
-        return Ok(());
+        println!(
+            "{} Would run: {}",
+            style("→").dim(),
+            describe_commit_command(&policy.finalize(&message), &commit_options)
+        );
+        if cli.stats {
+            print_message_stats(&message_stats(&message), policy.subject_max_len);
+        }
+        if cli.copy.is_some() {
+            copy_for_user(&policy.finalize(&message), "commit message").await;
+        }
+        // Lets a previewed message be committed without another API call
+        save_recovery(&message).await;
+        return Ok(exit_code::SUCCESS);
     }
 
-    if cli.yes || config.auto_commit {
-        run_git_commit(&message).await?;
-        println!("{} Committed", style("✓").green());
+    if yes || config.auto_commit || refined || auto_accepted {
+        let committed = commit_or_roll_back(
+            &policy,
+            &message,
+            &commit_options,
+            new_branch.as_ref(),
+            false,
+        )
+        .await?;
+        if !print_only {
+            println!("{} {}", style("✓").green(), done_label);
+        }
+        record_history(config, model, &committed, Outcome::Accepted).await;
+        attach_note(
+            config,
+            &message_model,
+            Outcome::Accepted,
+            run_usage.get(),
+            verbose,
+        )
+        .await;
+    } else if !can_prompt() {
+        println!("{} {}", style("✗").red(), NO_TTY_MESSAGE);
+        save_recovery(&message).await;
+        return Ok(exit_code::FAILURE);
     } else {
+        let editor = resolve_editor(config.editor.as_deref());
         let mut show_branch_option = !branch_already_handled;
         let mut current_message = message.clone();
 
         loop {
-            match prompt_commit(&current_message, show_branch_option) {
+            match prompt_commit(
+                &current_message,
+                show_branch_option,
+                &editor,
+                editor_diff.as_deref(),
+                &mut StdinInput,
+            ) {
                 CommitAction::Commit(final_message) => {
-                    run_git_commit(&final_message).await?;
-                    println!("{} Committed", style("✓").green());
+                    if let Err(violations) = policy.lint(&final_message) {
+                        println!();
+                        print_lint_violations(&violations);
+                        println!();
+                        println!("{}", final_message);
+                        current_message = final_message;
+                        continue;
+                    }
+                    let committed = commit_or_roll_back(
+                        &policy,
+                        &final_message,
+                        &commit_options,
+                        new_branch.as_ref(),
+                        true,
+                    )
+                    .await?;
+                    println!("{} {}", style("✓").green(), done_label);
+                    let outcome = if final_message == message {
+                        Outcome::Accepted
+                    } else {
+                        Outcome::Edited
+                    };
+                    record_history(config, model, &committed, outcome).await;
+                    attach_note(config, &message_model, outcome, run_usage.get(), verbose).await;
                     break;
                 }
                 CommitAction::Cancel => {
                     println!("{} Cancelled", style("—").dim());
+                    record_history(config, model, &current_message, Outcome::Cancelled).await;
+                    save_recovery(&current_message).await;
                     break;
                 }
                 CommitAction::CreateBranch(msg) => {
@@ -357,30 +2626,34 @@ async fn main() -> Result<(), Box<dyn std::error::Error>> {
 
                     let _ = term.hide_cursor();
 
-                    let branch_spinner = ProgressBar::new_spinner();
-                    branch_spinner.set_style(
-                        ProgressStyle::default_spinner()
-                            .tick_strings(&["⠋", "⠙", "⠹", "⠸", "⠼", "⠴", "⠦", "⠧", "⠇", "⠏"])
-                            .template("{spinner:.cyan} Generating branch name...")
-                            .unwrap(),
-                    );
+                    // The local fallback makes no request, so there's no model to name
+                    let branch_spinner = phase_spinner(match config.branch_suggestion_mode {
+                        BranchSuggestionMode::Fallback => "Generating branch name...".to_string(),
+                        _ => phase_message("Generating branch name", model),
+                    });
                     branch_spinner.enable_steady_tick(std::time::Duration::from_millis(120));
 
-                    let suggested = match generate_branch_suggestion(
+                    let suggested = suggest_branch(
                         &client,
-                        &api_key,
+                        api_key,
                         model,
                         &current_message,
+                        provider.as_ref(),
+                        config.branch_suggestion_mode,
                     )
-                    .await
-                    {
-                        Ok(name) => name,
-                        Err(_) => generate_fallback_branch(&current_message),
-                    };
+                    .await;
 
                     branch_spinner.finish_and_clear();
                     let _ = term.show_cursor();
 
+                    let suggested = match suggested {
+                        Ok(name) => fit_branch(name),
+                        Err(e) => {
+                            println!("{} Branch name generation failed: {}", style("✗").red(), e);
+                            continue;
+                        }
+                    };
+
                     let current_branch = get_current_branch().await.unwrap_or_default();
                     println!(
                         "{} Suggested branch: {}",
@@ -389,43 +2662,318 @@ async fn main() -> Result<(), Box<dyn std::error::Error>> {
                     );
                     println!();
 
-                    let branch_created =
-                        match prompt_branch_action(&current_branch, &suggested, "", false) {
-                            BranchAction::Create(name) => {
-                                create_and_switch_branch(&name).await?;
-                                println!(
-                                    "{} Switched to branch '{}'",
-                                    style("✓").green(),
-                                    style(&name).green()
-                                );
-                                true
-                            }
-                            BranchAction::Skip => {
-                                println!(
-                                    "{} Continuing on '{}'",
-                                    style("→").dim(),
-                                    style(&current_branch).dim()
-                                );
-                                false
-                            }
-                        };
+                    let branch_created = match prompt_branch_action(
+                        &current_branch,
+                        &suggested,
+                        "",
+                        false,
+                        &mut StdinInput,
+                    ) {
+                        BranchAction::Create(name) => {
+                            new_branch = Some(create_and_switch_branch(&name).await?);
+                            println!(
+                                "{} Switched to branch '{}'",
+                                style("✓").green(),
+                                style(&name).green()
+                            );
+                            true
+                        }
+                        BranchAction::Skip => {
+                            println!(
+                                "{} Continuing on '{}'",
+                                style("→").dim(),
+                                style(&current_branch).dim()
+                            );
+                            false
+                        }
+                    };
 
-                    // Auto-commit if config enabled and branch was created
-                    if config.commit_after_branch && branch_created {
-                        run_git_commit(&current_message).await?;
-                        println!("{} Committed", style("✓").green());
+                    // Auto-commit if config enabled and branch was created; a
+                    // message that fails lint is offered for review instead
+                    let passes_lint = policy.lint(&current_message).is_ok();
+                    if config.commit_after_branch && branch_created && passes_lint {
+                        let committed = commit_or_roll_back(
+                            &policy,
+                            &current_message,
+                            &commit_options,
+                            new_branch.as_ref(),
+                            true,
+                        )
+                        .await?;
+                        println!("{} {}", style("✓").green(), done_label);
+                        let outcome = if current_message == message {
+                            Outcome::Accepted
+                        } else {
+                            Outcome::Edited
+                        };
+                        record_history(config, model, &committed, outcome).await;
+                        attach_note(config, &message_model, outcome, run_usage.get(), verbose)
+                            .await;
                         break;
                     }
 
+                    // Staying on the current branch: follow `branch_skip_action`
+                    if !branch_created {
+                        match branch_skip_action {
+                            BranchSkipAction::Prompt => {}
+                            BranchSkipAction::Commit if !passes_lint => {}
+                            BranchSkipAction::Commit => {
+                                let committed =
+                                    policy.commit(&current_message, &commit_options).await?;
+                                println!("{} {}", style("✓").green(), done_label);
+                                let outcome = if current_message == message {
+                                    Outcome::Accepted
+                                } else {
+                                    Outcome::Edited
+                                };
+                                record_history(config, model, &committed, outcome).await;
+                                attach_note(
+                                    config,
+                                    &message_model,
+                                    outcome,
+                                    run_usage.get(),
+                                    verbose,
+                                )
+                                .await;
+                                break;
+                            }
+                            BranchSkipAction::Cancel => {
+                                println!("{} Cancelled", style("—").dim());
+                                record_history(config, model, &current_message, Outcome::Cancelled)
+                                    .await;
+                                save_recovery(&current_message).await;
+                                break;
+                            }
+                        }
+                    }
+
                     println!();
                     println!("{}", current_message);
 
-                    // Disable branch option for next iteration
+                    // The branch question has been answered; don't offer it again
                     show_branch_option = false;
                 }
             }
         }
     }
 
-    Ok(())
+    let committed = get_head_sha().await != head_before;
+
+    if output_mode == OutputMode::Json {
+        let final_message = match committed {
+            true => get_head_message().await,
+            false => None,
+        };
+        let branch = get_current_branch().await.ok();
+        report_run(
+            final_message
+                .as_deref()
+                .map(str::trim_end)
+                .unwrap_or(&policy.finalize(&message)),
+            model,
+            run_usage.get(),
+            branch.as_deref(),
+            suggested_branch.as_deref(),
+            committed,
+        );
+    }
+
+    // The recovered message is used up once it has been committed
+    if cli.from_recovery && committed {
+        clear_recovery().await;
+    }
+
+    if let (Some(target), true) = (cli.copy, committed) {
+        let copied = match target {
+            CopyTarget::Message => get_head_message().await.map(|m| (m, "commit message")),
+            CopyTarget::Sha => get_head_sha().await.map(|sha| (sha, "commit SHA")),
+        };
+        if let Some((text, what)) = copied {
+            copy_for_user(&text, what).await;
+        }
+    }
+
+    Ok(exit_code::SUCCESS)
+}
+
+/// Commits with `policy`, offering to undo `new_branch` if the commit fails.
+///
+/// Without a prompt (`interactive` false, e.g. `--auto-branch -y`) the branch
+/// is undone automatically, so a failed run leaves the repository as it was.
+async fn commit_or_roll_back(
+    policy: &CommitPolicy,
+    message: &str,
+    options: &CommitOptions,
+    new_branch: Option<&NewBranch>,
+    interactive: bool,
+) -> Result<String, Box<dyn std::error::Error>> {
+    let result = policy.commit(message, options).await;
+    let (Err(_), Some(branch)) = (&result, new_branch) else {
+        return result;
+    };
+
+    println!(
+        "{} Commit failed on the new branch '{}'",
+        style("✗").red(),
+        branch.name
+    );
+    if interactive && !prompt_roll_back_branch(branch, &mut StdinInput) {
+        return result;
+    }
+    match roll_back_new_branch(branch).await {
+        Ok(()) => println!(
+            "{} Switched back to '{}' and deleted '{}'",
+            style("↩").cyan(),
+            branch.original,
+            branch.name
+        ),
+        Err(e) => println!(
+            "{} Could not undo the new branch: {}",
+            style("⚠").yellow(),
+            e
+        ),
+    }
+    result
+}
+
+/// Styles a configured model name, showing the model id an alias stands for.
+fn describe_model(config: &Config, name: &str) -> String {
+    let resolved = config.resolve_model(name);
+    if resolved == name {
+        style(name).yellow().to_string()
+    } else {
+        format!(
+            "{} {}",
+            style(name).yellow(),
+            style(format!("→ {}", resolved)).dim()
+        )
+    }
+}
+
+/// Asks the user to pick a model from the OpenRouter list for `config model`.
+///
+/// Exits without changing anything if there's no terminal to prompt on, the
+/// list can't be fetched, or the user cancels.
+async fn pick_model(config: &Config) -> Result<String, Box<dyn std::error::Error>> {
+    if !Term::stderr().is_term() {
+        println!("{} No model given", style("✗").red());
+        println!(
+            "  {} Pass one: committer config model <id>",
+            style("→").dim()
+        );
+        std::process::exit(exit_code::FAILURE);
+    }
+
+    let client = build_client(&config.extra_headers, config.timeout_secs)?;
+    let Some(mut models) = get_models(&client, config.verbose).await else {
+        println!("{} Could not fetch the models list", style("✗").red());
+        std::process::exit(exit_code::FAILURE);
+    };
+    models.sort_by(|a, b| a.id.cmp(&b.id));
+
+    match prompt_select_model(&models, &config.model) {
+        Some(index) => Ok(models.swap_remove(index).id),
+        None => {
+            println!("{} Model unchanged", style("—").dim());
+            std::process::exit(exit_code::SUCCESS);
+        }
+    }
+}
+
+/// Reads the diff and file list for `--stdin-diff`.
+///
+/// The diff is cleaned up like git's (control characters, exclusions, size
+/// limit). With `files_from_stdin`, the lines before the first `diff --git`
+/// header are the file list; otherwise it's worked out from the diff.
+fn read_stdin_diff(
+    files_from_stdin: bool,
+    verbose: bool,
+) -> Result<(String, String), Box<dyn std::error::Error>> {
+    let input = std::io::read_to_string(std::io::stdin())?;
+    let (files, raw_diff) = match (files_from_stdin, input.find("diff --git ")) {
+        (true, Some(start)) => (Some(&input[..start]), &input[start..]),
+        (true, None) => (Some(input.as_str()), ""),
+        (false, _) => (None, input.as_str()),
+    };
+    let diff = truncate_diff(
+        &filter_excluded_diffs(&sanitize_diff(raw_diff, verbose), verbose),
+        verbose,
+    );
+    let files = match files {
+        Some(files) => annotate_file_list(
+            &files
+                .lines()
+                .map(str::trim)
+                .filter(|line| !line.is_empty())
+                .collect::<Vec<_>>()
+                .join("\n"),
+        ),
+        None => files_from_diff(raw_diff),
+    };
+    Ok((diff, files))
+}
+
+/// Runs the offline `--check` gate and returns the exit code.
+///
+/// Problems are printed one per line, or as `{"status", "problems"}` with
+/// `--json`; `--quiet` leaves only the exit code.
+async fn run_check(cli: &Cli, config: &Config) -> Result<i32, Box<dyn std::error::Error>> {
+    let verbose = cli.verbose || config.verbose;
+    let mut problems = Vec::new();
+
+    let code = if get_uncommitted_changes().await?.staged.is_empty() {
+        problems.push(Problem::general("nothing-staged"));
+        exit_code::NOTHING_STAGED
+    } else {
+        if uses_openrouter() && get_api_key(config, cli.api_key_file.as_deref())?.is_none() {
+            problems.push(Problem::general("no-api-key"));
+        }
+        problems.extend(scan_diff(&get_check_diff(verbose).await?));
+        if problems.is_empty() {
+            exit_code::SUCCESS
+        } else {
+            exit_code::FAILURE
+        }
+    };
+
+    match OutputMode::from_flags(cli.quiet, cli.json) {
+        OutputMode::Pretty => {
+            for problem in &problems {
+                println!("{}", problem);
+            }
+        }
+        OutputMode::Quiet => {}
+        OutputMode::Json => println!(
+            "{}",
+            serde_json::json!({
+                "status": if problems.is_empty() { "ok" } else { "failed" },
+                "problems": problems,
+            })
+        ),
+    }
+    Ok(code)
+}
+
+/// With `--verbose`, prints the tokens a generation used and their cost.
+async fn report_usage(client: &reqwest::Client, model: &str, usage: Option<Usage>, verbose: bool) {
+    let Some(usage) = usage.filter(|_| verbose) else {
+        return;
+    };
+    let info = get_model_info(client, model, verbose).await;
+    print_usage(&usage, info.as_ref());
+}
+
+/// Copies `text` to the clipboard for `--copy`.
+///
+/// A missing clipboard (headless sessions) is only a warning: the commit
+/// has already been made.
+async fn copy_for_user(text: &str, what: &str) {
+    match copy_to_clipboard(text).await {
+        Ok(_) => println!("{} Copied {} to the clipboard", style("✓").green(), what),
+        Err(e) => println!(
+            "{} Couldn't copy to the clipboard: {}",
+            style("⚠").yellow(),
+            e
+        ),
+    }
 }
@@ -0,0 +1,257 @@
+//! Model metadata from the OpenRouter models list.
+//!
+//! The list is fetched from [`OPENROUTER_MODELS_URL`] and cached next to the
+//! config file for [`MODELS_CACHE_TTL_SECS`], so most runs never hit the
+//! network for it. A stale cache is still used if a refresh fails.
+//!
+//! # Context-aware truncation
+//!
+//! [`diff_char_budget`] turns a model's context length into a diff size
+//! limit using a rough 4-characters-per-token estimate, after reserving room
+//! for the prompt template, file list, and generated output.
+//! [`fit_diff_to_context`] applies it on top of the fixed diff size limit.
+//!
+//! # Cost estimates
+//!
+//! [`estimate_cost`] prices a token estimate with the per-token prices the
+//! models list publishes, for `--count`.
+
+use reqwest::Client;
+use serde::{Deserialize, Serialize};
+use std::path::PathBuf;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+use crate::api::uses_openrouter;
+use crate::config::config_dir;
+use crate::git::truncate_diff_to;
+
+/// OpenRouter endpoint listing available models.
+pub const OPENROUTER_MODELS_URL: &str = "https://openrouter.ai/api/v1/models";
+
+/// How long the cached models list is considered fresh.
+pub const MODELS_CACHE_TTL_SECS: u64 = 24 * 60 * 60;
+
+/// Approximate characters per token for budget estimates.
+pub const CHARS_PER_TOKEN: usize = 4;
+
+/// Tokens reserved for the prompt template and repository notes.
+pub const PROMPT_RESERVE_TOKENS: usize = 3_000;
+
+/// Tokens reserved for the generated output.
+pub const OUTPUT_RESERVE_TOKENS: usize = 1_000;
+
+/// Smallest diff budget, so tiny context windows still see some changes.
+pub const MIN_DIFF_CHARS: usize = 4_000;
+
+/// Metadata for a single model.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ModelInfo {
+    /// Model identifier (e.g., "anthropic/claude-sonnet-4").
+    pub id: String,
+    /// Human-readable name.
+    #[serde(default)]
+    pub name: String,
+    /// Context window size in tokens, if published.
+    #[serde(default)]
+    pub context_length: Option<u64>,
+    /// Published prices, if any.
+    #[serde(default)]
+    pub pricing: Option<ModelPricing>,
+}
+
+/// Per-token prices in USD, as decimal strings (e.g. `"0.000003"`).
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ModelPricing {
+    /// Price per prompt (input) token.
+    #[serde(default)]
+    pub prompt: Option<String>,
+    /// Price per completion (output) token.
+    #[serde(default)]
+    pub completion: Option<String>,
+}
+
+/// Estimated cost of a request, in USD.
+pub struct CostEstimate {
+    /// Cost of the prompt tokens.
+    pub input: f64,
+    /// Cost if the output uses its full token reserve.
+    pub max_output: f64,
+}
+
+/// Response body of the models endpoint.
+#[derive(Deserialize)]
+struct ModelsResponse {
+    data: Vec<ModelInfo>,
+}
+
+/// On-disk cache of the models list.
+#[derive(Serialize, Deserialize)]
+struct ModelsCache {
+    /// Seconds since the Unix epoch when the list was fetched.
+    fetched_at: u64,
+    models: Vec<ModelInfo>,
+}
+
+/// Returns the path to the models cache file.
+pub fn models_cache_path() -> PathBuf {
+    config_dir().join("models.json")
+}
+
+fn now_secs() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.as_secs())
+        .unwrap_or(0)
+}
+
+fn load_models_cache() -> Option<ModelsCache> {
+    let contents = std::fs::read_to_string(models_cache_path()).ok()?;
+    serde_json::from_str(&contents).ok()
+}
+
+fn save_models_cache(models: &[ModelInfo]) -> Result<(), Box<dyn std::error::Error>> {
+    let path = models_cache_path();
+    if let Some(parent) = path.parent() {
+        std::fs::create_dir_all(parent)?;
+    }
+    let cache = ModelsCache {
+        fetched_at: now_secs(),
+        models: models.to_vec(),
+    };
+    std::fs::write(&path, serde_json::to_string(&cache)?)?;
+    Ok(())
+}
+
+/// Fetches the models list from OpenRouter.
+pub async fn fetch_models(client: &Client) -> Result<Vec<ModelInfo>, Box<dyn std::error::Error>> {
+    let response = client
+        .get(OPENROUTER_MODELS_URL)
+        .timeout(Duration::from_secs(5))
+        .send()
+        .await?;
+
+    if !response.status().is_success() {
+        return Err(format!("Models list request failed ({})", response.status()).into());
+    }
+
+    Ok(response.json::<ModelsResponse>().await?.data)
+}
+
+/// Returns the models list, from cache when fresh.
+///
+/// Refreshes a missing or stale cache, falling back to the stale copy if the
+/// request fails. Returns `None` when no list is available at all, or when
+/// `base_url` points somewhere other than OpenRouter.
+pub async fn get_models(client: &Client, verbose: bool) -> Option<Vec<ModelInfo>> {
+    if !uses_openrouter() {
+        if verbose {
+            eprintln!("— Not using OpenRouter; skipping its models list");
+        }
+        return None;
+    }
+
+    let cached = load_models_cache();
+    if let Some(cache) = &cached {
+        if now_secs().saturating_sub(cache.fetched_at) < MODELS_CACHE_TTL_SECS {
+            return cached.map(|cache| cache.models);
+        }
+    }
+
+    match fetch_models(client).await {
+        Ok(models) => {
+            if let Err(e) = save_models_cache(&models) {
+                if verbose {
+                    eprintln!("— Failed to cache models list: {}", e);
+                }
+            }
+            Some(models)
+        }
+        Err(e) => {
+            if verbose {
+                eprintln!("— Failed to fetch models list: {}", e);
+            }
+            cached.map(|cache| cache.models)
+        }
+    }
+}
+
+/// Returns the metadata of a model, if it's in the models list.
+pub async fn get_model_info(client: &Client, model: &str, verbose: bool) -> Option<ModelInfo> {
+    get_models(client, verbose)
+        .await?
+        .into_iter()
+        .find(|info| info.id == model)
+}
+
+/// Returns the context length of a model, if known.
+pub async fn get_context_length(client: &Client, model: &str, verbose: bool) -> Option<u64> {
+    get_model_info(client, model, verbose)
+        .await
+        .and_then(|info| info.context_length)
+}
+
+/// Prices `prompt_tokens` of input and up to `output_tokens` of output.
+///
+/// Returns `None` unless the model publishes both prices.
+pub fn estimate_cost(
+    info: &ModelInfo,
+    prompt_tokens: usize,
+    output_tokens: usize,
+) -> Option<CostEstimate> {
+    let pricing = info.pricing.as_ref()?;
+    let prompt: f64 = pricing.prompt.as_deref()?.parse().ok()?;
+    let completion: f64 = pricing.completion.as_deref()?.parse().ok()?;
+    Some(CostEstimate {
+        input: prompt * prompt_tokens as f64,
+        max_output: completion * output_tokens as f64,
+    })
+}
+
+/// Roughly estimates the number of tokens in a text.
+pub fn estimate_tokens(text: &str) -> usize {
+    text.len().div_ceil(CHARS_PER_TOKEN)
+}
+
+/// Computes how many diff characters fit in a model's context window.
+///
+/// `other_text` is the rest of the variable prompt content (e.g. the file
+/// list), which is subtracted from the budget along with the fixed reserves.
+pub fn diff_char_budget(context_length: u64, other_text: &str) -> usize {
+    let context = usize::try_from(context_length).unwrap_or(usize::MAX);
+    let available = context
+        .saturating_sub(PROMPT_RESERVE_TOKENS + OUTPUT_RESERVE_TOKENS)
+        .saturating_sub(estimate_tokens(other_text));
+    available
+        .saturating_mul(CHARS_PER_TOKEN)
+        .max(MIN_DIFF_CHARS)
+}
+
+/// Truncates a diff further if it won't fit the model's context window.
+///
+/// Returns the diff unchanged when the model's context length is unknown.
+pub async fn fit_diff_to_context(
+    client: &Client,
+    model: &str,
+    diff: String,
+    other_text: &str,
+    verbose: bool,
+) -> String {
+    let Some(context_length) = get_context_length(client, model, verbose).await else {
+        return diff;
+    };
+
+    let budget = diff_char_budget(context_length, other_text);
+    if diff.len() <= budget {
+        return diff;
+    }
+
+    if verbose {
+        eprintln!(
+            "— {} has a {} token context; limiting diff to {} KB",
+            model,
+            context_length,
+            budget / 1024
+        );
+    }
+    truncate_diff_to(&diff, budget, verbose)
+}
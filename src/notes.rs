@@ -0,0 +1,102 @@
+//! Generation metadata attached to commits as git notes.
+//!
+//! When `attach_notes` is enabled, each commit Committer creates gets a note
+//! under `notes_ref` (default `refs/notes/committer`) recording how its
+//! message was produced. The commit message itself stays clean; read the
+//! metadata with `git notes --ref=committer show <commit>`.
+//!
+//! A note looks like:
+//!
+//! ```text
+//! Committer-Version: 0.1.0
+//! Model: google/gemini-3-flash-preview
+//! Message: edited
+//! Tokens: 1203 prompt + 88 completion
+//! ```
+//!
+//! `Model` is the model that wrote the message (the fallback model, if the
+//! first one declined), and `Tokens` is left out when the server didn't
+//! report usage.
+
+use tokio::process::Command;
+
+use crate::api::Usage;
+use crate::config::Config;
+use crate::git::log_command;
+use crate::history::Outcome;
+
+/// Renders the note recorded for a commit.
+pub fn build_note(model: &str, outcome: Outcome, usage: Option<Usage>) -> String {
+    let message = match outcome {
+        Outcome::Edited => "edited",
+        _ => "generated",
+    };
+    let mut note = format!(
+        "Committer-Version: {}\nModel: {}\nMessage: {}\n",
+        env!("CARGO_PKG_VERSION"),
+        model,
+        message
+    );
+    if let Some(usage) = usage {
+        note.push_str(&format!(
+            "Tokens: {} prompt + {} completion\n",
+            usage.prompt_tokens, usage.completion_tokens
+        ));
+    }
+    note
+}
+
+/// Attaches a generation note to `HEAD` if `attach_notes` is enabled.
+///
+/// Replaces any note already on the commit (e.g. after `--amend`). Failures
+/// are reported as warnings; the commit itself has already succeeded.
+pub async fn attach_note(
+    config: &Config,
+    model: &str,
+    outcome: Outcome,
+    usage: Option<Usage>,
+    verbose: bool,
+) {
+    if !config.attach_notes || outcome == Outcome::Cancelled {
+        return;
+    }
+
+    let note = build_note(model, outcome, usage);
+    let notes_ref = format!("--ref={}", config.notes_ref);
+    let args = ["notes", &notes_ref, "add", "-f", "-m", &note, "HEAD"];
+    let output = Command::new("git").args(args).output().await;
+    log_command(verbose, "git", &args, &output);
+
+    match output {
+        Ok(output) if output.status.success() => {}
+        Ok(output) => eprintln!(
+            "— Failed to attach git note: {}",
+            String::from_utf8_lossy(&output.stderr).trim()
+        ),
+        Err(e) => eprintln!("— Failed to attach git note: {}", e),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn note_records_model_and_usage() {
+        let usage = Usage {
+            prompt_tokens: 120,
+            completion_tokens: 30,
+        };
+        let note = build_note("fallback/model", Outcome::Edited, Some(usage));
+        assert!(note.contains("Model: fallback/model\n"));
+        assert!(note.contains("Message: edited\n"));
+        assert!(note.ends_with("Tokens: 120 prompt + 30 completion\n"));
+    }
+
+    #[test]
+    fn note_omits_unknown_usage() {
+        let note = build_note("some/model", Outcome::Accepted, None);
+        assert!(note.contains("Message: generated\n"));
+        assert!(!note.contains("Tokens:"));
+    }
+}
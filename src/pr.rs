@@ -1,12 +1,25 @@
-//! Pull request generation and GitHub CLI integration.
+//! Pull request generation.
 //!
 //! This module handles the `committer pr` subcommand workflow:
 //!
-//! 1. Validates GitHub CLI is installed and authenticated
-//! 2. Detects base branch automatically (or uses `--base`)
+//! 1. Picks the forge (GitHub or GitLab, see [`crate::forge`]) and checks it
+//!    can be used
+//! 2. Detects base branch automatically (or uses `--base`), consulting
+//!    `base_branch_rules` first when configured; when detection is unsure,
+//!    or with `--interactive-base`, asks the user to pick one
 //! 3. Handles uncommitted changes (commit, skip, or quit)
 //! 4. Generates PR title and description using LLM
-//! 5. Pushes branch and creates PR via GitHub CLI
+//! 5. Pushes branch and creates the PR (a merge request on GitLab)
+//!
+//! With `--update`, step 5 instead rewrites the title and body of the
+//! branch's open PR, after showing what changed. This is GitHub-only for now.
+//!
+//! # Fork Workflow
+//!
+//! When both `origin` and `upstream` remotes exist, the branch is pushed to
+//! `origin` and the PR is opened against `upstream` using the cross-repo
+//! `owner:branch` head syntax. Override with `--head-remote` and
+//! `--base-remote-name` (or the `head_remote`/`base_remote` config fields).
 //!
 //! # Example
 //!
@@ -15,112 +28,309 @@
 //! committer pr --yes        # Auto-create without confirmation
 //! committer pr --draft      # Create as draft PR
 //! committer pr --dry-run    # Preview without creating
+//! committer pr --update     # Refresh the open PR's description
+//! committer pr --fill-from-commits  # Describe the PR from its commit messages
 //! ```
 
 use console::{style, Term};
-use indicatif::{ProgressBar, ProgressStyle};
-use reqwest::Client;
+use ignore::gitignore::GitignoreBuilder;
+use std::collections::BTreeMap;
+use std::path::{Path, PathBuf};
 use tokio::process::Command;
 
-use crate::api::{stream_commit_message, stream_pr_content};
+use crate::api::{
+    build_client, build_pr_prompt, build_pr_prompt_from_commits, describe_headers,
+    stream_commit_message, stream_pr_content, uses_openrouter, GenerationOptions,
+    ProviderPreference,
+};
 use crate::branch::PROTECTED_BRANCHES;
 use crate::cli::PrArgs;
-use crate::config::{get_api_key, Config};
+use crate::commit::{collect_prompt_notes, metadata_trailers, CommitPolicy, PolicyOverrides};
+use crate::config::{get_api_key, Config, ForgeKind};
+use crate::convention::load_commit_rules;
+use crate::exit_code;
+use crate::forge::{build_forge, detect_forge};
 use crate::git::{
-    branch_has_merge_base, get_branch_commits, get_branch_diff, get_cached_remote_head,
-    get_current_branch, get_git_diff, get_pr_changed_files, get_remote_default_branch,
-    get_staged_files, get_uncommitted_changes, get_upstream_remote, push_branch_with_spinner,
-    run_git_commit, stage_all_changes,
+    branch_has_merge_base, detect_default_base, get_branch_commit_messages, get_branch_commits,
+    get_branch_diff, get_changed_paths, get_current_branch, get_git_diff, get_long_lived_branches,
+    get_pr_changed_files, get_remote_default_branch, get_remote_url, get_repo_root,
+    get_staged_files, get_uncommitted_changes, get_upstream_remote, log_command,
+    push_branch_with_spinner, run_diff_preprocessor, sign_flag_needed, stage_all_changes,
+    CommitOptions,
 };
+use crate::history::Outcome;
+use crate::lint::LintRules;
+use crate::models::fit_diff_to_context;
+use crate::notes::attach_note;
 use crate::ui::{
-    prompt_commit, prompt_pr, prompt_uncommitted_changes, CommitAction, PrAction, UncommittedAction,
+    can_prompt, phase_message, phase_spinner, print_text_diff, prompt_commit, prompt_pr,
+    prompt_select_base, prompt_uncommitted_changes, resolve_editor, CommitAction, PrAction,
+    StdinInput, UncommittedAction, NO_TTY_MESSAGE,
 };
 
-/// Checks if the GitHub CLI (`gh`) is installed.
-pub async fn check_gh_installed() -> Result<(), Box<dyn std::error::Error>> {
-    let output = Command::new("gh").args(["--version"]).output().await;
+/// Remotes involved in pushing a branch and opening its PR.
+pub struct PrRemotes {
+    /// Remote the branch is pushed to.
+    pub head: String,
+    /// Remote whose repository receives the PR, when it differs from `head`.
+    pub base: Option<String>,
+}
 
-    match output {
-        Ok(o) if o.status.success() => Ok(()),
-        _ => Err("GitHub CLI (gh) is not installed.\n\
-                 Install it from: https://cli.github.com/\n\
-                 Then run: gh auth login"
-            .into()),
+/// Target for a PR whose head branch lives in a different repository.
+#[derive(Clone)]
+pub struct CrossRepoTarget {
+    /// `owner/repo` of the repository the PR is opened against.
+    pub repo: String,
+    /// Head reference in `owner:branch` form.
+    pub head: String,
+    /// Base branch name in the target repository.
+    pub base: String,
+}
+
+/// An open pull request for the current branch.
+#[derive(serde::Deserialize)]
+pub struct ExistingPr {
+    pub number: u64,
+    pub title: String,
+    pub body: String,
+    pub url: String,
+    pub state: String,
+}
+
+/// Finds the open PR whose head is the current branch.
+///
+/// For cross-repo PRs the lookup runs against the target repository using
+/// the `owner:branch` head. Returns `None` if no open PR exists.
+pub async fn find_open_pr(
+    branch: &str,
+    cross_repo: Option<&CrossRepoTarget>,
+    verbose: bool,
+) -> Result<Option<ExistingPr>, Box<dyn std::error::Error>> {
+    let mut args = vec!["pr", "view", "--json", "number,title,body,url,state"];
+    match cross_repo {
+        Some(target) => args.extend([target.head.as_str(), "--repo", target.repo.as_str()]),
+        None => args.push(branch),
+    }
+
+    let output = Command::new("gh").args(&args).output().await;
+    log_command(verbose, "gh", &args, &output);
+    let output = output?;
+    if !output.status.success() {
+        let stderr = String::from_utf8_lossy(&output.stderr);
+        if stderr.contains("no pull requests found") {
+            return Ok(None);
+        }
+        return Err(format!("Failed to look up PR: {}", stderr.trim()).into());
     }
+
+    let pr: ExistingPr = serde_json::from_slice(&output.stdout)?;
+    Ok((pr.state == "OPEN").then_some(pr))
+}
+
+/// Replaces the title and body of an existing PR.
+pub async fn update_pr(
+    pr: &ExistingPr,
+    title: &str,
+    body: &str,
+    verbose: bool,
+) -> Result<(), Box<dyn std::error::Error>> {
+    // The URL identifies the PR unambiguously, including for cross-repo PRs
+    let args = ["pr", "edit", &pr.url, "--title", title, "--body", body];
+    let output = Command::new("gh").args(args).output().await;
+    log_command(verbose, "gh", &args, &output);
+    let output = output?;
+
+    if !output.status.success() {
+        let stderr = String::from_utf8_lossy(&output.stderr);
+        return Err(format!("Failed to update PR: {}", stderr.trim()).into());
+    }
+
+    Ok(())
 }
 
+/// Resolves which remote to push to and which to open the PR against.
+///
+/// CLI flags take precedence over config. When neither names a base remote
+/// and an `upstream` remote exists, assumes the fork workflow.
+pub async fn resolve_pr_remotes(args: &PrArgs, config: &Config) -> PrRemotes {
+    let verbose = args.verbose || config.verbose;
+    let head = args
+        .head_remote
+        .clone()
+        .or_else(|| config.head_remote.clone())
+        .unwrap_or_else(|| "origin".to_string());
+
+    let base = match args
+        .base_remote_name
+        .clone()
+        .or_else(|| config.base_remote.clone())
+    {
+        Some(remote) => Some(remote),
+        None => get_upstream_remote(verbose).await.ok().flatten(),
+    };
+
+    PrRemotes {
+        base: base.filter(|remote| *remote != head),
+        head,
+    }
+}
+
+/// Extracts the `owner/repo` path from a git remote URL.
+///
+/// Handles both `https://host/owner/repo.git` and `git@host:owner/repo.git`.
+pub fn repo_slug_from_url(url: &str) -> Option<String> {
+    let url = url.trim().trim_end_matches('/');
+    let url = url.strip_suffix(".git").unwrap_or(url);
+
+    let path = if let Some((_, rest)) = url.split_once("://") {
+        rest.split_once('/').map(|(_, path)| path)?
+    } else {
+        url.split_once(':').map(|(_, path)| path)?
+    };
+
+    let path = path.trim_matches('/');
+    if path.contains('/') {
+        Some(path.to_string())
+    } else {
+        None
+    }
+}
+
+/// Builds the cross-repo PR target for pushing to one remote and opening
+/// the PR against another.
+pub async fn build_cross_repo_target(
+    remotes: &PrRemotes,
+    branch: &str,
+    base_branch: &str,
+    verbose: bool,
+) -> Result<Option<CrossRepoTarget>, Box<dyn std::error::Error>> {
+    let Some(base_remote) = &remotes.base else {
+        return Ok(None);
+    };
+
+    let base_url = get_remote_url(base_remote, verbose)
+        .await
+        .ok_or_else(|| format!("Remote '{}' not found", base_remote))?;
+    let head_url = get_remote_url(&remotes.head, verbose)
+        .await
+        .ok_or_else(|| format!("Remote '{}' not found", remotes.head))?;
+
+    let repo = repo_slug_from_url(&base_url)
+        .ok_or_else(|| format!("Could not parse repository from '{}'", base_url))?;
+    let head_slug = repo_slug_from_url(&head_url)
+        .ok_or_else(|| format!("Could not parse repository from '{}'", head_url))?;
+    let owner = head_slug.split('/').next().unwrap_or(&head_slug);
+
+    Ok(Some(CrossRepoTarget {
+        repo,
+        head: format!("{}:{}", owner, branch),
+        base: remote_branch_name(base_branch, remotes).to_string(),
+    }))
+}
+
+/// Returns the base branch without its remote prefix.
+///
+/// "upstream/main" -> "main"; the target repo only knows its own branch names.
+fn remote_branch_name<'a>(base_branch: &'a str, remotes: &PrRemotes) -> &'a str {
+    remotes
+        .base
+        .iter()
+        .chain([&remotes.head])
+        .find_map(|remote| base_branch.strip_prefix(&format!("{}/", remote)))
+        .unwrap_or(base_branch)
+}
+
+/// Error when no base branch can be found.
+const NO_BASE_BRANCH: &str =
+    "Could not determine default base branch. Use --base <branch> to specify manually.";
+
 /// Detects the default base branch for the PR.
 ///
-/// Tries multiple strategies: GitHub CLI, cached origin/HEAD, remote query,
-/// and common branch name fallbacks.
-pub async fn get_default_base_branch(verbose: bool) -> Result<String, Box<dyn std::error::Error>> {
+/// Tries multiple strategies: GitHub CLI, what git knows about `remote`
+/// (the one the PR targets) and `default_base`, a query of the remote, and
+/// common branch name fallbacks.
+pub async fn get_default_base_branch(
+    remote: &str,
+    config: &Config,
+    verbose: bool,
+) -> Result<String, Box<dyn std::error::Error>> {
+    match detect_base_branch(remote, config, verbose).await {
+        Some(branch) => Ok(branch),
+        None => Ok(guess_base_branch(verbose).await.ok_or(NO_BASE_BRANCH)?),
+    }
+}
+
+/// Finds the repository's default branch from the host or the remote.
+///
+/// Returns `None` if none of the sources name a branch sharing history with
+/// HEAD; [`guess_base_branch`] is the fallback.
+async fn detect_base_branch(remote: &str, config: &Config, verbose: bool) -> Option<String> {
     // Strategy 1: Try gh CLI (works for GitHub repos)
-    let gh_output = Command::new("gh")
-        .args([
-            "repo",
-            "view",
-            "--json",
-            "defaultBranchRef",
-            "-q",
-            ".defaultBranchRef.name",
-        ])
-        .output()
-        .await;
+    let gh_args = [
+        "repo",
+        "view",
+        "--json",
+        "defaultBranchRef",
+        "-q",
+        ".defaultBranchRef.name",
+    ];
+    let gh_output = Command::new("gh").args(gh_args).output().await;
+    log_command(verbose, "gh", &gh_args, &gh_output);
 
     if let Ok(output) = gh_output {
         if output.status.success() {
             let branch = String::from_utf8_lossy(&output.stdout).trim().to_string();
-            if !branch.is_empty() && branch_has_merge_base(&branch).await {
+            if !branch.is_empty() && branch_has_merge_base(&branch, verbose).await {
                 if verbose {
                     eprintln!("— Base branch detection: gh CLI (GitHub API)");
                 }
-                return Ok(branch);
+                return Some(branch);
             }
-            // gh returned a branch but no merge base - try with origin/ prefix
-            let origin_branch = format!("origin/{}", branch);
-            if branch_has_merge_base(&origin_branch).await {
+            // gh returned a branch but no merge base - try with the remote's prefix
+            let remote_branch = format!("{}/{}", remote, branch);
+            if branch_has_merge_base(&remote_branch, verbose).await {
                 if verbose {
-                    eprintln!("— Base branch detection: gh CLI (GitHub API, using origin/)");
+                    eprintln!(
+                        "— Base branch detection: gh CLI (GitHub API, using {}/)",
+                        remote
+                    );
                 }
-                return Ok(origin_branch);
+                return Some(remote_branch);
             }
         }
     }
 
-    // Strategy 2: Try cached git symbolic-ref for origin/HEAD
-    if let Some(branch) = get_cached_remote_head().await {
-        if branch_has_merge_base(&branch).await {
-            if verbose {
-                eprintln!("— Base branch detection: cached origin/HEAD ref");
-            }
-            return Ok(branch);
-        }
-        let origin_branch = format!("origin/{}", branch);
-        if branch_has_merge_base(&origin_branch).await {
-            if verbose {
-                eprintln!("— Base branch detection: cached origin/HEAD ref (using origin/)");
-            }
-            return Ok(origin_branch);
-        }
+    // Strategy 2: The remote's cached HEAD or common branches, then `default_base`
+    if let Some(branch) = detect_default_base(remote, config.default_base.as_deref(), verbose).await
+    {
+        return Some(branch);
     }
 
     // Strategy 3: Query remote directly (works for any git host)
-    if let Some(branch) = get_remote_default_branch().await {
-        if branch_has_merge_base(&branch).await {
+    if let Some(branch) = get_remote_default_branch(remote, verbose).await {
+        if branch_has_merge_base(&branch, verbose).await {
             if verbose {
                 eprintln!("— Base branch detection: git ls-remote (queried remote)");
             }
-            return Ok(branch);
+            return Some(branch);
         }
-        let origin_branch = format!("origin/{}", branch);
-        if branch_has_merge_base(&origin_branch).await {
+        let remote_branch = format!("{}/{}", remote, branch);
+        if branch_has_merge_base(&remote_branch, verbose).await {
             if verbose {
-                eprintln!("— Base branch detection: git ls-remote (queried remote, using origin/)");
+                eprintln!(
+                    "— Base branch detection: git ls-remote (queried remote, using {}/)",
+                    remote
+                );
             }
-            return Ok(origin_branch);
+            return Some(remote_branch);
         }
     }
 
+    None
+}
+
+/// Guesses the base branch from common default branch names.
+async fn guess_base_branch(verbose: bool) -> Option<String> {
     // Strategy 4: Last resort - check common default branch names
     let common_branches = [
         "origin/main",
@@ -134,47 +344,244 @@ pub async fn get_default_base_branch(verbose: bool) -> Result<String, Box<dyn st
     ];
 
     for branch in common_branches {
-        if branch_has_merge_base(branch).await {
+        if branch_has_merge_base(branch, verbose).await {
             if verbose {
                 eprintln!("— Base branch detection: fallback (checked common names)");
             }
-            return Ok(branch.to_string());
+            return Some(branch.to_string());
         }
     }
 
-    Err("Could not determine default base branch. Use --base <branch> to specify manually.".into())
+    None
+}
+
+/// Returns the base branches of the user's recent PRs, most recent first.
+async fn get_recent_pr_bases(verbose: bool) -> Vec<String> {
+    let args = [
+        "pr",
+        "list",
+        "--author",
+        "@me",
+        "--state",
+        "all",
+        "--limit",
+        "20",
+        "--json",
+        "baseRefName",
+        "-q",
+        ".[].baseRefName",
+    ];
+    let output = Command::new("gh").args(args).output().await;
+    log_command(verbose, "gh", &args, &output);
+    match output {
+        Ok(output) if output.status.success() => String::from_utf8_lossy(&output.stdout)
+            .lines()
+            .map(str::to_string)
+            .collect(),
+        _ => Vec::new(),
+    }
 }
 
-/// Creates a pull request via GitHub CLI.
+/// Returns `branch`, or `origin/<branch>` if only that shares history with HEAD.
+async fn resolve_base_ref(branch: &str, verbose: bool) -> Option<String> {
+    if branch_has_merge_base(branch, verbose).await {
+        return Some(branch.to_string());
+    }
+    let origin_branch = format!("origin/{}", branch);
+    branch_has_merge_base(&origin_branch, verbose)
+        .await
+        .then_some(origin_branch)
+}
+
+/// Asks the user to pick the base branch from likely candidates.
 ///
-/// Returns the PR URL on success.
-pub async fn create_pr(
-    title: &str,
-    body: &str,
-    draft: bool,
-) -> Result<String, Box<dyn std::error::Error>> {
-    let mut args = vec!["pr", "create", "--title", title, "--body", body];
-    if draft {
-        args.push("--draft");
+/// `preferred` candidates (e.g. the detected default) come first, then the
+/// bases of the user's recent PRs, then long-lived local and remote
+/// branches. Only branches sharing history with HEAD are listed. Exits if
+/// the user cancels.
+async fn pick_base_branch(
+    preferred: Vec<(String, &'static str)>,
+    current_branch: &str,
+    verbose: bool,
+) -> String {
+    let recent = get_recent_pr_bases(verbose)
+        .await
+        .into_iter()
+        .map(|branch| (branch, "recent PR base"));
+    let long_lived = get_long_lived_branches(verbose)
+        .await
+        .into_iter()
+        .map(|branch| (branch, "long-lived"));
+
+    let mut candidates: Vec<(String, &str)> = Vec::new();
+    for (branch, reason) in preferred.into_iter().chain(recent).chain(long_lived) {
+        if candidates.iter().any(|(listed, _)| *listed == branch) {
+            continue;
+        }
+        let Some(branch) = resolve_base_ref(&branch, verbose).await else {
+            continue;
+        };
+        let is_current = branch == current_branch
+            || branch.split_once('/').map(|(_, name)| name) == Some(current_branch);
+        if !is_current && !candidates.iter().any(|(listed, _)| *listed == branch) {
+            candidates.push((branch, reason));
+        }
     }
 
-    let output = Command::new("gh").args(&args).output().await?;
+    match prompt_select_base(&candidates) {
+        Some(branch) => branch,
+        None => {
+            println!("{} Cancelled", style("—").dim());
+            std::process::exit(exit_code::SUCCESS);
+        }
+    }
+}
 
-    if !output.status.success() {
-        let stderr = String::from_utf8_lossy(&output.stderr);
-        if stderr.contains("auth") {
-            return Err(format!(
-                "GitHub authentication failed.\nRun: gh auth login\n\nError: {}",
-                stderr
-            )
-            .into());
+/// Outcome of matching changed paths against `base_branch_rules`.
+pub enum RuleMatch {
+    /// No rule matched any path.
+    None,
+    /// Every matching path maps to this base branch, via the given rule.
+    Base { base: String, rule: String },
+    /// Paths map to different base branches: `(base, example path)` for each.
+    Conflict(Vec<(String, String)>),
+}
+
+/// Matches paths against `rules` (gitignore-style glob → base branch).
+///
+/// Paths no rule covers are ignored, so shared files like a root README
+/// don't make an otherwise unambiguous PR conflict. Invalid globs are
+/// skipped with a warning.
+pub fn match_base_branch_rules(rules: &BTreeMap<String, String>, paths: &[String]) -> RuleMatch {
+    let mut matchers = Vec::new();
+    for (glob, base) in rules {
+        let mut builder = GitignoreBuilder::new("");
+        let built = match builder.add_line(None, glob) {
+            Ok(_) => builder.build(),
+            Err(e) => Err(e),
+        };
+        match built {
+            Ok(matcher) => matchers.push((glob, base, matcher)),
+            Err(e) => eprintln!("— Skipping base_branch_rules entry '{}': {}", glob, e),
+        }
+    }
+
+    // (base, rule, first matching path), in first-match order
+    let mut matched: Vec<(&String, &String, &String)> = Vec::new();
+    for path in paths {
+        for (glob, base, matcher) in &matchers {
+            let is_match = matcher.matched_path_or_any_parents(path, false).is_ignore();
+            if is_match && !matched.iter().any(|(b, _, _)| b == base) {
+                matched.push((base, glob, path));
+            }
         }
-        return Err(format!("Failed to create PR: {}", stderr).into());
     }
 
-    // gh pr create outputs the PR URL on success
-    let url = String::from_utf8_lossy(&output.stdout).trim().to_string();
-    Ok(url)
+    match matched.as_slice() {
+        [] => RuleMatch::None,
+        [(base, rule, _)] => RuleMatch::Base {
+            base: base.to_string(),
+            rule: rule.to_string(),
+        },
+        _ => RuleMatch::Conflict(
+            matched
+                .iter()
+                .map(|(base, _, path)| (base.to_string(), path.to_string()))
+                .collect(),
+        ),
+    }
+}
+
+/// Picks the base branch from `base_branch_rules`, falling back to detection.
+///
+/// Changed paths are taken relative to the detected default base (plus
+/// anything staged). If paths map to different bases, asks which one when
+/// `interactive`, and exits with an error otherwise.
+async fn get_rule_base_branch(
+    config: &Config,
+    remote: &str,
+    interactive: bool,
+    current_branch: &str,
+    verbose: bool,
+) -> Result<String, Box<dyn std::error::Error>> {
+    let default_base = get_default_base_branch(remote, config, verbose).await;
+    let paths = get_changed_paths(default_base.as_deref().ok(), verbose).await;
+
+    let base = match match_base_branch_rules(&config.base_branch_rules, &paths) {
+        RuleMatch::None => return default_base,
+        RuleMatch::Base { base, rule } => {
+            if verbose {
+                eprintln!("— Base branch detection: base_branch_rules ('{}')", rule);
+            }
+            base
+        }
+        RuleMatch::Conflict(matches) => {
+            println!(
+                "{} Changes span several base_branch_rules:",
+                style("!").yellow()
+            );
+            for (base, path) in &matches {
+                println!(
+                    "    {} {} (e.g. {})",
+                    style("→").dim(),
+                    style(base).cyan(),
+                    path
+                );
+            }
+            if interactive {
+                let bases = matches
+                    .into_iter()
+                    .map(|(base, _)| (base, "base_branch_rules"))
+                    .collect();
+                return Ok(pick_base_branch(bases, current_branch, verbose).await);
+            }
+            println!("  {} Choose one with --base <branch>", style("→").dim());
+            std::process::exit(exit_code::FAILURE);
+        }
+    };
+
+    if branch_has_merge_base(&base, verbose).await {
+        return Ok(base);
+    }
+    let origin_base = format!("origin/{}", base);
+    if branch_has_merge_base(&origin_base, verbose).await {
+        return Ok(origin_base);
+    }
+    Err(format!(
+        "Base branch '{}' from base_branch_rules has no common history with HEAD",
+        base
+    )
+    .into())
+}
+
+/// Where PR templates are looked for, in order: a directory and the file
+/// name within it (matched case-insensitively).
+const PR_TEMPLATE_LOCATIONS: &[(&str, &str)] = &[
+    (".github", "pull_request_template.md"),
+    ("", "pull_request_template.md"),
+    ("docs", "pull_request_template.md"),
+    (".gitlab/merge_request_templates", "default.md"),
+];
+
+/// Finds the repository's PR template, returning its path and contents.
+///
+/// Searches [`PR_TEMPLATE_LOCATIONS`] under `repo_root`; an empty template
+/// counts as none.
+pub fn load_pr_template(repo_root: &Path) -> Option<(PathBuf, String)> {
+    PR_TEMPLATE_LOCATIONS.iter().find_map(|(dir, name)| {
+        let path = std::fs::read_dir(repo_root.join(dir))
+            .ok()?
+            .flatten()
+            .map(|entry| entry.path())
+            .find(|path| {
+                path.is_file()
+                    && path
+                        .file_name()
+                        .is_some_and(|file| file.eq_ignore_ascii_case(name))
+            })?;
+        let contents = std::fs::read_to_string(&path).ok()?;
+        (!contents.trim().is_empty()).then_some((path, contents))
+    })
 }
 
 /// Main handler for the `committer pr` subcommand.
@@ -184,32 +591,42 @@ pub async fn handle_pr_command(
     args: PrArgs,
     config: &Config,
 ) -> Result<(), Box<dyn std::error::Error>> {
-    // Check gh CLI is installed
-    check_gh_installed().await?;
+    let verbose = args.verbose || config.verbose;
 
     // Get API key
-    let api_key = match get_api_key() {
+    let api_key = match get_api_key(config, args.api_key_file.as_deref())? {
         Some(key) => key,
+        // Local servers usually don't check one
+        None if !uses_openrouter() => String::new(),
         None => {
             println!("{} No API key found", style("✗").red());
             println!(
-                "  {} Set OPENROUTER_API_KEY environment variable",
+                "  {} Set OPENROUTER_API_KEY, api_key in the config file, or pass --api-key-file",
                 style("→").dim()
             );
-            std::process::exit(1);
+            std::process::exit(exit_code::FAILURE);
         }
     };
 
-    let verbose = args.verbose || config.verbose;
-    let model = args.model.as_ref().unwrap_or(&config.model);
+    let model = &config.resolve_model(args.model.as_deref().unwrap_or(&config.model));
+    // One client for the whole run so the commit-first and PR requests share connections
+    let client = build_client(&config.extra_headers, config.timeout_secs)?;
+    if verbose && !config.extra_headers.is_empty() {
+        eprintln!(
+            "— Extra API headers: {}",
+            describe_headers(&config.extra_headers)
+        );
+    }
+    let provider = args.provider_only.as_deref().map(ProviderPreference::only);
 
     // Get current branch
     let current_branch = get_current_branch().await?;
+    let remotes = resolve_pr_remotes(&args, config).await;
 
     // Check if on protected branch
     if PROTECTED_BRANCHES.contains(&current_branch.as_str()) {
-        // Check for upstream remote (fork workflow)
-        if get_upstream_remote().await?.is_none() {
+        // Protected branches are only allowed when the PR targets another repo (fork workflow)
+        if remotes.base.is_none() {
             println!(
                 "{} Cannot create PR from protected branch '{}'",
                 style("✗").red(),
@@ -219,66 +636,214 @@ pub async fn handle_pr_command(
                 "  {} Create a feature branch first: git checkout -b feat/your-feature",
                 style("→").dim()
             );
-            std::process::exit(1);
+            std::process::exit(exit_code::FAILURE);
         }
     }
 
-    // Determine base branch
+    // Determine base branch, asking when detection is unsure and someone can answer
+    let base_remote = remotes.base.as_deref().unwrap_or(&remotes.head);
+    let interactive = !args.yes && Term::stderr().is_term();
+    if args.interactive_base && !interactive && verbose {
+        eprintln!("— Not interactive; detecting the base branch instead of asking");
+    }
     let base_branch = match &args.base {
         Some(base) => base.clone(),
-        None => get_default_base_branch(verbose).await?,
+        None if args.interactive_base && interactive => {
+            let detected = get_default_base_branch(base_remote, config, verbose)
+                .await
+                .ok();
+            let preferred = detected
+                .map(|base| (base, "detected"))
+                .into_iter()
+                .collect();
+            pick_base_branch(preferred, &current_branch, verbose).await
+        }
+        None if !config.base_branch_rules.is_empty() => {
+            get_rule_base_branch(config, base_remote, interactive, &current_branch, verbose).await?
+        }
+        None => match detect_base_branch(base_remote, config, verbose).await {
+            Some(base) => base,
+            None if interactive => {
+                let guess = guess_base_branch(verbose).await;
+                let preferred = guess
+                    .map(|base| (base, "common name"))
+                    .into_iter()
+                    .collect();
+                pick_base_branch(preferred, &current_branch, verbose).await
+            }
+            None => guess_base_branch(verbose).await.ok_or(NO_BASE_BRANCH)?,
+        },
     };
 
+    let cross_repo =
+        build_cross_repo_target(&remotes, &current_branch, &base_branch, verbose).await?;
+
     if verbose {
         eprintln!("— Base branch: {}", base_branch);
         eprintln!("— Current branch: {}", current_branch);
+        eprintln!("— Head remote: {}", remotes.head);
+        if let Some(target) = &cross_repo {
+            eprintln!(
+                "— Cross-repo PR: {} → {}:{}",
+                target.head, target.repo, target.base
+            );
+        }
     }
 
+    // Set up the forge now so a missing tool or token fails before generation
+    let forge_kind = detect_forge(config, base_remote, verbose).await;
+    if args.update && forge_kind != ForgeKind::Github {
+        return Err(format!("--update isn't supported on {} yet", forge_kind.as_str()).into());
+    }
+    let forge = build_forge(
+        forge_kind,
+        config,
+        &current_branch,
+        base_remote,
+        &remotes.head,
+        cross_repo.clone(),
+        verbose,
+    )
+    .await?;
+    let pr_base = remote_branch_name(&base_branch, &remotes);
+
+    // With --update, find the PR up front so a missing one fails before generation
+    let existing_pr = if args.update {
+        match find_open_pr(&current_branch, cross_repo.as_ref(), verbose).await? {
+            Some(pr) => {
+                if verbose {
+                    eprintln!("— Updating PR #{}: {}", pr.number, pr.url);
+                }
+                Some(pr)
+            }
+            None => {
+                println!(
+                    "{} No open PR for branch '{}'",
+                    style("✗").red(),
+                    style(&current_branch).cyan()
+                );
+                println!("  {} Run 'committer pr' to create one", style("→").dim());
+                std::process::exit(exit_code::FAILURE);
+            }
+        }
+    } else {
+        None
+    };
+
     // Check for uncommitted changes
     let uncommitted = get_uncommitted_changes().await?;
     if !uncommitted.staged.is_empty() || !uncommitted.unstaged.is_empty() {
-        match prompt_uncommitted_changes(&uncommitted) {
+        // Without a terminal, --yes and --dry-run leave the changes out rather than ask
+        let action = if can_prompt() {
+            prompt_uncommitted_changes(&uncommitted, &mut StdinInput)
+        } else if args.yes || args.dry_run {
+            UncommittedAction::Skip
+        } else {
+            println!("{} {}", style("✗").red(), NO_TTY_MESSAGE);
+            std::process::exit(exit_code::FAILURE);
+        };
+        match action {
             UncommittedAction::Commit => {
                 // Stage all and run commit flow
                 stage_all_changes().await?;
 
-                let commit_diff = get_git_diff(true, verbose).await?;
+                let mut commit_diff = get_git_diff(true, verbose).await?;
+                if let Some(command) = &config.diff_preprocessor {
+                    commit_diff = run_diff_preprocessor(command, &commit_diff, verbose).await?;
+                }
                 let commit_files = get_staged_files(verbose).await?;
 
                 if commit_diff.trim().is_empty() {
                     println!("{} No changes to commit", style("→").dim());
                 } else {
-                    let client = Client::builder().build()?;
+                    let mut policy = CommitPolicy::from_config(
+                        config,
+                        &PolicyOverrides {
+                            verbose: args.verbose,
+                            ..Default::default()
+                        },
+                    )
+                    .await?;
+                    let convention =
+                        load_commit_rules(get_repo_root().await.as_deref(), &config.valid_scopes);
+                    policy.allowed_scopes = convention.as_ref().and_then(|c| c.scopes.clone());
+                    policy.lint_rules = config
+                        .lint
+                        .then(|| LintRules::new(config, convention.as_ref()));
+                    if let Some(max) = convention.as_ref().and_then(|c| c.header_max_length) {
+                        policy.subject_max_len = max;
+                    }
+                    if config.stamp_metadata {
+                        policy.trailers.extend(metadata_trailers(model));
+                    }
+                    let prompt_notes =
+                        collect_prompt_notes(&commit_diff, convention.as_ref(), config).await;
                     let term = Term::stdout();
                     let _ = term.hide_cursor();
 
-                    let spinner = ProgressBar::new_spinner();
-                    spinner.set_style(
-                        ProgressStyle::default_spinner()
-                            .tick_strings(&["⠋", "⠙", "⠹", "⠸", "⠼", "⠴", "⠦", "⠧", "⠇", "⠏"])
-                            .template("{spinner:.cyan} Generating commit message...")
-                            .unwrap(),
-                    );
+                    let spinner = phase_spinner(phase_message("Generating commit message", model));
                     spinner.enable_steady_tick(std::time::Duration::from_millis(80));
 
-                    let commit_msg = stream_commit_message(
+                    let generated = stream_commit_message(
                         &client,
                         &api_key,
                         model,
                         &commit_diff,
                         &commit_files,
                         &spinner,
-                        verbose,
+                        &GenerationOptions {
+                            show_stream: true,
+                            show_tokens: config.show_stream && !args.no_stream_display,
+                            structured_output: config.structured_output,
+                            verbose,
+                            prompt_notes,
+                            stream_buffer_size: config.stream_buffer_size,
+                            provider: provider.clone(),
+                            fallback_model: config
+                                .fallback_model
+                                .as_deref()
+                                .map(|model| config.resolve_model(model)),
+                            conversation: Vec::new(),
+                            timeout_secs: config.timeout_secs,
+                            max_retries: config.max_retries,
+                            temperature: config.temperature,
+                            max_tokens: config.max_tokens,
+                            request_usage: config.attach_notes,
+                        },
                     )
                     .await?;
+                    let note_model = generated.fallback_model.as_deref().unwrap_or(model);
+                    let note_usage = generated.usage;
+                    let commit_msg = generated.message;
 
                     let _ = term.show_cursor();
+                    let commit_msg = policy.prepare(&commit_msg);
 
                     if !commit_msg.is_empty() {
-                        match prompt_commit(&commit_msg, false) {
+                        let editor = resolve_editor(config.editor.as_deref());
+                        let reference_diff = config.verbose_editor.then_some(commit_diff.as_str());
+                        match prompt_commit(
+                            &commit_msg,
+                            false,
+                            &editor,
+                            reference_diff,
+                            &mut StdinInput,
+                        ) {
                             CommitAction::Commit(msg) => {
-                                run_git_commit(&msg).await?;
+                                let options = CommitOptions {
+                                    sign: sign_flag_needed(config.sign).await,
+                                    no_verify: args.no_verify,
+                                    verbose,
+                                    ..Default::default()
+                                };
+                                policy.commit(&msg, &options).await?;
                                 println!("{} Committed", style("✓").green());
+                                let outcome = if msg == commit_msg {
+                                    Outcome::Accepted
+                                } else {
+                                    Outcome::Edited
+                                };
+                                attach_note(config, note_model, outcome, note_usage, verbose).await;
                                 println!();
                             }
                             CommitAction::Cancel => {
@@ -299,13 +864,13 @@ pub async fn handle_pr_command(
             }
             UncommittedAction::Quit => {
                 println!("{} Cancelled", style("—").dim());
-                std::process::exit(0);
+                std::process::exit(exit_code::SUCCESS);
             }
         }
     }
 
     // Get commits on this branch
-    let commits = get_branch_commits(&base_branch).await?;
+    let commits = get_branch_commits(&base_branch, verbose).await?;
     if commits.is_empty() {
         println!(
             "{} No commits found between '{}' and '{}'",
@@ -317,86 +882,292 @@ pub async fn handle_pr_command(
             "  {} Make some commits first, or check your base branch",
             style("→").dim()
         );
-        std::process::exit(1);
+        std::process::exit(exit_code::FAILURE);
     }
 
     if verbose {
         eprintln!("— Found {} commits on branch", commits.len());
     }
 
-    // Get diff and file list
-    let (diff_result, files_result) = tokio::join!(
-        get_branch_diff(&base_branch, verbose),
-        get_pr_changed_files(&base_branch, verbose)
-    );
-
-    let diff = diff_result?;
-    let files = files_result?;
+    // The repository's template, if any, replaces the default sections
+    let template = match get_repo_root().await {
+        Some(root) => load_pr_template(&root),
+        None => None,
+    };
+    if verbose {
+        if let Some((path, _)) = &template {
+            eprintln!("— PR template: {}", path.display());
+        }
+    }
+    let template = template.as_ref().map(|(_, contents)| contents.as_str());
 
-    if diff.trim().is_empty() {
-        println!(
-            "{} No changes found between '{}' and '{}'",
-            style("✗").red(),
-            style(&base_branch).dim(),
-            style(&current_branch).cyan()
+    let prompt = if args.fill_from_commits {
+        // The commit messages stand in for the diff, which is never read
+        let (messages_result, files_result) = tokio::join!(
+            get_branch_commit_messages(&base_branch, verbose),
+            get_pr_changed_files(&base_branch, verbose)
+        );
+        let messages = messages_result?;
+        if verbose {
+            eprintln!(
+                "— Writing the description from {} commit messages",
+                messages.len()
+            );
+        }
+        build_pr_prompt_from_commits(&files_result?, &messages, template)
+    } else {
+        // Get diff and file list
+        let (diff_result, files_result) = tokio::join!(
+            get_branch_diff(&base_branch, verbose),
+            get_pr_changed_files(&base_branch, verbose)
         );
-        std::process::exit(1);
-    }
 
-    // Create HTTP client
-    let client = Client::builder().build()?;
+        let mut diff = diff_result?;
+        let files = files_result?;
+        if let Some(command) = &config.diff_preprocessor {
+            diff = run_diff_preprocessor(command, &diff, verbose).await?;
+        }
+
+        if diff.trim().is_empty() {
+            println!(
+                "{} No changes found between '{}' and '{}'",
+                style("✗").red(),
+                style(&base_branch).dim(),
+                style(&current_branch).cyan()
+            );
+            std::process::exit(exit_code::FAILURE);
+        }
+
+        let diff = fit_diff_to_context(
+            &client,
+            model,
+            diff,
+            &format!("{}\n{}", files, commits.join("\n")),
+            verbose,
+        )
+        .await;
+        build_pr_prompt(&diff, &files, &commits, template)
+    };
+
     let term = Term::stdout();
     let _ = term.hide_cursor();
 
     // Stream PR content with spinner
-    let spinner = ProgressBar::new_spinner();
-    spinner.set_style(
-        ProgressStyle::default_spinner()
-            .tick_strings(&["⠋", "⠙", "⠹", "⠸", "⠼", "⠴", "⠦", "⠧", "⠇", "⠏"])
-            .template("{spinner:.cyan} Generating PR content...")
-            .unwrap(),
-    );
+    let spinner = phase_spinner(phase_message("Generating PR content", model));
     spinner.enable_steady_tick(std::time::Duration::from_millis(80));
 
     let (title, body) = stream_pr_content(
-        &client, &api_key, model, &diff, &files, &commits, &spinner, verbose,
+        &client,
+        &api_key,
+        model,
+        prompt,
+        &spinner,
+        config.stream_buffer_size,
+        provider.as_ref(),
+        config.max_retries,
+        config.show_stream && !args.no_stream_display,
     )
     .await?;
 
     let _ = term.show_cursor();
 
+    if let Some(pr) = &existing_pr {
+        return update_existing_pr(pr, &title, &body, &args, config, &current_branch, &remotes)
+            .await;
+    }
+
     if args.dry_run {
         println!();
         println!("{} Dry run complete (PR not created)", style("✓").green());
         return Ok(());
     }
 
-    if args.yes {
-        // Push branch if needed
-        push_branch_with_spinner(&current_branch).await?;
-        let url = create_pr(&title, &body, args.draft).await?;
-        println!(
-            "{} PR created: {}",
-            style("✓").green(),
-            style(&url).cyan().underlined()
-        );
+    let (title, body) = if args.yes {
+        (title, body)
+    } else if !can_prompt() {
+        println!("{} {}", style("✗").red(), NO_TTY_MESSAGE);
+        std::process::exit(exit_code::FAILURE);
     } else {
-        match prompt_pr(&title, &body) {
-            PrAction::Create(final_title, final_body) => {
-                // Push branch if needed
-                push_branch_with_spinner(&current_branch).await?;
-                let url = create_pr(&final_title, &final_body, args.draft).await?;
-                println!(
-                    "{} PR created: {}",
-                    style("✓").green(),
-                    style(&url).cyan().underlined()
-                );
-            }
+        match prompt_pr(
+            &title,
+            &body,
+            &resolve_editor(config.editor.as_deref()),
+            &format!("Create {}", forge.request_name()),
+            &mut StdinInput,
+        ) {
+            PrAction::Create(final_title, final_body) => (final_title, final_body),
             PrAction::Cancel => {
                 println!("{} Cancelled", style("—").dim());
+                return Ok(());
             }
         }
+    };
+
+    // Push branch if needed
+    push_branch_with_spinner(&current_branch, &remotes.head, verbose).await?;
+    let url = forge.create_pr(&title, &body, pr_base, args.draft).await?;
+    println!(
+        "{} {} created: {}",
+        style("✓").green(),
+        forge.request_name(),
+        style(&url).cyan().underlined()
+    );
+
+    let reviewers = merge_unique(&config.default_reviewers, &args.reviewers);
+    let labels = merge_unique(&config.default_labels, &args.labels);
+    if !reviewers.is_empty() || !labels.is_empty() {
+        let warnings = forge
+            .add_reviewers_and_labels(&url, &reviewers, &labels)
+            .await;
+        for warning in &warnings {
+            println!("{} {}", style("⚠").yellow(), warning);
+        }
+    }
+
+    Ok(())
+}
+
+/// Combines config and CLI lists, keeping the first of any duplicates.
+///
+/// Entries are trimmed of whitespace and a leading `@` (so `@alice` is
+/// `alice`), and compared case-insensitively, since GitHub treats usernames
+/// and label names that way. Empty entries are dropped.
+pub fn merge_unique(config: &[String], cli: &[String]) -> Vec<String> {
+    let mut merged: Vec<String> = Vec::new();
+    for entry in config.iter().chain(cli) {
+        let entry = entry.trim().trim_start_matches('@');
+        if !entry.is_empty() && !merged.iter().any(|seen| seen.eq_ignore_ascii_case(entry)) {
+            merged.push(entry.to_string());
+        }
+    }
+    merged
+}
+
+/// Shows how the regenerated description differs and applies it to `pr`.
+async fn update_existing_pr(
+    pr: &ExistingPr,
+    title: &str,
+    body: &str,
+    args: &PrArgs,
+    config: &Config,
+    branch: &str,
+    remotes: &PrRemotes,
+) -> Result<(), Box<dyn std::error::Error>> {
+    let verbose = args.verbose || config.verbose;
+    let show_changes = |new_title: &str, new_body: &str| {
+        println!();
+        println!("{} Changes to PR #{}:", style("→").dim(), pr.number);
+        println!();
+        print_text_diff(
+            &format!("{}\n\n{}", pr.title, pr.body.trim()),
+            &format!("{}\n\n{}", new_title, new_body),
+        );
+        println!();
+    };
+
+    show_changes(title, body);
+
+    if args.dry_run {
+        println!("{} Dry run complete (PR not updated)", style("✓").green());
+        return Ok(());
     }
 
+    let (final_title, final_body) = if args.yes {
+        (title.to_string(), body.to_string())
+    } else if !can_prompt() {
+        println!("{} {}", style("✗").red(), NO_TTY_MESSAGE);
+        std::process::exit(exit_code::FAILURE);
+    } else {
+        match prompt_pr(
+            title,
+            body,
+            &resolve_editor(config.editor.as_deref()),
+            "Update PR",
+            &mut StdinInput,
+        ) {
+            PrAction::Create(final_title, final_body) => (final_title, final_body),
+            PrAction::Cancel => {
+                println!("{} Cancelled", style("—").dim());
+                return Ok(());
+            }
+        }
+    };
+
+    // Push first so the PR's commits match the description
+    push_branch_with_spinner(branch, &remotes.head, verbose).await?;
+    update_pr(pr, &final_title, &final_body, verbose).await?;
+    println!(
+        "{} PR updated: {}",
+        style("✓").green(),
+        style(&pr.url).cyan().underlined()
+    );
+
     Ok(())
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_support::TempDir;
+
+    fn strings(items: &[&str]) -> Vec<String> {
+        items.iter().map(|item| item.to_string()).collect()
+    }
+
+    #[test]
+    fn merge_unique_keeps_config_entries_first_without_duplicates() {
+        assert_eq!(
+            merge_unique(
+                &strings(&["alice", "bug"]),
+                &strings(&["@Alice", "carol", "BUG"])
+            ),
+            ["alice", "bug", "carol"]
+        );
+    }
+
+    #[test]
+    fn merge_unique_drops_duplicates_within_one_list() {
+        assert_eq!(
+            merge_unique(&[], &strings(&["docs", " docs ", "Docs", "ci"])),
+            ["docs", "ci"]
+        );
+    }
+
+    #[test]
+    fn merge_unique_drops_empty_entries() {
+        assert_eq!(
+            merge_unique(&strings(&["", "  ", "@"]), &strings(&["bob"])),
+            ["bob"]
+        );
+        assert!(merge_unique(&[], &[]).is_empty());
+    }
+
+    #[test]
+    fn load_pr_template_finds_a_template_in_any_case() {
+        let repo = TempDir::new("template-present");
+        let path = repo.write(".github/PULL_REQUEST_TEMPLATE.md", "## What\n");
+        assert_eq!(
+            load_pr_template(repo.path()),
+            Some((path, "## What\n".to_string()))
+        );
+    }
+
+    #[test]
+    fn load_pr_template_prefers_the_github_directory() {
+        let repo = TempDir::new("template-order");
+        repo.write("docs/pull_request_template.md", "docs\n");
+        let path = repo.write(".github/pull_request_template.md", "github\n");
+        assert_eq!(
+            load_pr_template(repo.path()).map(|(found, _)| found),
+            Some(path)
+        );
+    }
+
+    #[test]
+    fn load_pr_template_without_a_template_is_none() {
+        let repo = TempDir::new("template-absent");
+        assert_eq!(load_pr_template(repo.path()), None);
+        repo.write("pull_request_template.md", "  \n");
+        assert_eq!(load_pr_template(repo.path()), None);
+    }
+}
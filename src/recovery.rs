@@ -0,0 +1,85 @@
+//! Saved messages from runs that ended without a commit.
+//!
+//! When a generated message is cancelled, previewed with `--dry-run`, or
+//! `git commit` fails (e.g. a hook rejects it), the message is saved to
+//! `.git/COMMITTER_RECOVERY.json` together with the index tree it was
+//! generated for. `--from-recovery` commits with that message instead of
+//! generating a new one, warning if the staged changes have changed since.
+
+use serde::{Deserialize, Serialize};
+use std::path::PathBuf;
+use std::time::{SystemTime, UNIX_EPOCH};
+use tokio::process::Command;
+
+use crate::git::get_git_path;
+
+/// File name of the recovery file inside the git directory.
+pub const RECOVERY_FILE: &str = "COMMITTER_RECOVERY.json";
+
+/// A message saved from a run that didn't commit.
+#[derive(Serialize, Deserialize)]
+pub struct Recovery {
+    /// Seconds since the Unix epoch when the message was saved.
+    pub saved_at: u64,
+    /// The message as last reviewed, without trailers.
+    pub message: String,
+    /// Index tree the message was generated for (`git write-tree`).
+    pub tree: Option<String>,
+}
+
+async fn git_output(args: &[&str]) -> Option<String> {
+    let output = Command::new("git").args(args).output().await.ok()?;
+    if !output.status.success() {
+        return None;
+    }
+    Some(String::from_utf8_lossy(&output.stdout).trim().to_string())
+}
+
+/// Returns the path of the recovery file for the current repository.
+pub async fn recovery_path() -> Option<PathBuf> {
+    get_git_path(RECOVERY_FILE).await
+}
+
+/// Returns the tree object the index currently records.
+pub async fn get_index_tree() -> Option<String> {
+    git_output(&["write-tree"]).await
+}
+
+/// Saves a message for `--from-recovery`, replacing any earlier one.
+///
+/// Failures are reported as warnings; recovery is best-effort.
+pub async fn save_recovery(message: &str) {
+    let Some(path) = recovery_path().await else {
+        return;
+    };
+
+    let recovery = Recovery {
+        saved_at: SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map(|d| d.as_secs())
+            .unwrap_or(0),
+        message: message.to_string(),
+        tree: get_index_tree().await,
+    };
+
+    let result = serde_json::to_string_pretty(&recovery)
+        .map_err(|e| e.to_string())
+        .and_then(|json| std::fs::write(&path, json).map_err(|e| e.to_string()));
+    match result {
+        Ok(()) => eprintln!("— Message saved; commit it later with: committer --from-recovery"),
+        Err(e) => eprintln!("— Failed to save recovery message: {}", e),
+    }
+}
+
+/// Loads the saved message, if any.
+pub async fn load_recovery() -> Option<Recovery> {
+    let contents = std::fs::read_to_string(recovery_path().await?).ok()?;
+    serde_json::from_str(&contents).ok()
+}
+
+/// Removes the recovery file once its message has been committed.
+pub async fn clear_recovery() {
+    if let Some(path) = recovery_path().await {
+        let _ = std::fs::remove_file(path);
+    }
+}
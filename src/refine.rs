@@ -0,0 +1,97 @@
+//! Reformatting a message the author already wrote (`--refine`).
+//!
+//! Meant for `commit-msg` hooks: the message comes from the hook's file
+//! (rewritten in place) or from stdin (printed to stdout). One that already
+//! follows Conventional Commits is left alone; otherwise the model rewords it
+//! as little as possible, guided by [`refine_prompt_note`], and the original
+//! trailers are put back unchanged.
+//!
+//! Nothing is committed, and whenever the message can't be refined it's left
+//! as written, so the hook never blocks a commit over it.
+
+use std::path::{Path, PathBuf};
+
+use crate::format::{append_trailers, split_message, strip_scissors};
+
+/// A message given to `--refine`, and where the result goes.
+pub struct RefineTarget {
+    /// File to rewrite; `None` reads stdin and prints to stdout.
+    path: Option<PathBuf>,
+    /// The message as written, without git's comment lines.
+    pub message: String,
+}
+
+impl RefineTarget {
+    /// Reads the message from `path`, or from stdin if it is `-`.
+    pub fn read(path: &Path) -> Result<Self, Box<dyn std::error::Error>> {
+        let (path, raw) = if path == Path::new("-") {
+            (None, std::io::read_to_string(std::io::stdin())?)
+        } else {
+            let raw = std::fs::read_to_string(path)
+                .map_err(|e| format!("Could not read '{}': {}", path.display(), e))?;
+            (Some(path.to_path_buf()), raw)
+        };
+        Ok(Self {
+            path,
+            message: strip_git_comments(&raw),
+        })
+    }
+
+    /// Returns true if the result is printed rather than written to a file.
+    pub fn prints(&self) -> bool {
+        self.path.is_none()
+    }
+
+    /// Writes `message`, followed by the original trailers, as the result.
+    pub fn write(&self, message: &str) -> Result<(), Box<dyn std::error::Error>> {
+        let (_, _, trailers) = split_message(&self.message);
+        let trailers: Vec<String> = trailers.lines().map(str::to_string).collect();
+        let message = append_trailers(message.trim(), &trailers);
+        match &self.path {
+            Some(path) => std::fs::write(path, format!("{}\n", message))
+                .map_err(|e| format!("Could not write '{}': {}", path.display(), e).into()),
+            None => {
+                println!("{}", message);
+                Ok(())
+            }
+        }
+    }
+
+    /// Leaves the message as written: the file untouched, or printed as read.
+    pub fn keep(&self) -> Result<(), Box<dyn std::error::Error>> {
+        if self.path.is_none() && !self.message.is_empty() {
+            println!("{}", self.message);
+        }
+        Ok(())
+    }
+}
+
+/// Removes what git drops from an edited message: `#` comment lines and
+/// everything below a scissors line.
+pub fn strip_git_comments(message: &str) -> String {
+    strip_scissors(message)
+        .lines()
+        .filter(|line| !line.starts_with('#'))
+        .collect::<Vec<_>>()
+        .join("\n")
+        .trim()
+        .to_string()
+}
+
+/// Prompt guidance that has the model reformat `message` rather than
+/// describe the diff afresh.
+pub fn refine_prompt_note(message: &str) -> String {
+    let (subject, body, _) = split_message(message);
+    let message = if body.is_empty() {
+        subject.to_string()
+    } else {
+        format!("{}\n\n{}", subject, body)
+    };
+    format!(
+        "The author already wrote this commit message:\n\"\"\"\n{}\n\"\"\"\n\
+         Reformat it into the required format instead of writing a new one. Keep the author's \
+         wording and intent, use the diff only to choose the type and scope, and don't add \
+         changes or details the message doesn't mention. Leave out any trailers.",
+        message
+    )
+}
@@ -0,0 +1,223 @@
+//! Commit message templates for a fixed house style.
+//!
+//! A template (`template` in config, or `--template-file`) is plain text with
+//! placeholders. The generated message is split into [`CommitParts`] and
+//! rendered with [`render_template`]:
+//!
+//! | Placeholder  | Value                                         |
+//! |--------------|-----------------------------------------------|
+//! | `{type}`     | Conventional-commit type, e.g. `feat`         |
+//! | `{scope}`    | Scope, without the parentheses                |
+//! | `{breaking}` | `!` for a breaking change, otherwise empty    |
+//! | `{subject}`  | Subject description after `type(scope): `     |
+//! | `{body}`     | Body, including any trailers                  |
+//! | `{ticket}`   | Ticket from the branch name (`issue_pattern`) |
+//!
+//! ```text
+//! [{ticket}] {type}({scope}): {subject}
+//!
+//! {body}
+//! ```
+//!
+//! A placeholder with no value renders as nothing, and on lines without
+//! `{body}` the brackets and spaces left around it are tidied, so
+//! `{type}({scope}): {subject}` gives `docs: fix typo` when there's no
+//! scope. Unknown `{tokens}` are kept as written. A template without
+//! `{body}` describes the subject only; the body follows it after a blank
+//! line.
+
+use crate::emoji::strip_emoji_prefix;
+use crate::format::{normalize_spacing, split_message};
+
+/// The pieces of a generated commit message a template can use.
+#[derive(Debug, Default, Clone, PartialEq, Eq)]
+pub struct CommitParts {
+    /// Conventional-commit type; empty if the subject isn't conventional.
+    pub commit_type: String,
+    /// Scope, empty if none.
+    pub scope: String,
+    /// Whether the subject marks a breaking change (`feat!:`).
+    pub breaking: bool,
+    /// Subject description, or the whole subject if it isn't conventional.
+    pub subject: String,
+    /// Everything after the subject line, trailers included.
+    pub body: String,
+    /// Ticket from the branch name, empty if none.
+    pub ticket: String,
+}
+
+impl CommitParts {
+    /// Splits a `type(scope)!: subject` message into its parts.
+    pub fn parse(message: &str, ticket: Option<&str>) -> Self {
+        let (subject, body, trailers) = split_message(message);
+        let body = [body, trailers]
+            .iter()
+            .filter(|part| !part.is_empty())
+            .copied()
+            .collect::<Vec<_>>()
+            .join("\n\n");
+        let ticket = ticket.unwrap_or_default().to_string();
+
+        let conventional =
+            strip_emoji_prefix(subject)
+                .split_once(": ")
+                .and_then(|(header, description)| {
+                    let (header, breaking) = match header.strip_suffix('!') {
+                        Some(header) => (header, true),
+                        None => (header, false),
+                    };
+                    let (commit_type, scope) = match header.split_once('(') {
+                        Some((commit_type, scope)) => (commit_type, scope.strip_suffix(')')?),
+                        None => (header, ""),
+                    };
+                    let valid = !commit_type.is_empty()
+                        && commit_type.chars().all(|c| c.is_ascii_alphanumeric());
+                    valid.then_some((commit_type, scope, breaking, description))
+                });
+
+        match conventional {
+            Some((commit_type, scope, breaking, description)) => Self {
+                commit_type: commit_type.to_string(),
+                scope: scope.trim().to_string(),
+                breaking,
+                subject: description.trim().to_string(),
+                body,
+                ticket,
+            },
+            None => Self {
+                subject: subject.to_string(),
+                body,
+                ticket,
+                ..Default::default()
+            },
+        }
+    }
+
+    /// Returns the value for a placeholder name, or `None` if it's unknown.
+    fn value(&self, name: &str) -> Option<&str> {
+        match name {
+            "type" => Some(&self.commit_type),
+            "scope" => Some(&self.scope),
+            "breaking" => Some(if self.breaking { "!" } else { "" }),
+            "subject" => Some(&self.subject),
+            "body" => Some(&self.body),
+            "ticket" => Some(&self.ticket),
+            _ => None,
+        }
+    }
+}
+
+/// Whether `template` uses the `{name}` placeholder.
+pub fn uses_placeholder(template: &str, name: &str) -> bool {
+    template.contains(&format!("{{{}}}", name))
+}
+
+/// Renders `template` with the values in `parts`.
+///
+/// See the [module docs](self) for the placeholders and how empty values
+/// are tidied.
+pub fn render_template(template: &str, parts: &CommitParts) -> String {
+    let mut lines: Vec<String> = template
+        .trim()
+        .lines()
+        .map(|line| {
+            let rendered = substitute(line, parts);
+            if uses_placeholder(line, "body") {
+                rendered
+            } else {
+                tidy_line(&rendered)
+            }
+        })
+        .collect();
+    if !uses_placeholder(template, "body") && !parts.body.is_empty() {
+        lines.push(String::new());
+        lines.push(parts.body.clone());
+    }
+    normalize_spacing(&lines.join("\n"))
+}
+
+/// Replaces known `{name}` placeholders in one line, keeping unknown ones.
+fn substitute(line: &str, parts: &CommitParts) -> String {
+    let mut result = String::with_capacity(line.len());
+    let mut rest = line;
+    while let Some(start) = rest.find('{') {
+        result.push_str(&rest[..start]);
+        let after = &rest[start + 1..];
+        let value = after
+            .find('}')
+            .and_then(|end| Some((parts.value(&after[..end])?, end)));
+        match value {
+            Some((value, end)) => {
+                result.push_str(value);
+                rest = &after[end + 1..];
+            }
+            None => {
+                result.push('{');
+                rest = after;
+            }
+        }
+    }
+    result.push_str(rest);
+    result
+}
+
+/// Drops brackets left empty by missing values and collapses extra spaces.
+///
+/// Leading indentation is kept.
+fn tidy_line(line: &str) -> String {
+    let mut line = line.trim_end().to_string();
+    for empty in ["()", "[]"] {
+        line = line.replace(empty, "");
+    }
+    let indent_len = line.len() - line.trim_start().len();
+    let (indent, text) = line.split_at(indent_len);
+    let words: Vec<&str> = text.split(' ').filter(|word| !word.is_empty()).collect();
+    // Missing values can leave a stray space before punctuation, e.g. "feat :"
+    let text = words.join(" ").replace(" :", ":");
+    format!("{}{}", indent, text.trim_start_matches([':', ' ']))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn render_template_fills_known_placeholders() {
+        let parts =
+            CommitParts::parse("feat(api)!: add pagination\n\n- Add cursor", Some("PROJ-7"));
+        assert_eq!(
+            render_template(
+                "[{ticket}] {type}({scope}){breaking}: {subject}\n\n{body}",
+                &parts
+            ),
+            "[PROJ-7] feat(api)!: add pagination\n\n- Add cursor"
+        );
+    }
+
+    #[test]
+    fn render_template_tidies_missing_placeholders() {
+        let parts = CommitParts::parse("docs: fix typo", None);
+        assert_eq!(
+            render_template("[{ticket}] {type}({scope}): {subject}", &parts),
+            "docs: fix typo"
+        );
+    }
+
+    #[test]
+    fn render_template_keeps_unknown_tokens() {
+        let parts = CommitParts::parse("fix(ui): handle scroll", None);
+        assert_eq!(
+            render_template("{type}({scope}): {subject} {unknown} {", &parts),
+            "fix(ui): handle scroll {unknown} {"
+        );
+    }
+
+    #[test]
+    fn render_template_appends_the_body_when_not_placed() {
+        let parts = CommitParts::parse("fix: handle scroll\n\n- Clamp offset", None);
+        assert_eq!(
+            render_template("{type}: {subject}", &parts),
+            "fix: handle scroll\n\n- Clamp offset"
+        );
+    }
+}
@@ -0,0 +1,102 @@
+//! Scratch directories and repositories for unit tests.
+//!
+//! Each fixture lives in its own directory under the system temp dir and is
+//! removed on drop, so tests can run in parallel without sharing state.
+
+use std::path::{Path, PathBuf};
+use std::process::Command;
+use std::sync::atomic::{AtomicUsize, Ordering};
+
+/// Numbers fixtures so two tests using the same name don't collide.
+static NEXT_ID: AtomicUsize = AtomicUsize::new(0);
+
+/// A scratch directory named after the test, removed on drop.
+pub struct TempDir(PathBuf);
+
+impl TempDir {
+    pub fn new(name: &str) -> Self {
+        let dir = std::env::temp_dir().join(format!(
+            "committer-{}-{}-{}",
+            name,
+            std::process::id(),
+            NEXT_ID.fetch_add(1, Ordering::Relaxed)
+        ));
+        let _ = std::fs::remove_dir_all(&dir);
+        std::fs::create_dir_all(&dir).unwrap();
+        Self(dir)
+    }
+
+    pub fn path(&self) -> &Path {
+        &self.0
+    }
+
+    /// Writes `contents` to `path` inside the directory, creating parents.
+    pub fn write(&self, path: &str, contents: &str) -> PathBuf {
+        let path = self.0.join(path);
+        std::fs::create_dir_all(path.parent().unwrap()).unwrap();
+        std::fs::write(&path, contents).unwrap();
+        path
+    }
+}
+
+impl Drop for TempDir {
+    fn drop(&mut self) {
+        let _ = std::fs::remove_dir_all(&self.0);
+    }
+}
+
+/// A scratch repository with one committed file, `a.txt`.
+pub struct TestRepo(TempDir);
+
+impl TestRepo {
+    pub fn new(name: &str) -> Self {
+        let repo = Self(TempDir::new(name));
+        repo.git(&["init", "-q"]);
+        repo.write("a.txt", "one\n");
+        repo.git(&["add", "a.txt"]);
+        repo.commit("initial");
+        repo
+    }
+
+    pub fn path(&self) -> &Path {
+        self.0.path()
+    }
+
+    /// Runs git in the repository, returning its trimmed stdout.
+    pub fn git(&self, args: &[&str]) -> String {
+        let output = Command::new("git")
+            .arg("-C")
+            .arg(self.path())
+            .args(args)
+            .output()
+            .unwrap();
+        assert!(output.status.success(), "git {:?} failed", args);
+        String::from_utf8_lossy(&output.stdout).trim().to_string()
+    }
+
+    pub fn write(&self, path: &str, contents: &str) -> PathBuf {
+        self.0.write(path, contents)
+    }
+
+    /// Commits the index with a fixed identity, skipping hooks and signing.
+    pub fn commit(&self, message: &str) {
+        self.git(&[
+            "-c",
+            "user.name=Test",
+            "-c",
+            "user.email=test@example.com",
+            "-c",
+            "commit.gpgsign=false",
+            "commit",
+            "-q",
+            "--no-verify",
+            "-m",
+            message,
+        ]);
+    }
+
+    /// Returns the staged file names, one per line.
+    pub fn staged(&self) -> String {
+        self.git(&["diff", "--cached", "--name-only"])
+    }
+}
@@ -5,7 +5,15 @@
 //!
 //! - Single-key responses (y/n/e)
 //! - Full word responses (yes/no/edit)
-//! - Editor integration for message editing
+//! - Editor integration for message editing, using the same editor as
+//!   `git commit` (see [`resolve_editor`])
+//!
+//! Each prompt reads its answers from the [`PromptInput`] it is given
+//! ([`StdinInput`] outside tests). End of input, or input that isn't a
+//! terminal, answers a prompt the safe way (quit, cancel, or stay). Runs
+//! without a terminal on stdin, such as pipes and CI jobs, check
+//! [`can_prompt`] first and stop with [`NO_TTY_MESSAGE`] rather than wait for
+//! an answer that never comes.
 //!
 //! # Prompts
 //!
@@ -13,13 +21,98 @@
 //! - [`prompt_pr`]: Confirm or edit PR title/body
 //! - [`prompt_branch_action`]: Create or skip branch creation
 //! - [`prompt_uncommitted_changes`]: Handle uncommitted changes before PR
+//! - [`prompt_continue_stale`]: Continue or abort on a stale/detached branch
+//! - [`prompt_feedback`]: Accept, cancel, or critique a message for revision
+//! - [`prompt_roll_back_branch`]: Undo a branch created for a failed commit
+//! - [`prompt_select_files`]: Choose the files to commit for `--select-files`
+//! - [`prompt_select_model`]: Choose the default model for `config model`
+//! - [`prompt_select_base`]: Choose the PR base branch for `pr --interactive-base`
+//!
+//! # Reports
+//!
+//! - [`print_message_stats`]: Show subject/body length metrics in dry-run
+//! - [`print_text_diff`]: Show line changes between two texts
+//! - [`print_token_estimate`]: Show the `--count` prompt size and cost estimate
+//! - [`print_usage`]: Show the tokens a generation used (`--verbose`)
+//! - [`report_outcome`]: Report a final outcome per `--quiet` / `--json`
+//!
+//! # Progress
+//!
+//! - [`phase_spinner`]: Spinner for one step of a run, usually labeled with
+//!   [`phase_message`] so it names the model doing the work
 
 use console::style;
-use dialoguer::Input;
-use std::io::{self, Write};
+use dialoguer::{Input, MultiSelect, Select};
+use indicatif::{ProgressBar, ProgressStyle};
+use std::io::{self, IsTerminal, Write};
 
+use crate::api::Usage;
 use crate::branch::BranchAction;
-use crate::git::UncommittedChanges;
+use crate::format::{
+    add_reference_diff, convert_line_endings, message_stats, normalize_line_endings,
+    strip_scissors, MessageStats, BODY_MAX_WIDTH,
+};
+use crate::git::{ChangedFile, NewBranch, UncommittedChanges};
+use crate::models::{estimate_cost, ModelInfo};
+
+/// Shown instead of a prompt when stdin isn't a terminal.
+pub const NO_TTY_MESSAGE: &str = "No TTY; pass --yes or --dry-run";
+
+/// Where prompts read their answers from.
+pub trait PromptInput {
+    /// Returns true if someone can answer prompts, i.e. input is a terminal.
+    fn is_terminal(&self) -> bool;
+    /// Reads one line, or `None` at the end of input.
+    fn read_line(&mut self) -> Option<String>;
+}
+
+/// Prompt answers typed on stdin.
+pub struct StdinInput;
+
+impl PromptInput for StdinInput {
+    fn is_terminal(&self) -> bool {
+        io::stdin().is_terminal()
+    }
+
+    fn read_line(&mut self) -> Option<String> {
+        let mut line = String::new();
+        match io::stdin().read_line(&mut line) {
+            Ok(0) | Err(_) => None,
+            Ok(_) => Some(line),
+        }
+    }
+}
+
+/// Checks that `input` can answer a prompt, failing with [`NO_TTY_MESSAGE`].
+pub fn ensure_can_prompt(input: &dyn PromptInput) -> Result<(), &'static str> {
+    if input.is_terminal() {
+        Ok(())
+    } else {
+        Err(NO_TTY_MESSAGE)
+    }
+}
+
+/// Returns true if stdin is a terminal someone can answer prompts on.
+pub fn can_prompt() -> bool {
+    ensure_can_prompt(&StdinInput).is_ok()
+}
+
+/// Reads one answer to a prompt, or `None` at the end of input.
+///
+/// Input that isn't a terminal is never read, so a prompt reached without
+/// a [`can_prompt`] check still answers the safe way instead of taking
+/// piped text as the answer.
+fn read_choice(input: &mut dyn PromptInput) -> Option<String> {
+    let line = if input.is_terminal() {
+        input.read_line()
+    } else {
+        None
+    };
+    if line.is_none() {
+        println!();
+    }
+    line
+}
 
 /// User's choice when uncommitted changes are detected.
 pub enum UncommittedAction {
@@ -28,10 +121,272 @@ pub enum UncommittedAction {
     Quit,
 }
 
+/// Warns about the branch state and asks whether to commit anyway.
+///
+/// Returns true to continue.
+pub fn prompt_continue_stale(warning: &str, hint: &str, input: &mut dyn PromptInput) -> bool {
+    println!("{} {}", style("⚠").yellow(), warning);
+    println!("  {} {}", style("→").dim(), hint);
+    println!();
+    println!("  {} Continue anyway", style("[c]").cyan().bold());
+    println!("  {} Quit", style("[q]").cyan().bold());
+    println!();
+
+    loop {
+        print!("{} ", style("Choice:").bold());
+        io::stdout().flush().unwrap();
+
+        let Some(answer) = read_choice(input) else {
+            return false;
+        };
+
+        match answer.trim().to_lowercase().as_str() {
+            "c" | "continue" => return true,
+            "q" | "quit" => return false,
+            _ => println!("  {} Please enter c or q", style("→").dim()),
+        }
+    }
+}
+
+/// Offers the closest allowed scope for one the model made up.
+///
+/// Returns true to use `suggestion`, false to drop the scope.
+pub fn prompt_use_scope(rejected: &str, suggestion: &str, input: &mut dyn PromptInput) -> bool {
+    println!();
+    println!(
+        "{} Scope '{}' isn't one of the allowed scopes",
+        style("⚠").yellow(),
+        rejected
+    );
+    println!();
+    println!("  {} Use '{}'", style("[y]").cyan().bold(), suggestion);
+    println!("  {} Drop the scope", style("[n]").cyan().bold());
+    println!();
+
+    loop {
+        print!("{} ", style("Choice:").bold());
+        io::stdout().flush().unwrap();
+
+        let Some(answer) = read_choice(input) else {
+            return false;
+        };
+
+        match answer.trim().to_lowercase().as_str() {
+            "y" | "yes" => return true,
+            "n" | "no" => return false,
+            _ => println!("  {} Please enter y or n", style("→").dim()),
+        }
+    }
+}
+
+/// Asks whether to undo the branch created for a commit that failed.
+///
+/// Returns true to switch back and delete the new branch.
+pub fn prompt_roll_back_branch(branch: &NewBranch, input: &mut dyn PromptInput) -> bool {
+    println!();
+    println!(
+        "  {} Switch back to '{}' and delete '{}'",
+        style("[y]").cyan().bold(),
+        branch.original,
+        branch.name
+    );
+    println!("  {} Stay on '{}'", style("[n]").cyan().bold(), branch.name);
+    println!();
+
+    loop {
+        print!("{} ", style("Choice:").bold());
+        io::stdout().flush().unwrap();
+
+        let Some(answer) = read_choice(input) else {
+            return false;
+        };
+
+        match answer.trim().to_lowercase().as_str() {
+            "y" | "yes" => return true,
+            "n" | "no" => return false,
+            _ => println!("  {} Please enter y or n", style("→").dim()),
+        }
+    }
+}
+
+/// User's response in the `--interactive-regenerate` loop.
+pub enum Feedback {
+    /// Use the current message.
+    Accept,
+    /// Stop without committing.
+    Cancel,
+    /// Regenerate, taking this critique into account.
+    Revise(String),
+}
+
+/// Asks for a critique of the current message, or accept/cancel.
+///
+/// An empty line or `y` accepts, `q` cancels, anything else is feedback.
+pub fn prompt_feedback() -> Feedback {
+    println!();
+    println!(
+        "  {} Type feedback to regenerate (e.g. \"too verbose\")",
+        style("→").dim()
+    );
+    println!(
+        "  {} Enter or {} to accept, {} to cancel",
+        style("→").dim(),
+        style("y").cyan().bold(),
+        style("q").cyan().bold()
+    );
+    println!();
+    print!("{} ", style("Feedback:").bold());
+    io::stdout().flush().unwrap();
+
+    let mut input = String::new();
+    // End of input cancels rather than committing unattended
+    if io::stdin().read_line(&mut input).unwrap_or(0) == 0 {
+        return Feedback::Cancel;
+    }
+
+    match input.trim() {
+        "" | "y" | "Y" | "yes" => Feedback::Accept,
+        "q" | "Q" | "quit" | "n" => Feedback::Cancel,
+        feedback => Feedback::Revise(feedback.to_string()),
+    }
+}
+
+/// Asks which base branch the PR targets.
+///
+/// `candidates` are `(branch, why it's listed)`; a last item lets the user
+/// type any other branch. Returns `None` if the user cancels.
+pub fn prompt_select_base(candidates: &[(String, &str)]) -> Option<String> {
+    let mut items: Vec<String> = candidates
+        .iter()
+        .map(|(branch, reason)| format!("{} {}", branch, style(format!("({})", reason)).dim()))
+        .collect();
+    items.push("Other…".to_string());
+
+    let index = Select::new()
+        .with_prompt("Base branch (arrows to move, enter to pick, esc to cancel)")
+        .items(&items)
+        .default(0)
+        .max_length(15)
+        .interact_opt()
+        .ok()
+        .flatten()?;
+    if let Some((branch, _)) = candidates.get(index) {
+        return Some(branch.clone());
+    }
+
+    let branch: String = Input::new()
+        .with_prompt("Base branch")
+        .interact_text()
+        .ok()?;
+    let branch = branch.trim();
+    (!branch.is_empty()).then(|| branch.to_string())
+}
+
+/// Shows the candidate messages and asks which one to review.
+///
+/// Returns [`CommitAction::Commit`] with the chosen message, which still
+/// goes through the usual review, or [`CommitAction::Cancel`] on escape.
+pub fn prompt_select_message(candidates: &[String]) -> CommitAction {
+    for (i, candidate) in candidates.iter().enumerate() {
+        println!();
+        println!("{}", style(format!("[{}]", i + 1)).cyan().bold());
+        println!("{}", candidate);
+    }
+    println!();
+
+    let items: Vec<String> = candidates
+        .iter()
+        .enumerate()
+        .map(|(i, candidate)| {
+            let subject = candidate.lines().next().unwrap_or_default();
+            let body_lines = message_stats(candidate).body_lines;
+            if body_lines == 0 {
+                format!("{}. {}", i + 1, subject)
+            } else {
+                format!(
+                    "{}. {} {}",
+                    i + 1,
+                    subject,
+                    style(format!("(+{} body lines)", body_lines)).dim()
+                )
+            }
+        })
+        .collect();
+
+    let index = Select::new()
+        .with_prompt("Message (arrows to move, enter to pick, esc to cancel)")
+        .items(&items)
+        .default(0)
+        .interact_opt()
+        .ok()
+        .flatten();
+    match index {
+        Some(index) => CommitAction::Commit(candidates[index].clone()),
+        None => CommitAction::Cancel,
+    }
+}
+
+/// Asks which model to use, starting on `current` if it's in the list.
+///
+/// Returns the index of the chosen model, or `None` if the user cancels.
+pub fn prompt_select_model(models: &[ModelInfo], current: &str) -> Option<usize> {
+    let items: Vec<String> = models
+        .iter()
+        .map(|info| match info.context_length {
+            Some(context) => format!(
+                "{} {}",
+                info.id,
+                style(format!("({} tokens)", context)).dim()
+            ),
+            None => info.id.clone(),
+        })
+        .collect();
+    let default = models
+        .iter()
+        .position(|info| info.id == current)
+        .unwrap_or(0);
+
+    Select::new()
+        .with_prompt("Model (arrows to move, enter to pick, esc to cancel)")
+        .items(&items)
+        .default(default)
+        .max_length(15)
+        .interact_opt()
+        .ok()
+        .flatten()
+}
+
+/// Asks which changed files to commit, with the staged ones preselected.
+///
+/// Returns the indices of the chosen files, or `None` if the user cancels
+/// (Esc or `q`) or chooses nothing.
+pub fn prompt_select_files(files: &[ChangedFile]) -> Option<Vec<usize>> {
+    let items: Vec<String> = files
+        .iter()
+        .map(|file| {
+            let marker = if file.staged { "staged" } else { "unstaged" };
+            format!("{} {}", file.path, style(format!("({})", marker)).dim())
+        })
+        .collect();
+    let defaults: Vec<bool> = files.iter().map(|file| file.staged).collect();
+
+    let selected = MultiSelect::new()
+        .with_prompt("Files to commit (space to toggle, enter to confirm)")
+        .items(&items)
+        .defaults(&defaults)
+        .interact_opt()
+        .ok()
+        .flatten()?;
+    (!selected.is_empty()).then_some(selected)
+}
+
 /// Prompts user to handle uncommitted changes before creating a PR.
 ///
 /// Displays staged and unstaged files, then asks user to commit, skip, or quit.
-pub fn prompt_uncommitted_changes(changes: &UncommittedChanges) -> UncommittedAction {
+pub fn prompt_uncommitted_changes(
+    changes: &UncommittedChanges,
+    input: &mut dyn PromptInput,
+) -> UncommittedAction {
     println!();
     println!(
         "{} Uncommitted changes won't be included in this PR",
@@ -64,10 +419,11 @@ pub fn prompt_uncommitted_changes(changes: &UncommittedChanges) -> UncommittedAc
         print!("{} ", style("Choice:").bold());
         io::stdout().flush().unwrap();
 
-        let mut input = String::new();
-        io::stdin().read_line(&mut input).unwrap();
+        let Some(answer) = read_choice(input) else {
+            return UncommittedAction::Quit;
+        };
 
-        match input.trim().to_lowercase().as_str() {
+        match answer.trim().to_lowercase().as_str() {
             "c" | "commit" => return UncommittedAction::Commit,
             "s" | "skip" => return UncommittedAction::Skip,
             "q" | "quit" => return UncommittedAction::Quit,
@@ -84,6 +440,7 @@ pub fn prompt_branch_action(
     suggested: &str,
     reason: &str,
     show_mismatch_header: bool,
+    input: &mut dyn PromptInput,
 ) -> BranchAction {
     if show_mismatch_header {
         println!();
@@ -115,19 +472,22 @@ pub fn prompt_branch_action(
         print!("{} ", style("Choice:").bold());
         io::stdout().flush().unwrap();
 
-        let mut input = String::new();
-        io::stdin().read_line(&mut input).unwrap();
+        let Some(answer) = read_choice(input) else {
+            return BranchAction::Skip;
+        };
 
-        match input.trim().to_lowercase().as_str() {
+        match answer.trim().to_lowercase().as_str() {
             "y" | "yes" => return BranchAction::Create(current_suggestion),
             "n" | "no" => return BranchAction::Skip,
             "e" | "edit" => {
-                let edited: String = Input::new()
+                let Ok(edited) = Input::<String>::new()
                     .with_prompt("Branch name")
                     .default(current_suggestion.clone())
                     .interact_text()
-                    .unwrap();
-                current_suggestion = edited.clone();
+                else {
+                    return BranchAction::Skip;
+                };
+                current_suggestion = edited;
                 // Reprint menu with updated branch name
                 println!();
                 println!(
@@ -158,17 +518,72 @@ pub enum CommitAction {
     CreateBranch(String),
 }
 
+/// Resolves the editor the same way `git commit` does.
+///
+/// Order: the `editor` config override, `GIT_EDITOR`, git's `core.editor`,
+/// `VISUAL`, `EDITOR`, then the platform default.
+pub fn resolve_editor(config_editor: Option<&str>) -> String {
+    let non_empty = |value: String| {
+        let value = value.trim().to_string();
+        (!value.is_empty()).then_some(value)
+    };
+
+    config_editor
+        .map(str::to_string)
+        .and_then(non_empty)
+        .or_else(|| std::env::var("GIT_EDITOR").ok().and_then(non_empty))
+        .or_else(|| {
+            std::process::Command::new("git")
+                .args(["config", "--get", "core.editor"])
+                .output()
+                .ok()
+                .filter(|output| output.status.success())
+                .and_then(|output| non_empty(String::from_utf8_lossy(&output.stdout).into()))
+        })
+        .or_else(|| std::env::var("VISUAL").ok().and_then(non_empty))
+        .or_else(|| std::env::var("EDITOR").ok().and_then(non_empty))
+        .unwrap_or_else(|| {
+            if cfg!(windows) {
+                "notepad".to_string()
+            } else {
+                "vi".to_string()
+            }
+        })
+}
+
+/// Opens `contents` in `editor`, returning the original text if editing fails.
+///
+/// Line endings of the edited text are converted to LF (see
+/// [`convert_line_endings`]); commit messages are tidied further by the caller.
+fn edit_in(editor: &str, contents: &str, extension: &str) -> String {
+    dialoguer::Editor::new()
+        .executable(editor)
+        .extension(extension)
+        .edit(contents)
+        .unwrap_or(None)
+        .map(|edited| convert_line_endings(&edited))
+        .unwrap_or_else(|| contents.to_string())
+}
+
 /// Prompts user to confirm, edit, or cancel a commit.
 ///
-/// Options: `y` (commit), `n` (cancel), `e` (edit in $EDITOR), `b` (create branch first).
-pub fn prompt_commit(message: &str, show_branch_option: bool) -> CommitAction {
+/// Options: `y` (commit), `n` (cancel), `e` (edit in `editor`), `b` (create branch first).
+/// With `reference_diff`, editing shows it below a scissors line, and
+/// everything from that line down is dropped on save.
+pub fn prompt_commit(
+    message: &str,
+    show_branch_option: bool,
+    editor: &str,
+    reference_diff: Option<&str>,
+    input: &mut dyn PromptInput,
+) -> CommitAction {
     let mut current_message = message.to_string();
 
     let print_menu = |show_branch: bool| {
         println!();
         println!("  {} Commit", style("[y]").cyan().bold());
         println!("  {} Cancel", style("[n]").cyan().bold());
-        println!("  {} Edit in $EDITOR", style("[e]").cyan().bold());
+        println!("  {} Edit in {}", style("[e]").cyan().bold(), editor);
         if show_branch {
             println!("  {} Create branch first", style("[b]").cyan().bold());
         }
@@ -187,21 +602,23 @@ pub fn prompt_commit(message: &str, show_branch_option: bool) -> CommitAction {
         print!("{} ", style("Choice:").bold());
         io::stdout().flush().unwrap();
 
-        let mut input = String::new();
-        io::stdin().read_line(&mut input).unwrap();
+        let Some(answer) = read_choice(input) else {
+            return CommitAction::Cancel;
+        };
 
-        match input.trim().to_lowercase().as_str() {
+        match answer.trim().to_lowercase().as_str() {
             "y" | "yes" => return CommitAction::Commit(current_message),
             "n" | "no" => return CommitAction::Cancel,
             "e" | "edit" => {
-                let edited: String = dialoguer::Editor::new()
-                    .extension(".txt")
-                    .edit(&current_message)
-                    .unwrap_or(None)
-                    .unwrap_or_else(|| current_message.clone());
-                current_message = edited;
+                current_message = match reference_diff.filter(|diff| !diff.trim().is_empty()) {
+                    Some(diff) => {
+                        let contents = add_reference_diff(&current_message, diff);
+                        normalize_line_endings(strip_scissors(&edit_in(editor, &contents, ".txt")))
+                    }
+                    None => normalize_line_endings(&edit_in(editor, &current_message, ".txt")),
+                };
                 println!();
-                println!("{}", current_message);
+                println!("{}", current_message.trim_end());
                 print_menu(show_branch_option);
             }
             "b" | "branch" if show_branch_option => {
@@ -220,18 +637,24 @@ pub enum PrAction {
     Cancel,
 }
 
-/// Prompts user to confirm, edit, or cancel PR creation.
+/// Prompts user to confirm, edit, or cancel PR creation (or update).
 ///
-/// Options: `y` (create), `n` (cancel), `e` (edit in $EDITOR).
-pub fn prompt_pr(title: &str, body: &str) -> PrAction {
+/// Options: `y` (`confirm_label`), `n` (cancel), `e` (edit in `editor`).
+pub fn prompt_pr(
+    title: &str,
+    body: &str,
+    editor: &str,
+    confirm_label: &str,
+    input: &mut dyn PromptInput,
+) -> PrAction {
     let mut current_title = title.to_string();
     let mut current_body = body.to_string();
 
     let print_menu = || {
         println!();
-        println!("  {} Create PR", style("[y]").cyan().bold());
+        println!("  {} {}", style("[y]").cyan().bold(), confirm_label);
         println!("  {} Cancel", style("[n]").cyan().bold());
-        println!("  {} Edit in $EDITOR", style("[e]").cyan().bold());
+        println!("  {} Edit in {}", style("[e]").cyan().bold(), editor);
         println!();
     };
 
@@ -241,19 +664,16 @@ pub fn prompt_pr(title: &str, body: &str) -> PrAction {
         print!("{} ", style("Choice:").bold());
         io::stdout().flush().unwrap();
 
-        let mut input = String::new();
-        io::stdin().read_line(&mut input).unwrap();
+        let Some(answer) = read_choice(input) else {
+            return PrAction::Cancel;
+        };
 
-        match input.trim().to_lowercase().as_str() {
+        match answer.trim().to_lowercase().as_str() {
             "y" | "yes" => return PrAction::Create(current_title, current_body),
             "n" | "no" => return PrAction::Cancel,
             "e" | "edit" => {
                 let combined = format!("{}\n\n{}", current_title, current_body);
-                let edited: String = dialoguer::Editor::new()
-                    .extension(".md")
-                    .edit(&combined)
-                    .unwrap_or(None)
-                    .unwrap_or_else(|| combined.clone());
+                let edited = edit_in(editor, &combined, ".md");
 
                 // Parse edited content back into title and body
                 let mut lines = edited.lines();
@@ -272,3 +692,389 @@ pub fn prompt_pr(title: &str, body: &str) -> PrAction {
         }
     }
 }
+
+/// Prints why a message fails `lint`, one violation per line.
+pub fn print_lint_violations(violations: &[String]) {
+    println!(
+        "{} Message doesn't follow Conventional Commits:",
+        style("✗").red()
+    );
+    for violation in violations {
+        println!("  {} {}", style("→").dim(), violation);
+    }
+}
+
+/// Prints a small report of commit message length metrics.
+///
+/// Each limited metric is marked with whether it passes its limit.
+pub fn print_message_stats(stats: &MessageStats, subject_limit: usize) {
+    let verdict = |ok: bool| {
+        if ok {
+            style("✓").green()
+        } else {
+            style("✗").red()
+        }
+    };
+
+    println!();
+    println!("{}", style("Message stats").bold());
+    println!(
+        "  {} {} / {} {}",
+        style("subject width:").cyan(),
+        stats.subject_width,
+        subject_limit,
+        verdict(stats.subject_ok(subject_limit))
+    );
+    println!("  {} {}", style("body lines:").cyan(), stats.body_lines);
+    println!(
+        "  {} {} / {} {}",
+        style("longest body line:").cyan(),
+        stats.longest_body_line,
+        BODY_MAX_WIDTH,
+        verdict(stats.body_ok())
+    );
+}
+
+/// Prints the `--count` report: estimated prompt tokens and cost for a model.
+pub fn print_token_estimate(
+    model: &str,
+    prompt_tokens: usize,
+    output_tokens: usize,
+    info: Option<&ModelInfo>,
+) {
+    println!();
+    println!(
+        "{} {}",
+        style("Token estimate for").bold(),
+        style(model).cyan()
+    );
+    println!("  {} ~{} tokens", style("prompt:").cyan(), prompt_tokens);
+    println!(
+        "  {} up to ~{} tokens",
+        style("output:").cyan(),
+        output_tokens
+    );
+    if let Some(context_length) = info.and_then(|info| info.context_length) {
+        println!("  {} {} tokens", style("context:").cyan(), context_length);
+    }
+    match info.and_then(|info| estimate_cost(info, prompt_tokens, output_tokens)) {
+        Some(cost) => println!(
+            "  {} ~${:.4} input + up to ${:.4} output",
+            style("cost:").cyan(),
+            cost.input,
+            cost.max_output
+        ),
+        None => println!(
+            "  {} {}",
+            style("cost:").cyan(),
+            style("unknown (no pricing for this model in the models list)").dim()
+        ),
+    }
+    println!(
+        "  {}",
+        style("Estimated at ~4 characters per token; no request was sent").dim()
+    );
+}
+
+/// Formats a count with thousands separators, e.g. `1,203`.
+pub fn format_count(n: u64) -> String {
+    let digits = n.to_string();
+    let mut out = String::with_capacity(digits.len() + digits.len() / 3);
+    for (i, digit) in digits.chars().enumerate() {
+        if i > 0 && (digits.len() - i).is_multiple_of(3) {
+            out.push(',');
+        }
+        out.push(digit);
+    }
+    out
+}
+
+/// Reports the tokens a generation used, with its cost if the model is priced.
+///
+/// Printed to stderr, like other verbose output.
+pub fn print_usage(usage: &Usage, info: Option<&ModelInfo>) {
+    let cost = info.and_then(|info| {
+        estimate_cost(
+            info,
+            usage.prompt_tokens as usize,
+            usage.completion_tokens as usize,
+        )
+    });
+    let cost = match cost {
+        Some(cost) => format!(" (~${:.4})", cost.input + cost.max_output),
+        None => String::new(),
+    };
+    eprintln!(
+        "— Used {} prompt + {} completion tokens{}",
+        format_count(usage.prompt_tokens),
+        format_count(usage.completion_tokens),
+        cost
+    );
+}
+
+/// How final outcomes are reported on stdout.
+#[derive(Clone, Copy, Default, PartialEq)]
+pub enum OutputMode {
+    /// Styled, human-readable output.
+    #[default]
+    Pretty,
+    /// No output; the exit code carries the outcome.
+    Quiet,
+    /// A single JSON object.
+    Json,
+}
+
+impl OutputMode {
+    /// Picks the mode from the `--quiet` and `--json` flags.
+    pub fn from_flags(quiet: bool, json: bool) -> Self {
+        if json {
+            OutputMode::Json
+        } else if quiet {
+            OutputMode::Quiet
+        } else {
+            OutputMode::Pretty
+        }
+    }
+}
+
+/// Reports an outcome: `pretty` prints it for humans, or `{"status": status}` for JSON.
+pub fn report_outcome(mode: OutputMode, status: &str, pretty: impl FnOnce()) {
+    match mode {
+        OutputMode::Pretty => pretty(),
+        OutputMode::Quiet => {}
+        OutputMode::Json => println!("{}", serde_json::json!({ "status": status })),
+    }
+}
+
+/// Prints the `--json` result of a commit run (see [`run_json`]).
+pub fn report_run(
+    message: &str,
+    model: &str,
+    tokens: Option<Usage>,
+    branch: Option<&str>,
+    suggested_branch: Option<&str>,
+    committed: bool,
+) {
+    println!(
+        "{}",
+        run_json(message, model, tokens, branch, suggested_branch, committed)
+    );
+}
+
+/// Builds the `--json` result of a commit run.
+///
+/// `message` is what was committed (or would be, on a dry run); `tokens`
+/// covers message generation, and is `null` when the server didn't report it
+/// or nothing was generated. An unknown branch or suggestion is `null` too.
+pub fn run_json(
+    message: &str,
+    model: &str,
+    tokens: Option<Usage>,
+    branch: Option<&str>,
+    suggested_branch: Option<&str>,
+    committed: bool,
+) -> serde_json::Value {
+    let tokens = tokens.map(|usage| {
+        serde_json::json!({
+            "prompt": usage.prompt_tokens,
+            "completion": usage.completion_tokens,
+        })
+    });
+    serde_json::json!({
+        "message": message,
+        "model": model,
+        "tokens": tokens,
+        "branch": branch,
+        "suggested_branch": suggested_branch,
+        "committed": committed,
+    })
+}
+
+/// Short name of a model for display: the id without its provider or variant.
+///
+/// `x-ai/grok-4.1-fast:free` becomes `grok-4.1-fast`.
+pub fn model_display_name(model: &str) -> &str {
+    let name = model.rsplit('/').next().unwrap_or(model);
+    name.split(':').next().unwrap_or(name)
+}
+
+/// Spinner label for a phase run by a model, e.g. "Analyzing branch alignment with grok-4.1-fast...".
+pub fn phase_message(phase: &str, model: &str) -> String {
+    format!("{} with {}...", phase, model_display_name(model))
+}
+
+/// Creates a spinner showing `message`; the caller starts its tick.
+///
+/// The label can be changed with `set_message` as the run moves on.
+pub fn phase_spinner(message: String) -> ProgressBar {
+    let spinner = ProgressBar::new_spinner();
+    spinner.set_style(
+        ProgressStyle::default_spinner()
+            .tick_strings(&["⠋", "⠙", "⠹", "⠸", "⠼", "⠴", "⠦", "⠧", "⠇", "⠏"])
+            .template("{spinner:.cyan} {msg}")
+            .unwrap(),
+    );
+    spinner.set_message(message);
+    spinner
+}
+
+/// Prints a line diff between `old` and `new` (`-` removed, `+` added).
+pub fn print_text_diff(old: &str, new: &str) {
+    let old: Vec<&str> = old.lines().collect();
+    let new: Vec<&str> = new.lines().collect();
+
+    // Longest common subsequence table, filled from the end
+    let mut lcs = vec![vec![0usize; new.len() + 1]; old.len() + 1];
+    for i in (0..old.len()).rev() {
+        for j in (0..new.len()).rev() {
+            lcs[i][j] = if old[i] == new[j] {
+                lcs[i + 1][j + 1] + 1
+            } else {
+                lcs[i + 1][j].max(lcs[i][j + 1])
+            };
+        }
+    }
+
+    let (mut i, mut j) = (0, 0);
+    while i < old.len() || j < new.len() {
+        if i < old.len() && j < new.len() && old[i] == new[j] {
+            println!("  {}", style(old[i]).dim());
+            i += 1;
+            j += 1;
+        } else if j < new.len() && (i == old.len() || lcs[i][j + 1] >= lcs[i + 1][j]) {
+            println!("{} {}", style("+").green(), style(new[j]).green());
+            j += 1;
+        } else {
+            println!("{} {}", style("-").red(), style(old[i]).red());
+            i += 1;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::collections::VecDeque;
+
+    /// Scripted answers, from a terminal or a pipe.
+    struct ScriptedInput {
+        terminal: bool,
+        lines: VecDeque<String>,
+    }
+
+    impl ScriptedInput {
+        fn new(terminal: bool, lines: &[&str]) -> Self {
+            Self {
+                terminal,
+                lines: lines.iter().map(|line| format!("{}\n", line)).collect(),
+            }
+        }
+    }
+
+    impl PromptInput for ScriptedInput {
+        fn is_terminal(&self) -> bool {
+            self.terminal
+        }
+
+        fn read_line(&mut self) -> Option<String> {
+            self.lines.pop_front()
+        }
+    }
+
+    #[test]
+    fn non_tty_input_cannot_prompt() {
+        let input = ScriptedInput::new(false, &["y"]);
+        assert_eq!(ensure_can_prompt(&input), Err(NO_TTY_MESSAGE));
+        // The guard fails before any answer is read
+        assert_eq!(input.lines.len(), 1);
+    }
+
+    #[test]
+    fn terminal_input_can_prompt() {
+        assert_eq!(ensure_can_prompt(&ScriptedInput::new(true, &[])), Ok(()));
+    }
+
+    #[test]
+    fn choices_end_with_the_input() {
+        let mut input = ScriptedInput::new(true, &["e", "y"]);
+        assert_eq!(read_choice(&mut input).as_deref(), Some("e\n"));
+        assert_eq!(read_choice(&mut input).as_deref(), Some("y\n"));
+        assert_eq!(read_choice(&mut input), None);
+    }
+
+    #[test]
+    fn commit_prompt_takes_an_answer_from_a_terminal() {
+        let mut input = ScriptedInput::new(true, &["maybe", "y"]);
+        let action = prompt_commit("fix: handle crash", true, "vi", None, &mut input);
+        assert!(matches!(action, CommitAction::Commit(message) if message == "fix: handle crash"));
+        assert!(input.lines.is_empty());
+    }
+
+    #[test]
+    fn commit_prompt_cancels_on_non_tty_input() {
+        let mut input = ScriptedInput::new(false, &["y"]);
+        let action = prompt_commit("fix: handle crash", true, "vi", None, &mut input);
+        assert!(matches!(action, CommitAction::Cancel));
+        // Piped text is never taken as the answer
+        assert_eq!(input.lines.len(), 1);
+    }
+
+    #[test]
+    fn other_prompts_answer_the_safe_way_on_non_tty_input() {
+        let mut input = ScriptedInput::new(false, &["y"]);
+        assert!(matches!(
+            prompt_pr("Title", "Body", "vi", "Create PR", &mut input),
+            PrAction::Cancel
+        ));
+        assert!(matches!(
+            prompt_branch_action("main", "feat/login", "", false, &mut input),
+            BranchAction::Skip
+        ));
+        assert!(!prompt_continue_stale(
+            "Behind upstream",
+            "Pull first",
+            &mut input
+        ));
+        assert_eq!(input.lines.len(), 1);
+    }
+
+    #[test]
+    fn run_json_has_every_field_even_when_unknown() {
+        let value = run_json("fix: handle crash", "x-ai/grok", None, None, None, false);
+        assert_eq!(
+            value,
+            serde_json::json!({
+                "message": "fix: handle crash",
+                "model": "x-ai/grok",
+                "tokens": null,
+                "branch": null,
+                "suggested_branch": null,
+                "committed": false,
+            })
+        );
+    }
+
+    #[test]
+    fn run_json_reports_tokens_branches_and_the_commit() {
+        let usage = Usage {
+            prompt_tokens: 1203,
+            completion_tokens: 88,
+        };
+        let value = run_json(
+            "fix(ui): handle crash",
+            "x-ai/grok",
+            Some(usage),
+            Some("main"),
+            Some("fix/ui-crash"),
+            true,
+        );
+        assert_eq!(
+            value["tokens"],
+            serde_json::json!({ "prompt": 1203, "completion": 88 })
+        );
+        assert_eq!(value["branch"], "main");
+        assert_eq!(value["suggested_branch"], "fix/ui-crash");
+        assert_eq!(value["committed"], true);
+        assert_eq!(value.as_object().unwrap().len(), 6);
+    }
+}